@@ -0,0 +1,1298 @@
+/* DNS */
+use anyhow::Result;
+use log::debug;
+use prettytable::row;
+use prettytable::Cell;
+use prettytable::Row;
+use prettytable::Table;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::UdpSocket;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::errors::CanNotFoundNameServer;
+use crate::errors::DnsPacketParseFailed;
+use crate::errors::DnsZoneTransferFailed;
+use crate::utils::get_default_timeout;
+use crate::utils::get_threads_pool;
+use crate::Host;
+use crate::Target;
+
+pub const DNS_HEADER_SIZE: usize = 12;
+// the max number of compression pointers followed in one name
+const DNS_MAX_POINTERS: usize = 16;
+
+pub const DNS_TYPE_A: u16 = 1;
+pub const DNS_TYPE_NS: u16 = 2;
+pub const DNS_TYPE_CNAME: u16 = 5;
+pub const DNS_TYPE_SOA: u16 = 6;
+pub const DNS_TYPE_PTR: u16 = 12;
+pub const DNS_TYPE_MX: u16 = 15;
+pub const DNS_TYPE_TXT: u16 = 16;
+pub const DNS_TYPE_AAAA: u16 = 28;
+pub const DNS_TYPE_SRV: u16 = 33;
+pub const DNS_TYPE_AXFR: u16 = 252;
+pub const DNS_CLASS_IN: u16 = 1;
+pub const DNS_PORT: u16 = 53;
+const DNS_UDP_BUFF_SIZE: usize = 4096;
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+    pub qclass: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsResourceRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub rclass: u16,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+    /// Offset of the rdata in the message, the names in rdata may be compressed.
+    pub rdata_offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsResourceRecord>,
+    pub authorities: Vec<DnsResourceRecord>,
+    pub additionals: Vec<DnsResourceRecord>,
+}
+
+fn read_u16(buff: &[u8], offset: usize) -> Result<u16> {
+    if offset + 2 <= buff.len() {
+        Ok(u16::from_be_bytes([buff[offset], buff[offset + 1]]))
+    } else {
+        Err(DnsPacketParseFailed::new().into())
+    }
+}
+
+fn read_u32(buff: &[u8], offset: usize) -> Result<u32> {
+    if offset + 4 <= buff.len() {
+        Ok(u32::from_be_bytes([
+            buff[offset],
+            buff[offset + 1],
+            buff[offset + 2],
+            buff[offset + 3],
+        ]))
+    } else {
+        Err(DnsPacketParseFailed::new().into())
+    }
+}
+
+/// Read a (maybe compressed) domain name from the message,
+/// returns the name and the offset right after the name.
+pub fn read_name(buff: &[u8], offset: usize) -> Result<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = offset;
+    // the offset after the name in the original position
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        if pos >= buff.len() {
+            return Err(DnsPacketParseFailed::new().into());
+        }
+        let len = buff[pos] as usize;
+        if len == 0 {
+            if end.is_none() {
+                end = Some(pos + 1);
+            }
+            break;
+        } else if len & 0xC0 == 0xC0 {
+            // compression pointer
+            let pointer = (read_u16(buff, pos)? & 0x3FFF) as usize;
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+            pointers += 1;
+            if pointers > DNS_MAX_POINTERS {
+                return Err(DnsPacketParseFailed::new().into());
+            }
+            pos = pointer;
+        } else {
+            let start = pos + 1;
+            if start + len > buff.len() {
+                return Err(DnsPacketParseFailed::new().into());
+            }
+            let label = String::from_utf8_lossy(&buff[start..start + len]).to_string();
+            labels.push(label);
+            pos = start + len;
+        }
+    }
+    let end = match end {
+        Some(e) => e,
+        None => return Err(DnsPacketParseFailed::new().into()),
+    };
+    Ok((labels.join("."), end))
+}
+
+/// Encode the domain name as dns labels, without compression.
+pub fn write_name(name: &str) -> Vec<u8> {
+    let mut ret = Vec::new();
+    for label in name.split('.') {
+        if !label.is_empty() {
+            // the label is at most 63 bytes
+            let label = &label.as_bytes()[..label.len().min(63)];
+            ret.push(label.len() as u8);
+            ret.extend(label);
+        }
+    }
+    ret.push(0);
+    ret
+}
+
+/// Split the rdata of a TXT record into its strings.
+pub fn read_txt(rdata: &[u8]) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    while pos < rdata.len() {
+        let len = rdata[pos] as usize;
+        let start = pos + 1;
+        if start + len > rdata.len() {
+            break;
+        }
+        if len > 0 {
+            ret.push(String::from_utf8_lossy(&rdata[start..start + len]).to_string());
+        }
+        pos = start + len;
+    }
+    ret
+}
+
+fn read_records(buff: &[u8], offset: usize, count: u16) -> Result<(Vec<DnsResourceRecord>, usize)> {
+    let mut records = Vec::new();
+    let mut pos = offset;
+    for _ in 0..count {
+        let (name, next) = read_name(buff, pos)?;
+        let rtype = read_u16(buff, next)?;
+        let rclass = read_u16(buff, next + 2)?;
+        let ttl = read_u32(buff, next + 4)?;
+        let rdlength = read_u16(buff, next + 8)? as usize;
+        let rdata_offset = next + 10;
+        if rdata_offset + rdlength > buff.len() {
+            return Err(DnsPacketParseFailed::new().into());
+        }
+        let rdata = buff[rdata_offset..rdata_offset + rdlength].to_vec();
+        records.push(DnsResourceRecord {
+            name,
+            rtype,
+            rclass,
+            ttl,
+            rdata,
+            rdata_offset,
+        });
+        pos = rdata_offset + rdlength;
+    }
+    Ok((records, pos))
+}
+
+impl DnsMessage {
+    /// Parse a dns message, also works for mdns and llmnr which use the same format.
+    pub fn parse(buff: &[u8]) -> Result<DnsMessage> {
+        if buff.len() < DNS_HEADER_SIZE {
+            return Err(DnsPacketParseFailed::new().into());
+        }
+        let id = read_u16(buff, 0)?;
+        let flags = read_u16(buff, 2)?;
+        let qdcount = read_u16(buff, 4)?;
+        let ancount = read_u16(buff, 6)?;
+        let nscount = read_u16(buff, 8)?;
+        let arcount = read_u16(buff, 10)?;
+
+        let mut questions = Vec::new();
+        let mut pos = DNS_HEADER_SIZE;
+        for _ in 0..qdcount {
+            let (name, next) = read_name(buff, pos)?;
+            let qtype = read_u16(buff, next)?;
+            let qclass = read_u16(buff, next + 2)?;
+            questions.push(DnsQuestion {
+                name,
+                qtype,
+                qclass,
+            });
+            pos = next + 4;
+        }
+        let (answers, pos) = read_records(buff, pos, ancount)?;
+        let (authorities, pos) = read_records(buff, pos, nscount)?;
+        let (additionals, _) = read_records(buff, pos, arcount)?;
+        Ok(DnsMessage {
+            id,
+            flags,
+            questions,
+            answers,
+            authorities,
+            additionals,
+        })
+    }
+    /// Build a standard query message with the given questions.
+    pub fn build_query(id: u16, flags: u16, questions: &[DnsQuestion]) -> Vec<u8> {
+        let mut ret = Vec::new();
+        ret.extend(id.to_be_bytes());
+        ret.extend(flags.to_be_bytes());
+        ret.extend((questions.len() as u16).to_be_bytes());
+        // no answer, authority and additional records
+        ret.extend([0u8; 6]);
+        for q in questions {
+            ret.extend(write_name(&q.name));
+            ret.extend(q.qtype.to_be_bytes());
+            ret.extend(q.qclass.to_be_bytes());
+        }
+        ret
+    }
+    /// QR bit, the message is a response.
+    pub fn is_response(&self) -> bool {
+        self.flags & 0x8000 != 0
+    }
+    /// TC bit, the udp response is truncated and should be retried over tcp.
+    pub fn is_truncated(&self) -> bool {
+        self.flags & 0x0200 != 0
+    }
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x000F) as u8
+    }
+}
+
+/// The name of the record type, such as MX.
+pub fn type_name(rtype: u16) -> String {
+    match rtype {
+        DNS_TYPE_A => String::from("A"),
+        DNS_TYPE_NS => String::from("NS"),
+        DNS_TYPE_CNAME => String::from("CNAME"),
+        DNS_TYPE_SOA => String::from("SOA"),
+        DNS_TYPE_PTR => String::from("PTR"),
+        DNS_TYPE_MX => String::from("MX"),
+        DNS_TYPE_TXT => String::from("TXT"),
+        DNS_TYPE_AAAA => String::from("AAAA"),
+        DNS_TYPE_SRV => String::from("SRV"),
+        DNS_TYPE_AXFR => String::from("AXFR"),
+        _ => format!("TYPE{}", rtype),
+    }
+}
+
+/// The name of the response code, such as NXDOMAIN.
+pub fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => String::from("NOERROR"),
+        1 => String::from("FORMERR"),
+        2 => String::from("SERVFAIL"),
+        3 => String::from("NXDOMAIN"),
+        4 => String::from("NOTIMP"),
+        5 => String::from("REFUSED"),
+        _ => format!("RCODE{}", rcode),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Ptr,
+    Mx,
+    Ns,
+    Txt,
+    Soa,
+    Srv,
+    Cname,
+}
+
+impl DnsRecordType {
+    pub fn qtype(&self) -> u16 {
+        match self {
+            DnsRecordType::A => DNS_TYPE_A,
+            DnsRecordType::Aaaa => DNS_TYPE_AAAA,
+            DnsRecordType::Ptr => DNS_TYPE_PTR,
+            DnsRecordType::Mx => DNS_TYPE_MX,
+            DnsRecordType::Ns => DNS_TYPE_NS,
+            DnsRecordType::Txt => DNS_TYPE_TXT,
+            DnsRecordType::Soa => DNS_TYPE_SOA,
+            DnsRecordType::Srv => DNS_TYPE_SRV,
+            DnsRecordType::Cname => DNS_TYPE_CNAME,
+        }
+    }
+}
+
+impl fmt::Display for DnsRecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", type_name(self.qtype()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DnsTransport {
+    /// Fall back to tcp when the response is truncated.
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DnsRecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ptr(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Ns(String),
+    Txt(Vec<String>),
+    Soa {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Cname(String),
+    Unknown(Vec<u8>),
+}
+
+impl fmt::Display for DnsRecordData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // same as the zone file
+        match self {
+            DnsRecordData::A(ipv4) => write!(f, "{}", ipv4),
+            DnsRecordData::Aaaa(ipv6) => write!(f, "{}", ipv6),
+            DnsRecordData::Ptr(name) => write!(f, "{}", name),
+            DnsRecordData::Mx {
+                preference,
+                exchange,
+            } => write!(f, "{} {}", preference, exchange),
+            DnsRecordData::Ns(name) => write!(f, "{}", name),
+            DnsRecordData::Txt(txt) => {
+                let txt: Vec<String> = txt.iter().map(|t| format!("\"{}\"", t)).collect();
+                write!(f, "{}", txt.join(" "))
+            }
+            DnsRecordData::Soa {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                mname, rname, serial, refresh, retry, expire, minimum
+            ),
+            DnsRecordData::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(f, "{} {} {} {}", priority, weight, port, target),
+            DnsRecordData::Cname(name) => write!(f, "{}", name),
+            DnsRecordData::Unknown(rdata) => {
+                let rdata: Vec<String> = rdata.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(f, "\\# {} {}", rdata.len(), rdata.join(""))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: DnsRecordData,
+}
+
+impl DnsRecord {
+    /// Decode the rdata of the resource record, `buff` is the whole message.
+    pub fn parse(buff: &[u8], rr: &DnsResourceRecord) -> Result<DnsRecord> {
+        let offset = rr.rdata_offset;
+        let data = match rr.rtype {
+            DNS_TYPE_A => {
+                if rr.rdata.len() != 4 {
+                    return Err(DnsPacketParseFailed::new().into());
+                }
+                let ipv4 = Ipv4Addr::new(rr.rdata[0], rr.rdata[1], rr.rdata[2], rr.rdata[3]);
+                DnsRecordData::A(ipv4)
+            }
+            DNS_TYPE_AAAA => {
+                let octets: [u8; 16] = match rr.rdata.as_slice().try_into() {
+                    Ok(o) => o,
+                    Err(_) => return Err(DnsPacketParseFailed::new().into()),
+                };
+                DnsRecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            DNS_TYPE_PTR => DnsRecordData::Ptr(read_name(buff, offset)?.0),
+            DNS_TYPE_NS => DnsRecordData::Ns(read_name(buff, offset)?.0),
+            DNS_TYPE_CNAME => DnsRecordData::Cname(read_name(buff, offset)?.0),
+            DNS_TYPE_MX => {
+                let preference = read_u16(buff, offset)?;
+                let (exchange, _) = read_name(buff, offset + 2)?;
+                DnsRecordData::Mx {
+                    preference,
+                    exchange,
+                }
+            }
+            DNS_TYPE_TXT => DnsRecordData::Txt(read_txt(&rr.rdata)),
+            DNS_TYPE_SOA => {
+                let (mname, next) = read_name(buff, offset)?;
+                let (rname, next) = read_name(buff, next)?;
+                DnsRecordData::Soa {
+                    mname,
+                    rname,
+                    serial: read_u32(buff, next)?,
+                    refresh: read_u32(buff, next + 4)?,
+                    retry: read_u32(buff, next + 8)?,
+                    expire: read_u32(buff, next + 12)?,
+                    minimum: read_u32(buff, next + 16)?,
+                }
+            }
+            DNS_TYPE_SRV => {
+                let priority = read_u16(buff, offset)?;
+                let weight = read_u16(buff, offset + 2)?;
+                let port = read_u16(buff, offset + 4)?;
+                let (target, _) = read_name(buff, offset + 6)?;
+                DnsRecordData::Srv {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                }
+            }
+            _ => DnsRecordData::Unknown(rr.rdata.clone()),
+        };
+        Ok(DnsRecord {
+            name: rr.name.clone(),
+            rtype: rr.rtype,
+            ttl: rr.ttl,
+            data,
+        })
+    }
+}
+
+fn parse_records(buff: &[u8], rrs: &[DnsResourceRecord]) -> Result<Vec<DnsRecord>> {
+    let mut ret = Vec::new();
+    for rr in rrs {
+        ret.push(DnsRecord::parse(buff, rr)?);
+    }
+    Ok(ret)
+}
+
+fn records_to_table(table: &mut Table, records: &[DnsRecord]) {
+    for r in records {
+        let type_str = type_name(r.rtype);
+        let data_str = r.data.to_string();
+        table.add_row(row![c -> r.name, c -> r.ttl, c -> type_str, c -> data_str]);
+    }
+}
+
+/// The PTR name of the address, such as 1.1.168.192.in-addr.arpa.
+pub fn reverse_name(addr: &IpAddr) -> String {
+    match addr {
+        IpAddr::V4(ipv4) => {
+            let o = ipv4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(ipv6) => {
+            let mut nibbles = Vec::new();
+            for b in ipv6.octets().iter().rev() {
+                nibbles.push(format!("{:x}", b & 0x0F));
+                nibbles.push(format!("{:x}", b >> 4));
+            }
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+/// The first name server in /etc/resolv.conf.
+pub fn get_system_name_server() -> Result<SocketAddr> {
+    match fs::read_to_string(RESOLV_CONF) {
+        Ok(resolv_conf) => {
+            for line in resolv_conf.lines() {
+                let line_split: Vec<&str> = line.split_whitespace().collect();
+                if line_split.len() >= 2 && line_split[0] == "nameserver" {
+                    // remove the zone index of the link-local address
+                    let addr = line_split[1].split('%').next().unwrap_or("");
+                    if let Ok(addr) = addr.parse::<IpAddr>() {
+                        return Ok(SocketAddr::new(addr, DNS_PORT));
+                    }
+                }
+            }
+            Err(CanNotFoundNameServer::new().into())
+        }
+        Err(_) => Err(CanNotFoundNameServer::new().into()),
+    }
+}
+
+fn new_query(questions: &[DnsQuestion]) -> (u16, Vec<u8>) {
+    let mut rng = rand::thread_rng();
+    let id: u16 = rng.gen();
+    // recursion desired
+    let query = DnsMessage::build_query(id, 0x0100, questions);
+    (id, query)
+}
+
+/// Send the query to the server over udp and wait for the response with the same id,
+/// returns the response and its raw bytes (the names in rdata may point into the message).
+pub fn dns_udp_query(
+    server: SocketAddr,
+    questions: &[DnsQuestion],
+    timeout: Duration,
+) -> Result<(DnsMessage, Vec<u8>)> {
+    let (id, query) = new_query(questions);
+    let local_addr: IpAddr = match server {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local_addr, 0))?;
+    socket.set_write_timeout(Some(timeout))?;
+    socket.connect(server)?;
+    socket.send(&query)?;
+
+    let start_time = Instant::now();
+    let mut recv_buff = [0u8; DNS_UDP_BUFF_SIZE];
+    loop {
+        let elapsed = start_time.elapsed();
+        if elapsed >= timeout {
+            return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+        }
+        socket.set_read_timeout(Some(timeout - elapsed))?;
+        let n = socket.recv(&mut recv_buff)?;
+        let buff = recv_buff[..n].to_vec();
+        if let Ok(message) = DnsMessage::parse(&buff) {
+            if message.id == id && message.is_response() {
+                return Ok((message, buff));
+            }
+        }
+    }
+}
+
+fn tcp_connect(server: SocketAddr, query: &[u8], timeout: Duration) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect_timeout(&server, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    // the message over tcp is prefixed with its length
+    let mut buff = (query.len() as u16).to_be_bytes().to_vec();
+    buff.extend(query);
+    stream.write_all(&buff)?;
+    Ok(stream)
+}
+
+fn tcp_read_message(stream: &mut TcpStream) -> Result<Vec<u8>> {
+    let mut len_buff = [0u8; 2];
+    stream.read_exact(&mut len_buff)?;
+    let len = u16::from_be_bytes(len_buff) as usize;
+    let mut buff = vec![0u8; len];
+    stream.read_exact(&mut buff)?;
+    Ok(buff)
+}
+
+/// Send the query to the server over tcp, returns the response and its raw bytes.
+pub fn dns_tcp_query(
+    server: SocketAddr,
+    questions: &[DnsQuestion],
+    timeout: Duration,
+) -> Result<(DnsMessage, Vec<u8>)> {
+    let (id, query) = new_query(questions);
+    let mut stream = tcp_connect(server, &query, timeout)?;
+    let buff = tcp_read_message(&mut stream)?;
+    let message = DnsMessage::parse(&buff)?;
+    if message.id != id || !message.is_response() {
+        return Err(DnsPacketParseFailed::new().into());
+    }
+    Ok((message, buff))
+}
+
+fn dns_query_message(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    transport: DnsTransport,
+    timeout: Duration,
+) -> Result<(DnsMessage, Vec<u8>)> {
+    let question = DnsQuestion {
+        name: name.to_string(),
+        qtype,
+        qclass: DNS_CLASS_IN,
+    };
+    match transport {
+        DnsTransport::Udp => {
+            let (message, buff) = dns_udp_query(server, std::slice::from_ref(&question), timeout)?;
+            if message.is_truncated() {
+                debug!("dns response of {} is truncated, retry over tcp", name);
+                dns_tcp_query(server, &[question], timeout)
+            } else {
+                Ok((message, buff))
+            }
+        }
+        DnsTransport::Tcp => dns_tcp_query(server, &[question], timeout),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsQueryResults {
+    pub name: String,
+    pub rtype: DnsRecordType,
+    pub server: SocketAddr,
+    pub rcode: u8,
+    pub answers: Vec<DnsRecord>,
+    pub authorities: Vec<DnsRecord>,
+    pub additionals: Vec<DnsRecord>,
+}
+
+impl DnsQueryResults {
+    /// The data of the answers with the queried type, the cname chain is skipped.
+    pub fn get(&self) -> Vec<&DnsRecordData> {
+        let qtype = self.rtype.qtype();
+        self.answers
+            .iter()
+            .filter(|r| r.rtype == qtype)
+            .map(|r| &r.data)
+            .collect()
+    }
+}
+
+impl fmt::Display for DnsQueryResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("DNS Query Results")
+            .style_spec("c")
+            .with_hspan(4)]));
+
+        records_to_table(&mut table, &self.answers);
+        records_to_table(&mut table, &self.authorities);
+        records_to_table(&mut table, &self.additionals);
+
+        let summary = format!(
+            "Summary:\nquery: {} {}\nserver: {}\nstatus: {}\nanswers: {}",
+            self.name,
+            self.rtype,
+            self.server,
+            rcode_name(self.rcode),
+            self.answers.len()
+        );
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(4)]));
+        write!(f, "{}", table)
+    }
+}
+
+/// Query the records of the name from the dns server (default is the first name server in /etc/resolv.conf),
+/// the negative responses such as NXDOMAIN are returned in the results, not as errors.
+pub fn dns_resolve(
+    name: &str,
+    rtype: DnsRecordType,
+    server: Option<SocketAddr>,
+    transport: DnsTransport,
+    timeout: Option<Duration>,
+) -> Result<DnsQueryResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let server = match server {
+        Some(s) => s,
+        None => get_system_name_server()?,
+    };
+    let (message, buff) = dns_query_message(server, name, rtype.qtype(), transport, timeout)?;
+    Ok(DnsQueryResults {
+        name: name.to_string(),
+        rtype,
+        server,
+        rcode: message.rcode(),
+        answers: parse_records(&buff, &message.answers)?,
+        authorities: parse_records(&buff, &message.authorities)?,
+        additionals: parse_records(&buff, &message.additionals)?,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsZoneTransferResults {
+    pub zone: String,
+    pub server: SocketAddr,
+    pub records: Vec<DnsRecord>,
+}
+
+impl fmt::Display for DnsZoneTransferResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("DNS Zone Transfer Results")
+            .style_spec("c")
+            .with_hspan(4)]));
+
+        records_to_table(&mut table, &self.records);
+
+        let summary = format!(
+            "Summary:\nzone: {}\nserver: {}\nrecords: {}",
+            self.zone,
+            self.server,
+            self.records.len()
+        );
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(4)]));
+        write!(f, "{}", table)
+    }
+}
+
+/// Try the AXFR of the zone from the server, only use it against the zones you are authorized to test.
+/// The transfer is complete when the second SOA record arrives.
+pub fn dns_zone_transfer(
+    zone: &str,
+    server: SocketAddr,
+    timeout: Option<Duration>,
+) -> Result<DnsZoneTransferResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let question = DnsQuestion {
+        name: zone.to_string(),
+        qtype: DNS_TYPE_AXFR,
+        qclass: DNS_CLASS_IN,
+    };
+    let (id, query) = new_query(&[question]);
+    let mut stream = tcp_connect(server, &query, timeout)?;
+
+    let mut records = Vec::new();
+    let mut soa_num = 0;
+    while soa_num < 2 {
+        // the zone may be sent in many messages
+        let buff = tcp_read_message(&mut stream)?;
+        let message = DnsMessage::parse(&buff)?;
+        if message.id != id || !message.is_response() {
+            return Err(DnsPacketParseFailed::new().into());
+        }
+        if message.rcode() != 0 || message.answers.is_empty() {
+            return Err(DnsZoneTransferFailed::new().into());
+        }
+        for r in parse_records(&buff, &message.answers)? {
+            if r.rtype == DNS_TYPE_SOA {
+                soa_num += 1;
+                // the last SOA is the same as the first one
+                if soa_num == 2 {
+                    break;
+                }
+            }
+            records.push(r);
+        }
+    }
+    Ok(DnsZoneTransferResults {
+        zone: zone.to_string(),
+        server,
+        records,
+    })
+}
+
+fn resolve_addrs_qtype(
+    name: &str,
+    qtype: u16,
+    server: SocketAddr,
+    timeout: Duration,
+) -> Result<Vec<IpAddr>> {
+    let (message, buff) = dns_query_message(server, name, qtype, DnsTransport::Udp, timeout)?;
+    let mut ret = Vec::new();
+    for r in parse_records(&buff, &message.answers)? {
+        match r.data {
+            DnsRecordData::A(ipv4) => ret.push(ipv4.into()),
+            DnsRecordData::Aaaa(ipv6) => ret.push(ipv6.into()),
+            _ => (),
+        }
+    }
+    Ok(ret)
+}
+
+/// The A and AAAA records of the name, the failure of one record type (e.g. the AAAA query timeout)
+/// does not drop the addresses of the other, the error is returned only if both failed.
+fn resolve_addrs(name: &str, server: SocketAddr, timeout: Duration) -> Result<Vec<IpAddr>> {
+    let a_ret = resolve_addrs_qtype(name, DNS_TYPE_A, server, timeout);
+    let aaaa_ret = resolve_addrs_qtype(name, DNS_TYPE_AAAA, server, timeout);
+    merge_addrs_results(a_ret, aaaa_ret)
+}
+
+fn merge_addrs_results(
+    a_ret: Result<Vec<IpAddr>>,
+    aaaa_ret: Result<Vec<IpAddr>>,
+) -> Result<Vec<IpAddr>> {
+    match (a_ret, aaaa_ret) {
+        (Ok(mut a), Ok(aaaa)) => {
+            a.extend(aaaa);
+            Ok(a)
+        }
+        (Ok(a), Err(e)) => {
+            debug!("aaaa query failed: {}", e);
+            Ok(a)
+        }
+        (Err(e), Ok(aaaa)) => {
+            debug!("a query failed: {}", e);
+            Ok(aaaa)
+        }
+        (Err(e), Err(_)) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsBruteResults {
+    pub domain: String,
+    pub subdomains: BTreeMap<String, Vec<IpAddr>>,
+    /// The addresses of the wildcard record, the subdomains only point to them are dropped.
+    pub wildcard_addrs: Vec<IpAddr>,
+}
+
+impl DnsBruteResults {
+    pub fn get(&self, k: &str) -> Option<&Vec<IpAddr>> {
+        self.subdomains.get(k)
+    }
+    /// All the found addresses as the scan target.
+    pub fn target(&self, ports: Option<Vec<u16>>) -> Target {
+        let mut addrs: Vec<IpAddr> = Vec::new();
+        for a in self.subdomains.values() {
+            for addr in a {
+                if !addrs.contains(addr) {
+                    addrs.push(*addr);
+                }
+            }
+        }
+        let hosts = addrs
+            .into_iter()
+            .map(|a| Host::new(a, ports.clone()))
+            .collect();
+        Target::new(hosts)
+    }
+}
+
+impl fmt::Display for DnsBruteResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("DNS Brute Results")
+            .style_spec("c")
+            .with_hspan(2)]));
+
+        for (name, addrs) in &self.subdomains {
+            let addrs_str: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+            let addrs_str = addrs_str.join("\n");
+            table.add_row(row![c -> name, c -> addrs_str]);
+        }
+
+        let summary = format!(
+            "Summary:\ndomain: {}\nsubdomains: {}\nwildcard: {}",
+            self.domain,
+            self.subdomains.len(),
+            !self.wildcard_addrs.is_empty()
+        );
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(2)]));
+        write!(f, "{}", table)
+    }
+}
+
+/// Brute-force the subdomains of the domain with the words (such as www, mail) in parallel,
+/// use `DnsBruteResults::target` to scan the found hosts.
+pub fn dns_subdomain_brute(
+    domain: &str,
+    wordlist: &[String],
+    server: Option<SocketAddr>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<DnsBruteResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let server = match server {
+        Some(s) => s,
+        None => get_system_name_server()?,
+    };
+    let domain = domain.trim_end_matches('.').to_string();
+
+    // a random name resolves only if the zone has the wildcard record
+    let mut rng = rand::thread_rng();
+    let random_name = format!("{:016x}.{}", rng.gen::<u64>(), domain);
+    let wildcard_addrs = resolve_addrs(&random_name, server, timeout).unwrap_or_default();
+
+    let pool = get_threads_pool(threads_num);
+    let (tx, rx) = channel();
+    let mut recv_size = 0;
+    for word in wordlist {
+        let word = word.trim();
+        if word.is_empty() || word.starts_with("#") {
+            continue;
+        }
+        let name = format!("{}.{}", word, domain);
+        let tx = tx.clone();
+        recv_size += 1;
+        pool.execute(move || {
+            let resolve_ret = resolve_addrs(&name, server, timeout);
+            let _ = tx.send((name, resolve_ret));
+        });
+    }
+
+    let mut subdomains = BTreeMap::new();
+    let iter = rx.into_iter().take(recv_size);
+    for (name, resolve_ret) in iter {
+        match resolve_ret {
+            Ok(addrs) => {
+                if !addrs.is_empty() && !addrs.iter().all(|a| wildcard_addrs.contains(a)) {
+                    subdomains.insert(name, addrs);
+                }
+            }
+            Err(e) => debug!("dns brute {} failed: {}", name, e),
+        }
+    }
+    Ok(DnsBruteResults {
+        domain,
+        subdomains,
+        wildcard_addrs,
+    })
+}
+
+/// Query the PTR name of the address, returns none if the server has no such name.
+pub fn ptr_lookup(addr: IpAddr, server: SocketAddr, timeout: Duration) -> Result<Option<String>> {
+    let question = DnsQuestion {
+        name: reverse_name(&addr),
+        qtype: DNS_TYPE_PTR,
+        qclass: DNS_CLASS_IN,
+    };
+    let (message, buff) = dns_udp_query(server, &[question], timeout)?;
+    for rr in &message.answers {
+        if rr.rtype == DNS_TYPE_PTR {
+            let (name, _) = read_name(&buff, rr.rdata_offset)?;
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
+/// Reverse DNS.
+/// Query the PTR names of all the addresses in parallel, the `resolver` is the dns server (default is the first name server in /etc/resolv.conf),
+/// the addresses without the name or timeout are not in the results.
+/// The `no_resolve` switch skips all the queries and returns the empty results.
+pub fn reverse_dns(
+    addrs: &[IpAddr],
+    resolver: Option<SocketAddr>,
+    no_resolve: bool,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<HashMap<IpAddr, String>> {
+    let mut ret = HashMap::new();
+    if no_resolve || addrs.is_empty() {
+        return Ok(ret);
+    }
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let resolver = match resolver {
+        Some(r) => r,
+        None => get_system_name_server()?,
+    };
+
+    let pool = get_threads_pool(threads_num);
+    let (tx, rx) = channel();
+    let mut recv_size = 0;
+    for addr in addrs {
+        let addr = *addr;
+        let tx = tx.clone();
+        recv_size += 1;
+        pool.execute(move || {
+            let lookup_ret = ptr_lookup(addr, resolver, timeout);
+            let _ = tx.send((addr, lookup_ret));
+        });
+    }
+    let iter = rx.into_iter().take(recv_size);
+    for (addr, lookup_ret) in iter {
+        match lookup_ret {
+            Ok(Some(name)) => {
+                ret.insert(addr, name);
+            }
+            Ok(None) => (),
+            Err(e) => debug!("reverse dns {} failed: {}", addr, e),
+        }
+    }
+    Ok(ret)
+}
+
+/// The scan results which show their hosts with the PTR names.
+pub trait ResolveHostnames {
+    /// The addresses of all the hosts in the results.
+    fn addrs(&self) -> Vec<IpAddr>;
+    /// The PTR names of the hosts, shown by `addr_with_hostname`.
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String>;
+    /// Query the PTR names of all the hosts, see `reverse_dns`.
+    fn resolve_hostnames(
+        &mut self,
+        resolver: Option<SocketAddr>,
+        no_resolve: bool,
+        threads_num: usize,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let addrs = self.addrs();
+        *self.hostnames_mut() = reverse_dns(&addrs, resolver, no_resolve, threads_num, timeout)?;
+        Ok(())
+    }
+}
+
+/// Show the address with its name, such as 192.168.1.1 (router.lan).
+pub fn addr_with_hostname(addr: &IpAddr, hostnames: &HashMap<IpAddr, String>) -> String {
+    match hostnames.get(addr) {
+        Some(h) => format!("{} ({})", addr, h),
+        None => addr.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    #[test]
+    fn test_reverse_name() {
+        let addr: IpAddr = Ipv4Addr::new(192, 168, 1, 10).into();
+        assert_eq!(reverse_name(&addr), "10.1.168.192.in-addr.arpa");
+        let addr: IpAddr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into();
+        assert_eq!(
+            reverse_name(&addr),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+    // answer the query, every answer points to the question name
+    fn build_response(query: &[u8], rcode: u16, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2..4].copy_from_slice(&(0x8180 | rcode).to_be_bytes());
+        response[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+        for (rtype, rdata) in answers {
+            response.extend([0xC0, 12]);
+            response.extend(rtype.to_be_bytes());
+            response.extend(DNS_CLASS_IN.to_be_bytes());
+            response.extend(3600u32.to_be_bytes());
+            response.extend((rdata.len() as u16).to_be_bytes());
+            response.extend(rdata);
+        }
+        response
+    }
+    #[test]
+    fn test_reverse_dns() -> Result<()> {
+        // a local stand-in of the dns server which only knows 192.168.1.1
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let server_addr = server.local_addr()?;
+        let known = Ipv4Addr::new(192, 168, 1, 1).into();
+        let unknown = Ipv4Addr::new(192, 168, 1, 2).into();
+        let known_name = reverse_name(&known);
+        thread::spawn(move || {
+            let mut buff = [0u8; DNS_UDP_BUFF_SIZE];
+            for _ in 0..2 {
+                let (n, peer) = server.recv_from(&mut buff).unwrap();
+                let query = DnsMessage::parse(&buff[..n]).unwrap();
+                let response = if query.questions[0].name == known_name {
+                    build_response(&buff[..n], 0, &[(DNS_TYPE_PTR, write_name("router.lan"))])
+                } else {
+                    // nxdomain
+                    build_response(&buff[..n], 3, &[])
+                };
+                server.send_to(&response, peer).unwrap();
+            }
+        });
+
+        let timeout = Some(Duration::new(1, 0));
+        let addrs = vec![known, unknown];
+        let ret = reverse_dns(&addrs, Some(server_addr), true, 2, timeout)?;
+        assert_eq!(ret.len(), 0);
+        let ret = reverse_dns(&addrs, Some(server_addr), false, 2, timeout)?;
+        assert_eq!(ret.len(), 1);
+        assert_eq!(ret.get(&known), Some(&String::from("router.lan")));
+        Ok(())
+    }
+    #[test]
+    fn test_dns_record_parse() -> Result<()> {
+        let question = DnsQuestion {
+            name: String::from("example.com"),
+            qtype: DNS_TYPE_MX,
+            qclass: DNS_CLASS_IN,
+        };
+        let query = DnsMessage::build_query(0x1234, 0x0100, &[question]);
+        // mail.example.com with the pointer to example.com
+        let mut mx = vec![0, 10, 4];
+        mx.extend(b"mail");
+        mx.extend([0xC0, 12]);
+        let mut soa = write_name("ns1.example.com");
+        soa.extend(write_name("admin.example.com"));
+        for v in [2024010101u32, 7200, 3600, 1209600, 300] {
+            soa.extend(v.to_be_bytes());
+        }
+        let mut srv = vec![0, 1, 0, 5, 0x13, 0xC4];
+        srv.extend(write_name("sip.example.com"));
+        let answers = vec![
+            (DNS_TYPE_MX, mx),
+            (DNS_TYPE_SOA, soa),
+            (DNS_TYPE_SRV, srv),
+            (DNS_TYPE_AAAA, Ipv6Addr::LOCALHOST.octets().to_vec()),
+        ];
+        let buff = build_response(&query, 0, &answers);
+        let message = DnsMessage::parse(&buff)?;
+        let records = parse_records(&buff, &message.answers)?;
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            records[0].data,
+            DnsRecordData::Mx {
+                preference: 10,
+                exchange: String::from("mail.example.com"),
+            }
+        );
+        assert_eq!(
+            records[1].data.to_string(),
+            "ns1.example.com admin.example.com 2024010101 7200 3600 1209600 300"
+        );
+        assert_eq!(records[2].data.to_string(), "1 5 5060 sip.example.com");
+        assert_eq!(records[3].data, DnsRecordData::Aaaa(Ipv6Addr::LOCALHOST));
+        // truncated address
+        let mut bad = message.answers[3].clone();
+        bad.rdata.pop();
+        assert!(DnsRecord::parse(&buff, &bad).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_dns_zone_transfer() -> Result<()> {
+        // a local stand-in of the dns server which sends the zone in two messages
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let server_addr = listener.local_addr()?;
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let query = tcp_read_message(&mut stream).unwrap();
+            let mut soa = write_name("ns1.example.com");
+            soa.extend(write_name("admin.example.com"));
+            soa.extend([0u8; 20]);
+            let messages = vec![
+                build_response(
+                    &query,
+                    0,
+                    &[(DNS_TYPE_SOA, soa.clone()), (DNS_TYPE_A, vec![10, 0, 0, 1])],
+                ),
+                build_response(&query, 0, &[(DNS_TYPE_SOA, soa)]),
+            ];
+            for m in messages {
+                let mut buff = (m.len() as u16).to_be_bytes().to_vec();
+                buff.extend(m);
+                stream.write_all(&buff).unwrap();
+            }
+        });
+
+        let timeout = Some(Duration::new(1, 0));
+        let ret = dns_zone_transfer("example.com", server_addr, timeout)?;
+        println!("{}", ret);
+        assert_eq!(ret.records.len(), 2);
+        assert_eq!(ret.records[0].rtype, DNS_TYPE_SOA);
+        assert_eq!(
+            ret.records[1].data,
+            DnsRecordData::A(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_dns_subdomain_brute() -> Result<()> {
+        // a local stand-in of the dns server which knows www and mail
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let server_addr = server.local_addr()?;
+        thread::spawn(move || {
+            let mut buff = [0u8; DNS_UDP_BUFF_SIZE];
+            server.set_read_timeout(Some(Duration::new(5, 0))).unwrap();
+            while let Ok((n, peer)) = server.recv_from(&mut buff) {
+                let query = DnsMessage::parse(&buff[..n]).unwrap();
+                let q = &query.questions[0];
+                let response = match (q.name.as_str(), q.qtype) {
+                    ("www.example.com", DNS_TYPE_A) => {
+                        build_response(&buff[..n], 0, &[(DNS_TYPE_A, vec![10, 0, 0, 80])])
+                    }
+                    ("mail.example.com", DNS_TYPE_A) => {
+                        build_response(&buff[..n], 0, &[(DNS_TYPE_A, vec![10, 0, 0, 25])])
+                    }
+                    ("www.example.com", _) | ("mail.example.com", _) => {
+                        build_response(&buff[..n], 0, &[])
+                    }
+                    _ => build_response(&buff[..n], 3, &[]),
+                };
+                server.send_to(&response, peer).unwrap();
+            }
+        });
+
+        let wordlist: Vec<String> = ["www", "mail", "ftp", "# comment"]
+            .iter()
+            .map(|w| w.to_string())
+            .collect();
+        let timeout = Some(Duration::new(1, 0));
+        let ret = dns_subdomain_brute("example.com", &wordlist, Some(server_addr), 2, timeout)?;
+        println!("{}", ret);
+        assert_eq!(ret.subdomains.len(), 2);
+        assert_eq!(ret.wildcard_addrs.len(), 0);
+        let target = ret.target(Some(vec![80]));
+        assert_eq!(target.hosts.len(), 2);
+        Ok(())
+    }
+    #[test]
+    fn test_read_name_compression() -> Result<()> {
+        let mut buff = vec![0u8; DNS_HEADER_SIZE];
+        // example.com at offset 12
+        buff.push(7);
+        buff.extend(b"example");
+        buff.push(3);
+        buff.extend(b"com");
+        buff.push(0);
+        // www + pointer to offset 12
+        let offset = buff.len();
+        buff.push(3);
+        buff.extend(b"www");
+        buff.extend([0xC0, 12]);
+        let (name, end) = read_name(&buff, offset)?;
+        assert_eq!(name, "www.example.com");
+        assert_eq!(end, buff.len());
+        // pointer loop
+        let buff = vec![0xC0, 0];
+        assert!(read_name(&buff, 0).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_build_query() -> Result<()> {
+        let question = DnsQuestion {
+            name: String::from("_services._dns-sd._udp.local"),
+            qtype: DNS_TYPE_PTR,
+            qclass: DNS_CLASS_IN,
+        };
+        let buff = DnsMessage::build_query(0x1234, 0, std::slice::from_ref(&question));
+        let message = DnsMessage::parse(&buff)?;
+        assert_eq!(message.id, 0x1234);
+        assert!(!message.is_response());
+        assert_eq!(message.questions, vec![question]);
+        assert_eq!(read_txt(b"\x05md=TV\x00\x03a=b"), vec!["md=TV", "a=b"]);
+        Ok(())
+    }
+    #[test]
+    fn test_merge_addrs_results() {
+        let ipv4: IpAddr = Ipv4Addr::new(192, 168, 1, 1).into();
+        let ipv6: IpAddr = "fe80::1".parse().unwrap();
+        let failed = || -> Result<Vec<IpAddr>> { Err(DnsPacketParseFailed::new().into()) };
+        let ret = merge_addrs_results(Ok(vec![ipv4]), Ok(vec![ipv6])).unwrap();
+        assert_eq!(ret, vec![ipv4, ipv6]);
+        // the ipv4 only name
+        let ret = merge_addrs_results(Ok(vec![ipv4]), failed()).unwrap();
+        assert_eq!(ret, vec![ipv4]);
+        let ret = merge_addrs_results(failed(), Ok(vec![ipv6])).unwrap();
+        assert_eq!(ret, vec![ipv6]);
+        assert!(merge_addrs_results(failed(), failed()).is_err());
+    }
+}
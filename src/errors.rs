@@ -17,6 +17,26 @@ impl CanNotFoundRouterAddress {
 }
 impl Error for CanNotFoundRouterAddress {}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotVersionMismatch {
+    version: String,
+}
+impl fmt::Display for SnapshotVersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the snapshot is created by pistol {}, please create it again",
+            self.version
+        )
+    }
+}
+impl SnapshotVersionMismatch {
+    pub fn new(version: String) -> SnapshotVersionMismatch {
+        SnapshotVersionMismatch { version }
+    }
+}
+impl Error for SnapshotVersionMismatch {}
+
 /* OS DETECT ERRORS */
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -162,6 +182,178 @@ impl OsDetectPortError {
 }
 impl Error for OsDetectPortError {}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsDetectNoOpenPort;
+impl fmt::Display for OsDetectNoOpenPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no open tcp port found for os detect")
+    }
+}
+impl OsDetectNoOpenPort {
+    pub fn new() -> OsDetectNoOpenPort {
+        OsDetectNoOpenPort {}
+    }
+}
+impl Error for OsDetectNoOpenPort {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsDetectTtlIpv6Unsupported;
+impl fmt::Display for OsDetectTtlIpv6Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the ttl of the os detect probes can not be set on ipv6")
+    }
+}
+impl OsDetectTtlIpv6Unsupported {
+    pub fn new() -> OsDetectTtlIpv6Unsupported {
+        OsDetectTtlIpv6Unsupported {}
+    }
+}
+impl Error for OsDetectTtlIpv6Unsupported {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchPointsParseFailed {
+    line: String,
+}
+impl fmt::Display for MatchPointsParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid nmap-os-db match points: {}", self.line)
+    }
+}
+impl MatchPointsParseFailed {
+    pub fn new(line: String) -> MatchPointsParseFailed {
+        MatchPointsParseFailed { line }
+    }
+}
+impl Error for MatchPointsParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintParseFailed {
+    test: String,
+}
+impl fmt::Display for FingerprintParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid fingerprint test: {}", self.test)
+    }
+}
+impl FingerprintParseFailed {
+    pub fn new(test: String) -> FingerprintParseFailed {
+        FingerprintParseFailed { test }
+    }
+}
+impl Error for FingerprintParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsClassParseFailed {
+    class: String,
+}
+impl fmt::Display for OsClassParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid os class: {}", self.class)
+    }
+}
+impl OsClassParseFailed {
+    pub fn new(class: String) -> OsClassParseFailed {
+        OsClassParseFailed { class }
+    }
+}
+impl Error for OsClassParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpeParseFailed {
+    cpe: String,
+}
+impl fmt::Display for CpeParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid cpe: {}", self.cpe)
+    }
+}
+impl CpeParseFailed {
+    pub fn new(cpe: String) -> CpeParseFailed {
+        CpeParseFailed { cpe }
+    }
+}
+impl Error for CpeParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P0fParseFailed {
+    line: String,
+}
+impl fmt::Display for P0fParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid p0f db line: {}", self.line)
+    }
+}
+impl P0fParseFailed {
+    pub fn new(line: String) -> P0fParseFailed {
+        P0fParseFailed { line }
+    }
+}
+impl Error for P0fParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcapParseFailed {
+    path: String,
+}
+impl fmt::Display for PcapParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid pcap file: {}", self.path)
+    }
+}
+impl PcapParseFailed {
+    pub fn new(path: String) -> PcapParseFailed {
+        PcapParseFailed { path }
+    }
+}
+impl Error for PcapParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcapLinkTypeUnsupported {
+    linktype: u32,
+}
+impl fmt::Display for PcapLinkTypeUnsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pcap link type {} is not supported", self.linktype)
+    }
+}
+impl PcapLinkTypeUnsupported {
+    pub fn new(linktype: u32) -> PcapLinkTypeUnsupported {
+        PcapLinkTypeUnsupported { linktype }
+    }
+}
+impl Error for PcapLinkTypeUnsupported {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinearParametersInvalid {
+    reason: String,
+}
+impl fmt::Display for LinearParametersInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid linear model parameters: {}", self.reason)
+    }
+}
+impl LinearParametersInvalid {
+    pub fn new(reason: String) -> LinearParametersInvalid {
+        LinearParametersInvalid { reason }
+    }
+}
+impl Error for LinearParametersInvalid {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinearTrainFailed {
+    reason: String,
+}
+impl fmt::Display for LinearTrainFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "train linear model failed: {}", self.reason)
+    }
+}
+impl LinearTrainFailed {
+    pub fn new(reason: String) -> LinearTrainFailed {
+        LinearTrainFailed { reason }
+    }
+}
+impl Error for LinearTrainFailed {}
+
 /* layer */
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -266,3 +458,61 @@ impl OsDetectResultsNullError {
     }
 }
 impl Error for OsDetectResultsNullError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintsNullError {}
+impl fmt::Display for FingerprintsNullError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no fingerprint to build the nmap-os-db entry")
+    }
+}
+impl FingerprintsNullError {
+    pub fn new() -> FingerprintsNullError {
+        FingerprintsNullError {}
+    }
+}
+impl Error for FingerprintsNullError {}
+
+/* DNS */
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsPacketParseFailed;
+impl fmt::Display for DnsPacketParseFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dns packet is malformed or truncated")
+    }
+}
+impl DnsPacketParseFailed {
+    pub fn new() -> DnsPacketParseFailed {
+        DnsPacketParseFailed {}
+    }
+}
+impl Error for DnsPacketParseFailed {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanNotFoundNameServer;
+impl fmt::Display for CanNotFoundNameServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can not found the name server, please set the resolver")
+    }
+}
+impl CanNotFoundNameServer {
+    pub fn new() -> CanNotFoundNameServer {
+        CanNotFoundNameServer {}
+    }
+}
+impl Error for CanNotFoundNameServer {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsZoneTransferFailed;
+impl fmt::Display for DnsZoneTransferFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "dns zone transfer is refused or incomplete")
+    }
+}
+impl DnsZoneTransferFailed {
+    pub fn new() -> DnsZoneTransferFailed {
+        DnsZoneTransferFailed {}
+    }
+}
+impl Error for DnsZoneTransferFailed {}
@@ -0,0 +1,581 @@
+/* Firewall Ruleset Inference */
+use anyhow::Result;
+use log::debug;
+use log::warn;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpFlags;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::Packet;
+use prettytable::row;
+use prettytable::Cell;
+use prettytable::Row;
+use prettytable::Table;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use crate::errors::CanNotFoundSourceAddress;
+use crate::hop::tcp;
+use crate::hop::tcp6;
+use crate::hop::TraceProbe;
+use crate::scan::PortStatus;
+use crate::utils::find_source_addr;
+use crate::utils::find_source_addr6;
+use crate::utils::get_default_timeout;
+use crate::utils::get_threads_pool;
+use crate::utils::random_port;
+use crate::Target;
+
+const FIREWALL_TTL: u8 = 64;
+const FIREWALL_MAX_HOPS: u8 = 30;
+const FIREWALL_PROBE_TRIES: usize = 2;
+// stop locating the filter after so many silent hops in a row
+const FIREWALL_SILENT_HOPS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeResponse {
+    SynAck,
+    Rst {
+        window: u16,
+    },
+    TimeExceeded {
+        addr: IpAddr,
+    },
+    /// The icmp (icmpv6) destination unreachable, such as the code 13 (communication administratively prohibited).
+    Unreachable {
+        addr: IpAddr,
+        code: u8,
+    },
+    NoResponse,
+}
+
+impl ProbeResponse {
+    /// The target itself answered the probe.
+    pub fn is_answered(&self) -> bool {
+        matches!(self, ProbeResponse::SynAck | ProbeResponse::Rst { .. })
+    }
+}
+
+impl fmt::Display for ProbeResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProbeResponse::SynAck => write!(f, "syn/ack"),
+            ProbeResponse::Rst { window } => write!(f, "rst (win {})", window),
+            ProbeResponse::TimeExceeded { addr } => write!(f, "time exceeded from {}", addr),
+            ProbeResponse::Unreachable { addr, code } => {
+                write!(f, "unreachable (code {}) from {}", code, addr)
+            }
+            ProbeResponse::NoResponse => write!(f, "no response"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FirewallStatus {
+    /// The unsolicited ack is dropped, the filter keeps the connection state.
+    StatefulFilter,
+    /// The syn is dropped but the ack passes, the filter only looks at the flags.
+    StatelessFilter,
+    NoFilter,
+    /// Both the syn and the ack are dropped, the filter may keep the state or drop all,
+    /// the same as nmap ack scan filtered.
+    Filtered,
+    /// The filter rejects the probe with the icmp unreachable.
+    IcmpReject,
+}
+
+impl fmt::Display for FirewallStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            FirewallStatus::StatefulFilter => "stateful filter",
+            FirewallStatus::StatelessFilter => "stateless filter",
+            FirewallStatus::NoFilter => "no filter",
+            FirewallStatus::Filtered => "filtered",
+            FirewallStatus::IcmpReject => "rejecting with icmp",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterHop {
+    pub ttl: u8,
+    /// Only known when the filter answers with the icmp unreachable.
+    pub addr: Option<IpAddr>,
+    /// The last router which answered the time exceeded before the filter.
+    pub prev_addr: Option<IpAddr>,
+}
+
+impl fmt::Display for FilterHop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = format!("hop {}", self.ttl);
+        if let Some(addr) = self.addr {
+            s += &format!(" ({})", addr);
+        }
+        if let Some(prev_addr) = self.prev_addr {
+            s += &format!(" after {}", prev_addr);
+        }
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PortFirewallProfile {
+    pub syn: ProbeResponse,
+    pub ack: ProbeResponse,
+    /// Same as the tcp syn scan.
+    pub syn_status: PortStatus,
+    /// Same as the tcp ack scan.
+    pub ack_status: PortStatus,
+    /// Same as the tcp window scan, which reads the window of the ack rst.
+    pub window_status: PortStatus,
+    pub status: FirewallStatus,
+    pub filter_hop: Option<FilterHop>,
+}
+
+impl PortFirewallProfile {
+    pub fn new(syn: ProbeResponse, ack: ProbeResponse) -> PortFirewallProfile {
+        let syn_status = match syn {
+            ProbeResponse::SynAck => PortStatus::Open,
+            ProbeResponse::Rst { .. } => PortStatus::Closed,
+            _ => PortStatus::Filtered,
+        };
+        let ack_status = match ack {
+            ProbeResponse::Rst { .. } => PortStatus::Unfiltered,
+            _ => PortStatus::Filtered,
+        };
+        let window_status = match ack {
+            ProbeResponse::Rst { window } => {
+                if window > 0 {
+                    PortStatus::Open
+                } else {
+                    PortStatus::Closed
+                }
+            }
+            _ => PortStatus::Filtered,
+        };
+        PortFirewallProfile {
+            syn,
+            ack,
+            syn_status,
+            ack_status,
+            window_status,
+            status: classify(syn, ack),
+            filter_hop: None,
+        }
+    }
+}
+
+/// Correlate the syn and ack responses of the port.
+fn classify(syn: ProbeResponse, ack: ProbeResponse) -> FirewallStatus {
+    match (syn, ack) {
+        (ProbeResponse::Unreachable { .. }, _) | (_, ProbeResponse::Unreachable { .. }) => {
+            FirewallStatus::IcmpReject
+        }
+        _ => match (syn.is_answered(), ack.is_answered()) {
+            (true, true) => FirewallStatus::NoFilter,
+            // the syn passes and the unsolicited ack is dropped
+            (true, false) => FirewallStatus::StatefulFilter,
+            // the filter drops the syn by the flags, the ack looks like an established connection
+            (false, true) => FirewallStatus::StatelessFilter,
+            // nothing tells the kind of the filter
+            (false, false) => FirewallStatus::Filtered,
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FirewallProfileResults {
+    pub profiles: HashMap<IpAddr, HashMap<u16, PortFirewallProfile>>,
+}
+
+impl Default for FirewallProfileResults {
+    fn default() -> FirewallProfileResults {
+        FirewallProfileResults::new()
+    }
+}
+
+impl FirewallProfileResults {
+    pub fn new() -> FirewallProfileResults {
+        FirewallProfileResults {
+            profiles: HashMap::new(),
+        }
+    }
+    pub fn get(&self, k: &IpAddr) -> Option<&HashMap<u16, PortFirewallProfile>> {
+        self.profiles.get(k)
+    }
+    fn insert(&mut self, dst_addr: IpAddr, dst_port: u16, profile: PortFirewallProfile) {
+        self.profiles
+            .entry(dst_addr)
+            .or_default()
+            .insert(dst_port, profile);
+    }
+}
+
+impl fmt::Display for FirewallProfileResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Firewall Profile Results")
+            .style_spec("c")
+            .with_hspan(6)]));
+
+        let mut status_num: BTreeMap<String, usize> = BTreeMap::new();
+        let profiles = &self.profiles;
+        let profiles: BTreeMap<IpAddr, &HashMap<u16, PortFirewallProfile>> =
+            profiles.iter().map(|(i, p)| (*i, p)).collect();
+        for (ip, ports_profile) in profiles {
+            let ports_profile: BTreeMap<u16, &PortFirewallProfile> =
+                ports_profile.iter().map(|(p, f)| (*p, f)).collect();
+            for (port, pfp) in ports_profile {
+                let filter_hop_str = match pfp.filter_hop {
+                    Some(h) => h.to_string(),
+                    None => String::new(),
+                };
+                table.add_row(
+                    row![c -> ip, c -> port, c -> pfp.syn, c -> pfp.ack, c -> pfp.status, c -> filter_hop_str],
+                );
+                *status_num.entry(pfp.status.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        let status_str: Vec<String> = status_num
+            .iter()
+            .map(|(s, n)| format!("{}: {}", s, n))
+            .collect();
+        let summary = format!("Summary:\n{}", status_str.join("\n"));
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(6)]));
+        write!(f, "{}", table)
+    }
+}
+
+fn parse_probe_response(buff: &[u8]) -> ProbeResponse {
+    let (src_addr, protocol, payload, ipv6) = match buff.first().map(|b| b >> 4) {
+        Some(4) => match Ipv4Packet::new(buff) {
+            Some(ipv4_packet) => (
+                IpAddr::V4(ipv4_packet.get_source()),
+                ipv4_packet.get_next_level_protocol(),
+                ipv4_packet.payload().to_vec(),
+                false,
+            ),
+            None => return ProbeResponse::NoResponse,
+        },
+        Some(6) => match Ipv6Packet::new(buff) {
+            Some(ipv6_packet) => (
+                IpAddr::V6(ipv6_packet.get_source()),
+                ipv6_packet.get_next_header(),
+                ipv6_packet.payload().to_vec(),
+                true,
+            ),
+            None => return ProbeResponse::NoResponse,
+        },
+        _ => return ProbeResponse::NoResponse,
+    };
+    match protocol {
+        IpNextHeaderProtocols::Tcp => match TcpPacket::new(&payload) {
+            Some(tcp_packet) => {
+                let tcp_flags = tcp_packet.get_flags();
+                if tcp_flags & TcpFlags::RST != 0 {
+                    ProbeResponse::Rst {
+                        window: tcp_packet.get_window(),
+                    }
+                } else if tcp_flags & (TcpFlags::SYN | TcpFlags::ACK)
+                    == TcpFlags::SYN | TcpFlags::ACK
+                {
+                    ProbeResponse::SynAck
+                } else {
+                    ProbeResponse::NoResponse
+                }
+            }
+            None => ProbeResponse::NoResponse,
+        },
+        IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => {
+            let (icmp_type, icmp_code) = match (payload.first(), payload.get(1)) {
+                (Some(t), Some(c)) => (*t, *c),
+                _ => return ProbeResponse::NoResponse,
+            };
+            // icmp: 3 unreachable and 11 time exceeded, icmpv6: 1 and 3
+            match (ipv6, icmp_type) {
+                (false, 3) | (true, 1) => ProbeResponse::Unreachable {
+                    addr: src_addr,
+                    code: icmp_code,
+                },
+                (false, 11) | (true, 3) => ProbeResponse::TimeExceeded { addr: src_addr },
+                _ => ProbeResponse::NoResponse,
+            }
+        }
+        _ => ProbeResponse::NoResponse,
+    }
+}
+
+fn send_probe(
+    src_addr: IpAddr,
+    src_port: u16,
+    dst_addr: IpAddr,
+    dst_port: u16,
+    tcp_flags: u8,
+    ttl: u8,
+    timeout: Duration,
+) -> Result<ProbeResponse> {
+    // the ttl tells apart the firewalking probes of the same ports
+    let probe = TraceProbe {
+        ttl,
+        probe_id: ttl as u16,
+        flow_label: src_port as u32,
+    };
+    for _ in 0..FIREWALL_PROBE_TRIES {
+        let (ret, _rtt) = match (src_addr, dst_addr) {
+            (IpAddr::V4(src_ipv4), IpAddr::V4(dst_ipv4)) => tcp::send_tcp_trace_packet(
+                src_ipv4, src_port, dst_ipv4, dst_port, tcp_flags, probe, timeout,
+            )?,
+            (IpAddr::V6(src_ipv6), IpAddr::V6(dst_ipv6)) => tcp6::send_tcp_trace_packet(
+                src_ipv6, src_port, dst_ipv6, dst_port, tcp_flags, probe, timeout,
+            )?,
+            _ => return Err(CanNotFoundSourceAddress::new().into()),
+        };
+        if let Some(buff) = ret {
+            return Ok(parse_probe_response(&buff));
+        }
+    }
+    Ok(ProbeResponse::NoResponse)
+}
+
+/// Firewalking, send the dropped probe with the increasing ttl,
+/// the filter is right after the last router which answers the time exceeded.
+/// The routers which never answer the time exceeded make the filter look closer than it is.
+fn locate_filter(
+    src_addr: IpAddr,
+    src_port: Option<u16>,
+    dst_addr: IpAddr,
+    dst_port: u16,
+    tcp_flags: u8,
+    max_hops: u8,
+    timeout: Duration,
+) -> Result<Option<FilterHop>> {
+    let mut last_router: Option<(u8, IpAddr)> = None;
+    let mut silent_hops = 0;
+    for ttl in 1..=max_hops {
+        let src_port = match src_port {
+            Some(p) => p,
+            None => random_port(),
+        };
+        let response = send_probe(
+            src_addr, src_port, dst_addr, dst_port, tcp_flags, ttl, timeout,
+        )?;
+        debug!(
+            "locate filter {}:{} ttl {}: {}",
+            dst_addr, dst_port, ttl, response
+        );
+        match response {
+            ProbeResponse::TimeExceeded { addr } => {
+                last_router = Some((ttl, addr));
+                silent_hops = 0;
+            }
+            ProbeResponse::Unreachable { addr, .. } => {
+                let filter_hop = FilterHop {
+                    ttl,
+                    addr: Some(addr),
+                    prev_addr: last_router.map(|(_, a)| a),
+                };
+                return Ok(Some(filter_hop));
+            }
+            // the probe reaches the target this time
+            ProbeResponse::SynAck | ProbeResponse::Rst { .. } => return Ok(None),
+            ProbeResponse::NoResponse => {
+                silent_hops += 1;
+                if silent_hops >= FIREWALL_SILENT_HOPS {
+                    break;
+                }
+            }
+        }
+    }
+    match last_router {
+        Some((ttl, addr)) => {
+            let filter_hop = FilterHop {
+                ttl: ttl + 1,
+                addr: None,
+                prev_addr: Some(addr),
+            };
+            Ok(Some(filter_hop))
+        }
+        None => Ok(None),
+    }
+}
+
+fn profile_port(
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    dst_addr: IpAddr,
+    dst_port: u16,
+    locate: bool,
+    max_hops: u8,
+    timeout: Duration,
+) -> Result<PortFirewallProfile> {
+    let src_addr: IpAddr = match dst_addr {
+        IpAddr::V4(dst_ipv4) => match find_source_addr(src_addr, dst_ipv4)? {
+            Some(s) => s.into(),
+            None => return Err(CanNotFoundSourceAddress::new().into()),
+        },
+        IpAddr::V6(dst_ipv6) => match find_source_addr6(src_addr, dst_ipv6)? {
+            Some(s) => s.into(),
+            None => return Err(CanNotFoundSourceAddress::new().into()),
+        },
+    };
+    let get_src_port = || match src_port {
+        Some(p) => p,
+        None => random_port(),
+    };
+    // the ack probe is the same as the tcp ack scan and the tcp window scan
+    let syn = send_probe(
+        src_addr,
+        get_src_port(),
+        dst_addr,
+        dst_port,
+        TcpFlags::SYN,
+        FIREWALL_TTL,
+        timeout,
+    )?;
+    let ack = send_probe(
+        src_addr,
+        get_src_port(),
+        dst_addr,
+        dst_port,
+        TcpFlags::ACK,
+        FIREWALL_TTL,
+        timeout,
+    )?;
+    let mut profile = PortFirewallProfile::new(syn, ack);
+    if locate && profile.status != FirewallStatus::NoFilter {
+        // trace with the probe which did not reach the target
+        let tcp_flags = if syn.is_answered() {
+            TcpFlags::ACK
+        } else {
+            TcpFlags::SYN
+        };
+        profile.filter_hop = locate_filter(
+            src_addr, src_port, dst_addr, dst_port, tcp_flags, max_hops, timeout,
+        )?;
+    }
+    Ok(profile)
+}
+
+/// Firewall Profile.
+/// Send the tcp syn and ack probes (the ack rst is also read as the tcp window scan) to every port and correlate them:
+/// both answered is no filter, only the syn answered means the unsolicited ack is dropped by a stateful filter,
+/// only the ack answered means a stateless filter drops the syn by its flags, and the icmp unreachable means rejecting with icmp.
+/// With `locate_filter`, the filtered ports are traced with the increasing ttl (up to `max_hops`, default 30) to find which hop filters.
+pub fn firewall_profile(
+    target: Target,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    locate_filter: bool,
+    max_hops: Option<u8>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<FirewallProfileResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let max_hops = match max_hops {
+        Some(m) => m,
+        None => FIREWALL_MAX_HOPS,
+    };
+    let pool = get_threads_pool(threads_num);
+    let (tx, rx) = channel();
+    let mut recv_size = 0;
+    for host in target.hosts {
+        let dst_addr = host.addr;
+        for dst_port in host.ports {
+            let tx = tx.clone();
+            recv_size += 1;
+            pool.execute(move || {
+                let profile_ret = profile_port(
+                    src_addr,
+                    src_port,
+                    dst_addr,
+                    dst_port,
+                    locate_filter,
+                    max_hops,
+                    timeout,
+                );
+                let _ = tx.send((dst_addr, dst_port, profile_ret));
+            });
+        }
+    }
+
+    let mut ret = FirewallProfileResults::new();
+    let iter = rx.into_iter().take(recv_size);
+    for (dst_addr, dst_port, profile_ret) in iter {
+        match profile_ret {
+            Ok(profile) => ret.insert(dst_addr, dst_port, profile),
+            Err(e) => warn!("firewall profile error: {}", e),
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Host;
+    use std::net::Ipv4Addr;
+    #[test]
+    fn test_parse_probe_response() {
+        // ipv4 tcp rst with window 0 from 192.168.1.1
+        let mut buff = vec![
+            0x45, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x40, 0x06, 0x00, 0x00, 192, 168, 1, 1,
+            192, 168, 1, 2,
+        ];
+        buff.extend([
+            0, 80, 0x30, 0x39, 0, 0, 0, 0, 0, 0, 0, 0, 0x50, 0x04, 0, 0, 0, 0, 0, 0,
+        ]);
+        assert_eq!(
+            parse_probe_response(&buff),
+            ProbeResponse::Rst { window: 0 }
+        );
+        // ipv4 icmp communication administratively prohibited from 10.0.0.1
+        let mut buff = vec![
+            0x45, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 10, 0, 0, 1,
+            192, 168, 1, 2,
+        ];
+        buff.extend([3, 13, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            parse_probe_response(&buff),
+            ProbeResponse::Unreachable {
+                addr: Ipv4Addr::new(10, 0, 0, 1).into(),
+                code: 13
+            }
+        );
+    }
+    #[test]
+    fn test_classify() {
+        let rst = ProbeResponse::Rst { window: 0 };
+        let unreachable = ProbeResponse::Unreachable {
+            addr: Ipv4Addr::new(10, 0, 0, 1).into(),
+            code: 13,
+        };
+        let none = ProbeResponse::NoResponse;
+        let ret = PortFirewallProfile::new(ProbeResponse::SynAck, rst);
+        assert_eq!(ret.status, FirewallStatus::NoFilter);
+        assert_eq!(ret.syn_status, PortStatus::Open);
+        assert_eq!(ret.window_status, PortStatus::Closed);
+        assert_eq!(classify(rst, none), FirewallStatus::StatefulFilter);
+        assert_eq!(classify(none, rst), FirewallStatus::StatelessFilter);
+        assert_eq!(classify(none, none), FirewallStatus::Filtered);
+        assert_eq!(classify(unreachable, none), FirewallStatus::IcmpReject);
+    }
+    #[test]
+    fn test_firewall_profile() -> Result<()> {
+        let host = Host::new(Ipv4Addr::new(114, 114, 114, 114).into(), Some(vec![53, 80]));
+        let target = Target::new(vec![host]);
+        let timeout = Some(Duration::new(1, 0));
+        let ret = firewall_profile(target, None, None, true, Some(16), 4, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+}
@@ -1,14 +1,965 @@
 use anyhow::Result;
 use log::debug;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::Packet;
+use prettytable::row;
+use prettytable::Cell;
+use prettytable::Row;
+use prettytable::Table;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
+use std::sync::mpsc::channel;
 use std::time::Duration;
 
+use crate::errors::CanNotFoundSourceAddress;
 use crate::hop::icmp::send_icmp_ping_packet;
 use crate::hop::icmpv6::send_icmpv6_ping_packet;
+use crate::layers::ETHERNET_BUFF_SIZE;
+use crate::layers::ETHERNET_HEADER_SIZE;
+use crate::utils::find_source_addr;
+use crate::utils::find_source_addr6;
+use crate::utils::get_default_timeout;
+use crate::utils::get_threads_pool;
+use crate::utils::random_port;
 
 pub mod icmp;
 pub mod icmpv6;
+pub mod tcp;
+pub mod tcp6;
+pub mod udp;
+pub mod udp6;
+
+const TRACEROUTE_MAX_HOPS: u8 = 30;
+const TRACEROUTE_QUERIES: usize = 3;
+// the classic traceroute udp ports start from 33434 and increase with every probe
+const TRACEROUTE_UDP_BASE_PORT: u16 = 33434;
+const TRACEROUTE_TCP_PORT: u16 = 80;
+const MULTIPATH_MAX_FLOWS: usize = 64;
+// the mtu of ethernet
+const PMTU_DEFAULT_MAX: usize = 1500;
+const PMTU_IPV4_MIN: usize = 68;
+const PMTU_IPV6_MIN: usize = 1280;
+const PMTU_PROBE_TRIES: usize = 2;
+// the number of flows needed to reject the k + 1 next hops hypothesis with 95% confidence,
+// when k next hops are found (index), see the multipath detection algorithm (Veitch et al.)
+const MDA_STOPPING_POINTS: [usize; 17] = [
+    1, 6, 11, 16, 21, 27, 33, 38, 44, 51, 57, 63, 70, 76, 83, 90, 96,
+];
+
+/// The one's complement sum of two 16 bits words, the arithmetic of the internet checksum.
+fn ones_complement_add(a: u16, b: u16) -> u16 {
+    let sum = a as u32 + b as u32;
+    ((sum & 0xFFFF) + (sum >> 16)) as u16
+}
+
+/// The word which makes the one's complement sum of `word` and itself equal to `sum`,
+/// so the checksum does not change when the `word` changes.
+fn compensate_word(sum: u16, word: u16) -> u16 {
+    ones_complement_add(sum, !word)
+}
+
+/// The data word which turns the `checksum` computed with this word zero into the `target`.
+fn checksum_fix_word(checksum: u16, target: u16) -> u16 {
+    ones_complement_add(!target, checksum)
+}
+
+fn mda_stopping_point(next_hops: usize) -> usize {
+    match MDA_STOPPING_POINTS.get(next_hops) {
+        Some(n) => *n,
+        None => MDA_STOPPING_POINTS[MDA_STOPPING_POINTS.len() - 1],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TracerouteMethod {
+    Icmp,
+    Udp,
+    TcpSyn,
+}
+
+impl fmt::Display for TracerouteMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TracerouteMethod::Icmp => "icmp",
+            TracerouteMethod::Udp => "udp",
+            TracerouteMethod::TcpSyn => "tcp syn",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The ttl (hop limit) and the ids of one probe, see `send_trace_probe`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceProbe {
+    pub ttl: u8,
+    /// Tells apart the probes with the same ports,
+    /// carried in the icmp sequence, the udp checksum or the tcp sequence number.
+    pub probe_id: u16,
+    /// The flow label of the ipv6 probe, not used on ipv4.
+    pub flow_label: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HopProbe {
+    pub addr: Option<IpAddr>,
+    pub rtt: Option<Duration>,
+    // the icmp (icmpv6) type and code of the response, none if the target answered with tcp
+    pub icmp_type: Option<u8>,
+    pub icmp_code: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hop {
+    pub ttl: u8,
+    pub probes: Vec<HopProbe>,
+}
+
+impl Hop {
+    /// All the addresses responded at this hop, more than one if the path is load balanced.
+    pub fn addrs(&self) -> Vec<IpAddr> {
+        let mut ret = Vec::new();
+        for p in &self.probes {
+            if let Some(addr) = p.addr {
+                if !ret.contains(&addr) {
+                    ret.push(addr);
+                }
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TracerouteResults {
+    pub dst_addr: IpAddr,
+    pub method: TracerouteMethod,
+    pub hops: Vec<Hop>,
+    pub reached: bool,
+}
+
+impl TracerouteResults {
+    pub fn new(dst_addr: IpAddr, method: TracerouteMethod) -> TracerouteResults {
+        TracerouteResults {
+            dst_addr,
+            method,
+            hops: Vec::new(),
+            reached: false,
+        }
+    }
+}
+
+impl fmt::Display for TracerouteResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Traceroute Results")
+            .style_spec("c")
+            .with_hspan(4)]));
+
+        for hop in &self.hops {
+            let addrs_str: Vec<String> = hop.addrs().iter().map(|a| a.to_string()).collect();
+            let addrs_str = if !addrs_str.is_empty() {
+                addrs_str.join("\n")
+            } else {
+                String::from("*")
+            };
+            let rtts_str: Vec<String> = hop
+                .probes
+                .iter()
+                .map(|p| match p.rtt {
+                    Some(rtt) => format!("{:.1}ms", rtt.as_secs_f64() * 1000.0),
+                    None => String::from("*"),
+                })
+                .collect();
+            let rtts_str = rtts_str.join(" ");
+            let mut types_str: Vec<String> = Vec::new();
+            for p in &hop.probes {
+                let t = match (p.addr, p.icmp_type, p.icmp_code) {
+                    (Some(_), Some(t), Some(c)) => format!("{}/{}", t, c),
+                    (Some(_), _, _) => String::from("tcp"),
+                    (None, _, _) => continue,
+                };
+                if !types_str.contains(&t) {
+                    types_str.push(t);
+                }
+            }
+            let types_str = types_str.join("|");
+            table.add_row(row![c -> hop.ttl, c -> addrs_str, c -> rtts_str, c -> types_str]);
+        }
+
+        let summary = format!(
+            "Summary:\ndst: {} ({}), hops: {}, reached: {}",
+            self.dst_addr,
+            self.method,
+            self.hops.len(),
+            self.reached
+        );
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(4)]));
+
+        write!(f, "{}", table)
+    }
+}
+
+/// A link between two interfaces of the adjacent hops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PathLink {
+    pub ttl: u8,
+    pub from: IpAddr,
+    pub to: IpAddr,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipathResults {
+    pub dst_addr: IpAddr,
+    pub method: TracerouteMethod,
+    // the interfaces found at every ttl
+    pub hops: BTreeMap<u8, Vec<IpAddr>>,
+    // the path graph
+    pub links: Vec<PathLink>,
+    // the path of every flow
+    pub paths: Vec<TracerouteResults>,
+    pub reached: bool,
+}
+
+impl MultipathResults {
+    pub fn new(dst_addr: IpAddr, method: TracerouteMethod) -> MultipathResults {
+        MultipathResults {
+            dst_addr,
+            method,
+            hops: BTreeMap::new(),
+            links: Vec::new(),
+            paths: Vec::new(),
+            reached: false,
+        }
+    }
+    /// The max number of the interfaces at one hop.
+    pub fn max_width(&self) -> usize {
+        let mut ret = 0;
+        for addrs in self.hops.values() {
+            if addrs.len() > ret {
+                ret = addrs.len();
+            }
+        }
+        ret
+    }
+    /// The number of flows needed by the stopping rule of the MDA, checked at every interface (and the source):
+    /// the flows through the interface which found its next hop must reach the stopping point of the number of next hops,
+    /// the flows are spread over the interfaces so the total is scaled by the share of this interface.
+    /// The interfaces without any next hop found (the target, before the silent hops) are skipped.
+    pub fn flows_needed(&self) -> usize {
+        // (ttl, interface) => (the flows through it with a next hop, the next hops), ttl 0 is the source
+        let mut next_hops: BTreeMap<(u8, Option<IpAddr>), (usize, Vec<IpAddr>)> = BTreeMap::new();
+        for path in &self.paths {
+            let mut prev = Some((0, None));
+            for hop in &path.hops {
+                let addr = hop.addrs().first().copied();
+                if let (Some(key), Some(addr)) = (prev, addr) {
+                    let e = next_hops.entry(key).or_insert((0, Vec::new()));
+                    e.0 += 1;
+                    if !e.1.contains(&addr) {
+                        e.1.push(addr);
+                    }
+                }
+                prev = addr.map(|addr| (hop.ttl, Some(addr)));
+            }
+        }
+        let total = self.paths.len();
+        // one flow finds one next hop at least
+        let mut ret = total.max(mda_stopping_point(1));
+        for (flows, addrs) in next_hops.values() {
+            let stopping_point = mda_stopping_point(addrs.len());
+            if *flows < stopping_point {
+                let needed = (stopping_point * total).div_ceil(*flows);
+                ret = ret.max(needed);
+            }
+        }
+        ret
+    }
+    fn insert(&mut self, path: TracerouteResults) {
+        let mut prev: Option<(u8, Vec<IpAddr>)> = None;
+        for hop in &path.hops {
+            let addrs = hop.addrs();
+            if addrs.is_empty() {
+                // the link over the silent hop is unknown
+                prev = None;
+                continue;
+            }
+            let hop_addrs = self.hops.entry(hop.ttl).or_default();
+            for addr in &addrs {
+                if !hop_addrs.contains(addr) {
+                    hop_addrs.push(*addr);
+                }
+            }
+            if let Some((ttl, prev_addrs)) = prev {
+                for from in &prev_addrs {
+                    for to in &addrs {
+                        let link = PathLink {
+                            ttl,
+                            from: *from,
+                            to: *to,
+                        };
+                        if !self.links.contains(&link) {
+                            self.links.push(link);
+                        }
+                    }
+                }
+            }
+            prev = Some((hop.ttl, addrs));
+        }
+        if path.reached {
+            self.reached = true;
+        }
+        self.paths.push(path);
+    }
+}
+
+impl fmt::Display for MultipathResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Multipath Traceroute Results")
+            .style_spec("c")
+            .with_hspan(3)]));
+
+        for (ttl, addrs) in &self.hops {
+            let addrs_str: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+            let addrs_str = addrs_str.join("\n");
+            let mut links_str = Vec::new();
+            for link in &self.links {
+                if link.ttl == *ttl {
+                    links_str.push(format!("{} -> {}", link.from, link.to));
+                }
+            }
+            let links_str = links_str.join("\n");
+            table.add_row(row![c -> ttl, c -> addrs_str, c -> links_str]);
+        }
+
+        let summary = format!(
+            "Summary:\ndst: {} ({}), flows: {}, max width: {}, reached: {}",
+            self.dst_addr,
+            self.method,
+            self.paths.len(),
+            self.max_width(),
+            self.reached
+        );
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(3)]));
+
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmtuResults {
+    pub dst_addr: IpAddr,
+    // none if the target does not answer even the smallest probe
+    pub mtu: Option<u16>,
+    // the hop which enforces the mtu
+    pub hop_addr: Option<IpAddr>,
+    pub hop_ttl: Option<u8>,
+    // the big packets are dropped silently without the fragmentation needed (packet too big)
+    pub black_hole: bool,
+}
+
+impl PmtuResults {
+    pub fn new(dst_addr: IpAddr) -> PmtuResults {
+        PmtuResults {
+            dst_addr,
+            mtu: None,
+            hop_addr: None,
+            hop_ttl: None,
+            black_hole: false,
+        }
+    }
+}
+
+impl fmt::Display for PmtuResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Path MTU Discovery Results")
+            .style_spec("c")
+            .with_hspan(4)]));
+
+        let mtu_str = match self.mtu {
+            Some(m) => m.to_string(),
+            None => String::from("unknown"),
+        };
+        let hop_addr_str = match self.hop_addr {
+            Some(a) => a.to_string(),
+            None => String::new(),
+        };
+        let hop_ttl_str = match self.hop_ttl {
+            Some(t) => t.to_string(),
+            None => String::new(),
+        };
+        table.add_row(row![c -> "dst", c -> "mtu", c -> "hop", c -> "ttl"]);
+        table.add_row(row![c -> self.dst_addr, c -> mtu_str, c -> hop_addr_str, c -> hop_ttl_str]);
+
+        let summary = format!("Summary:\nblack hole: {}", self.black_hole);
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(4)]));
+
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PmtuProbeStatus {
+    Fits,
+    // the router and the mtu of the next hop it reported (0 from the old routers)
+    TooBig(IpAddr, u32),
+    TimeExceeded(IpAddr),
+    Dropped,
+}
+
+/// Returns the mtu in the fragmentation needed (icmp 3/4) or packet too big (icmpv6 2) message.
+fn parse_mtu_response(buff: &[u8]) -> Option<(IpAddr, u32)> {
+    let version = buff.first()? >> 4;
+    match version {
+        4 => {
+            let ipv4_packet = Ipv4Packet::new(buff)?;
+            if ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Icmp {
+                return None;
+            }
+            let payload = ipv4_packet.payload();
+            match (payload.first(), payload.get(1), payload.get(6..8)) {
+                (Some(3), Some(4), Some(m)) => Some((
+                    ipv4_packet.get_source().into(),
+                    u16::from_be_bytes([m[0], m[1]]) as u32,
+                )),
+                (_, _, _) => None,
+            }
+        }
+        6 => {
+            let ipv6_packet = Ipv6Packet::new(buff)?;
+            if ipv6_packet.get_next_header() != IpNextHeaderProtocols::Icmpv6 {
+                return None;
+            }
+            let payload = ipv6_packet.payload();
+            match (payload.first(), payload.get(4..8)) {
+                (Some(2), Some(m)) => Some((
+                    ipv6_packet.get_source().into(),
+                    u32::from_be_bytes([m[0], m[1], m[2], m[3]]),
+                )),
+                (_, _) => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn send_pmtu_probe(
+    src_addr: IpAddr,
+    dst_addr: IpAddr,
+    ttl: u8,
+    size: usize,
+    identifier: u16,
+    sequence: u16,
+    timeout: Duration,
+) -> Result<PmtuProbeStatus> {
+    // one lost packet should not be taken as the black hole
+    let probe = TraceProbe {
+        ttl,
+        probe_id: sequence,
+        flow_label: identifier as u32,
+    };
+    for _ in 0..PMTU_PROBE_TRIES {
+        let (response, _rtt) = match (src_addr, dst_addr) {
+            (IpAddr::V4(src_ipv4), IpAddr::V4(dst_ipv4)) => {
+                icmp::send_icmp_size_packet(src_ipv4, dst_ipv4, size, identifier, probe, timeout)?
+            }
+            (IpAddr::V6(src_ipv6), IpAddr::V6(dst_ipv6)) => icmpv6::send_icmpv6_size_packet(
+                src_ipv6, dst_ipv6, size, identifier, probe, timeout,
+            )?,
+            (_, _) => return Err(CanNotFoundSourceAddress::new().into()),
+        };
+        if let Some(r) = response {
+            if let Some((addr, mtu)) = parse_mtu_response(&r) {
+                return Ok(PmtuProbeStatus::TooBig(addr, mtu));
+            }
+            match parse_hop_response(&r) {
+                // icmp 11 and icmpv6 3
+                Some((addr, Some(11), _)) | Some((addr, Some(3), _)) if addr != dst_addr => {
+                    return Ok(PmtuProbeStatus::TimeExceeded(addr))
+                }
+                // the echo reply or any other answer from the target
+                _ => return Ok(PmtuProbeStatus::Fits),
+            }
+        }
+    }
+    Ok(PmtuProbeStatus::Dropped)
+}
+
+/// The largest probe size to search, the jumbo mtu is clamped to the packet which the send buffer can hold.
+fn pmtu_max_size(max_mtu: Option<u16>, min_mtu: usize) -> usize {
+    let max_mtu = match max_mtu {
+        Some(m) => m as usize,
+        None => PMTU_DEFAULT_MAX,
+    };
+    max_mtu
+        .min(ETHERNET_BUFF_SIZE - ETHERNET_HEADER_SIZE)
+        .max(min_mtu)
+}
+
+/// Path MTU Discovery.
+/// Send the icmp echo requests with the DF bit (ipv6 routers never fragment), and binary search the largest size
+/// which reaches the target, the fragmentation needed (ipv4) or packet too big (ipv6) messages tell the mtu of the next hop
+/// and the router which enforces it.
+/// If the big packets are dropped silently (the mtu black hole, common on the vpn links),
+/// the big probes are sent again with the increasing ttl to find the last router they reach.
+/// The `max_mtu` is the mtu of the local link (default 1500), the jumbo mtu is searched up to 4082 bytes.
+pub fn path_mtu_discovery(
+    dst_addr: IpAddr,
+    src_addr: Option<IpAddr>,
+    max_mtu: Option<u16>,
+    max_hops: Option<u8>,
+    timeout: Option<Duration>,
+) -> Result<PmtuResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let max_hops = match max_hops {
+        Some(m) => m,
+        None => TRACEROUTE_MAX_HOPS,
+    };
+    let min_mtu = match dst_addr {
+        IpAddr::V4(_) => PMTU_IPV4_MIN,
+        IpAddr::V6(_) => PMTU_IPV6_MIN,
+    };
+    let max_mtu = pmtu_max_size(max_mtu, min_mtu);
+    let src_addr = get_trace_source_addr(dst_addr, src_addr)?;
+    let identifier = random_port();
+    let mut sequence: u16 = 0;
+    let mut ret = PmtuResults::new(dst_addr);
+
+    let mut probe = |ttl: u8, size: usize| -> Result<PmtuProbeStatus> {
+        sequence = sequence.wrapping_add(1);
+        let status = send_pmtu_probe(src_addr, dst_addr, ttl, size, identifier, sequence, timeout)?;
+        debug!("pmtu probe ttl {} size {} => {:?}", ttl, size, status);
+        Ok(status)
+    };
+
+    // the largest size known to fit and the smallest size known to fail
+    let mut lo = min_mtu;
+    let mut hi = max_mtu + 1;
+    match probe(max_hops, lo)? {
+        PmtuProbeStatus::Fits => (),
+        // the target is not reachable at all
+        _ => return Ok(ret),
+    }
+    let mut size = max_mtu;
+    while lo + 1 < hi {
+        match probe(max_hops, size)? {
+            PmtuProbeStatus::Fits => lo = size,
+            PmtuProbeStatus::TooBig(addr, mtu) => {
+                hi = size;
+                ret.hop_addr = Some(addr);
+                ret.black_hole = false;
+                let mtu = mtu as usize;
+                if mtu >= lo && mtu < size {
+                    // trust the reported mtu and check it next
+                    size = mtu;
+                    continue;
+                }
+            }
+            PmtuProbeStatus::TimeExceeded(_) | PmtuProbeStatus::Dropped => {
+                hi = size;
+                if ret.hop_addr.is_none() {
+                    ret.black_hole = true;
+                }
+            }
+        }
+        size = (lo + hi) / 2;
+    }
+    ret.mtu = Some(lo as u16);
+
+    if ret.black_hole && lo < max_mtu {
+        // find the last router which the big packet reaches
+        let mut last_addr = None;
+        for ttl in 1..=max_hops {
+            match probe(ttl, lo + 1)? {
+                PmtuProbeStatus::TimeExceeded(addr) => last_addr = Some(addr),
+                PmtuProbeStatus::Dropped => match probe(ttl, lo)? {
+                    PmtuProbeStatus::Dropped => (), // silent router
+                    _ => {
+                        // the small packet goes further, the hop before drops the big one
+                        ret.hop_addr = last_addr;
+                        ret.hop_ttl = Some(ttl - 1);
+                        break;
+                    }
+                },
+                _ => break,
+            }
+        }
+    } else if ret.hop_addr.is_some() {
+        // find the distance of the router which reported the mtu, the big packet expires on it
+        // or it answers the fragmentation needed once the ttl reaches past it
+        for ttl in 1..=max_hops {
+            match probe(ttl, lo + 1)? {
+                PmtuProbeStatus::TimeExceeded(addr) if Some(addr) == ret.hop_addr => {
+                    ret.hop_ttl = Some(ttl);
+                    break;
+                }
+                PmtuProbeStatus::TooBig(_, _) => {
+                    ret.hop_ttl = Some(ttl.saturating_sub(1).max(1));
+                    break;
+                }
+                PmtuProbeStatus::Fits => break,
+                _ => (),
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Returns the source address of the response and its icmp type and code.
+fn parse_hop_response(buff: &[u8]) -> Option<(IpAddr, Option<u8>, Option<u8>)> {
+    let version = buff.first()? >> 4;
+    match version {
+        4 => {
+            let ipv4_packet = Ipv4Packet::new(buff)?;
+            let addr = ipv4_packet.get_source().into();
+            match ipv4_packet.get_next_level_protocol() {
+                IpNextHeaderProtocols::Icmp => {
+                    let payload = ipv4_packet.payload();
+                    Some((addr, payload.first().copied(), payload.get(1).copied()))
+                }
+                _ => Some((addr, None, None)),
+            }
+        }
+        6 => {
+            let ipv6_packet = Ipv6Packet::new(buff)?;
+            let addr = ipv6_packet.get_source().into();
+            match ipv6_packet.get_next_header() {
+                IpNextHeaderProtocols::Icmpv6 => {
+                    let payload = ipv6_packet.payload();
+                    Some((addr, payload.first().copied(), payload.get(1).copied()))
+                }
+                _ => Some((addr, None, None)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The icmp identifier takes the place of the `src_port`, and the `probe.probe_id` is the icmp sequence,
+/// see `get_probe_ports`.
+fn send_trace_probe(
+    src_addr: IpAddr,
+    dst_addr: IpAddr,
+    method: TracerouteMethod,
+    src_port: u16,
+    dst_port: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    match (src_addr, dst_addr) {
+        (IpAddr::V4(src_ipv4), IpAddr::V4(dst_ipv4)) => match method {
+            TracerouteMethod::Icmp => {
+                icmp::send_icmp_trace_packet(src_ipv4, dst_ipv4, src_port, probe, timeout)
+            }
+            TracerouteMethod::Udp => {
+                udp::send_udp_trace_packet(src_ipv4, src_port, dst_ipv4, dst_port, probe, timeout)
+            }
+            TracerouteMethod::TcpSyn => {
+                tcp::send_syn_trace_packet(src_ipv4, src_port, dst_ipv4, dst_port, probe, timeout)
+            }
+        },
+        (IpAddr::V6(src_ipv6), IpAddr::V6(dst_ipv6)) => match method {
+            TracerouteMethod::Icmp => {
+                icmpv6::send_icmpv6_trace_packet(src_ipv6, dst_ipv6, src_port, probe, timeout)
+            }
+            TracerouteMethod::Udp => {
+                udp6::send_udp_trace_packet(src_ipv6, src_port, dst_ipv6, dst_port, probe, timeout)
+            }
+            TracerouteMethod::TcpSyn => {
+                tcp6::send_syn_trace_packet(src_ipv6, src_port, dst_ipv6, dst_port, probe, timeout)
+            }
+        },
+        _ => Err(CanNotFoundSourceAddress::new().into()),
+    }
+}
+
+fn get_trace_source_addr(dst_addr: IpAddr, src_addr: Option<IpAddr>) -> Result<IpAddr> {
+    match dst_addr {
+        IpAddr::V4(dst_ipv4) => match find_source_addr(src_addr, dst_ipv4)? {
+            Some(s) => Ok(s.into()),
+            None => Err(CanNotFoundSourceAddress::new().into()),
+        },
+        IpAddr::V6(dst_ipv6) => match find_source_addr6(src_addr, dst_ipv6)? {
+            Some(s) => Ok(s.into()),
+            None => Err(CanNotFoundSourceAddress::new().into()),
+        },
+    }
+}
+
+/// Returns the ports (icmp identifier and sequence) of the probe.
+/// Without the flow, every probe has its own ports so the responses can not be mixed up,
+/// with the flow, all the probes keep the same five-tuple (and icmp checksum) so the load balancers send them along the same path,
+/// and the responses are told apart by the probe index in the fields which are not hashed:
+/// the icmp sequence (the identifier keeps the checksum), the udp checksum or the tcp sequence, see `send_trace_probe`.
+fn get_probe_ports(
+    method: TracerouteMethod,
+    dst_port: Option<u16>,
+    base_src_port: u16,
+    probe_index: u16,
+    flow: Option<u16>,
+) -> (u16, u16) {
+    match flow {
+        Some(flow) => {
+            let src_port = base_src_port.wrapping_add(flow);
+            match method {
+                TracerouteMethod::Icmp => (compensate_word(src_port, probe_index), probe_index),
+                TracerouteMethod::Udp => match dst_port {
+                    Some(p) => (src_port, p),
+                    None => (src_port, TRACEROUTE_UDP_BASE_PORT),
+                },
+                TracerouteMethod::TcpSyn => match dst_port {
+                    Some(p) => (src_port, p),
+                    None => (src_port, TRACEROUTE_TCP_PORT),
+                },
+            }
+        }
+        None => match method {
+            TracerouteMethod::Icmp => (base_src_port, probe_index),
+            TracerouteMethod::Udp => {
+                let base = match dst_port {
+                    Some(p) => p,
+                    None => TRACEROUTE_UDP_BASE_PORT,
+                };
+                (base_src_port, base.wrapping_add(probe_index))
+            }
+            TracerouteMethod::TcpSyn => {
+                let dst_port = match dst_port {
+                    Some(p) => p,
+                    None => TRACEROUTE_TCP_PORT,
+                };
+                (base_src_port.wrapping_add(probe_index), dst_port)
+            }
+        },
+    }
+}
+
+/// The parameters which all the flows of one traceroute share.
+#[derive(Debug, Clone, Copy)]
+struct TraceTarget {
+    src_addr: IpAddr,
+    dst_addr: IpAddr,
+    method: TracerouteMethod,
+    dst_port: Option<u16>,
+    max_hops: u8,
+    base_src_port: u16,
+}
+
+impl TraceTarget {
+    fn new(
+        dst_addr: IpAddr,
+        method: TracerouteMethod,
+        dst_port: Option<u16>,
+        src_addr: Option<IpAddr>,
+        max_hops: Option<u8>,
+    ) -> Result<TraceTarget> {
+        let max_hops = match max_hops {
+            Some(m) => m,
+            None => TRACEROUTE_MAX_HOPS,
+        };
+        let src_addr = get_trace_source_addr(dst_addr, src_addr)?;
+        Ok(TraceTarget {
+            src_addr,
+            dst_addr,
+            method,
+            dst_port,
+            max_hops,
+            base_src_port: random_port(),
+        })
+    }
+}
+
+fn trace_flow(
+    target: TraceTarget,
+    queries: usize,
+    flow: Option<u16>,
+    timeout: Duration,
+) -> Result<TracerouteResults> {
+    let TraceTarget {
+        src_addr,
+        dst_addr,
+        method,
+        dst_port,
+        max_hops,
+        base_src_port,
+    } = target;
+    let mut ret = TracerouteResults::new(dst_addr, method);
+    let mut probe_index: u16 = 0;
+    // the same flow label for all the probes of the flow
+    let flow_label = match flow {
+        Some(flow) => base_src_port.wrapping_add(flow) as u32,
+        None => base_src_port as u32,
+    };
+    for ttl in 1..=max_hops {
+        let mut hop = Hop {
+            ttl,
+            probes: Vec::new(),
+        };
+        for _ in 0..queries {
+            probe_index = probe_index.wrapping_add(1);
+            let (src_port, dst_port) =
+                get_probe_ports(method, dst_port, base_src_port, probe_index, flow);
+            let probe = TraceProbe {
+                ttl,
+                probe_id: probe_index,
+                flow_label,
+            };
+            let (response, rtt) = send_trace_probe(
+                src_addr, dst_addr, method, src_port, dst_port, probe, timeout,
+            )?;
+            let probe = match response {
+                Some(r) => match parse_hop_response(&r) {
+                    Some((addr, icmp_type, icmp_code)) => HopProbe {
+                        addr: Some(addr),
+                        rtt,
+                        icmp_type,
+                        icmp_code,
+                    },
+                    None => HopProbe {
+                        addr: None,
+                        rtt: None,
+                        icmp_type: None,
+                        icmp_code: None,
+                    },
+                },
+                None => HopProbe {
+                    addr: None,
+                    rtt: None,
+                    icmp_type: None,
+                    icmp_code: None,
+                },
+            };
+            debug!("ttl: {} = {:?}", ttl, probe);
+            if probe.addr == Some(dst_addr) {
+                ret.reached = true;
+            }
+            hop.probes.push(probe);
+        }
+        ret.hops.push(hop);
+        if ret.reached {
+            break;
+        }
+    }
+    Ok(ret)
+}
+
+/// Traceroute.
+/// Send the probes with the increasing ttl (hop limit) until the target answers or the max hops is reached,
+/// every router on the path answers with the icmp time exceeded.
+/// The `queries` probes are sent for every hop (default 3), the icmp mode uses the echo request,
+/// the udp mode sends to the port 33434 and up (or `dst_port` and up), and the tcp syn mode sends to `dst_port` (default 80).
+pub fn traceroute(
+    dst_addr: IpAddr,
+    method: TracerouteMethod,
+    dst_port: Option<u16>,
+    src_addr: Option<IpAddr>,
+    max_hops: Option<u8>,
+    queries: usize,
+    timeout: Option<Duration>,
+) -> Result<TracerouteResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let queries = if queries > 0 {
+        queries
+    } else {
+        TRACEROUTE_QUERIES
+    };
+    let target = TraceTarget::new(dst_addr, method, dst_port, src_addr, max_hops)?;
+    trace_flow(target, queries, None, timeout)
+}
+
+/// Paris Traceroute.
+/// The classic traceroute changes the ports (icmp sequence) of every probe, so the per-flow load balancers may send
+/// the probes of two hops along the different paths and the result shows the links which do not exist.
+/// Here all the probes keep the same five-tuple, the same icmp checksum and the same ipv6 flow label,
+/// so they are hashed to one path, which is the path of this flow.
+/// The responses are matched by the probe index in the icmp sequence, the udp checksum or the tcp sequence,
+/// so a late response of the last hop is not taken as the response of this one.
+pub fn paris_traceroute(
+    dst_addr: IpAddr,
+    method: TracerouteMethod,
+    dst_port: Option<u16>,
+    src_addr: Option<IpAddr>,
+    max_hops: Option<u8>,
+    queries: usize,
+    timeout: Option<Duration>,
+) -> Result<TracerouteResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let queries = if queries > 0 {
+        queries
+    } else {
+        TRACEROUTE_QUERIES
+    };
+    let target = TraceTarget::new(dst_addr, method, dst_port, src_addr, max_hops)?;
+    trace_flow(target, queries, Some(0), timeout)
+}
+
+/// Multipath Traceroute.
+/// Run the paris traceroute with many flows to enumerate all the ecmp branches to the target.
+/// The number of flows follows the stopping rule of the multipath detection algorithm (MDA),
+/// when k next hops of one interface are found, the flows are added until the k + 1 hypothesis is rejected
+/// with 95% confidence at every interface, see `MultipathResults::flows_needed`,
+/// and `max_flows` is the upper limit (default 64).
+/// The new flows of every round are traced in parallel.
+pub fn multipath_traceroute(
+    dst_addr: IpAddr,
+    method: TracerouteMethod,
+    dst_port: Option<u16>,
+    src_addr: Option<IpAddr>,
+    max_hops: Option<u8>,
+    max_flows: usize,
+    timeout: Option<Duration>,
+) -> Result<MultipathResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let max_flows = if max_flows > 0 {
+        max_flows
+    } else {
+        MULTIPATH_MAX_FLOWS
+    };
+    let target = TraceTarget::new(dst_addr, method, dst_port, src_addr, max_hops)?;
+
+    let mut ret = MultipathResults::new(dst_addr, method);
+    loop {
+        let flows_num = ret.flows_needed().min(max_flows);
+        if ret.paths.len() >= flows_num {
+            break;
+        }
+        debug!("multipath flows: {} => {}", ret.paths.len(), flows_num);
+        let pool = get_threads_pool(flows_num - ret.paths.len());
+        let (tx, rx) = channel();
+        let mut recv_size = 0;
+        for flow in ret.paths.len()..flows_num {
+            let tx = tx.clone();
+            recv_size += 1;
+            pool.execute(move || {
+                // one query for every hop is enough, the flow is the same
+                let trace_ret = trace_flow(target, 1, Some(flow as u16), timeout);
+                let _ = tx.send(trace_ret);
+            });
+        }
+        let iter = rx.into_iter().take(recv_size);
+        for v in iter {
+            ret.insert(v?);
+        }
+    }
+    Ok(ret)
+}
 
 pub fn ipv4_get_hops(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, timeout: Duration) -> Result<u8> {
     debug!("30 hops max");
@@ -59,6 +1010,180 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn test_parse_hop_response() {
+        // ipv4 icmp time exceeded from 10.0.0.1
+        let mut buff = vec![
+            0x45, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 10, 0, 0, 1,
+            192, 168, 1, 2,
+        ];
+        buff.extend([11, 0, 0, 0, 0, 0, 0, 0]);
+        let (addr, icmp_type, icmp_code) = parse_hop_response(&buff).unwrap();
+        assert_eq!(addr, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(icmp_type, Some(11));
+        assert_eq!(icmp_code, Some(0));
+    }
+    #[test]
+    fn test_traceroute() -> Result<()> {
+        let dst_addr = Ipv4Addr::new(114, 114, 114, 114).into();
+        let timeout = Some(Duration::new(1, 0));
+        for method in [
+            TracerouteMethod::Icmp,
+            TracerouteMethod::Udp,
+            TracerouteMethod::TcpSyn,
+        ] {
+            let ret = traceroute(dst_addr, method, None, None, None, 3, timeout)?;
+            println!("{}", ret);
+        }
+        Ok(())
+    }
+    #[test]
+    fn test_multipath_insert() {
+        let dst_addr: IpAddr = Ipv4Addr::new(8, 8, 8, 8).into();
+        let hop = |ttl: u8, addr: Option<IpAddr>| Hop {
+            ttl,
+            probes: vec![HopProbe {
+                addr,
+                rtt: None,
+                icmp_type: Some(11),
+                icmp_code: Some(0),
+            }],
+        };
+        let a: IpAddr = Ipv4Addr::new(10, 0, 0, 1).into();
+        let b1: IpAddr = Ipv4Addr::new(10, 0, 1, 1).into();
+        let b2: IpAddr = Ipv4Addr::new(10, 0, 2, 1).into();
+        let mut ret = MultipathResults::new(dst_addr, TracerouteMethod::Udp);
+        for b in [b1, b2] {
+            let mut path = TracerouteResults::new(dst_addr, TracerouteMethod::Udp);
+            path.hops = vec![hop(1, Some(a)), hop(2, Some(b)), hop(3, Some(dst_addr))];
+            path.reached = true;
+            ret.insert(path);
+        }
+        assert_eq!(ret.max_width(), 2);
+        assert_eq!(ret.hops.get(&2), Some(&vec![b1, b2]));
+        assert_eq!(ret.links.len(), 4);
+        assert!(ret.reached);
+    }
+    #[test]
+    fn test_multipath_flows_needed() {
+        let dst_addr: IpAddr = Ipv4Addr::new(8, 8, 8, 8).into();
+        let hop = |ttl: u8, addr: Option<IpAddr>| Hop {
+            ttl,
+            probes: vec![HopProbe {
+                addr,
+                rtt: None,
+                icmp_type: Some(11),
+                icmp_code: Some(0),
+            }],
+        };
+        let a: IpAddr = Ipv4Addr::new(10, 0, 0, 1).into();
+        let b1: IpAddr = Ipv4Addr::new(10, 0, 1, 1).into();
+        let b2: IpAddr = Ipv4Addr::new(10, 0, 2, 1).into();
+        let c1: IpAddr = Ipv4Addr::new(10, 0, 3, 1).into();
+        let c2: IpAddr = Ipv4Addr::new(10, 0, 4, 1).into();
+        let mut ret = MultipathResults::new(dst_addr, TracerouteMethod::Udp);
+        assert_eq!(ret.flows_needed(), 6);
+        // a => b1 | b2, only b1 is load balanced again (c1 | c2)
+        for i in 0..12 {
+            let b = if i % 2 == 0 { b1 } else { b2 };
+            let c = if i % 2 == 1 {
+                dst_addr
+            } else if i % 4 == 0 {
+                c1
+            } else {
+                c2
+            };
+            let mut path = TracerouteResults::new(dst_addr, TracerouteMethod::Udp);
+            path.hops = vec![hop(1, Some(a)), hop(2, Some(b)), hop(3, Some(c))];
+            ret.insert(path);
+        }
+        // the 6 flows through b1 need 11 for the 2 next hops of b1, so 22 in total
+        assert_eq!(ret.flows_needed(), 22);
+        // the silent hop is skipped
+        let mut path = TracerouteResults::new(dst_addr, TracerouteMethod::Udp);
+        path.hops = vec![hop(1, None), hop(2, Some(b2))];
+        ret.insert(path);
+        assert_eq!(ret.flows_needed(), 24);
+    }
+    #[test]
+    fn test_probe_id_checksum() {
+        // the icmp identifier keeps the checksum when the sequence changes
+        let src_port = 40000;
+        for probe_id in [1, 2, 300, 0xFFFE] {
+            let identifier = compensate_word(src_port, probe_id);
+            assert_eq!(ones_complement_add(identifier, probe_id), src_port);
+        }
+        // the data word turns the checksum into the probe id
+        let mut buff = vec![
+            0x9c, 0x40, 0x82, 0x9a, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34,
+        ];
+        let checksum = pnet::util::checksum(&buff, 3);
+        for probe_id in [1, 2, 300, 0xFFFE] {
+            buff[8..10].copy_from_slice(&checksum_fix_word(checksum, probe_id).to_be_bytes());
+            assert_eq!(pnet::util::checksum(&buff, 3), probe_id);
+        }
+    }
+    #[test]
+    fn test_paris_traceroute() -> Result<()> {
+        let dst_addr = Ipv4Addr::new(114, 114, 114, 114).into();
+        let timeout = Some(Duration::new(1, 0));
+        let ret = paris_traceroute(
+            dst_addr,
+            TracerouteMethod::Udp,
+            None,
+            None,
+            None,
+            3,
+            timeout,
+        )?;
+        println!("{}", ret);
+        let ret = multipath_traceroute(
+            dst_addr,
+            TracerouteMethod::Udp,
+            None,
+            None,
+            None,
+            16,
+            timeout,
+        )?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_parse_mtu_response() {
+        // ipv4 icmp fragmentation needed from 10.0.0.1, next hop mtu 1400
+        let mut buff = vec![
+            0x45, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 10, 0, 0, 1,
+            192, 168, 1, 2,
+        ];
+        buff.extend([3, 4, 0, 0, 0, 0, 0x05, 0x78]);
+        let (addr, mtu) = parse_mtu_response(&buff).unwrap();
+        assert_eq!(addr, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(mtu, 1400);
+        // time exceeded is not
+        buff[20] = 11;
+        buff[21] = 0;
+        assert_eq!(parse_mtu_response(&buff), None);
+    }
+    #[test]
+    fn test_pmtu_max_size() {
+        assert_eq!(pmtu_max_size(None, PMTU_IPV4_MIN), 1500);
+        assert_eq!(pmtu_max_size(Some(1400), PMTU_IPV4_MIN), 1400);
+        // the jumbo mtu does not overflow the send buffer
+        assert_eq!(
+            pmtu_max_size(Some(9000), PMTU_IPV4_MIN),
+            ETHERNET_BUFF_SIZE - ETHERNET_HEADER_SIZE
+        );
+        assert_eq!(pmtu_max_size(Some(1000), PMTU_IPV6_MIN), PMTU_IPV6_MIN);
+    }
+    #[test]
+    fn test_path_mtu_discovery() -> Result<()> {
+        let dst_addr = Ipv4Addr::new(114, 114, 114, 114).into();
+        let timeout = Some(Duration::new(1, 0));
+        let ret = path_mtu_discovery(dst_addr, None, None, None, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
     fn test_get_hops6() -> Result<()> {
         let src_ipv6 = find_source_addr6(None, TEST_IPV6_LOCAL)?;
         match src_ipv6 {
@@ -18,9 +18,11 @@ use rand::Rng;
 use std::net::Ipv4Addr;
 use std::time::Duration;
 
+use crate::hop::TraceProbe;
 use crate::layers::layer3_ipv4_send;
 use crate::layers::Layer3Match;
 use crate::layers::Layer4MatchIcmp;
+use crate::layers::Layer4MatchIcmpError;
 use crate::layers::LayersMatch;
 use crate::layers::ICMP_HEADER_SIZE;
 use crate::layers::IPV4_HEADER_SIZE;
@@ -109,3 +111,96 @@ pub fn send_icmp_ping_packet(
     }
     Ok(false)
 }
+
+/// Send one icmp echo request with the ttl for traceroute,
+/// returns the echo reply from the target or the icmp error from the router.
+pub fn send_icmp_trace_packet(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    identifier: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    const ICMP_DATA_SIZE: usize = 16;
+    let size = IPV4_HEADER_SIZE + ICMP_HEADER_SIZE + ICMP_DATA_SIZE;
+    send_icmp_size_packet(src_ipv4, dst_ipv4, size, identifier, probe, timeout)
+}
+
+/// Send one icmp echo request of `size` bytes (the whole ip packet) with the DF bit,
+/// returns the echo reply from the target or the icmp error (fragmentation needed, time exceeded) from the router.
+/// The `probe.probe_id` is the icmp sequence.
+pub fn send_icmp_size_packet(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    size: usize,
+    identifier: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    let ttl = probe.ttl;
+    let sequence = probe.probe_id;
+    let mut rng = rand::thread_rng();
+    let size = size.max(IPV4_HEADER_SIZE + ICMP_HEADER_SIZE);
+    // ip header
+    let mut ip_buff = vec![0u8; size];
+    let mut ip_header = MutableIpv4Packet::new(&mut ip_buff).unwrap();
+    ip_header.set_version(4);
+    ip_header.set_header_length(5);
+    ip_header.set_source(src_ipv4);
+    ip_header.set_destination(dst_ipv4);
+    ip_header.set_total_length(size as u16);
+    let id = rng.gen();
+    ip_header.set_identification(id);
+    ip_header.set_flags(Ipv4Flags::DontFragment);
+    ip_header.set_ttl(ttl);
+    ip_header.set_next_level_protocol(IpNextHeaderProtocols::Icmp);
+    let c = ipv4::checksum(&ip_header.to_immutable());
+    ip_header.set_checksum(c);
+
+    let mut icmp_header = MutableEchoRequestPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+    icmp_header.set_icmp_type(IcmpType(8));
+    icmp_header.set_icmp_code(IcmpCode(0));
+    icmp_header.set_sequence_number(sequence);
+    icmp_header.set_identifier(identifier);
+
+    let mut icmp_header = MutableIcmpPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+    let checksum = icmp::checksum(&icmp_header.to_immutable());
+    icmp_header.set_checksum(checksum);
+
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: Some(dst_ipv4.into()),
+        dst_addr: Some(src_ipv4.into()),
+    };
+    let layer4_icmp = Layer4MatchIcmp {
+        layer3: Some(layer3),
+        types: Some(IcmpType(0)), // echo reply
+        codes: None,
+    };
+    // time exceeded from the routers
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv4.into()),
+    };
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv4.into()),
+        quoted_dst_addr: Some(dst_ipv4.into()),
+        quoted_protocol: Some(IpNextHeaderProtocols::Icmp),
+        quoted_src_port: Some(identifier),
+        quoted_dst_port: Some(sequence),
+        quoted_id: None,
+    };
+    let layers_match_1 = LayersMatch::Layer4MatchIcmp(layer4_icmp);
+    let layers_match_2 = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    debug!("send icmp trace {} packet, size {}", ttl, size);
+    layer3_ipv4_send(
+        src_ipv4,
+        dst_ipv4,
+        &ip_buff,
+        vec![layers_match_1, layers_match_2],
+        timeout,
+    )
+}
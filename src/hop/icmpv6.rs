@@ -14,8 +14,10 @@ use rand::Rng;
 use std::net::Ipv6Addr;
 use std::time::Duration;
 
+use crate::hop::TraceProbe;
 use crate::layers::layer3_ipv6_send;
 use crate::layers::Layer3Match;
+use crate::layers::Layer4MatchIcmpError;
 use crate::layers::Layer4MatchIcmpv6;
 use crate::layers::LayersMatch;
 use crate::layers::ICMPV6_ER_HEADER_SIZE;
@@ -104,3 +106,92 @@ pub fn send_icmpv6_ping_packet(
     }
     Ok(false)
 }
+
+/// Send one icmpv6 echo request with the hop limit for traceroute,
+/// returns the echo reply from the target or the icmpv6 error from the router.
+pub fn send_icmpv6_trace_packet(
+    src_ipv6: Ipv6Addr,
+    dst_ipv6: Ipv6Addr,
+    identifier: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    const ICMPV6_DATA_SIZE: usize = 16;
+    let size = IPV6_HEADER_SIZE + ICMPV6_ER_HEADER_SIZE + ICMPV6_DATA_SIZE;
+    send_icmpv6_size_packet(src_ipv6, dst_ipv6, size, identifier, probe, timeout)
+}
+
+/// Send one icmpv6 echo request of `size` bytes (the whole ipv6 packet),
+/// returns the echo reply from the target or the icmpv6 error (packet too big, time exceeded) from the router.
+/// The `probe.probe_id` is the icmpv6 sequence.
+pub fn send_icmpv6_size_packet(
+    src_ipv6: Ipv6Addr,
+    dst_ipv6: Ipv6Addr,
+    size: usize,
+    identifier: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    let ttl = probe.ttl;
+    let sequence = probe.probe_id;
+    let flow_label = probe.flow_label;
+    let size = size.max(IPV6_HEADER_SIZE + ICMPV6_ER_HEADER_SIZE);
+    // ipv6 header
+    let mut ipv6_buff = vec![0u8; size];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_flow_label(flow_label);
+    let payload_length = size - IPV6_HEADER_SIZE;
+    ipv6_header.set_payload_length(payload_length as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocols::Icmpv6);
+    ipv6_header.set_hop_limit(ttl);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(dst_ipv6);
+
+    let mut icmpv6_header =
+        MutableEchoRequestPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    icmpv6_header.set_icmpv6_type(Icmpv6Type(128));
+    icmpv6_header.set_icmpv6_code(Icmpv6Code(0));
+    icmpv6_header.set_sequence_number(sequence);
+    icmpv6_header.set_identifier(identifier);
+
+    let mut icmp_header = MutableIcmpv6Packet::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    let checksum = icmpv6::checksum(&icmp_header.to_immutable(), &src_ipv6, &dst_ipv6);
+    icmp_header.set_checksum(checksum);
+
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: Some(dst_ipv6.into()),
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmpv6 = Layer4MatchIcmpv6 {
+        layer3: Some(layer3),
+        icmpv6_type: Some(Icmpv6Type(129)), // echo reply
+        icmpv6_code: None,
+    };
+    // time exceeded from the routers
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv6.into()),
+        quoted_dst_addr: Some(dst_ipv6.into()),
+        quoted_protocol: Some(IpNextHeaderProtocols::Icmpv6),
+        quoted_src_port: Some(identifier),
+        quoted_dst_port: Some(sequence),
+        quoted_id: None,
+    };
+    let layers_match_1 = LayersMatch::Layer4MatchIcmpv6(layer4_icmpv6);
+    let layers_match_2 = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    layer3_ipv6_send(
+        src_ipv6,
+        dst_ipv6,
+        &ipv6_buff,
+        vec![layers_match_1, layers_match_2],
+        timeout,
+    )
+}
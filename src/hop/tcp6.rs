@@ -0,0 +1,124 @@
+use anyhow::Result;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::tcp;
+use pnet::packet::tcp::MutableTcpPacket;
+use pnet::packet::tcp::TcpFlags;
+use rand::Rng;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use crate::hop::compensate_word;
+use crate::hop::TraceProbe;
+use crate::layers::layer3_ipv6_send;
+use crate::layers::Layer3Match;
+use crate::layers::Layer4MatchIcmpError;
+use crate::layers::Layer4MatchTcpUdp;
+use crate::layers::LayersMatch;
+use crate::layers::IPV6_HEADER_SIZE;
+use crate::layers::TCP_HEADER_SIZE;
+
+/// Send one tcp syn packet with the hop limit for traceroute,
+/// returns the time exceeded from the router or the syn/ack (rst) from the target.
+pub fn send_syn_trace_packet(
+    src_ipv6: Ipv6Addr,
+    src_port: u16,
+    dst_ipv6: Ipv6Addr,
+    dst_port: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    send_tcp_trace_packet(
+        src_ipv6,
+        src_port,
+        dst_ipv6,
+        dst_port,
+        TcpFlags::SYN,
+        probe,
+        timeout,
+    )
+}
+
+/// Send one tcp packet with the flags and the hop limit,
+/// returns the icmp error (time exceeded or unreachable) from the router or the response from the target.
+/// The sequence number carries the `probe_id`, see `tcp::send_tcp_trace_packet`.
+pub fn send_tcp_trace_packet(
+    src_ipv6: Ipv6Addr,
+    src_port: u16,
+    dst_ipv6: Ipv6Addr,
+    dst_port: u16,
+    tcp_flags: u8,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    let ttl = probe.ttl;
+    let probe_id = probe.probe_id;
+    let flow_label = probe.flow_label;
+    let mut rng = rand::thread_rng();
+    // ipv6 header
+    let mut ipv6_buff = [0u8; IPV6_HEADER_SIZE + TCP_HEADER_SIZE];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_flow_label(flow_label);
+    ipv6_header.set_payload_length(TCP_HEADER_SIZE as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocols::Tcp);
+    ipv6_header.set_hop_limit(ttl);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(dst_ipv6);
+
+    // tcp header
+    let mut tcp_header = MutableTcpPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    tcp_header.set_source(src_port);
+    tcp_header.set_destination(dst_port);
+    // the probe id and the word which keeps the checksum of the same ports unchanged
+    let sequence = (probe_id as u32) << 16 | compensate_word(src_port, probe_id) as u32;
+    tcp_header.set_sequence(sequence);
+    if tcp_flags & TcpFlags::ACK != 0 {
+        tcp_header.set_acknowledgement(rng.gen());
+    } else {
+        tcp_header.set_acknowledgement(0);
+    }
+    tcp_header.set_reserved(0);
+    tcp_header.set_flags(tcp_flags);
+    tcp_header.set_urgent_ptr(0);
+    tcp_header.set_window(1024);
+    tcp_header.set_data_offset(5);
+    let checksum = tcp::ipv6_checksum(&tcp_header.to_immutable(), &src_ipv6, &dst_ipv6);
+    tcp_header.set_checksum(checksum);
+
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: Some(dst_ipv6.into()),
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_tcp_udp = Layer4MatchTcpUdp {
+        layer3: Some(layer3),
+        src_port: Some(dst_port),
+        dst_port: Some(src_port),
+    };
+    // time exceeded from the routers
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv6.into()),
+        quoted_dst_addr: Some(dst_ipv6.into()),
+        quoted_protocol: Some(IpNextHeaderProtocols::Tcp),
+        quoted_src_port: Some(src_port),
+        quoted_dst_port: Some(dst_port),
+        quoted_id: Some((4, probe_id)),
+    };
+    let layers_match_1 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp);
+    let layers_match_2 = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    layer3_ipv6_send(
+        src_ipv6,
+        dst_ipv6,
+        &ipv6_buff,
+        vec![layers_match_1, layers_match_2],
+        timeout,
+    )
+}
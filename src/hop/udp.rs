@@ -0,0 +1,124 @@
+use anyhow::Result;
+use log::debug;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4;
+use pnet::packet::ipv4::Ipv4Flags;
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::udp::ipv4_checksum;
+use pnet::packet::udp::MutableUdpPacket;
+use rand::Rng;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::hop::checksum_fix_word;
+use crate::hop::TraceProbe;
+use crate::layers::layer3_ipv4_send;
+use crate::layers::Layer3Match;
+use crate::layers::Layer4MatchIcmpError;
+use crate::layers::LayersMatch;
+use crate::layers::IPV4_HEADER_SIZE;
+use crate::layers::UDP_HEADER_SIZE;
+
+const UDP_DATA_SIZE: usize = 16;
+
+/// Build the udp trace packet, returns the packet and the udp checksum.
+/// The first two bytes of the data turn the udp checksum into the `probe_id`.
+fn udp_trace_packet(
+    src_ipv4: Ipv4Addr,
+    src_port: u16,
+    dst_ipv4: Ipv4Addr,
+    dst_port: u16,
+    ttl: u8,
+    probe_id: u16,
+) -> (
+    [u8; IPV4_HEADER_SIZE + UDP_HEADER_SIZE + UDP_DATA_SIZE],
+    u16,
+) {
+    let mut rng = rand::thread_rng();
+    // ip header
+    let mut ip_buff = [0u8; IPV4_HEADER_SIZE + UDP_HEADER_SIZE + UDP_DATA_SIZE];
+    let mut ip_header = MutableIpv4Packet::new(&mut ip_buff).unwrap();
+    ip_header.set_version(4);
+    ip_header.set_header_length(5);
+    ip_header.set_source(src_ipv4);
+    ip_header.set_destination(dst_ipv4);
+    ip_header.set_total_length((IPV4_HEADER_SIZE + UDP_HEADER_SIZE + UDP_DATA_SIZE) as u16);
+    let id = rng.gen();
+    ip_header.set_identification(id);
+    ip_header.set_flags(Ipv4Flags::DontFragment);
+    ip_header.set_ttl(ttl);
+    ip_header.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+    let c = ipv4::checksum(&ip_header.to_immutable());
+    ip_header.set_checksum(c);
+
+    // udp header
+    let mut udp_header = MutableUdpPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+    udp_header.set_source(src_port);
+    udp_header.set_destination(dst_port);
+    udp_header.set_length((UDP_HEADER_SIZE + UDP_DATA_SIZE) as u16);
+    let checksum = ipv4_checksum(&udp_header.to_immutable(), &src_ipv4, &dst_ipv4);
+    let fix_word = checksum_fix_word(checksum, probe_id);
+    udp_header.set_payload(&fix_word.to_be_bytes());
+    let checksum = ipv4_checksum(&udp_header.to_immutable(), &src_ipv4, &dst_ipv4);
+    udp_header.set_checksum(checksum);
+
+    (ip_buff, checksum)
+}
+
+/// Send one udp packet with the ttl for traceroute,
+/// returns the time exceeded from the router or the port unreachable from the target.
+/// The probes with the same ports are told apart by the udp checksum (`probe_id`) quoted in the icmp error.
+pub fn send_udp_trace_packet(
+    src_ipv4: Ipv4Addr,
+    src_port: u16,
+    dst_ipv4: Ipv4Addr,
+    dst_port: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    let ttl = probe.ttl;
+    let (ip_buff, checksum) =
+        udp_trace_packet(src_ipv4, src_port, dst_ipv4, dst_port, ttl, probe.probe_id);
+
+    // the target answers with the port unreachable too
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv4.into()),
+    };
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv4.into()),
+        quoted_dst_addr: Some(dst_ipv4.into()),
+        quoted_protocol: Some(IpNextHeaderProtocols::Udp),
+        quoted_src_port: Some(src_port),
+        quoted_dst_port: Some(dst_port),
+        quoted_id: Some((6, checksum)),
+    };
+    let layers_match = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    debug!("send udp trace {} packet", ttl);
+    layer3_ipv4_send(src_ipv4, dst_ipv4, &ip_buff, vec![layers_match], timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ipv4::Ipv4Packet;
+    use pnet::packet::udp::UdpPacket;
+    use pnet::packet::Packet;
+    #[test]
+    fn test_udp_trace_packet() {
+        let src_ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        let dst_ipv4 = Ipv4Addr::new(8, 8, 8, 8);
+        let (ip_buff, checksum) = udp_trace_packet(src_ipv4, 45678, dst_ipv4, 33434, 3, 0x1234);
+        let ip_packet = Ipv4Packet::new(&ip_buff).unwrap();
+        assert_eq!(ip_packet.get_flags(), Ipv4Flags::DontFragment);
+        assert_eq!(ip_packet.get_fragment_offset(), 0);
+        assert_eq!(ip_packet.get_ttl(), 3);
+        assert_eq!(ip_packet.get_checksum(), ipv4::checksum(&ip_packet));
+        let udp_packet = UdpPacket::new(ip_packet.payload()).unwrap();
+        assert_eq!(udp_packet.get_checksum(), checksum);
+        assert_eq!(checksum, 0x1234);
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::udp::ipv6_checksum;
+use pnet::packet::udp::MutableUdpPacket;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use crate::hop::checksum_fix_word;
+use crate::hop::TraceProbe;
+use crate::layers::layer3_ipv6_send;
+use crate::layers::Layer3Match;
+use crate::layers::Layer4MatchIcmpError;
+use crate::layers::LayersMatch;
+use crate::layers::IPV6_HEADER_SIZE;
+use crate::layers::UDP_HEADER_SIZE;
+
+const UDP_DATA_SIZE: usize = 16;
+
+/// Send one udp packet with the hop limit for traceroute,
+/// returns the time exceeded from the router or the port unreachable from the target.
+/// The first two bytes of the data turn the udp checksum into the `probe_id`, see `udp::send_udp_trace_packet`.
+pub fn send_udp_trace_packet(
+    src_ipv6: Ipv6Addr,
+    src_port: u16,
+    dst_ipv6: Ipv6Addr,
+    dst_port: u16,
+    probe: TraceProbe,
+    timeout: Duration,
+) -> Result<(Option<Vec<u8>>, Option<Duration>)> {
+    let ttl = probe.ttl;
+    let probe_id = probe.probe_id;
+    let flow_label = probe.flow_label;
+    // ipv6 header
+    let mut ipv6_buff = [0u8; IPV6_HEADER_SIZE + UDP_HEADER_SIZE + UDP_DATA_SIZE];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_flow_label(flow_label);
+    let payload_length = UDP_HEADER_SIZE + UDP_DATA_SIZE;
+    ipv6_header.set_payload_length(payload_length as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocols::Udp);
+    ipv6_header.set_hop_limit(ttl);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(dst_ipv6);
+
+    // udp header
+    let mut udp_header = MutableUdpPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    udp_header.set_source(src_port);
+    udp_header.set_destination(dst_port);
+    udp_header.set_length((UDP_HEADER_SIZE + UDP_DATA_SIZE) as u16);
+    let checksum = ipv6_checksum(&udp_header.to_immutable(), &src_ipv6, &dst_ipv6);
+    let fix_word = checksum_fix_word(checksum, probe_id);
+    udp_header.set_payload(&fix_word.to_be_bytes());
+    let checksum = ipv6_checksum(&udp_header.to_immutable(), &src_ipv6, &dst_ipv6);
+    udp_header.set_checksum(checksum);
+
+    // the target answers with the port unreachable too
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv6.into()),
+        quoted_dst_addr: Some(dst_ipv6.into()),
+        quoted_protocol: Some(IpNextHeaderProtocols::Udp),
+        quoted_src_port: Some(src_port),
+        quoted_dst_port: Some(dst_port),
+        quoted_id: Some((6, checksum)),
+    };
+    let layers_match = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    layer3_ipv6_send(src_ipv6, dst_ipv6, &ipv6_buff, vec![layers_match], timeout)
+}
@@ -29,6 +29,7 @@ use pnet::packet::icmpv6::Icmpv6Packet;
 use pnet::packet::icmpv6::Icmpv6Type;
 use pnet::packet::icmpv6::Icmpv6Types;
 use pnet::packet::icmpv6::MutableIcmpv6Packet;
+use pnet::packet::ip::IpNextHeaderProtocol;
 use pnet::packet::ip::IpNextHeaderProtocols;
 use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::ipv6::Ipv6Packet;
@@ -468,14 +469,173 @@ impl Layer4MatchIcmpv6 {
     }
 }
 
+/// Returns the src addr, dst addr, protocol and layer4 data of the packet quoted in the icmp or icmpv6 error message.
+fn get_icmp_error_quoted(
+    ethernet_buff: &[u8],
+) -> Option<(IpAddr, IpAddr, IpNextHeaderProtocol, Vec<u8>)> {
+    let ethernet_packet = EthernetPacket::new(ethernet_buff)?;
+    match ethernet_packet.get_ethertype() {
+        EtherTypes::Ipv4 => {
+            let ipv4_packet = Ipv4Packet::new(ethernet_packet.payload())?;
+            if ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Icmp {
+                return None;
+            }
+            let icmp_buff = ipv4_packet.payload();
+            match icmp_buff.first() {
+                // destination unreachable, source quench, redirect, time exceeded and parameter problem
+                Some(3) | Some(4) | Some(5) | Some(11) | Some(12) => (),
+                _ => return None,
+            }
+            let quoted = icmp_buff.get(ICMP_HEADER_SIZE..)?;
+            let quoted_ipv4 = Ipv4Packet::new(quoted)?;
+            let header_length = quoted_ipv4.get_header_length() as usize * 4;
+            let layer4 = quoted.get(header_length..)?;
+            Some((
+                quoted_ipv4.get_source().into(),
+                quoted_ipv4.get_destination().into(),
+                quoted_ipv4.get_next_level_protocol(),
+                layer4.to_vec(),
+            ))
+        }
+        EtherTypes::Ipv6 => {
+            let ipv6_packet = Ipv6Packet::new(ethernet_packet.payload())?;
+            if ipv6_packet.get_next_header() != IpNextHeaderProtocols::Icmpv6 {
+                return None;
+            }
+            let icmpv6_buff = ipv6_packet.payload();
+            match icmpv6_buff.first() {
+                // destination unreachable, packet too big, time exceeded and parameter problem
+                Some(1) | Some(2) | Some(3) | Some(4) => (),
+                _ => return None,
+            }
+            let quoted = icmpv6_buff.get(ICMPV6_ER_HEADER_SIZE..)?;
+            let quoted_ipv6 = Ipv6Packet::new(quoted)?;
+            let layer4 = quoted.get(IPV6_HEADER_SIZE..)?;
+            Some((
+                quoted_ipv6.get_source().into(),
+                quoted_ipv6.get_destination().into(),
+                quoted_ipv6.get_next_header(),
+                layer4.to_vec(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Match the icmp or icmpv6 error message (time exceeded, unreachable, packet too big...)
+/// by the probe packet it quoted, the error may come from any router on the path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layer4MatchIcmpError {
+    pub layer3: Option<Layer3Match>,
+    pub quoted_src_addr: Option<IpAddr>, // probe packet
+    pub quoted_dst_addr: Option<IpAddr>, // probe packet
+    pub quoted_protocol: Option<IpNextHeaderProtocol>, // probe packet
+    pub quoted_src_port: Option<u16>,    // tcp or udp src port, icmp identifier
+    pub quoted_dst_port: Option<u16>,    // tcp or udp dst port, icmp sequence number
+    pub quoted_id: Option<(usize, u16)>, // the 16 bits at the offset of the layer4 data, such as the udp checksum
+}
+
+impl Layer4MatchIcmpError {
+    pub fn do_match(&self, ethernet_buff: &[u8]) -> bool {
+        let m1 = match self.layer3 {
+            Some(layer3) => layer3.do_match(ethernet_buff),
+            None => true,
+        };
+        let (q_src_addr, q_dst_addr, q_protocol, q_layer4) =
+            match get_icmp_error_quoted(ethernet_buff) {
+                Some(q) => q,
+                None => return false,
+            };
+        let m2 = match self.quoted_src_addr {
+            Some(src_addr) => src_addr == q_src_addr,
+            None => true,
+        };
+        let m3 = match self.quoted_dst_addr {
+            Some(dst_addr) => dst_addr == q_dst_addr,
+            None => true,
+        };
+        let m4 = match self.quoted_protocol {
+            Some(protocol) => protocol == q_protocol,
+            None => true,
+        };
+        // the error message contains at least the first 8 bytes of the probe payload
+        let ports_offset = match q_protocol {
+            IpNextHeaderProtocols::Icmp | IpNextHeaderProtocols::Icmpv6 => 4,
+            _ => 0,
+        };
+        let (q_src_port, q_dst_port) = if q_layer4.len() >= ports_offset + 4 {
+            (
+                u16::from_be_bytes([q_layer4[ports_offset], q_layer4[ports_offset + 1]]),
+                u16::from_be_bytes([q_layer4[ports_offset + 2], q_layer4[ports_offset + 3]]),
+            )
+        } else if self.quoted_src_port.is_none() && self.quoted_dst_port.is_none() {
+            // the probe may have no layer4 data, e.g. the ip protocol ping
+            (0, 0)
+        } else {
+            return false;
+        };
+        let m5 = match self.quoted_src_port {
+            Some(src_port) => src_port == q_src_port,
+            None => true,
+        };
+        let m6 = match self.quoted_dst_port {
+            Some(dst_port) => dst_port == q_dst_port,
+            None => true,
+        };
+        let m7 = match self.quoted_id {
+            Some((offset, id)) => match q_layer4.get(offset..offset + 2) {
+                Some(q_id) => u16::from_be_bytes([q_id[0], q_id[1]]) == id,
+                None => false,
+            },
+            None => true,
+        };
+        m1 & m2 & m3 & m4 & m5 & m6 & m7
+    }
+}
+
+/// Match the response by the ip protocol (ipv4) or the next header (ipv6) of the packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layer3MatchProtocol {
+    pub layer3: Option<Layer3Match>,
+    pub protocol: IpNextHeaderProtocol, // response packet
+}
+
+impl Layer3MatchProtocol {
+    pub fn do_match(&self, ethernet_buff: &[u8]) -> bool {
+        let m1 = match self.layer3 {
+            Some(layer3) => layer3.do_match(ethernet_buff),
+            None => true,
+        };
+        let ethernet_packet = match EthernetPacket::new(ethernet_buff) {
+            Some(ethernet_packet) => ethernet_packet,
+            None => return false,
+        };
+        let protocol = match ethernet_packet.get_ethertype() {
+            EtherTypes::Ipv4 => match Ipv4Packet::new(ethernet_packet.payload()) {
+                Some(ipv4_packet) => ipv4_packet.get_next_level_protocol(),
+                None => return false,
+            },
+            EtherTypes::Ipv6 => match Ipv6Packet::new(ethernet_packet.payload()) {
+                Some(ipv6_packet) => ipv6_packet.get_next_header(),
+                None => return false,
+            },
+            _ => return false,
+        };
+        let m2 = protocol == self.protocol;
+        m1 & m2
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LayersMatch {
     Layer2Match(Layer2Match),
     Layer3Match(Layer3Match),
+    Layer3MatchProtocol(Layer3MatchProtocol),
     Layer4MatchTcpUdp(Layer4MatchTcpUdp),
     Layer4MatchIcmp(Layer4MatchIcmp),
     Layer4MatchIcmpv6(Layer4MatchIcmpv6),
+    Layer4MatchIcmpError(Layer4MatchIcmpError),
 }
 
 impl LayersMatch {
@@ -483,9 +643,11 @@ impl LayersMatch {
         match self {
             LayersMatch::Layer2Match(l2) => l2.do_match(ethernet_buff),
             LayersMatch::Layer3Match(l3) => l3.do_match(ethernet_buff),
+            LayersMatch::Layer3MatchProtocol(l3protocol) => l3protocol.do_match(ethernet_buff),
             LayersMatch::Layer4MatchTcpUdp(l4tcpudp) => l4tcpudp.do_match(ethernet_buff),
             LayersMatch::Layer4MatchIcmp(l4icmp) => l4icmp.do_match(ethernet_buff),
             LayersMatch::Layer4MatchIcmpv6(l4icmpv6) => l4icmpv6.do_match(ethernet_buff),
+            LayersMatch::Layer4MatchIcmpError(l4icmperror) => l4icmperror.do_match(ethernet_buff),
         }
     }
 }
@@ -604,6 +766,114 @@ pub fn layer2_send(
     }
 }
 
+/// Send the packet and collect every matched response until the timeout,
+/// for the multicast or broadcast probes which may be answered by many hosts.
+pub fn layer2_send_multi(
+    dst_mac: MacAddr,
+    interface: NetworkInterface,
+    send_buff: &[u8],
+    ethernet_type: EtherType,
+    layers_match: Vec<LayersMatch>,
+    timeout: Duration,
+) -> Result<Vec<(Vec<u8>, Duration)>> {
+    let (mut sender, mut receiver) = match datalink_channel(&interface)? {
+        Some((s, r)) => (s, r),
+        None => return Err(CreateDatalinkChannelFailed::new().into()),
+    };
+    let src_mac = match interface.mac {
+        Some(m) => m,
+        None => return Err(CanNotFoundMacAddress::new().into()),
+    };
+
+    let mut ethernet_buff = [0u8; ETHERNET_BUFF_SIZE];
+    let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buff).unwrap();
+    ethernet_packet.set_destination(dst_mac);
+    ethernet_packet.set_source(src_mac);
+    ethernet_packet.set_ethertype(ethernet_type);
+    ethernet_packet.set_payload(send_buff);
+
+    let final_buff = ethernet_buff[..(ETHERNET_HEADER_SIZE + send_buff.len())].to_vec();
+    let send_time = Instant::now();
+    debug!("layer2 send multi: {}", final_buff.len());
+    if let Some(Err(e)) = sender.send_to(&final_buff, Some(interface)) {
+        return Err(e.into());
+    }
+
+    let pool = get_threads_pool(32);
+    let (tx, rx) = channel();
+    pool.execute(move || loop {
+        let buff = match receiver.next() {
+            Ok(b) => b,
+            Err(_) => &[],
+        };
+        for m in &layers_match {
+            if m.do_match(buff) {
+                debug!("match found: {:?}", m);
+                match tx.send((buff.to_vec(), send_time.elapsed())) {
+                    Ok(_) => (),
+                    // the receiver side has been closed
+                    Err(_) => return,
+                }
+                break;
+            }
+        }
+    });
+
+    let mut ret = Vec::new();
+    loop {
+        let elapsed = send_time.elapsed();
+        if elapsed >= timeout {
+            break;
+        }
+        match rx.recv_timeout(timeout - elapsed) {
+            Ok(r) => ret.push(r),
+            Err(_) => break, // read timeout
+        }
+    }
+    Ok(ret)
+}
+
+/// Listen on the interface without sending anything,
+/// every received ethernet frame is passed to the handler until the duration is elapsed.
+pub fn layer2_capture<F>(
+    interface: NetworkInterface,
+    duration: Duration,
+    mut handler: F,
+) -> Result<()>
+where
+    F: FnMut(&[u8]),
+{
+    let (_, mut receiver) = match datalink_channel(&interface)? {
+        Some((s, r)) => (s, r),
+        None => return Err(CreateDatalinkChannelFailed::new().into()),
+    };
+
+    let start_time = Instant::now();
+    let pool = get_threads_pool(32);
+    let (tx, rx) = channel();
+    pool.execute(move || loop {
+        if let Ok(b) = receiver.next() {
+            match tx.send(b.to_vec()) {
+                Ok(_) => (),
+                // the receiver side has been closed
+                Err(_) => return,
+            }
+        }
+    });
+
+    loop {
+        let elapsed = start_time.elapsed();
+        if elapsed >= duration {
+            break;
+        }
+        match rx.recv_timeout(duration - elapsed) {
+            Ok(buff) => handler(&buff),
+            Err(_) => break, // read timeout
+        }
+    }
+    Ok(())
+}
+
 pub fn get_mac_from_arp(ethernet_buff: &[u8]) -> Option<MacAddr> {
     debug!("get mac from arp");
     let re = EthernetPacket::new(ethernet_buff).unwrap();
@@ -764,7 +1034,7 @@ pub fn multicast_mac(ip: Ipv6Addr) -> MacAddr {
     MacAddr::new(0x33, 0x33, 0xFF, ip[13], ip[14], ip[15])
 }
 
-fn get_mac_from_ndp_ns(buff: &[u8]) -> Option<MacAddr> {
+pub fn get_mac_from_ndp_ns(buff: &[u8]) -> Option<MacAddr> {
     debug!("get mac from ndp ns");
     // return mac address from ndp
     let ethernet_packet = EthernetPacket::new(buff).unwrap();
@@ -780,19 +1050,11 @@ fn get_mac_from_ndp_ns(buff: &[u8]) -> Option<MacAddr> {
     None
 }
 
-fn ndp_ns(src_ipv6: Ipv6Addr, dst_ipv6: Ipv6Addr) -> Result<(Option<MacAddr>, Option<Duration>)> {
-    // same as arp in ipv4
-    let interface = match find_interface_by_ip(src_ipv6.into()) {
-        Some(i) => i,
-        None => return Err(CanNotFoundInterface::new().into()),
-    };
-    let src_mac = match interface.mac {
-        Some(m) => m,
-        None => return Err(CanNotFoundMacAddress::new().into()),
-    };
-
+/// The neighbor solicitation of `dst_ipv6` sent to its solicited-node multicast address,
+/// with the `src_mac` as the source link-layer address option.
+pub fn ndp_ns_packet(src_ipv6: Ipv6Addr, src_mac: MacAddr, dst_ipv6: Ipv6Addr) -> Vec<u8> {
     // ipv6
-    let mut ipv6_buff = [0u8; IPV6_HEADER_SIZE + ICMPV6_NS_HEADER_SIZE];
+    let mut ipv6_buff = vec![0u8; IPV6_HEADER_SIZE + ICMPV6_NS_HEADER_SIZE];
     let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
     ipv6_header.set_version(6);
     ipv6_header.set_traffic_class(0);
@@ -823,6 +1085,59 @@ fn ndp_ns(src_ipv6: Ipv6Addr, dst_ipv6: Ipv6Addr) -> Result<(Option<MacAddr>, Op
     let checksum = icmpv6::checksum(&icmpv6_header.to_immutable(), &src_ipv6, &dst_multicast);
     icmpv6_header.set_checksum(checksum);
 
+    ipv6_buff
+}
+
+/// The router solicitation sent to all-routers (ff02::2),
+/// with the `src_mac` as the source link-layer address option.
+pub fn ndp_rs_packet(src_ipv6: Ipv6Addr, src_mac: MacAddr) -> Vec<u8> {
+    let route_addr_2 = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x0002);
+    // ipv6
+    let mut ipv6_buff = vec![0u8; IPV6_HEADER_SIZE + ICMPV6_RS_HEADER_SIZE];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_traffic_class(0);
+    ipv6_header.set_flow_label(0);
+    ipv6_header.set_payload_length(ICMPV6_RS_HEADER_SIZE as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocols::Icmpv6);
+    ipv6_header.set_hop_limit(255);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(route_addr_2);
+
+    // icmpv6
+    let mut icmpv6_header =
+        MutableRouterSolicitPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    // Router Solicitation
+    icmpv6_header.set_icmpv6_type(Icmpv6Types::RouterSolicit);
+    icmpv6_header.set_icmpv6_code(Icmpv6Code(0));
+    icmpv6_header.set_reserved(0);
+    let ndp_option = NdpOption {
+        option_type: NdpOptionTypes::SourceLLAddr,
+        length: 1,
+        data: src_mac.octets().to_vec(),
+    };
+    icmpv6_header.set_options(&[ndp_option]);
+
+    let mut icmpv6_header = MutableIcmpv6Packet::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    let checksum = icmpv6::checksum(&icmpv6_header.to_immutable(), &src_ipv6, &route_addr_2);
+    icmpv6_header.set_checksum(checksum);
+
+    ipv6_buff
+}
+
+fn ndp_ns(src_ipv6: Ipv6Addr, dst_ipv6: Ipv6Addr) -> Result<(Option<MacAddr>, Option<Duration>)> {
+    // same as arp in ipv4
+    let interface = match find_interface_by_ip(src_ipv6.into()) {
+        Some(i) => i,
+        None => return Err(CanNotFoundInterface::new().into()),
+    };
+    let src_mac = match interface.mac {
+        Some(m) => m,
+        None => return Err(CanNotFoundMacAddress::new().into()),
+    };
+
+    let ipv6_buff = ndp_ns_packet(src_ipv6, src_mac, dst_ipv6);
+
     let layer3 = Layer3Match {
         layer2: None,
         src_addr: Some(dst_ipv6.into()),
@@ -872,7 +1187,6 @@ fn get_mac_from_ndp_rs(buff: &[u8]) -> Option<MacAddr> {
 
 fn ndp_rs(src_ipv6: Ipv6Addr) -> Result<(Option<MacAddr>, Option<Duration>)> {
     // router solicitation
-    let route_addr_2 = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x0002);
     // let route_addr_1 = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x0001);
     let interface = match find_interface_by_ip(src_ipv6.into()) {
         Some(i) => i,
@@ -883,35 +1197,7 @@ fn ndp_rs(src_ipv6: Ipv6Addr) -> Result<(Option<MacAddr>, Option<Duration>)> {
         None => return Err(CanNotFoundMacAddress::new().into()),
     };
 
-    // ipv6
-    let mut ipv6_buff = [0u8; IPV6_HEADER_SIZE + ICMPV6_RS_HEADER_SIZE];
-    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
-    ipv6_header.set_version(6);
-    ipv6_header.set_traffic_class(0);
-    ipv6_header.set_flow_label(0);
-    ipv6_header.set_payload_length(ICMPV6_RS_HEADER_SIZE as u16);
-    ipv6_header.set_next_header(IpNextHeaderProtocols::Icmpv6);
-    ipv6_header.set_hop_limit(255);
-    ipv6_header.set_source(src_ipv6);
-    ipv6_header.set_destination(route_addr_2);
-
-    // icmpv6
-    let mut icmpv6_header =
-        MutableRouterSolicitPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
-    // Neighbor Solicitation
-    icmpv6_header.set_icmpv6_type(Icmpv6Types::RouterSolicit);
-    icmpv6_header.set_icmpv6_code(Icmpv6Code(0));
-    icmpv6_header.set_reserved(0);
-    let ndp_option = NdpOption {
-        option_type: NdpOptionTypes::SourceLLAddr,
-        length: 1,
-        data: src_mac.octets().to_vec(),
-    };
-    icmpv6_header.set_options(&vec![ndp_option]);
-
-    let mut icmpv6_header = MutableIcmpv6Packet::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
-    let checksum = icmpv6::checksum(&icmpv6_header.to_immutable(), &src_ipv6, &route_addr_2);
-    icmpv6_header.set_checksum(checksum);
+    let ipv6_buff = ndp_rs_packet(src_ipv6, src_mac);
 
     // let layer3 = Layer3Match {
     //     layer2: None,
@@ -1062,4 +1348,133 @@ mod tests {
         let ret = dns_query(hostname).unwrap();
         println!("{:?}", ret);
     }
+    #[test]
+    fn test_layer4_match_icmp_error() {
+        let src_ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        let dst_ipv4 = Ipv4Addr::new(8, 8, 8, 8);
+        let router_ipv4 = Ipv4Addr::new(10, 0, 0, 1);
+        // ethernet + ipv4 (router => us) + icmp time exceeded
+        let mut buff = vec![0u8; ETHERNET_HEADER_SIZE];
+        buff[12..14].copy_from_slice(&[0x08, 0x00]);
+        buff.extend([0x45, 0x00, 0x00, 0x38, 0, 0, 0, 0, 0x40, 0x01, 0, 0]);
+        buff.extend(router_ipv4.octets());
+        buff.extend(src_ipv4.octets());
+        buff.extend([11, 0, 0, 0, 0, 0, 0, 0]);
+        // quoted probe: ipv4 (us => dst) + udp 40000 => 33435
+        buff.extend([0x45, 0x00, 0x00, 0x24, 0, 0, 0, 0, 0x01, 0x11, 0, 0]);
+        buff.extend(src_ipv4.octets());
+        buff.extend(dst_ipv4.octets());
+        buff.extend([0x9c, 0x40, 0x82, 0x9b, 0x00, 0x10, 0x00, 0x00]);
+
+        let mut layer4_icmp_error = Layer4MatchIcmpError {
+            layer3: None,
+            quoted_src_addr: Some(src_ipv4.into()),
+            quoted_dst_addr: Some(dst_ipv4.into()),
+            quoted_protocol: Some(IpNextHeaderProtocols::Udp),
+            quoted_src_port: Some(40000),
+            quoted_dst_port: Some(33435),
+            quoted_id: None,
+        };
+        assert!(layer4_icmp_error.do_match(&buff));
+        layer4_icmp_error.quoted_dst_port = Some(33436);
+        assert!(!layer4_icmp_error.do_match(&buff));
+        // the udp checksum of the probe
+        layer4_icmp_error.quoted_dst_port = Some(33435);
+        layer4_icmp_error.quoted_id = Some((6, 0x0000));
+        assert!(layer4_icmp_error.do_match(&buff));
+        layer4_icmp_error.quoted_id = Some((6, 0x0001));
+        assert!(!layer4_icmp_error.do_match(&buff));
+    }
+    #[test]
+    fn test_layer3_match_protocol() {
+        let src_ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        let dst_ipv4 = Ipv4Addr::new(192, 168, 1, 3);
+        // ethernet + ipv4 (target => us) + igmp report
+        let mut buff = vec![0u8; ETHERNET_HEADER_SIZE];
+        buff[12..14].copy_from_slice(&[0x08, 0x00]);
+        buff.extend([0x45, 0x00, 0x00, 0x1c, 0, 0, 0, 0, 0x01, 0x02, 0, 0]);
+        buff.extend(dst_ipv4.octets());
+        buff.extend(src_ipv4.octets());
+        buff.extend([0x16, 0, 0, 0, 0, 0, 0, 0]);
+
+        let layer3 = Layer3Match {
+            layer2: None,
+            src_addr: Some(dst_ipv4.into()),
+            dst_addr: Some(src_ipv4.into()),
+        };
+        let mut layer3_protocol = Layer3MatchProtocol {
+            layer3: Some(layer3),
+            protocol: IpNextHeaderProtocols::Igmp,
+        };
+        assert!(layer3_protocol.do_match(&buff));
+        layer3_protocol.protocol = IpNextHeaderProtocols::Tcp;
+        assert!(!layer3_protocol.do_match(&buff));
+    }
+    #[test]
+    fn test_layer4_match_icmp_error_no_ports() {
+        let src_ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        let dst_ipv4 = Ipv4Addr::new(192, 168, 1, 3);
+        // ethernet + ipv4 (target => us) + icmp protocol unreachable
+        let mut buff = vec![0u8; ETHERNET_HEADER_SIZE];
+        buff[12..14].copy_from_slice(&[0x08, 0x00]);
+        buff.extend([0x45, 0x00, 0x00, 0x30, 0, 0, 0, 0, 0x40, 0x01, 0, 0]);
+        buff.extend(dst_ipv4.octets());
+        buff.extend(src_ipv4.octets());
+        buff.extend([3, 2, 0, 0, 0, 0, 0, 0]);
+        // quoted probe: ipv4 (us => target) ip-in-ip with no payload
+        buff.extend([0x45, 0x00, 0x00, 0x14, 0, 0, 0x40, 0, 0x40, 0x04, 0, 0]);
+        buff.extend(src_ipv4.octets());
+        buff.extend(dst_ipv4.octets());
+
+        let layer4_icmp_error = Layer4MatchIcmpError {
+            layer3: None,
+            quoted_src_addr: Some(src_ipv4.into()),
+            quoted_dst_addr: Some(dst_ipv4.into()),
+            quoted_protocol: Some(IpNextHeaderProtocol(4)),
+            quoted_src_port: None,
+            quoted_dst_port: None,
+            quoted_id: None,
+        };
+        assert!(layer4_icmp_error.do_match(&buff));
+        let layer4_icmp_error = Layer4MatchIcmpError {
+            quoted_protocol: Some(IpNextHeaderProtocols::Igmp),
+            ..layer4_icmp_error
+        };
+        assert!(!layer4_icmp_error.do_match(&buff));
+    }
+    #[test]
+    fn test_ndp_packets() {
+        let src_ipv6: Ipv6Addr = "fe80::20c:29ff:fe2a:e252".parse().unwrap();
+        let dst_ipv6: Ipv6Addr = "fe80::20c:29ff:fe5b:bd5c".parse().unwrap();
+        let src_mac = MacAddr::new(0x00, 0x0c, 0x29, 0x2a, 0xe2, 0x52);
+
+        let buff = ndp_ns_packet(src_ipv6, src_mac, dst_ipv6);
+        let ipv6_packet = Ipv6Packet::new(&buff).unwrap();
+        let dst_multicast = Ipv6::new(dst_ipv6).link_multicast();
+        assert_eq!(ipv6_packet.get_hop_limit(), 255);
+        assert_eq!(ipv6_packet.get_destination(), dst_multicast);
+        let icmpv6_packet = Icmpv6Packet::new(ipv6_packet.payload()).unwrap();
+        assert_eq!(
+            icmpv6_packet.get_icmpv6_type(),
+            Icmpv6Types::NeighborSolicit
+        );
+        let checksum = icmpv6::checksum(&icmpv6_packet, &src_ipv6, &dst_multicast);
+        assert_eq!(icmpv6_packet.get_checksum(), checksum);
+        // target address then the source link-layer address option
+        assert_eq!(
+            &buff[IPV6_HEADER_SIZE + 8..IPV6_HEADER_SIZE + 24],
+            &dst_ipv6.octets()
+        );
+        assert_eq!(&buff[IPV6_HEADER_SIZE + 26..], &src_mac.octets());
+
+        let buff = ndp_rs_packet(src_ipv6, src_mac);
+        let ipv6_packet = Ipv6Packet::new(&buff).unwrap();
+        let all_routers = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x0002);
+        assert_eq!(ipv6_packet.get_destination(), all_routers);
+        let icmpv6_packet = Icmpv6Packet::new(ipv6_packet.payload()).unwrap();
+        assert_eq!(icmpv6_packet.get_icmpv6_type(), Icmpv6Types::RouterSolicit);
+        let checksum = icmpv6::checksum(&icmpv6_packet, &src_ipv6, &all_routers);
+        assert_eq!(icmpv6_packet.get_checksum(), checksum);
+        assert_eq!(&buff[IPV6_HEADER_SIZE + 10..], &src_mac.octets());
+    }
 }
@@ -13,6 +13,8 @@ use std::sync::Arc;
 use std::sync::Mutex;
 use subnetwork::Ipv4Pool;
 
+pub mod dns;
+pub mod firewall;
 pub mod flood;
 pub mod hop;
 pub mod os;
@@ -213,8 +215,17 @@ impl Target {
 
 pub use scan::arp_scan;
 pub use scan::arp_scan_raw;
+pub use scan::discovery_scan;
+pub use scan::llmnr_discovery_scan;
+pub use scan::mdns_discovery_scan;
+pub use scan::ndp_multicast_scan;
+pub use scan::ndp_scan;
+pub use scan::ndp_scan_raw;
+pub use scan::netbios_discovery_scan;
+pub use scan::passive_scan;
 pub use scan::scan;
 pub use scan::scan_raw;
+pub use scan::ssdp_discovery_scan;
 pub use scan::tcp_ack_scan;
 pub use scan::tcp_ack_scan_raw;
 pub use scan::tcp_connect_scan;
@@ -240,6 +251,8 @@ pub use scan::udp_scan_raw;
 
 pub use ping::icmp_ping;
 pub use ping::icmp_ping_raw;
+pub use ping::ip_protocol_ping;
+pub use ping::ip_protocol_ping_raw;
 pub use ping::ping;
 pub use ping::tcp_ack_ping;
 pub use ping::tcp_ack_ping_raw;
@@ -267,8 +280,39 @@ pub use flood::udp_flood_raw;
 
 pub use os::os_detect;
 pub use os::os_detect_raw;
+pub use os::os_detect_raw_with_options;
+pub use os::os_detect_with_db;
+pub use os::os_detect_with_options;
+pub use os::passive_os_detect;
+pub use os::passive_os_detect_pcap;
+pub use os::passive_os_detect_pcap_with_db;
+pub use os::passive_os_detect_with_db;
+pub use os::OsDetectDb;
+pub use os::OsDetectOptions;
 pub use vs::vs_scan;
 pub use vs::vs_scan_raw;
+pub use vs::vs_scan_raw_with_db;
+pub use vs::vs_scan_with_db;
+pub use vs::ServiceProbesDb;
+pub use vs::VsProbeOptions;
+
+/* Traceroute */
+
+pub use hop::multipath_traceroute;
+pub use hop::paris_traceroute;
+pub use hop::path_mtu_discovery;
+pub use hop::traceroute;
+
+/* Firewall */
+
+pub use firewall::firewall_profile;
 
 /* DNS */
+pub use dns::dns_resolve;
+pub use dns::dns_subdomain_brute;
+pub use dns::dns_zone_transfer;
+pub use dns::reverse_dns;
+pub use dns::DnsRecordType;
+pub use dns::DnsTransport;
+pub use dns::ResolveHostnames;
 pub use layers::dns_query;
@@ -2,64 +2,192 @@
 use anyhow::Result;
 use log::debug;
 use log::warn;
+use once_cell::sync::OnceCell;
 use prettytable::row;
 use prettytable::Cell;
 use prettytable::Row;
 use prettytable::Table;
 use serde::Deserialize;
 use serde::Serialize;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
+use std::path::Path;
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::dns::addr_with_hostname;
+use crate::dns::ResolveHostnames;
+use crate::errors::CanNotFoundInterface;
 use crate::errors::CanNotFoundSourceAddress;
+use crate::errors::LinearParametersInvalid;
+use crate::errors::OsDetectNoOpenPort;
 use crate::errors::OsDetectPortError;
+use crate::errors::OsDetectTtlIpv6Unsupported;
+use crate::layers::layer2_capture;
+use crate::os::cpe::CpeName;
+use crate::os::cpe::OsClass;
+use crate::os::dbparser::MatchPoints;
 use crate::os::dbparser::NmapOsDb;
+use crate::os::operator6::feature_names;
+use crate::os::operator6::FEATURES_LEN;
+use crate::os::operator6::NMAP_FEATURES_LEN;
 use crate::os::osscan::threads_os_probe;
 use crate::os::osscan::PistolFingerprint;
 use crate::os::osscan6::threads_os_probe6;
 use crate::os::osscan6::PistolFingerprint6;
+use crate::os::p0f::guess_distance;
+use crate::os::p0f::pcap_ip_packets;
+use crate::os::p0f::P0fDb;
+use crate::os::p0f::P0fDirection;
+use crate::os::p0f::P0fMatch;
+use crate::os::p0f::P0fPacket;
+use crate::os::seqinfo::IsnInfo;
+use crate::os::seqinfo::SeqInfo;
+use crate::scan::tcp_syn_scan;
+use crate::scan::tcp_syn_scan_raw;
+use crate::scan::udp_scan_raw;
+use crate::scan::PortScanResults;
+use crate::scan::PortStatus;
+use crate::scan::ScanMethod;
+use crate::utils::find_interface_by_name;
 use crate::utils::find_source_addr;
 use crate::utils::find_source_addr6;
 use crate::utils::get_default_timeout;
 use crate::utils::get_threads_pool;
+use crate::utils::load_snapshot;
+use crate::utils::random_port;
+use crate::utils::save_snapshot;
+use crate::Host;
 use crate::Target;
 
+pub mod cpe;
 pub mod dbparser;
 pub mod operator;
 pub mod operator6;
 pub mod osscan;
 pub mod osscan6;
+pub mod p0f;
 pub mod packet;
 pub mod packet6;
 pub mod rr;
+pub mod seqinfo;
+pub mod train6;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsInfo {
     pub info: String,
     pub class: String,
     pub cpe: String,
+    /// The structured `class`, one for each `Class` line.
+    pub classes: Vec<OsClass>,
+    /// The structured `cpe`, one for each `CPE` line.
+    pub cpes: Vec<CpeName>,
     pub score: usize,
     pub total: usize,
+    /// The `score / total` in percent.
+    pub accuracy: f64,
+    /// Candidates with the same accuracy share the same rank, starting from 1.
+    pub rank: usize,
     pub db: NmapOsDb,
 }
 
+impl OsInfo {
+    pub fn new(db: &NmapOsDb, score: usize, total: usize) -> OsInfo {
+        let accuracy = if total > 0 {
+            score as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        OsInfo {
+            info: db.info.clone(),
+            class: db.class.clone(),
+            cpe: db.cpe.clone(),
+            classes: OsClass::parse_lines(&db.class),
+            cpes: CpeName::parse_lines(&db.cpe),
+            score,
+            total,
+            accuracy,
+            rank: 0,
+            db: db.clone(),
+        }
+    }
+    /// Compare the exact `score / total` ratio, avoiding float rounding when grouping ties.
+    fn cmp_accuracy(&self, other: &OsInfo) -> Ordering {
+        let a = self.score as u128 * other.total.max(1) as u128;
+        let b = other.score as u128 * self.total.max(1) as u128;
+        a.cmp(&b)
+    }
+}
+
+/// Sort the candidates by accuracy from high to low and keep every candidate of the `top_k` best distinct accuracies.
+/// Candidates with the same accuracy are all kept, share one rank and stay in the input (database) order,
+/// so the number of returned candidates may be larger than `top_k`.
+pub fn rank_os_infos(os_infos: Vec<OsInfo>, top_k: usize) -> Vec<OsInfo> {
+    let mut os_infos = os_infos;
+    // stable sort, ties keep the input order
+    os_infos.sort_by(|a, b| b.cmp_accuracy(a));
+
+    let mut ret: Vec<OsInfo> = Vec::new();
+    let mut rank = 0;
+    for mut os_info in os_infos {
+        let tied = match ret.last() {
+            Some(last) => last.cmp_accuracy(&os_info) == Ordering::Equal,
+            None => false,
+        };
+        if !tied {
+            rank += 1;
+            if rank > top_k {
+                break;
+            }
+        }
+        os_info.rank = rank;
+        ret.push(os_info);
+    }
+    ret
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HostOsDetect4 {
     pub fingerprint: PistolFingerprint,
     pub detects: Vec<OsInfo>,
+    /// Some tests are skipped, the results are matched with a partial fingerprint and less reliable.
+    pub partial: bool,
+    /// The probe rounds gave different results, see `PistolFingerprint.inconsistent_tests`.
+    pub inconsistent: bool,
+    /// The uptime guess and the TCP sequence prediction, like nmap.
+    pub seq_info: SeqInfo,
 }
 
 impl HostOsDetect4 {
+    /// Without the probes, only the ISN can be analysed from the SEQ test of the `fingerprint`.
     pub fn new(fingerprint: PistolFingerprint, detects: Vec<OsInfo>) -> HostOsDetect4 {
+        let seq_info = SeqInfo {
+            ts: None,
+            isn: IsnInfo::new(&fingerprint.seqx),
+        };
+        HostOsDetect4::with_seq_info(fingerprint, seq_info, detects)
+    }
+    /// The `seq_info` is analysed from the SEQ responses, see `threads_os_probe`.
+    pub fn with_seq_info(
+        fingerprint: PistolFingerprint,
+        seq_info: SeqInfo,
+        detects: Vec<OsInfo>,
+    ) -> HostOsDetect4 {
+        let partial = !fingerprint.skipped_tests.is_empty();
+        let inconsistent = !fingerprint.inconsistent_tests.is_empty();
         HostOsDetect4 {
             fingerprint,
             detects,
+            partial,
+            inconsistent,
+            seq_info,
         }
     }
 }
@@ -67,12 +195,14 @@ impl HostOsDetect4 {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsDetectResults {
     pub oss: HashMap<IpAddr, HostOsDetect>,
+    pub hostnames: HashMap<IpAddr, String>,
 }
 
 impl OsDetectResults {
     pub fn new() -> OsDetectResults {
         OsDetectResults {
             oss: HashMap::new(),
+            hostnames: HashMap::new(),
         }
     }
     pub fn get(&self, k: &IpAddr) -> Option<&HostOsDetect> {
@@ -80,6 +210,15 @@ impl OsDetectResults {
     }
 }
 
+impl ResolveHostnames for OsDetectResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.oss.keys().copied().collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
 impl fmt::Display for OsDetectResults {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new();
@@ -90,13 +229,30 @@ impl fmt::Display for OsDetectResults {
         let oss = &self.oss;
         let oss: BTreeMap<IpAddr, &HostOsDetect> = oss.into_iter().map(|(i, h)| (*i, h)).collect();
         for (ip, o) in oss {
+            let ip_str = addr_with_hostname(&ip, &self.hostnames);
             match o {
                 HostOsDetect::V4(o) => {
-                    for (i, ni) in o.detects.iter().enumerate() {
-                        let number_str = format!("#{}", i + 1);
-                        let score_str = format!("{}/{}", ni.score, ni.total);
+                    for ni in &o.detects {
+                        let number_str = format!("#{}", ni.rank);
+                        let mut notes = format!("{:.1}%", ni.accuracy);
+                        if o.partial {
+                            notes += ", partial";
+                        }
+                        if o.inconsistent {
+                            notes += ", inconsistent";
+                        }
+                        let score_str = format!("{}/{} ({})", ni.score, ni.total, notes);
                         let os_str = format!("{}", ni.db.info);
-                        table.add_row(row![c -> ip, c -> number_str, c -> score_str, c -> os_str]);
+                        table.add_row(
+                            row![c -> ip_str, c -> number_str, c -> score_str, c -> os_str],
+                        );
+                    }
+                    let seq_info_str = format!("{}", o.seq_info);
+                    if !seq_info_str.is_empty() {
+                        table.add_row(Row::new(vec![
+                            Cell::new(&ip_str).style_spec("c"),
+                            Cell::new(&seq_info_str).with_hspan(3),
+                        ]));
                     }
                 }
                 HostOsDetect::V6(o) => {
@@ -104,7 +260,21 @@ impl fmt::Display for OsDetectResults {
                         let number_str = format!("#{}", i + 1);
                         let score_str = format!("{:.1}", os_info6.score);
                         let os_str = &os_info6.info;
-                        table.add_row(row![c -> ip, c -> number_str, c -> score_str, c -> os_str]);
+                        table.add_row(
+                            row![c -> ip_str, c -> number_str, c -> score_str, c -> os_str],
+                        );
+                    }
+                    if o.detects.is_empty() {
+                        let status_str = format!(
+                            "{} (novelty {:.2}, margin {:.1}%)",
+                            o.status,
+                            o.novelty,
+                            o.margin * 100.0
+                        );
+                        table.add_row(Row::new(vec![
+                            Cell::new(&ip_str).style_spec("c"),
+                            Cell::new(&status_str).style_spec("c").with_hspan(3),
+                        ]));
                     }
                 }
             }
@@ -120,6 +290,8 @@ pub struct OsInfo6 {
     pub info: String,
     pub class: String,
     pub cpe: String,
+    pub classes: Vec<OsClass>,
+    pub cpes: Vec<CpeName>,
     pub score: f64,
     pub label: usize,
 }
@@ -127,14 +299,63 @@ pub struct OsInfo6 {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HostOsDetect6 {
     pub fingerprint: PistolFingerprint6,
+    /// Empty unless the `status` is `Matched`.
     pub detects: Vec<OsInfo6>,
+    /// The distance between the fingerprint and the best class, see `FP_NOVELTY_THRESHOLD`.
+    pub novelty: f64,
+    /// The relative score margin between the top two classes, see `FP_AMBIGUITY_MARGIN`.
+    pub margin: f64,
+    pub status: OsDetectStatus6,
 }
 
 impl HostOsDetect6 {
     pub fn new(fingerprint: PistolFingerprint6, detects: Vec<OsInfo6>) -> HostOsDetect6 {
+        let novelty = fingerprint.novelty;
+        let margin = fingerprint.margin;
+        let status = OsDetectStatus6::new(novelty, margin);
         HostOsDetect6 {
             fingerprint,
             detects,
+            novelty,
+            margin,
+            status,
+        }
+    }
+}
+
+/// The fingerprint is too far from the best class to be trusted when the novelty is not below this, like nmap.
+pub const FP_NOVELTY_THRESHOLD: f64 = 15.0;
+/// The classification is ambiguous when the top two classes differ by no more than 10%, like nmap.
+pub const FP_AMBIGUITY_MARGIN: f64 = 0.1;
+
+/// The result of the IPv6 classification, a probe failure is returned as an error instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OsDetectStatus6 {
+    Matched,
+    /// The novelty is not below `FP_NOVELTY_THRESHOLD`, the OS is probably not in the database.
+    TooNovel,
+    /// The margin is not above `FP_AMBIGUITY_MARGIN`.
+    Ambiguous,
+}
+
+impl OsDetectStatus6 {
+    pub fn new(novelty: f64, margin: f64) -> OsDetectStatus6 {
+        if margin <= FP_AMBIGUITY_MARGIN {
+            OsDetectStatus6::Ambiguous
+        } else if novelty >= FP_NOVELTY_THRESHOLD {
+            OsDetectStatus6::TooNovel
+        } else {
+            OsDetectStatus6::Matched
+        }
+    }
+}
+
+impl fmt::Display for OsDetectStatus6 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OsDetectStatus6::Matched => write!(f, "matched"),
+            OsDetectStatus6::TooNovel => write!(f, "too novel"),
+            OsDetectStatus6::Ambiguous => write!(f, "ambiguous"),
         }
     }
 }
@@ -158,6 +379,8 @@ pub struct CPE {
     pub cpe: Vec<String>,
 }
 
+/// The logistic regression model for the IPv6 OS detection, one OS class per `infolist` entry.
+/// The `w`, `mean` and `variance` are indexed by the class, then by the feature, the `scale` by the feature.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Linear {
     pub infolist: Vec<String>,
@@ -168,143 +391,554 @@ pub struct Linear {
     pub cpe: Vec<CPE>,
 }
 
+impl Linear {
+    /// Build the model from the content of the `variance.json`, `mean.json`, `scale.json`, `w.json` and `cpe.json` files.
+    /// The `w.json` holds one entry per feature, the weights of all OS classes in the order of `variance.json`.
+    pub fn from_json(
+        variance_json_data: &str,
+        mean_json_data: &str,
+        scale_json_data: &str,
+        w_json_data: &str,
+        cpe_json_data: &str,
+    ) -> Result<Linear> {
+        let variance_json: Vec<NmapJsonParameters> = serde_json::from_str(variance_json_data)?;
+        let mut infolist = Vec::new();
+        let mut variance = Vec::new();
+        for v in variance_json {
+            variance.push(v.value);
+            infolist.push(v.name);
+        }
+
+        let mean_json: Vec<NmapJsonParameters> = serde_json::from_str(mean_json_data)?;
+        let mut mean = Vec::new();
+        for m in mean_json {
+            mean.push(m.value);
+        }
+
+        let scale_json: Vec<NmapJsonParameters> = serde_json::from_str(scale_json_data)?;
+        let mut scale: Vec<Vec<f64>> = Vec::new();
+        for s in scale_json {
+            scale.push(s.value)
+        }
+
+        let w_json: Vec<NmapJsonParameters> = serde_json::from_str(w_json_data)?;
+        let mut w = Vec::new();
+        // [695, 92] => [92, 695]
+        if !w_json.is_empty() {
+            for i in 0..w_json[0].value.len() {
+                let mut tmp = Vec::new();
+                for x in &w_json {
+                    match x.value.get(i) {
+                        Some(v) => tmp.push(*v),
+                        None => {
+                            return Err(LinearParametersInvalid::new(format!(
+                                "weights of {} are not complete",
+                                x.name
+                            ))
+                            .into())
+                        }
+                    }
+                }
+                w.push(tmp);
+            }
+        }
+
+        let cpe: Vec<CPE> = serde_json::from_str(cpe_json_data)?;
+
+        let linear = Linear {
+            infolist,
+            scale,
+            w,
+            mean,
+            variance,
+            cpe,
+        };
+        linear.check()?;
+        Ok(linear)
+    }
+    /// Load the five json files from the `dir`, such as the files written by `save_json`.
+    pub fn from_dir(dir: &str) -> Result<Linear> {
+        let read = |name: &str| -> Result<String> {
+            let path = Path::new(dir).join(name);
+            Ok(fs::read_to_string(path)?)
+        };
+        Linear::from_json(
+            &read("variance.json")?,
+            &read("mean.json")?,
+            &read("scale.json")?,
+            &read("w.json")?,
+            &read("cpe.json")?,
+        )
+    }
+    /// Write the model as the five json files to the `dir`, in the format `from_dir` and `from_json` load.
+    pub fn save_json(&self, dir: &str) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let feature_names = feature_names();
+        let mut variance_json = Vec::new();
+        let mut mean_json = Vec::new();
+        for (i, name) in self.infolist.iter().enumerate() {
+            variance_json.push(NmapJsonParameters {
+                name: name.clone(),
+                value: self.variance[i].clone(),
+            });
+            mean_json.push(NmapJsonParameters {
+                name: name.clone(),
+                value: self.mean[i].clone(),
+            });
+        }
+        let mut scale_json = Vec::new();
+        let mut w_json = Vec::new();
+        for (i, name) in feature_names.iter().take(self.scale.len()).enumerate() {
+            scale_json.push(NmapJsonParameters {
+                name: name.clone(),
+                value: self.scale[i].clone(),
+            });
+            // [92, 695] => [695, 92]
+            w_json.push(NmapJsonParameters {
+                name: name.clone(),
+                value: self.w.iter().map(|w| w[i]).collect(),
+            });
+        }
+        let write = |name: &str, data: String| -> Result<()> {
+            let path = Path::new(dir).join(name);
+            Ok(fs::write(path, data)?)
+        };
+        write("variance.json", serde_json::to_string(&variance_json)?)?;
+        write("mean.json", serde_json::to_string(&mean_json)?)?;
+        write("scale.json", serde_json::to_string(&scale_json)?)?;
+        write("w.json", serde_json::to_string(&w_json)?)?;
+        write("cpe.json", serde_json::to_string(&self.cpe)?)?;
+        Ok(())
+    }
+    /// The number of the features the model uses, at most `operator6::FEATURES_LEN`.
+    pub fn features_len(&self) -> usize {
+        self.scale.len()
+    }
+    /// All the parameters must have one entry per OS class and one value per feature.
+    fn check(&self) -> Result<()> {
+        let classes = self.infolist.len();
+        let dims = self.scale.len();
+        if classes == 0 || dims == 0 || dims > FEATURES_LEN {
+            return Err(LinearParametersInvalid::new(format!(
+                "{} classes and {} features",
+                classes, dims
+            ))
+            .into());
+        }
+        if self.w.len() != classes
+            || self.mean.len() != classes
+            || self.variance.len() != classes
+            || self.cpe.len() != classes
+        {
+            return Err(LinearParametersInvalid::new(format!(
+                "the number of classes is not {}",
+                classes
+            ))
+            .into());
+        }
+        for x in self.w.iter().chain(&self.mean).chain(&self.variance) {
+            if x.len() != dims {
+                return Err(LinearParametersInvalid::new(format!(
+                    "the number of features is not {}",
+                    dims
+                ))
+                .into());
+            }
+        }
+        for ab in &self.scale {
+            if ab.len() != 2 {
+                return Err(LinearParametersInvalid::new(String::from(
+                    "the scale is not in [a, b] pairs",
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+static EMBEDDED_LINEAR: OnceCell<Linear> = OnceCell::new();
+
+/// The embedded IPv6 linear model, parsed at the first call and cached after that.
+fn embedded_linear() -> Result<Linear> {
+    let linear = EMBEDDED_LINEAR.get_or_try_init(gen_linear)?;
+    Ok(linear.clone())
+}
+
 fn gen_linear() -> Result<Linear> {
-    let variance_json_data = include_str!("./db/nmap-os-db-ipv6/variance.json");
-    let variance_json: Vec<NmapJsonParameters> = serde_json::from_str(variance_json_data)?;
-
-    let mut infolist = Vec::new();
-    let mut variance = Vec::new();
-    for v in variance_json {
-        variance.push(v.value);
-        infolist.push(v.name);
-    }
-    assert_eq!(infolist.len(), 92);
-    assert_eq!(variance.len(), 92);
-
-    let mean_json_data = include_str!("./db/nmap-os-db-ipv6/mean.json");
-    let mean_json: Vec<NmapJsonParameters> = serde_json::from_str(mean_json_data)?;
-    let mut mean = Vec::new();
-    for m in mean_json {
-        mean.push(m.value);
-    }
-    assert_eq!(mean.len(), 92);
-
-    let scale_json_data = include_str!("./db/nmap-os-db-ipv6/scale.json"); // static
-    let scale_json: Vec<NmapJsonParameters> = serde_json::from_str(scale_json_data)?;
-    let mut scale: Vec<Vec<f64>> = Vec::new();
-    for s in scale_json {
-        scale.push(s.value)
-    }
-    assert_eq!(scale.len(), 695);
-
-    let w_json_data = include_str!("./db/nmap-os-db-ipv6/w.json"); // static
-    let w_json: Vec<NmapJsonParameters> = serde_json::from_str(w_json_data)?;
-    assert_eq!(w_json.len(), 695);
-
-    let mut w = Vec::new();
-    // [695, 92] => [92, 695]
-    for i in 0..w_json[0].value.len() {
-        let mut tmp = Vec::new();
-        for x in &w_json {
-            tmp.push(x.value[i]);
-        }
-        w.push(tmp);
-    }
-
-    let cpe_json_data = include_str!("./db/nmap-os-db-ipv6/cpe.json"); // static
-    let cpe: Vec<CPE> = serde_json::from_str(cpe_json_data)?;
-    assert_eq!(cpe.len(), 92);
-
-    let linear = Linear {
-        infolist,
-        scale,
-        w,
-        mean,
-        variance,
-        cpe,
-    };
+    let linear = Linear::from_json(
+        include_str!("./db/nmap-os-db-ipv6/variance.json"),
+        include_str!("./db/nmap-os-db-ipv6/mean.json"),
+        include_str!("./db/nmap-os-db-ipv6/scale.json"), // static
+        include_str!("./db/nmap-os-db-ipv6/w.json"),     // static
+        include_str!("./db/nmap-os-db-ipv6/cpe.json"),   // static
+    )?;
+    assert_eq!(linear.infolist.len(), 92);
+    assert_eq!(linear.scale.len(), NMAP_FEATURES_LEN);
     Ok(linear)
 }
 
+static EMBEDDED_OS_DB: OnceCell<Arc<OsDetectDb>> = OnceCell::new();
+
+/// The parsed OS detection databases, the nmap-os-db for IPv4 and the linear model for IPv6.
+/// Load it once and share it across `os_detect_with_db` calls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsDetectDb {
+    pub nmap_os_db: Vec<NmapOsDb>,
+    pub match_points: MatchPoints,
+    pub linear: Linear,
+}
+
+impl OsDetectDb {
+    /// The embedded databases, parsed at the first call and cached after that.
+    pub fn embedded() -> Result<Arc<OsDetectDb>> {
+        let db = EMBEDDED_OS_DB.get_or_try_init(|| -> Result<Arc<OsDetectDb>> {
+            let db = OsDetectDb::from_nmap_os_db(include_str!("./db/nmap-os-db"))?;
+            Ok(Arc::new(db))
+        })?;
+        Ok(db.clone())
+    }
+    /// Parse the content of a nmap-os-db file, the IPv6 linear model is the embedded one.
+    pub fn from_nmap_os_db(nmap_os_file: &str) -> Result<OsDetectDb> {
+        let mut nmap_os_file_lines = Vec::new();
+        for l in nmap_os_file.lines() {
+            nmap_os_file_lines.push(l.to_string());
+        }
+        let match_points = MatchPoints::parser(&nmap_os_file_lines)?;
+        let nmap_os_db = dbparser::nmap_os_db_parser(nmap_os_file_lines)?;
+        debug!("ipv4 nmap os db parse finish");
+        let linear = embedded_linear()?;
+        Ok(OsDetectDb {
+            nmap_os_db,
+            match_points,
+            linear,
+        })
+    }
+    /// Load a nmap-os-db file, such as the one from a newer nmap release.
+    pub fn from_file(path: &str) -> Result<OsDetectDb> {
+        let nmap_os_file = fs::read_to_string(path)?;
+        OsDetectDb::from_nmap_os_db(&nmap_os_file)
+    }
+    /// Save the parsed databases as a binary snapshot, which loads much faster than parsing.
+    pub fn save_snapshot(&self, path: &str) -> Result<()> {
+        save_snapshot(self, path)
+    }
+    pub fn load_snapshot(path: &str) -> Result<OsDetectDb> {
+        load_snapshot(path)
+    }
+}
+
+/// The common ports tried to find the open and the closed TCP port when the ports are not given.
+const OS_DETECT_TCP_PORTS: [u16; 20] = [
+    80, 22, 443, 21, 23, 25, 53, 110, 111, 135, 139, 143, 445, 993, 995, 1723, 3306, 3389, 5900,
+    8080,
+];
+
+/// The three ports used by the OS detection.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OsDetectPorts {
+    pub open_tcp_port: u16,
+    /// None if no closed TCP port is found, the T5, T6 and T7 tests are skipped on IPv4.
+    pub closed_tcp_port: Option<u16>,
+    /// None if no closed UDP port is found, the U1 test is skipped on IPv4.
+    pub closed_udp_port: Option<u16>,
+    /// The ports status are confirmed by the port scan, written as `G` in the scan line.
+    pub confirmed: bool,
+}
+
+impl OsDetectPorts {
+    /// The ports given by the caller, `[open_tcp, closed_tcp, closed_udp]` in this order.
+    pub fn new(ports: &[u16]) -> Result<OsDetectPorts> {
+        if ports.len() >= 3 {
+            Ok(OsDetectPorts {
+                open_tcp_port: ports[0],
+                closed_tcp_port: Some(ports[1]),
+                closed_udp_port: Some(ports[2]),
+                confirmed: true,
+            })
+        } else {
+            Err(OsDetectPortError::new().into())
+        }
+    }
+}
+
+/// Returns the first open and the first closed port in the scan order.
+fn choose_tcp_ports(status: &[(u16, PortStatus)]) -> (Option<u16>, Option<u16>) {
+    let mut open_port = None;
+    let mut closed_port = None;
+    for (port, s) in status {
+        match s {
+            PortStatus::Open if open_port.is_none() => open_port = Some(*port),
+            PortStatus::Closed if closed_port.is_none() => closed_port = Some(*port),
+            _ => (),
+        }
+    }
+    (open_port, closed_port)
+}
+
+/// The first status of each scanned TCP port of the host, in the port order,
+/// the results of the UDP scan have no TCP port, and the results without the method are taken as TCP.
+fn scanned_ports_status(scan_ret: &PortScanResults, dst_addr: IpAddr) -> Vec<(u16, PortStatus)> {
+    let mut status = Vec::new();
+    if scan_ret.method == Some(ScanMethod::Udp) {
+        return status;
+    }
+    if let Some(ports_status) = scan_ret.get(&dst_addr) {
+        for (port, s) in ports_status {
+            if !s.is_empty() {
+                status.push((*port, s[0]));
+            }
+        }
+    }
+    status.sort_by_key(|(port, _)| *port);
+    status
+}
+
+/// Select the ports for the OS detection like nmap:
+/// SYN scan the common ports to find an open and a closed TCP port,
+/// then probe a random high port if no closed TCP port is found, and another one for the closed UDP port.
+/// The closed ports are None if they can not be confirmed, and the related tests are skipped,
+/// the `confirmed` is false in this case and the results of the OS detection may be unreliable.
+/// Returns the `OsDetectNoOpenPort` error if no open port is found, most of the tests need one.
+/// All the scans use the `src_port` if given.
+pub fn select_os_detect_ports(
+    dst_addr: IpAddr,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<OsDetectPorts> {
+    let host = Host::new(dst_addr, Some(OS_DETECT_TCP_PORTS.to_vec()));
+    let target = Target::new(vec![host]);
+    let scan_ret = tcp_syn_scan(target, src_addr, src_port, threads_num, timeout, 1)?;
+
+    let mut status = Vec::new();
+    if let Some(ports_status) = scan_ret.get(&dst_addr) {
+        for port in OS_DETECT_TCP_PORTS {
+            match ports_status.get(&port) {
+                Some(s) if !s.is_empty() => status.push((port, s[0])),
+                _ => (),
+            }
+        }
+    }
+    let (open_port, closed_port) = choose_tcp_ports(&status);
+    complete_os_detect_ports(
+        dst_addr,
+        open_port,
+        closed_port,
+        src_addr,
+        src_port,
+        timeout,
+    )
+}
+
+/// Select the ports from the results of a previous TCP port scan, with the same source port,
+/// see `PortScanResults.method`.
+/// Same as `select_os_detect_ports` if the host has no open TCP port in the results.
+pub fn select_os_detect_ports_from_scan(
+    scan_ret: &PortScanResults,
+    dst_addr: IpAddr,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<OsDetectPorts> {
+    let status = scanned_ports_status(scan_ret, dst_addr);
+    match choose_tcp_ports(&status) {
+        (Some(open_port), closed_port) => complete_os_detect_ports(
+            dst_addr,
+            Some(open_port),
+            closed_port,
+            src_addr,
+            src_port,
+            timeout,
+        ),
+        (None, _) => select_os_detect_ports(dst_addr, src_addr, src_port, threads_num, timeout),
+    }
+}
+
+/// Probe the closed ports not found by the port scan.
+fn complete_os_detect_ports(
+    dst_addr: IpAddr,
+    open_port: Option<u16>,
+    closed_port: Option<u16>,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    timeout: Option<Duration>,
+) -> Result<OsDetectPorts> {
+    debug!(
+        "os detect ports, open: {:?}, closed: {:?}",
+        open_port, closed_port
+    );
+
+    let open_tcp_port = match open_port {
+        Some(p) => p,
+        None => return Err(OsDetectNoOpenPort::new().into()),
+    };
+    let closed_tcp_port = match closed_port {
+        Some(p) => Some(p),
+        None => {
+            let p = random_port();
+            let (s, _) = tcp_syn_scan_raw(dst_addr, p, src_addr, src_port, timeout)?;
+            match s {
+                PortStatus::Closed => Some(p),
+                _ => None,
+            }
+        }
+    };
+    let p = random_port();
+    let (s, _) = udp_scan_raw(dst_addr, p, src_addr, src_port, timeout)?;
+    let closed_udp_port = match s {
+        PortStatus::Closed => Some(p),
+        _ => None,
+    };
+    let confirmed = closed_tcp_port.is_some() && closed_udp_port.is_some();
+
+    Ok(OsDetectPorts {
+        open_tcp_port,
+        closed_tcp_port,
+        closed_udp_port,
+        confirmed,
+    })
+}
+
+/// Use the ports given by the caller, or select them from the `scan_ret` or by a scan if the `ports` is empty.
+fn get_os_detect_ports(
+    dst_addr: IpAddr,
+    ports: &[u16],
+    scan_ret: Option<&PortScanResults>,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    timeout: Duration,
+) -> Result<OsDetectPorts> {
+    // already in a worker of the os detect pool, scan the ports one by one
+    let threads_num = 1;
+    if ports.is_empty() {
+        match scan_ret {
+            Some(scan_ret) => select_os_detect_ports_from_scan(
+                scan_ret,
+                dst_addr,
+                src_addr,
+                src_port,
+                threads_num,
+                Some(timeout),
+            ),
+            None => {
+                select_os_detect_ports(dst_addr, src_addr, src_port, threads_num, Some(timeout))
+            }
+        }
+    } else {
+        OsDetectPorts::new(ports)
+    }
+}
+
 fn ipv4_os_detect(
     dst_ipv4: Ipv4Addr,
-    dst_ports: Vec<u16>,
+    ports: OsDetectPorts,
     src_addr: Option<IpAddr>,
     src_port: Option<u16>,
     top_k: usize,
+    options: &OsDetectOptions,
     timeout: Duration,
-) -> Result<(PistolFingerprint, Vec<OsInfo>)> {
+) -> Result<(PistolFingerprint, SeqInfo, Vec<OsInfo>)> {
     let src_ipv4 = match find_source_addr(src_addr, dst_ipv4)? {
         Some(s) => s,
         None => return Err(CanNotFoundSourceAddress::new().into()),
     };
-    let nmap_os_file = include_str!("./db/nmap-os-db");
-    let mut nmap_os_file_lines = Vec::new();
-    for l in nmap_os_file.lines() {
-        nmap_os_file_lines.push(l.to_string());
-    }
-    let nmap_os_db = dbparser::nmap_os_db_parser(nmap_os_file_lines)?;
-    debug!("ipv4 nmap os db parse finish");
-
-    if dst_ports.len() >= 3 {
-        let dst_open_tcp_port = dst_ports[0];
-        let dst_closed_tcp_port = dst_ports[1];
-        let dst_closed_udp_port = dst_ports[2];
-        let nmap_os_db = nmap_os_db.to_vec();
-        let os_detect_ret = threads_os_probe(
-            src_ipv4,
-            src_port,
-            dst_ipv4,
-            dst_open_tcp_port,
-            dst_closed_tcp_port,
-            dst_closed_udp_port,
-            nmap_os_db,
-            top_k,
-            timeout,
-        );
-        os_detect_ret
-    } else {
-        Err(OsDetectPortError::new().into())
-    }
+    threads_os_probe(
+        src_ipv4, src_port, dst_ipv4, &ports, top_k, options, timeout,
+    )
 }
 
 fn ipv6_os_detect(
     dst_ipv6: Ipv6Addr,
-    dst_ports: Vec<u16>,
+    ports: OsDetectPorts,
     src_addr: Option<IpAddr>,
     src_port: Option<u16>,
     top_k: usize,
+    options: &OsDetectOptions,
     timeout: Duration,
 ) -> Result<(PistolFingerprint6, Vec<OsInfo6>)> {
+    // the osscan6 probes are built with their own hop limits
+    if options.ttl.is_some() {
+        return Err(OsDetectTtlIpv6Unsupported::new().into());
+    }
+    let db = options.get_db()?;
     let src_ipv6 = match find_source_addr6(src_addr, dst_ipv6)? {
         Some(s) => s,
         None => return Err(CanNotFoundSourceAddress::new().into()),
     };
-    let linear = gen_linear()?;
-    debug!("ipv6 gen linear parse finish");
-
-    if dst_ports.len() >= 3 {
-        let dst_open_tcp_port = dst_ports[0];
-        let dst_closed_tcp_port = dst_ports[1];
-        let dst_closed_udp_port = dst_ports[2];
-        let os_detect_ret = threads_os_probe6(
-            src_ipv6,
-            src_port,
-            dst_ipv6,
-            dst_open_tcp_port,
-            dst_closed_tcp_port,
-            dst_closed_udp_port,
-            top_k,
-            linear,
-            timeout,
-        );
-        os_detect_ret
-    } else {
-        Err(OsDetectPortError::new().into())
+    // The IPv6 classifier needs all the probes, so guess the unknown closed ports.
+    let closed_tcp_port = match ports.closed_tcp_port {
+        Some(p) => p,
+        None => random_port(),
+    };
+    let closed_udp_port = match ports.closed_udp_port {
+        Some(p) => p,
+        None => random_port(),
+    };
+    threads_os_probe6(
+        src_ipv6,
+        src_port,
+        dst_ipv6,
+        ports.open_tcp_port,
+        closed_tcp_port,
+        closed_udp_port,
+        ports.confirmed,
+        top_k,
+        &db.linear,
+        timeout,
+    )
+}
+
+/// The optional settings of the OS detection, the default works as `os_detect`.
+#[derive(Debug, Clone)]
+pub struct OsDetectOptions {
+    /// The databases to match, the embedded ones if None, see `OsDetectDb`.
+    pub db: Option<Arc<OsDetectDb>>,
+    /// On IPv4 the probes are sent for up to `max_tries` rounds until there is a perfect match,
+    /// nmap uses 5, see `threads_os_probe`; the IPv6 probes are sent once. Default is 1.
+    pub max_tries: usize,
+    /// The TTL of the IPv4 probes, `packet::TTL` if None,
+    /// the IPv6 targets are reported as `OsDetectTtlIpv6Unsupported` errors when it is set.
+    pub ttl: Option<u8>,
+    /// Send the six SEQ probes from `src_port` to `src_port + 5` like nmap `-g`,
+    /// instead of all of them from `src_port`. Default is false.
+    pub seq_port_step: bool,
+    /// The results of a previous port scan, its ports are reused instead of scanning again
+    /// when the ports of the host are not given.
+    /// The idle scan results can be reused for the ports, but the probes are always sent
+    /// from the source address, since the zombie only reveals the port status.
+    pub scan_ret: Option<PortScanResults>,
+}
+
+impl Default for OsDetectOptions {
+    fn default() -> OsDetectOptions {
+        OsDetectOptions {
+            db: None,
+            max_tries: 1,
+            ttl: None,
+            seq_port_step: false,
+            scan_ret: None,
+        }
+    }
+}
+
+impl OsDetectOptions {
+    fn get_db(&self) -> Result<Arc<OsDetectDb>> {
+        match &self.db {
+            Some(db) => Ok(db.clone()),
+            None => OsDetectDb::embedded(),
+        }
     }
 }
 
 /// Detect target machine OS on IPv4 and IPv6.
+/// The meaning of `top_k` differs between the two protocols:
+/// on IPv4 it is the number of distinct accuracies kept, every tied candidate is returned, see `rank_os_infos`;
+/// on IPv6 it is the number of entries returned by the classifier, and only when the match is unique.
+/// The `ports` of each host is `[open_tcp, closed_tcp, closed_udp]` in this order,
+/// or leave it empty to select them by a quick port scan, see `select_os_detect_ports`.
 pub fn os_detect(
     target: Target,
     src_addr: Option<IpAddr>,
@@ -312,11 +946,69 @@ pub fn os_detect(
     top_k: usize,
     threads_num: usize,
     timeout: Option<Duration>,
+) -> Result<OsDetectResults> {
+    let options = OsDetectOptions::default();
+    os_detect_with_options(
+        target,
+        src_addr,
+        src_port,
+        top_k,
+        threads_num,
+        timeout,
+        options,
+    )
+}
+
+/// Same as `os_detect`, but with the given databases, see `OsDetectDb`.
+pub fn os_detect_with_db(
+    target: Target,
+    db: Arc<OsDetectDb>,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    top_k: usize,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<OsDetectResults> {
+    let options = OsDetectOptions {
+        db: Some(db),
+        ..Default::default()
+    };
+    os_detect_with_options(
+        target,
+        src_addr,
+        src_port,
+        top_k,
+        threads_num,
+        timeout,
+        options,
+    )
+}
+
+/// Same as `os_detect`, with the retries, the TTL, the databases and the port scan results in `options`.
+/// Pass the same `src_port` as the port scan to go through the same firewall rules, e.g. 53 or 88,
+/// every probe and port scan then uses this source port, the probes which share it are sent one at a time
+/// so their responses are not mixed up. With `options.seq_port_step`, the six SEQ probes
+/// use `src_port` to `src_port + 5` like nmap `-g` instead.
+/// The `options.ttl` only applies to IPv4, the IPv6 targets are reported as errors when it is set.
+pub fn os_detect_with_options(
+    target: Target,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    top_k: usize,
+    threads_num: usize,
+    timeout: Option<Duration>,
+    options: OsDetectOptions,
 ) -> Result<OsDetectResults> {
     let timeout = match timeout {
         Some(t) => t,
         None => get_default_timeout(),
     };
+    // the databases are loaded once for all the hosts
+    let db = options.get_db()?;
+    let options = Arc::new(OsDetectOptions {
+        db: Some(db),
+        ..options
+    });
     let (tx, rx) = channel();
     let pool = get_threads_pool(threads_num);
     let mut recv_size = 0;
@@ -327,12 +1019,24 @@ pub fn os_detect(
         match dst_addr {
             IpAddr::V4(dst_ipv4) => {
                 let dst_ports = t.ports;
+                let options = options.clone();
                 pool.execute(move || {
-                    let ret = match ipv4_os_detect(
-                        dst_ipv4, dst_ports, src_addr, src_port, top_k, timeout,
-                    ) {
-                        Ok((fingerprint, detect_ret)) => {
-                            let oss = HostOsDetect4::new(fingerprint, detect_ret);
+                    let ret = match get_os_detect_ports(
+                        dst_addr,
+                        &dst_ports,
+                        options.scan_ret.as_ref(),
+                        src_addr,
+                        src_port,
+                        timeout,
+                    )
+                    .and_then(|ports| {
+                        ipv4_os_detect(
+                            dst_ipv4, ports, src_addr, src_port, top_k, &options, timeout,
+                        )
+                    }) {
+                        Ok((fingerprint, seq_info, detect_ret)) => {
+                            let oss =
+                                HostOsDetect4::with_seq_info(fingerprint, seq_info, detect_ret);
                             let oss = HostOsDetect::V4(oss);
                             Ok(oss)
                         }
@@ -348,10 +1052,21 @@ pub fn os_detect(
             }
             IpAddr::V6(dst_ipv6) => {
                 let dst_ports = t.ports;
+                let options = options.clone();
                 pool.execute(move || {
-                    let ret = match ipv6_os_detect(
-                        dst_ipv6, dst_ports, src_addr, src_port, top_k, timeout,
-                    ) {
+                    let ret = match get_os_detect_ports(
+                        dst_addr,
+                        &dst_ports,
+                        options.scan_ret.as_ref(),
+                        src_addr,
+                        src_port,
+                        timeout,
+                    )
+                    .and_then(|ports| {
+                        ipv6_os_detect(
+                            dst_ipv6, ports, src_addr, src_port, top_k, &options, timeout,
+                        )
+                    }) {
                         Ok((fingerprint, detect_ret)) => {
                             let oss = HostOsDetect6::new(fingerprint, detect_ret);
                             let oss = HostOsDetect::V6(oss);
@@ -384,6 +1099,7 @@ pub fn os_detect(
     Ok(ret)
 }
 
+/// Detect one target machine OS with the given ports, `top_k` works as in `os_detect`.
 pub fn os_detect_raw(
     dst_addr: IpAddr,
     dst_open_tcp_port: u16,
@@ -393,85 +1109,201 @@ pub fn os_detect_raw(
     top_k: usize,
     timeout: Option<Duration>,
 ) -> Result<OsDetectResults> {
-    let src_port = None;
+    let ports = OsDetectPorts::new(&[dst_open_tcp_port, dst_closed_tcp_port, dst_closed_udp_port])?;
+    os_detect_raw_with_options(
+        dst_addr,
+        ports,
+        src_addr,
+        None,
+        top_k,
+        timeout,
+        OsDetectOptions::default(),
+    )
+}
+
+/// Same as `os_detect_raw`, with the `ports`, the `src_port` and the `options` of `os_detect_with_options`,
+/// the `scan_ret` is not used.
+pub fn os_detect_raw_with_options(
+    dst_addr: IpAddr,
+    ports: OsDetectPorts,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    top_k: usize,
+    timeout: Option<Duration>,
+    options: OsDetectOptions,
+) -> Result<OsDetectResults> {
     let timeout = match timeout {
         Some(t) => t,
         None => get_default_timeout(),
     };
-    match dst_addr {
-        IpAddr::V4(dst_ipv4) => match find_source_addr(src_addr, dst_ipv4)? {
-            Some(src_ipv4) => {
-                let nmap_os_file = include_str!("./db/nmap-os-db");
-                let mut nmap_os_file_lines = Vec::new();
-                for l in nmap_os_file.lines() {
-                    nmap_os_file_lines.push(l.to_string());
-                }
-                let nmap_os_db = dbparser::nmap_os_db_parser(nmap_os_file_lines)?;
-                debug!("ipv4 nmap os db parse finish");
-
-                let nmap_os_db = nmap_os_db.to_vec();
-                match threads_os_probe(
-                    src_ipv4,
-                    src_port,
-                    dst_ipv4,
-                    dst_open_tcp_port,
-                    dst_closed_tcp_port,
-                    dst_closed_udp_port,
-                    nmap_os_db,
-                    top_k,
-                    timeout,
-                ) {
-                    Ok((fingerprint, ret)) => {
-                        let oss = HostOsDetect4::new(fingerprint, ret);
-                        let oss = HostOsDetect::V4(oss);
-                        let mut ret = OsDetectResults::new();
-                        ret.oss.insert(dst_addr, oss);
-                        Ok(ret)
-                    }
-                    Err(e) => Err(e),
-                }
+    let oss = match dst_addr {
+        IpAddr::V4(dst_ipv4) => {
+            let (fingerprint, seq_info, ret) = ipv4_os_detect(
+                dst_ipv4, ports, src_addr, src_port, top_k, &options, timeout,
+            )?;
+            HostOsDetect::V4(HostOsDetect4::with_seq_info(fingerprint, seq_info, ret))
+        }
+        IpAddr::V6(dst_ipv6) => {
+            let (fingerprint, ret) = ipv6_os_detect(
+                dst_ipv6, ports, src_addr, src_port, top_k, &options, timeout,
+            )?;
+            HostOsDetect::V6(HostOsDetect6::new(fingerprint, ret))
+        }
+    };
+    let mut ret = OsDetectResults::new();
+    ret.oss.insert(dst_addr, oss);
+    Ok(ret)
+}
+
+/// One OS guess of a host from the SYN or SYN+ACK packets it sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassiveOsGuess {
+    pub direction: P0fDirection,
+    /// None if no signature matches, add the `signature` to the p0f database to recognize it.
+    pub os: Option<P0fMatch>,
+    /// The hops to the host, from the matched signature or the nearest common initial TTL.
+    pub distance: u8,
+    /// The p0f signature of the packet.
+    pub signature: String,
+    /// The number of packets with this guess.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveOsDetectResults {
+    pub hosts: HashMap<IpAddr, Vec<PassiveOsGuess>>,
+    db: Arc<P0fDb>,
+}
+
+impl PassiveOsDetectResults {
+    /// Use `P0fDb::embedded` or a p0f.fp loaded with `P0fDb::from_file`.
+    pub fn new(db: Arc<P0fDb>) -> PassiveOsDetectResults {
+        PassiveOsDetectResults {
+            hosts: HashMap::new(),
+            db,
+        }
+    }
+    pub fn get(&self, k: &IpAddr) -> Option<&Vec<PassiveOsGuess>> {
+        self.hosts.get(k)
+    }
+    /// Match the packet if it is a SYN or SYN+ACK, the other packets are ignored.
+    pub fn insert_ip_packet(&mut self, ip_buff: &[u8]) {
+        if let Some(packet) = P0fPacket::parse_ip(ip_buff) {
+            self.insert(packet);
+        }
+    }
+    /// Same as `insert_ip_packet`, but with an ethernet frame.
+    pub fn insert_frame(&mut self, ethernet_buff: &[u8]) {
+        if let Some(packet) = P0fPacket::parse_frame(ethernet_buff) {
+            self.insert(packet);
+        }
+    }
+    fn insert(&mut self, packet: P0fPacket) {
+        let os = self.db.do_match(&packet);
+        let distance = match &os {
+            Some(m) => m.distance,
+            None => guess_distance(packet.ttl),
+        };
+        let signature = format!("{}", packet);
+        let guesses = self.hosts.entry(packet.src_addr).or_default();
+        for g in guesses.iter_mut() {
+            if g.direction == packet.direction && g.signature == signature {
+                g.count += 1;
+                return;
             }
-            None => Err(CanNotFoundSourceAddress::new().into()),
-        },
-        IpAddr::V6(dst_ipv6) => match find_source_addr6(src_addr, dst_ipv6)? {
-            Some(src_ipv6) => {
-                let linear = gen_linear()?;
-                debug!("ipv6 gen linear parse finish");
-
-                match threads_os_probe6(
-                    src_ipv6,
-                    src_port,
-                    dst_ipv6,
-                    dst_open_tcp_port,
-                    dst_closed_tcp_port,
-                    dst_closed_udp_port,
-                    top_k,
-                    linear,
-                    timeout,
-                ) {
-                    Ok((fingerprint, ret)) => {
-                        let oss = HostOsDetect6::new(fingerprint, ret);
-                        let oss = HostOsDetect::V6(oss);
-                        let mut ret = OsDetectResults::new();
-                        ret.oss.insert(dst_addr, oss);
-                        Ok(ret)
-                    }
-                    Err(e) => Err(e),
-                }
+        }
+        guesses.push(PassiveOsGuess {
+            direction: packet.direction,
+            os,
+            distance,
+            signature,
+            count: 1,
+        });
+    }
+}
+
+impl fmt::Display for PassiveOsDetectResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Passive OS Detect Results")
+            .style_spec("c")
+            .with_hspan(5)]));
+
+        let hosts = &self.hosts;
+        let hosts: BTreeMap<IpAddr, &Vec<PassiveOsGuess>> =
+            hosts.iter().map(|(i, g)| (*i, g)).collect();
+        for (ip, guesses) in hosts {
+            for g in guesses {
+                let os_str = match &g.os {
+                    Some(m) if m.fuzzy => format!("{} (fuzzy)", m.label),
+                    Some(m) => format!("{}", m.label),
+                    None => format!("unknown ({})", g.signature),
+                };
+                let distance_str = format!("{} hops", g.distance);
+                table.add_row(
+                    row![c -> ip, c -> g.direction, c -> os_str, c -> distance_str, c -> g.count],
+                );
             }
-            None => Err(CanNotFoundSourceAddress::new().into()),
-        },
+        }
+        write!(f, "{}", table)
     }
 }
 
+/// Passive OS Detect.
+/// Nothing is sent, this will listen on the interface for the duration and match every SYN and SYN+ACK seen
+/// against the embedded p0f signatures, the sender of each packet is reported with its OS guess and distance.
+pub fn passive_os_detect(iface: &str, duration: Duration) -> Result<PassiveOsDetectResults> {
+    passive_os_detect_with_db(iface, duration, P0fDb::embedded()?)
+}
+
+/// Same as `passive_os_detect`, but with the given p0f signatures, see `P0fDb`.
+pub fn passive_os_detect_with_db(
+    iface: &str,
+    duration: Duration,
+    db: Arc<P0fDb>,
+) -> Result<PassiveOsDetectResults> {
+    let interface = match find_interface_by_name(iface) {
+        Some(i) => i,
+        None => return Err(CanNotFoundInterface::new().into()),
+    };
+    let mut ret = PassiveOsDetectResults::new(db);
+    layer2_capture(interface, duration, |buff| ret.insert_frame(buff))?;
+    Ok(ret)
+}
+
+/// Same as `passive_os_detect`, but read the packets from a pcap file,
+/// the ethernet, raw IP and linux cooked captures are supported.
+pub fn passive_os_detect_pcap(path: &str) -> Result<PassiveOsDetectResults> {
+    passive_os_detect_pcap_with_db(path, P0fDb::embedded()?)
+}
+
+/// Same as `passive_os_detect_pcap`, but with the given p0f signatures, see `P0fDb`.
+pub fn passive_os_detect_pcap_with_db(
+    path: &str,
+    db: Arc<P0fDb>,
+) -> Result<PassiveOsDetectResults> {
+    let mut ret = PassiveOsDetectResults::new(db);
+    for ip_buff in pcap_ip_packets(path)? {
+        ret.insert_ip_packet(&ip_buff);
+    }
+    Ok(ret)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::os::dbparser::nmap_os_db_entry;
     use crate::os::dbparser::nmap_os_db_parser;
+    use crate::os::osscan::fingerprint_tests;
+    use crate::os::osscan::merge_iex;
+    use crate::os::osscan::merge_seqx;
+    use crate::os::osscan::IEX;
+    use crate::os::osscan::SEQX;
     use crate::Host;
     // use crate::Logger;
     use crate::TEST_IPV4_LOCAL;
     use crate::TEST_IPV6_LOCAL;
+    use std::env;
     use std::time::SystemTime;
     #[test]
     fn test_os_detect() -> Result<()> {
@@ -507,6 +1339,387 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn test_choose_tcp_ports() {
+        let status = vec![
+            (80, PortStatus::Filtered),
+            (22, PortStatus::Closed),
+            (443, PortStatus::Open),
+            (21, PortStatus::Closed),
+            (23, PortStatus::Open),
+        ];
+        assert_eq!(choose_tcp_ports(&status), (Some(443), Some(22)));
+        let status = vec![(80, PortStatus::Filtered), (22, PortStatus::Filtered)];
+        assert_eq!(choose_tcp_ports(&status), (None, None));
+    }
+    #[test]
+    fn test_scanned_ports_status() {
+        let addr: IpAddr = Ipv4Addr::new(192, 168, 1, 2).into();
+        let mut scan_ret = PortScanResults::new();
+        let mut ports_status = HashMap::new();
+        ports_status.insert(443, vec![PortStatus::Open]);
+        ports_status.insert(22, vec![PortStatus::Open, PortStatus::Filtered]);
+        ports_status.insert(8080, vec![PortStatus::Closed]);
+        ports_status.insert(80, vec![]);
+        scan_ret.scans.insert(addr, ports_status);
+        let status = scanned_ports_status(&scan_ret, addr);
+        assert_eq!(
+            status,
+            vec![
+                (22, PortStatus::Open),
+                (443, PortStatus::Open),
+                (8080, PortStatus::Closed)
+            ]
+        );
+        assert_eq!(choose_tcp_ports(&status), (Some(22), Some(8080)));
+        let other: IpAddr = Ipv4Addr::new(192, 168, 1, 3).into();
+        assert_eq!(scanned_ports_status(&scan_ret, other), vec![]);
+        // the open udp ports are not tcp ports
+        scan_ret.method = Some(ScanMethod::Udp);
+        assert_eq!(scanned_ports_status(&scan_ret, addr), vec![]);
+        scan_ret.method = Some(ScanMethod::Syn);
+        assert_eq!(scanned_ports_status(&scan_ret, addr).len(), 3);
+    }
+    #[test]
+    fn test_os_detect_auto_ports() -> Result<()> {
+        let host = Host::new(TEST_IPV4_LOCAL.into(), None);
+        let target = Target::new(vec![host]);
+        let timeout = Some(Duration::new(1, 0));
+        let ret = os_detect(target, None, None, 3, 8, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_os_detect_with_options() -> Result<()> {
+        let host = Host::new(TEST_IPV4_LOCAL.into(), None);
+        let target = Target::new(vec![host]);
+        let timeout = Some(Duration::new(1, 0));
+        let options = OsDetectOptions {
+            max_tries: 5,
+            ttl: Some(128),
+            ..Default::default()
+        };
+        let ret = os_detect_with_options(target, None, None, 3, 8, timeout, options)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_match_points() -> Result<()> {
+        let lines: Vec<String> = [
+            "# nmap-os-db",
+            "MatchPoints",
+            "SEQ(SP=25%GCD=75%ISR=25%TI=100%CI=50%II=100%SS=80%TS=100)",
+            "T1(R=100%DF=20%T=15%TG=15%S=20%A=20%F=30%RD=20%Q=20)",
+            "IE(R=50%DFI=40%T=15%TG=15%CD=100)",
+            "",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let match_points = MatchPoints::parser(&lines)?;
+        assert_eq!(match_points.get("SEQ", "SP"), 25);
+        assert_eq!(match_points.get("T1", "R"), 100);
+        assert_eq!(match_points.get("T1", "W"), 0);
+        // the section is not a fingerprint
+        let ret = nmap_os_db_parser(lines)?;
+        assert_eq!(ret.len(), 0);
+        // without the section every attribute is worth one point
+        assert_eq!(MatchPoints::new().get("SEQ", "SP"), 1);
+        Ok(())
+    }
+    #[test]
+    fn test_rank_os_infos() -> Result<()> {
+        let lines: Vec<String> = [
+            "Fingerprint Linux 2.6.32",
+            "Class Linux | Linux | 2.6.X | general purpose",
+            "CPE cpe:/o:linux:linux_kernel:2.6.32 auto",
+            "SEQ(SP=F8-102%GCD=1-6%ISR=FF-109%TI=Z%CI=Z%II=I%TS=A)",
+            "OPS(O1=M5B4ST11NW6%O2=M5B4ST11NW6%O3=M5B4NNT11NW6%O4=M5B4ST11NW6%O5=M5B4ST11NW6%O6=M5B4ST11)",
+            "WIN(W1=3890%W2=3890%W3=3890%W4=3890%W5=3890%W6=3890)",
+            "ECN(R=Y%DF=Y%T=3B-45%TG=40%W=3908%O=M5B4NNSNW6%CC=N%Q=)",
+            "T1(R=Y%DF=Y%T=3B-45%TG=40%S=O%A=S+%F=AS%RD=0%Q=)",
+            "T2(R=N)",
+            "T3(R=N)",
+            "T4(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T5(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "T6(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T7(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "U1(DF=N%T=3B-45%TG=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)",
+            "IE(DFI=N%T=3B-45%TG=40%CD=S)",
+            "",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let dbs = nmap_os_db_parser(lines)?;
+        let db = &dbs[0];
+        // 1/2 and 2/4 are the same accuracy
+        let os_infos = vec![
+            OsInfo::new(db, 1, 2),
+            OsInfo::new(db, 9, 10),
+            OsInfo::new(db, 2, 4),
+            OsInfo::new(db, 3, 10),
+            OsInfo::new(db, 90, 100),
+        ];
+        let ret = rank_os_infos(os_infos.clone(), 2);
+        let ranks: Vec<(usize, usize, usize)> =
+            ret.iter().map(|o| (o.score, o.total, o.rank)).collect();
+        assert_eq!(ranks, vec![(9, 10, 1), (90, 100, 1), (1, 2, 2), (2, 4, 2)]);
+        assert_eq!(ret[2].accuracy, 50.0);
+
+        let ret = rank_os_infos(os_infos.clone(), 0);
+        assert_eq!(ret.len(), 0);
+        let ret = rank_os_infos(os_infos, 10);
+        assert_eq!(ret.len(), 5);
+        assert_eq!(ret[4].rank, 3);
+        Ok(())
+    }
+    #[test]
+    fn test_os_detect_db_snapshot() -> Result<()> {
+        let nmap_os_file = [
+            "MatchPoints",
+            "SEQ(SP=25%GCD=75%ISR=25%TI=100%CI=50%II=100%SS=80%TS=100)",
+            "",
+            "Fingerprint Linux 2.6.32",
+            "Class Linux | Linux | 2.6.X | general purpose",
+            "CPE cpe:/o:linux:linux_kernel:2.6.32 auto",
+            "SEQ(SP=F8-102%GCD=1-6%ISR=FF-109%TI=Z%CI=Z%II=I%TS=A)",
+            "OPS(O1=M5B4ST11NW6%O2=M5B4ST11NW6%O3=M5B4NNT11NW6%O4=M5B4ST11NW6%O5=M5B4ST11NW6%O6=M5B4ST11)",
+            "WIN(W1=3890%W2=3890%W3=3890%W4=3890%W5=3890%W6=3890)",
+            "ECN(R=Y%DF=Y%T=3B-45%TG=40%W=3908%O=M5B4NNSNW6%CC=N%Q=)",
+            "T1(R=Y%DF=Y%T=3B-45%TG=40%S=O%A=S+%F=AS%RD=0%Q=)",
+            "T2(R=N)",
+            "T3(R=N)",
+            "T4(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T5(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "T6(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T7(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "U1(DF=N%T=3B-45%TG=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)",
+            "IE(DFI=N%T=3B-45%TG=40%CD=S)",
+            "",
+        ]
+        .join("\n");
+        let db = OsDetectDb::from_nmap_os_db(&nmap_os_file)?;
+        assert_eq!(db.nmap_os_db.len(), 1);
+        assert_eq!(db.match_points.get("SEQ", "GCD"), 75);
+
+        let path = env::temp_dir().join("pistol-test-os-db.snapshot");
+        let path = path.to_str().unwrap();
+        db.save_snapshot(path)?;
+        let loaded = OsDetectDb::load_snapshot(path)?;
+        assert_eq!(loaded.nmap_os_db[0].info, db.nmap_os_db[0].info);
+        assert_eq!(loaded.match_points.get("SEQ", "GCD"), 75);
+        assert_eq!(loaded.linear.infolist, db.linear.infolist);
+        fs::remove_file(path)?;
+        Ok(())
+    }
+    #[test]
+    fn test_partial_fingerprint() -> Result<()> {
+        let lines: Vec<String> = [
+            "Fingerprint Linux 2.6.32",
+            "Class Linux | Linux | 2.6.X | general purpose",
+            "CPE cpe:/o:linux:linux_kernel:2.6.32 auto",
+            "SEQ(SP=F8-102%GCD=1-6%ISR=FF-109%TI=Z%CI=Z%II=I%TS=A)",
+            "OPS(O1=M5B4ST11NW6%O2=M5B4ST11NW6%O3=M5B4NNT11NW6%O4=M5B4ST11NW6%O5=M5B4ST11NW6%O6=M5B4ST11)",
+            "WIN(W1=3890%W2=3890%W3=3890%W4=3890%W5=3890%W6=3890)",
+            "ECN(R=Y%DF=Y%T=3B-45%TG=40%W=3908%O=M5B4NNSNW6%CC=N%Q=)",
+            "T1(R=Y%DF=Y%T=3B-45%TG=40%S=O%A=S+%F=AS%RD=0%Q=)",
+            "T2(R=N)",
+            "T3(R=N)",
+            "T4(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T5(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "T6(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)",
+            "T7(R=Y%DF=Y%T=3B-45%TG=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)",
+            "U1(DF=N%T=3B-45%TG=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)",
+            "IE(DFI=N%T=3B-45%TG=40%CD=S)",
+            "",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        let dbs = nmap_os_db_parser(lines)?;
+        let match_points = MatchPoints::new();
+        let fingerprint = "SCAN(V=7.94%E=4%D=10/18%OT=22%CT=1%CU=40577%PV=Y%DS=0%DC=L%G=Y%TM=6710F2A1%P=x86_64-pc-linux-gnu)\
+            SEQ(SP=106%GCD=1%ISR=10C%TI=Z%CI=Z%II=I%TS=A)\
+            OPS(O1=MFFD7ST11NW7%O2=MFFD7ST11NW7%O3=MFFD7NNT11NW7%O4=MFFD7ST11NW7%O5=MFFD7ST11NW7%O6=MFFD7ST11)\
+            WIN(W1=FFCB%W2=FFCB%W3=FFCB%W4=FFCB%W5=FFCB%W6=FFCB)\
+            ECN(R=Y%DF=Y%T=40%W=FFD7%O=MFFD7NNSNW7%CC=Y%Q=)\
+            T1(R=Y%DF=Y%T=40%S=O%A=S+%F=AS%RD=0%Q=)\
+            T2(R=N)\
+            T3(R=N)\
+            T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            U1(R=Y%DF=N%T=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)\
+            IE(R=Y%DFI=N%T=40%CD=S)";
+        let full = PistolFingerprint::parser(fingerprint)?;
+        assert_eq!(full.skipped_tests.len(), 0);
+
+        // no closed port, T5-T7 and U1 are not run
+        let mut tests = fingerprint_tests(fingerprint)?;
+        tests.retain(|(n, _)| !["T5", "T6", "T7", "U1"].contains(&n.as_str()));
+        let text: Vec<String> = tests.iter().map(|(n, b)| format!("{}({})", n, b)).collect();
+        let partial = PistolFingerprint::parser(&text.join(""))?;
+        assert_eq!(partial.skipped_tests, vec!["T5", "T6", "T7", "U1"]);
+        assert!(!format!("{}", partial).contains("T5("));
+        assert!(!format!("{}", partial).contains("U1("));
+
+        let (full_score, full_total) = dbs[0].check(&full, &match_points);
+        let (t5_score, t5_total) = dbs[0].t5.check(&full.t5x, "T5", &match_points);
+        let (score, total) = dbs[0].check(&partial, &match_points);
+        assert!(total < full_total);
+        assert!(score <= full_score);
+        assert!(total <= full_total - t5_total);
+        assert!(score <= full_score - t5_score);
+
+        let host = HostOsDetect4::new(partial, Vec::new());
+        assert!(host.partial);
+        Ok(())
+    }
+    #[test]
+    fn test_nmap_os_db_entry() -> Result<()> {
+        let fingerprint = "SCAN(V=7.94%E=4%D=10/18%OT=22%CT=1%CU=40577%PV=Y%DS=0%DC=L%G=Y%TM=6710F2A1%P=x86_64-pc-linux-gnu)\
+            SEQ(SP=106%GCD=1%ISR=10C%TI=Z%CI=Z%II=I%TS=A)\
+            OPS(O1=MFFD7ST11NW7%O2=MFFD7ST11NW7%O3=MFFD7NNT11NW7%O4=MFFD7ST11NW7%O5=MFFD7ST11NW7%O6=MFFD7ST11)\
+            WIN(W1=FFCB%W2=FFCB%W3=FFCB%W4=FFCB%W5=FFCB%W6=FFCB)\
+            ECN(R=Y%DF=Y%T=40%W=FFD7%O=MFFD7NNSNW7%CC=Y%Q=)\
+            T1(R=Y%DF=Y%T=40%S=O%A=S+%F=AS%RD=0%Q=)\
+            T2(R=N)\
+            T3(R=N)\
+            T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            U1(R=Y%DF=N%T=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)\
+            IE(R=Y%DFI=N%T=40%CD=S)";
+        let fp1 = PistolFingerprint::parser(fingerprint)?;
+        let text = fingerprint
+            .replace("SP=106", "SP=10F")
+            .replace("W1=FFCB", "W1=FFFF");
+        let fp2 = PistolFingerprint::parser(&text)?;
+        let classes = vec![OsClass::parser("Linux | Linux | 6.X | general purpose")?];
+        let cpes = vec![CpeName::parser("cpe:/o:linux:linux_kernel:6 auto")?];
+        let entry = nmap_os_db_entry("Linux 6.8", &classes, &cpes, &[fp1.clone(), fp2.clone()])?;
+        assert!(entry.starts_with("Fingerprint Linux 6.8\nClass Linux | Linux | 6.X | general purpose\nCPE cpe:/o:linux:linux_kernel:6 auto\n"));
+        assert!(entry.contains("SEQ(SP=106-10F%GCD=1%ISR=10C%"));
+        assert!(entry.contains("WIN(W1=FFCB|FFFF%W2=FFCB"));
+        assert!(entry.contains("T2(R=N)"));
+        assert!(entry.contains("T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)"));
+
+        // both fingerprints match the entry in full
+        let lines: Vec<String> = entry.lines().map(|l| l.to_string()).collect();
+        let dbs = nmap_os_db_parser(lines)?;
+        assert_eq!(dbs.len(), 1);
+        let match_points = MatchPoints::new();
+        for fp in [&fp1, &fp2] {
+            let (score, total) = dbs[0].check(fp, &match_points);
+            assert_eq!(score, total);
+        }
+
+        assert!(nmap_os_db_entry("Linux 6.8", &classes, &cpes, &[]).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_os_detect_status6() {
+        assert_eq!(OsDetectStatus6::new(8.5, 0.3), OsDetectStatus6::Matched);
+        assert_eq!(OsDetectStatus6::new(21.0, 0.3), OsDetectStatus6::TooNovel);
+        assert_eq!(OsDetectStatus6::new(8.5, 0.05), OsDetectStatus6::Ambiguous);
+        // the ambiguity is reported first
+        assert_eq!(OsDetectStatus6::new(21.0, 0.1), OsDetectStatus6::Ambiguous);
+    }
+    #[test]
+    fn test_merge_rounds() -> Result<()> {
+        let seqxs = vec![
+            SEQX::parser("SP=100%GCD=1%ISR=108%TI=Z%CI=Z%II=I%TS=A")?,
+            // one lost packet, the TI is different
+            SEQX::parser("SP=F0%GCD=1%ISR=10C%TI=RD%CI=Z%II=I%TS=A")?,
+            SEQX::parser("SP=104%GCD=1%ISR=10A%TI=Z%CI=Z%II=I%TS=A")?,
+        ];
+        let (seqx, agreed) = merge_seqx(&seqxs).unwrap();
+        assert!(!agreed);
+        assert_eq!(seqx.ti, "Z");
+        assert_eq!(seqx.sp, 0x102);
+        assert_eq!(seqx.isr, 0x109);
+        let (_, agreed) = merge_seqx(&seqxs[..1]).unwrap();
+        assert!(agreed);
+        assert!(merge_seqx(&[]).is_none());
+
+        let iexs = vec![
+            IEX::parser("R=N")?,
+            IEX::parser("DFI=N%T=40%CD=S")?,
+            IEX::parser("DFI=N%T=40%CD=S")?,
+        ];
+        let (iex, agreed) = merge_iex(&iexs).unwrap();
+        assert!(!agreed);
+        assert_eq!(iex.r, "Y");
+        assert_eq!(iex.cd, "S");
+
+        let fingerprint = "SCAN(V=7.94%E=4%D=10/18%OT=22%CT=1%CU=40577%PV=Y%DS=0%DC=L%G=Y%TM=6710F2A1%P=x86_64-pc-linux-gnu)\
+            SEQ(SP=106%GCD=1%ISR=10C%TI=Z%CI=Z%II=I%TS=A)\
+            OPS(O1=MFFD7ST11NW7%O2=MFFD7ST11NW7%O3=MFFD7NNT11NW7%O4=MFFD7ST11NW7%O5=MFFD7ST11NW7%O6=MFFD7ST11)\
+            WIN(W1=FFCB%W2=FFCB%W3=FFCB%W4=FFCB%W5=FFCB%W6=FFCB)\
+            ECN(R=Y%DF=Y%T=40%W=FFD7%O=MFFD7NNSNW7%CC=Y%Q=)\
+            T1(R=Y%DF=Y%T=40%S=O%A=S+%F=AS%RD=0%Q=)\
+            T2(R=N)\
+            T3(R=N)\
+            T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            U1(R=Y%DF=N%T=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)\
+            IE(R=Y%DFI=N%T=40%CD=S)";
+        let mut fingerprint = PistolFingerprint::parser(fingerprint)?;
+        assert_eq!(fingerprint.tries, 1);
+        fingerprint.inconsistent_tests = vec![String::from("IE")];
+        let host = HostOsDetect4::new(fingerprint, Vec::new());
+        assert!(host.inconsistent);
+        assert_eq!(host.seq_info.isn.unwrap().sp, 0x106);
+        Ok(())
+    }
+    #[test]
+    fn test_fingerprint_parser() -> Result<()> {
+        let fingerprint = "SCAN(V=7.94%E=4%D=10/18%OT=22%CT=1%CU=40577%PV=Y%DS=0%DC=L%G=Y%TM=6710F2A1%P=x86_64-pc-linux-gnu)\
+            SEQ(SP=106%GCD=1%ISR=10C%TI=Z%CI=Z%II=I%TS=A)\
+            OPS(O1=MFFD7ST11NW7%O2=MFFD7ST11NW7%O3=MFFD7NNT11NW7%O4=MFFD7ST11NW7%O5=MFFD7ST11NW7%O6=MFFD7ST11)\
+            WIN(W1=FFCB%W2=FFCB%W3=FFCB%W4=FFCB%W5=FFCB%W6=FFCB)\
+            ECN(R=Y%DF=Y%T=40%W=FFD7%O=MFFD7NNSNW7%CC=Y%Q=)\
+            T1(R=Y%DF=Y%T=40%S=O%A=S+%F=AS%RD=0%Q=)\
+            T2(R=N)\
+            T3(R=N)\
+            T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=%RD=0%Q=)\
+            T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=%RD=0%Q=)\
+            U1(R=Y%DF=N%T=40%IPL=164%UN=0%RIPL=G%RID=G%RIPCK=G%RUCK=G%RUD=G)\
+            IE(R=Y%DFI=N%T=40%CD=S)";
+        // wrap it like the nmap output
+        let chars: Vec<char> = fingerprint.chars().collect();
+        let text: Vec<String> = chars
+            .chunks(71)
+            .map(|c| format!("OS:{}", c.iter().collect::<String>()))
+            .collect();
+        let text = text.join("\n");
+
+        let ret = PistolFingerprint::parser(&text)?;
+        assert_eq!(ret.seqx.sp, 0x106);
+        assert_eq!(ret.seqx.ti, "Z");
+        assert_eq!(ret.opsx.o6, "MFFD7ST11");
+        assert_eq!(ret.winx.w1, 0xFFCB);
+        assert_eq!(ret.ecnx.t, 0x40);
+        assert_eq!(ret.t2x.r, "N");
+        assert_eq!(ret.t5x.f, "AR");
+        assert_eq!(ret.u1x.ipl, 0x164);
+        assert_eq!(ret.iex.cd, "S");
+
+        // the nmap format of the parsed one can be parsed again
+        let again = PistolFingerprint::parser(&ret.nmap_format())?;
+        assert_eq!(format!("{}", ret), format!("{}", again));
+
+        assert!(PistolFingerprint::parser("SEQ(SP=106").is_err());
+        assert!(PistolFingerprint::parser("SEQ(SP=XYZ)").is_err());
+        Ok(())
+    }
+    #[test]
     fn test_parser() {
         let start = SystemTime::now();
 
@@ -0,0 +1,393 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+
+use crate::errors::CpeParseFailed;
+use crate::errors::OsClassParseFailed;
+
+/// One `Class` line of the nmap-os-db, such as `Class Linux | Linux | 4.X | general purpose`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OsClass {
+    pub vendor: String,
+    pub os_family: String,
+    /// None if the OS generation is unknown (empty or `NULL`).
+    pub os_generation: Option<String>,
+    /// Such as `general purpose`, `router`, `switch` and `WAP`.
+    pub device_type: String,
+}
+
+impl fmt::Display for OsClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let os_generation = match &self.os_generation {
+            Some(g) => g.as_str(),
+            None => "",
+        };
+        write!(
+            f,
+            "{} | {} | {} | {}",
+            self.vendor, self.os_family, os_generation, self.device_type
+        )
+    }
+}
+
+impl OsClass {
+    /// Parse the class with or without the `Class` prefix.
+    pub fn parser(class: &str) -> Result<OsClass> {
+        let class = class.trim();
+        let class = match class.strip_prefix("Class ") {
+            Some(c) => c,
+            None => class,
+        };
+        let fields: Vec<&str> = class.split('|').map(|c| c.trim()).collect();
+        if fields.len() != 4 || fields[0].is_empty() || fields[3].is_empty() {
+            return Err(OsClassParseFailed::new(class.to_string()).into());
+        }
+        let os_generation = match fields[2] {
+            "" | "NULL" => None,
+            g => Some(g.to_string()),
+        };
+        Ok(OsClass {
+            vendor: fields[0].to_string(),
+            os_family: fields[1].to_string(),
+            os_generation,
+            device_type: fields[3].to_string(),
+        })
+    }
+    /// Parse the fields from the IPv6 database, `["Linux", "Linux", "2.6.X", "general purpose"]`.
+    pub fn from_fields(fields: &[String]) -> Result<OsClass> {
+        OsClass::parser(&fields.join(" | "))
+    }
+    /// Parse all the `Class` lines of one nmap-os-db entry, the invalid ones are skipped.
+    pub fn parse_lines(classes: &str) -> Vec<OsClass> {
+        let mut ret = Vec::new();
+        for c in classes.lines() {
+            match OsClass::parser(c) {
+                Ok(c) => ret.push(c),
+                Err(e) => log::debug!("{}", e),
+            }
+        }
+        ret
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CpePart {
+    Application,
+    Hardware,
+    OperatingSystem,
+}
+
+impl CpePart {
+    fn parser(part: &str) -> Option<CpePart> {
+        match part {
+            "a" => Some(CpePart::Application),
+            "h" => Some(CpePart::Hardware),
+            "o" => Some(CpePart::OperatingSystem),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> &str {
+        match self {
+            CpePart::Application => "a",
+            CpePart::Hardware => "h",
+            CpePart::OperatingSystem => "o",
+        }
+    }
+}
+
+/// The CPE name, parsed from the CPE 2.2 URI (`cpe:/o:linux:linux_kernel:2.6.32`)
+/// or the CPE 2.3 formatted string (`cpe:2.3:o:linux:linux_kernel:2.6.32:*:*:*:*:*:*:*`).
+/// The fields not given or `*` (ANY) are None.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpeName {
+    pub part: CpePart,
+    pub vendor: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+    pub update: Option<String>,
+    pub edition: Option<String>,
+    pub language: Option<String>,
+    // The extended attributes of CPE 2.3.
+    pub sw_edition: Option<String>,
+    pub target_sw: Option<String>,
+    pub target_hw: Option<String>,
+    pub other: Option<String>,
+    /// The `auto` mark of the nmap-os-db, the CPE is generated automatically from the class.
+    pub auto: bool,
+}
+
+impl fmt::Display for CpeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.uri())
+    }
+}
+
+impl CpeName {
+    /// Parse the CPE with or without the `CPE` prefix and the `auto` mark of the nmap-os-db.
+    pub fn parser(cpe: &str) -> Result<CpeName> {
+        let cpe = cpe.trim();
+        let cpe = match cpe.strip_prefix("CPE ") {
+            Some(c) => c.trim(),
+            None => cpe,
+        };
+        let (cpe, auto) = match cpe.strip_suffix(" auto") {
+            Some(c) => (c.trim(), true),
+            None => (cpe, false),
+        };
+
+        let invalid = || CpeParseFailed::new(cpe.to_string());
+        let (part, fields) = if let Some(fs) = cpe.strip_prefix("cpe:2.3:") {
+            // cpe:2.3:part:vendor:product:version:update:edition:language:sw_edition:target_sw:target_hw:other
+            let fields = split_escaped(fs);
+            if fields.len() != 11 {
+                return Err(invalid().into());
+            }
+            let fields: Vec<Option<String>> = fields
+                .into_iter()
+                .map(|f| match f.as_str() {
+                    "*" | "" => None,
+                    _ => Some(unescape_fs(&f)),
+                })
+                .collect();
+            (fields[0].clone(), fields[1..].to_vec())
+        } else if let Some(uri) = cpe.strip_prefix("cpe:/") {
+            // cpe:/part:vendor:product:version:update:edition:language
+            let fields: Vec<&str> = uri.split(':').collect();
+            if fields.len() > 7 {
+                return Err(invalid().into());
+            }
+            let mut fields: Vec<Option<String>> = fields
+                .into_iter()
+                .map(|f| match f {
+                    "" => None,
+                    _ => Some(decode_uri(f)),
+                })
+                .collect();
+            fields.resize(11, None);
+            (fields[0].clone(), fields[1..].to_vec())
+        } else {
+            return Err(invalid().into());
+        };
+
+        let part = match part {
+            Some(p) => match CpePart::parser(&p) {
+                Some(p) => p,
+                None => return Err(invalid().into()),
+            },
+            None => return Err(invalid().into()),
+        };
+        Ok(CpeName {
+            part,
+            vendor: fields[0].clone(),
+            product: fields[1].clone(),
+            version: fields[2].clone(),
+            update: fields[3].clone(),
+            edition: fields[4].clone(),
+            language: fields[5].clone(),
+            sw_edition: fields[6].clone(),
+            target_sw: fields[7].clone(),
+            target_hw: fields[8].clone(),
+            other: fields[9].clone(),
+            auto,
+        })
+    }
+    /// The CPE 2.2 URI, the extended attributes of CPE 2.3 are not included.
+    pub fn uri(&self) -> String {
+        let fields = [
+            &self.vendor,
+            &self.product,
+            &self.version,
+            &self.update,
+            &self.edition,
+            &self.language,
+        ];
+        let mut fields: Vec<String> = fields
+            .iter()
+            .map(|f| match f {
+                Some(f) => encode_uri(f),
+                None => String::new(),
+            })
+            .collect();
+        // the trailing empty fields are omitted
+        while fields.last().is_some_and(|f| f.is_empty()) {
+            fields.pop();
+        }
+        fields.insert(0, self.part.as_str().to_string());
+        format!("cpe:/{}", fields.join(":"))
+    }
+    /// The CPE 2.3 formatted string.
+    pub fn formatted_string(&self) -> String {
+        let fields = [
+            &self.vendor,
+            &self.product,
+            &self.version,
+            &self.update,
+            &self.edition,
+            &self.language,
+            &self.sw_edition,
+            &self.target_sw,
+            &self.target_hw,
+            &self.other,
+        ];
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|f| match f {
+                Some(f) => escape_fs(f),
+                None => String::from("*"),
+            })
+            .collect();
+        format!("cpe:2.3:{}:{}", self.part.as_str(), fields.join(":"))
+    }
+    /// Parse all the `CPE` lines of one nmap-os-db entry, the invalid ones are skipped.
+    pub fn parse_lines(cpes: &str) -> Vec<CpeName> {
+        let mut ret = Vec::new();
+        for c in cpes.lines() {
+            match CpeName::parser(c) {
+                Ok(c) => ret.push(c),
+                Err(e) => log::debug!("{}", e),
+            }
+        }
+        ret
+    }
+}
+
+/// Split the CPE 2.3 formatted string by the colon, the escaped `\:` is not a separator.
+fn split_escaped(fs: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = fs.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                field.push(c);
+                if let Some(n) = chars.next() {
+                    field.push(n);
+                }
+            }
+            ':' => {
+                fields.push(field);
+                field = String::new();
+            }
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn unescape_fs(field: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(n) = chars.next() {
+                    ret.push(n);
+                }
+            }
+            _ => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn escape_fs(field: &str) -> String {
+    let mut ret = String::new();
+    for c in field.chars() {
+        if !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+fn decode_uri(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = String::from_utf8_lossy(&bytes[i + 1..i + 3]);
+            if let Ok(b) = u8::from_str_radix(&hex, 16) {
+                ret.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        ret.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&ret).to_string()
+}
+
+fn encode_uri(field: &str) -> String {
+    let mut ret = String::new();
+    for c in field.chars() {
+        if c.is_ascii_alphanumeric() || "_-.~".contains(c) {
+            ret.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                ret += &format!("%{:02x}", b);
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_os_class() -> Result<()> {
+        let class = OsClass::parser("Class Linux | Linux | 4.X | general purpose")?;
+        assert_eq!(class.vendor, "Linux");
+        assert_eq!(class.os_generation, Some(String::from("4.X")));
+        assert_eq!(class.device_type, "general purpose");
+
+        let fields: Vec<String> = ["HP", "embedded", "NULL", "switch"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let class = OsClass::from_fields(&fields)?;
+        assert_eq!(class.os_family, "embedded");
+        assert_eq!(class.os_generation, None);
+        assert_eq!(format!("{}", class), "HP | embedded |  | switch");
+
+        assert!(OsClass::parser("Class Linux | Linux").is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_cpe_name() -> Result<()> {
+        let cpe = CpeName::parser("CPE cpe:/o:linux:linux_kernel:2.6.32 auto")?;
+        assert_eq!(cpe.part, CpePart::OperatingSystem);
+        assert_eq!(cpe.vendor, Some(String::from("linux")));
+        assert_eq!(cpe.version, Some(String::from("2.6.32")));
+        assert_eq!(cpe.update, None);
+        assert!(cpe.auto);
+        assert_eq!(cpe.uri(), "cpe:/o:linux:linux_kernel:2.6.32");
+        assert_eq!(
+            cpe.formatted_string(),
+            "cpe:2.3:o:linux:linux_kernel:2.6.32:*:*:*:*:*:*:*"
+        );
+
+        let cpe = CpeName::parser("cpe:/o:microsoft:windows_7::sp1")?;
+        assert_eq!(cpe.version, None);
+        assert_eq!(cpe.update, Some(String::from("sp1")));
+        assert_eq!(cpe.uri(), "cpe:/o:microsoft:windows_7::sp1");
+
+        let cpe = CpeName::parser("cpe:2.3:h:cisco:catalyst_2960\\:24:-:*:*:*:*:*:*:*")?;
+        assert_eq!(cpe.part, CpePart::Hardware);
+        assert_eq!(cpe.product, Some(String::from("catalyst_2960:24")));
+        assert_eq!(cpe.version, Some(String::from("-")));
+        assert_eq!(cpe.uri(), "cpe:/h:cisco:catalyst_2960%3a24:-");
+        let again = CpeName::parser(&cpe.formatted_string())?;
+        assert_eq!(again, cpe);
+
+        assert!(CpeName::parser("cpe:/x:linux").is_err());
+        assert!(CpeName::parser("linux").is_err());
+        Ok(())
+    }
+}
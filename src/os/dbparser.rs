@@ -2,9 +2,16 @@ use anyhow::Result;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt;
 
+use crate::errors::FingerprintParseFailed;
+use crate::errors::FingerprintsNullError;
+use crate::errors::MatchPointsParseFailed;
 use crate::utils::SpHex;
 
+use super::cpe::CpeName;
+use super::cpe::OsClass;
+use super::osscan::fingerprint_tests;
 use super::osscan::PistolFingerprint;
 use super::osscan::ECNX;
 use super::osscan::IEX;
@@ -176,15 +183,273 @@ impl NmapOsDbValueTypes {
     }
 }
 
-fn bool_score(bool_vec: Vec<bool>) -> usize {
+impl fmt::Display for NmapOsDbValueTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let output = match self {
+            NmapOsDbValueTypes::DbMixStringValue(v) => v.value.join("|"),
+            NmapOsDbValueTypes::DbEmptyValue(_) => String::new(),
+            NmapOsDbValueTypes::DbMixValue(m) => {
+                let mut items = Vec::new();
+                for r in &m.range_values {
+                    let item = match r.range_value_type {
+                        DbRangeValueTypes::Both => format!("{:X}-{:X}", r.start, r.end),
+                        DbRangeValueTypes::Left => format!(">{:X}", r.start),
+                        DbRangeValueTypes::Right => format!("<{:X}", r.end),
+                    };
+                    items.push(item);
+                }
+                for s in &m.single_values {
+                    items.push(format!("{:X}", s.value));
+                }
+                items.join("|")
+            }
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl NmapOsDbValueTypes {
+    /// Merge the numbers seen into ranges, the numbers at most `gap` apart are in the same range.
+    pub fn from_usizes(values: &[usize], gap: usize) -> NmapOsDbValueTypes {
+        let mut values = values.to_vec();
+        values.sort();
+        values.dedup();
+        let mut range_values = Vec::new();
+        let mut single_values = Vec::new();
+        let mut push = |start: usize, end: usize| {
+            if start == end {
+                single_values.push(DbSingleValue::new(start));
+            } else {
+                range_values.push(DbRangeValue::new(start, end, DbRangeValueTypes::Both));
+            }
+        };
+        if !values.is_empty() {
+            let mut start = values[0];
+            let mut end = values[0];
+            for v in &values[1..] {
+                if v - end <= gap {
+                    end = *v;
+                } else {
+                    push(start, end);
+                    start = *v;
+                    end = *v;
+                }
+            }
+            push(start, end);
+        }
+        NmapOsDbValueTypes::DbMixValue(DbMixValue::new(range_values, single_values))
+    }
+    /// The strings seen as alternatives, the empty string is kept as `Q=` means no quirks.
+    pub fn from_strings(values: &[String]) -> NmapOsDbValueTypes {
+        let mut alternatives: Vec<String> = Vec::new();
+        for v in values {
+            if !alternatives.contains(v) {
+                alternatives.push(v.clone());
+            }
+        }
+        if alternatives.len() == 1 && alternatives[0].is_empty() {
+            NmapOsDbValueTypes::empty()
+        } else {
+            NmapOsDbValueTypes::DbMixStringValue(DbStringValue::new(alternatives))
+        }
+    }
+}
+
+/// The tests of the nmap-os-db entries in order.
+const DB_TESTS: [&str; 13] = [
+    "SEQ", "OPS", "WIN", "ECN", "T1", "T2", "T3", "T4", "T5", "T6", "T7", "U1", "IE",
+];
+
+/// The attributes whose values are hex numbers, the same as `value_parser_usize` parses.
+const DB_NUMBER_ATTRS: [&str; 15] = [
+    "SP", "GCD", "ISR", "T", "TG", "W", "W1", "W2", "W3", "W4", "W5", "W6", "RD", "IPL", "UN",
+];
+
+/// The numbers of the attribute closer than this are merged into one range.
+/// The SP and ISR vary between the probes and the TTL with the distance, the others are kept exact.
+fn db_range_gap(attr: &str) -> usize {
+    match attr {
+        "SP" | "ISR" => 16,
+        "T" => 32,
+        _ => 0,
+    }
+}
+
+/// Build the nmap-os-db entry of one OS from the fingerprints of its hosts, the lines are
+/// `Fingerprint`, `Class`, `CPE` and the tests, the values seen are merged into ranges or alternatives.
+/// The tests skipped in all the fingerprints are written as `R=N`, edit them before sharing the entry.
+/// Append the entry to a nmap-os-db file, or parse it with `nmap_os_db_parser` to grow a private database.
+pub fn nmap_os_db_entry(
+    name: &str,
+    classes: &[OsClass],
+    cpes: &[CpeName],
+    fingerprints: &[PistolFingerprint],
+) -> Result<String> {
+    if fingerprints.is_empty() {
+        return Err(FingerprintsNullError::new().into());
+    }
+
+    let mut lines = vec![format!("Fingerprint {}", name)];
+    for c in classes {
+        lines.push(format!("Class {}", c));
+    }
+    for c in cpes {
+        if c.auto {
+            lines.push(format!("CPE {} auto", c.uri()));
+        } else {
+            lines.push(format!("CPE {}", c.uri()));
+        }
+    }
+
+    let mut all_tests = Vec::new();
+    for fp in fingerprints {
+        all_tests.push(fingerprint_tests(&format!("{}", fp))?);
+    }
+    for test in DB_TESTS {
+        // the attributes in the order they first appear
+        let mut attrs: Vec<(String, Vec<String>)> = Vec::new();
+        let mut responses = Vec::new();
+        let mut show_r = false;
+        for tests in &all_tests {
+            let body = match tests.iter().find(|(n, _)| n == test) {
+                Some((_, body)) => body,
+                None => continue,
+            };
+            let mut r = String::from("Y");
+            for item in body.split('%') {
+                let (k, v) = match item.split_once('=') {
+                    Some(kv) => kv,
+                    None => return Err(FingerprintParseFailed::new(body.clone()).into()),
+                };
+                if k == "R" {
+                    r = v.to_string();
+                    show_r = true;
+                    continue;
+                }
+                match attrs.iter_mut().find(|(a, _)| a == k) {
+                    Some((_, values)) => values.push(v.to_string()),
+                    None => attrs.push((k.to_string(), vec![v.to_string()])),
+                }
+            }
+            responses.push(r);
+        }
+
+        let mut items = Vec::new();
+        if responses.is_empty() {
+            items.push(String::from("R=N"));
+        } else if show_r {
+            // do not show R if it is not in the fingerprints, like SEQ, OPS and WIN
+            let r = NmapOsDbValueTypes::from_strings(&responses);
+            items.push(format!("R={}", r));
+        }
+        for (attr, values) in attrs {
+            let value = if DB_NUMBER_ATTRS.contains(&attr.as_str()) {
+                let mut numbers = Vec::new();
+                for v in &values {
+                    match usize::from_str_radix(v, 16) {
+                        Ok(n) => numbers.push(n),
+                        Err(_) => {
+                            return Err(
+                                FingerprintParseFailed::new(format!("{}={}", attr, v)).into()
+                            )
+                        }
+                    }
+                }
+                NmapOsDbValueTypes::from_usizes(&numbers, db_range_gap(&attr))
+            } else {
+                NmapOsDbValueTypes::from_strings(&values)
+            };
+            items.push(format!("{}={}", attr, value));
+        }
+        lines.push(format!("{}({})", test, items.join("%")));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Sum the points of the matched attributes, returns the score and the total points.
+fn points_score(checks: Vec<(bool, usize)>) -> (usize, usize) {
     let mut score = 0;
-    for i in bool_vec {
-        match i {
-            true => score += 1,
-            _ => (),
+    let mut total = 0;
+    for (check, points) in checks {
+        if check {
+            score += points;
+        }
+        total += points;
+    }
+    (score, total)
+}
+
+/// The weight of every attribute in the `MatchPoints` section of nmap-os-db,
+/// such as the SP in SEQ is worth 25 points and the R in T1 is worth 100 points.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchPoints {
+    pub points: HashMap<String, HashMap<String, usize>>,
+}
+
+impl Default for MatchPoints {
+    fn default() -> MatchPoints {
+        MatchPoints::new()
+    }
+}
+
+impl MatchPoints {
+    /// Empty match points, every attribute is worth one point.
+    pub fn new() -> MatchPoints {
+        MatchPoints {
+            points: HashMap::new(),
+        }
+    }
+    /// The points of the attribute in the test (SEQ, OPS, T1 and so on),
+    /// the attribute which is not in the section is worth nothing.
+    pub fn get(&self, test: &str, attr: &str) -> usize {
+        if self.points.is_empty() {
+            return 1;
+        }
+        match self.points.get(test) {
+            Some(p) => match p.get(attr) {
+                Some(p) => *p,
+                None => 0,
+            },
+            None => 0,
+        }
+    }
+    /// Parse the `MatchPoints` section from the lines of nmap-os-db,
+    /// returns the empty match points if the db has no such section.
+    pub fn parser(lines: &[String]) -> Result<MatchPoints> {
+        let mut points = HashMap::new();
+        let mut in_section = false;
+        for l in lines {
+            let l = l.trim();
+            if l == "MatchPoints" {
+                in_section = true;
+            } else if in_section {
+                // the section ends with a blank line
+                if l.is_empty() {
+                    break;
+                }
+                let split_1: Vec<&str> = l.split("(").collect();
+                if split_1.len() != 2 {
+                    return Err(MatchPointsParseFailed::new(l.to_string()).into());
+                }
+                let test = split_1[0].to_string();
+                let many_info = split_1[1].trim_end_matches(")");
+                let mut test_points = HashMap::new();
+                for info in many_info.split("%") {
+                    let info_split: Vec<&str> = info.split("=").collect();
+                    if info_split.len() != 2 {
+                        return Err(MatchPointsParseFailed::new(l.to_string()).into());
+                    }
+                    let p: usize = match info_split[1].parse() {
+                        Ok(p) => p,
+                        Err(_) => return Err(MatchPointsParseFailed::new(l.to_string()).into()),
+                    };
+                    test_points.insert(info_split[0].to_string(), p);
+                }
+                points.insert(test, test_points);
+            }
         }
+        Ok(MatchPoints { points })
     }
-    score
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -201,11 +466,13 @@ pub struct SEQDB {
 }
 
 impl SEQDB {
-    pub fn check(&self, seqx: &SEQX) -> (usize, usize) {
+    pub fn check(&self, seqx: &SEQX, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("SEQ", attr);
         let r_check = self.r.check_r(&seqx.r);
         if r_check {
             match seqx.r.as_str() {
-                "N" => (),
+                // no response from both, only the R is compared
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let sp_check = self.sp.check_usize(seqx.sp as usize);
                     let gcd_check = self.gcd.check_usize(seqx.gcd as usize);
@@ -216,25 +483,22 @@ impl SEQDB {
                     let ss_check = self.ss.check_string(&seqx.ss);
                     let ts_check = self.ts.check_string(&seqx.ts);
 
-                    // println!(">>> {}", sp_check);
-                    // println!(">>> {}", gcd_check);
-                    // println!(">>> {}", isr_check);
-                    // println!(">>> {}", ti_check);
-                    // println!(">>> {}", ci_check);
-                    // println!(">>> {}", ii_check);
-                    // println!(">>> {}", ss_check);
-                    // println!(">>> {}", ts_check);
-
-                    let bool_vec = vec![
-                        sp_check, gcd_check, isr_check, ti_check, ci_check, ii_check, ss_check,
-                        ts_check,
+                    let checks = vec![
+                        (true, mp("R")),
+                        (sp_check, mp("SP")),
+                        (gcd_check, mp("GCD")),
+                        (isr_check, mp("ISR")),
+                        (ti_check, mp("TI")),
+                        (ci_check, mp("CI")),
+                        (ii_check, mp("II")),
+                        (ss_check, mp("SS")),
+                        (ts_check, mp("TS")),
                     ];
-
-                    return (bool_score(bool_vec), 8);
+                    return points_score(checks);
                 }
             }
         }
-        (0, 8)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> SEQDB {
         let sp = match map.get("sp") {
@@ -341,11 +605,12 @@ pub struct OPSDB {
 }
 
 impl OPSDB {
-    pub fn check(&self, opsx: &OPSX) -> (usize, usize) {
+    pub fn check(&self, opsx: &OPSX, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("OPS", attr);
         let r_check = self.r.check_r(&opsx.r);
         if r_check {
             match opsx.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let o1_check = self.o1.check_string(&opsx.o1);
                     let o2_check = self.o2.check_string(&opsx.o2);
@@ -353,12 +618,20 @@ impl OPSDB {
                     let o4_check = self.o4.check_string(&opsx.o4);
                     let o5_check = self.o5.check_string(&opsx.o5);
                     let o6_check = self.o6.check_string(&opsx.o6);
-                    let bool_vec = vec![o1_check, o2_check, o3_check, o4_check, o5_check, o6_check];
-                    return (bool_score(bool_vec), 6);
+                    let checks = vec![
+                        (true, mp("R")),
+                        (o1_check, mp("O1")),
+                        (o2_check, mp("O2")),
+                        (o3_check, mp("O3")),
+                        (o4_check, mp("O4")),
+                        (o5_check, mp("O5")),
+                        (o6_check, mp("O6")),
+                    ];
+                    return points_score(checks);
                 }
             }
         }
-        (0, 6)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> OPSDB {
         let o1 = match map.get("o1") {
@@ -451,11 +724,12 @@ pub struct WINDB {
 }
 
 impl WINDB {
-    pub fn check(&self, winx: &WINX) -> (usize, usize) {
+    pub fn check(&self, winx: &WINX, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("WIN", attr);
         let r_check = self.r.check_r(&winx.r);
         if r_check {
             match winx.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let w1_check = self.w1.check_usize(winx.w1 as usize);
                     let w2_check = self.w2.check_usize(winx.w2 as usize);
@@ -463,12 +737,20 @@ impl WINDB {
                     let w4_check = self.w4.check_usize(winx.w4 as usize);
                     let w5_check = self.w5.check_usize(winx.w5 as usize);
                     let w6_check = self.w6.check_usize(winx.w6 as usize);
-                    let bool_vec = vec![w1_check, w2_check, w3_check, w4_check, w5_check, w6_check];
-                    return (bool_score(bool_vec), 6);
+                    let checks = vec![
+                        (true, mp("R")),
+                        (w1_check, mp("W1")),
+                        (w2_check, mp("W2")),
+                        (w3_check, mp("W3")),
+                        (w4_check, mp("W4")),
+                        (w5_check, mp("W5")),
+                        (w6_check, mp("W6")),
+                    ];
+                    return points_score(checks);
                 }
             }
         }
-        (0, 6)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> WINDB {
         let w1 = match map.get("w1") {
@@ -560,28 +842,38 @@ pub struct ECNDB {
 }
 
 impl ECNDB {
-    pub fn check(&self, ecnx: &ECNX) -> (usize, usize) {
+    pub fn check(&self, ecnx: &ECNX, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("ECN", attr);
         let r_check = self.r.check_r(&ecnx.r);
         if r_check {
             match ecnx.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let df_check = self.df.check_string(&ecnx.df);
-                    let t_check = if ecnx.t > 0 {
-                        self.t.check_usize(ecnx.t as usize)
+                    // the exact ttl or the guess one
+                    let (t_check, t_points) = if ecnx.t > 0 {
+                        (self.t.check_usize(ecnx.t as usize), mp("T"))
                     } else {
-                        self.tg.check_usize(ecnx.tg as usize)
+                        (self.tg.check_usize(ecnx.tg as usize), mp("TG"))
                     };
                     let w_check = self.w.check_usize(ecnx.w as usize);
                     let o_check = self.o.check_string(&ecnx.o);
                     let cc_check = self.cc.check_string(&ecnx.cc);
                     let q_check = self.q.check_string(&ecnx.q);
-                    let bool_vec = vec![df_check, t_check, w_check, o_check, cc_check, q_check];
-                    return (bool_score(bool_vec), 6);
+                    let checks = vec![
+                        (true, mp("R")),
+                        (df_check, mp("DF")),
+                        (t_check, t_points),
+                        (w_check, mp("W")),
+                        (o_check, mp("O")),
+                        (cc_check, mp("CC")),
+                        (q_check, mp("Q")),
+                    ];
+                    return points_score(checks);
                 }
             }
         }
-        (0, 6)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> ECNDB {
         let r = match map.get("r") {
@@ -684,53 +976,47 @@ pub struct TX {
 }
 
 impl TX {
-    pub fn check(&self, txx: &TXX, name: &str) -> (usize, usize) {
+    pub fn check(&self, txx: &TXX, name: &str, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get(name, attr);
         let r_check = self.r.check_r(&txx.r);
         if r_check {
             match txx.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let df_check = self.df.check_string(&txx.df);
-                    let t_check = if txx.t > 0 {
-                        self.t.check_usize(txx.t as usize)
+                    let (t_check, t_points) = if txx.t > 0 {
+                        (self.t.check_usize(txx.t as usize), mp("T"))
                     } else {
-                        self.tg.check_usize(txx.tg as usize)
-                    };
-                    let w_check = if name != "T1" {
-                        self.w.check_usize(txx.w as usize)
-                    } else {
-                        true
+                        (self.tg.check_usize(txx.tg as usize), mp("TG"))
                     };
                     let s_check = self.s.check_string(&txx.s);
                     let a_check = self.a.check_string(&txx.a);
                     let f_check = self.f.check_string(&txx.f);
-                    let o_check = if name != "T1" {
-                        self.o.check_string(&txx.o)
-                    } else {
-                        true
-                    };
                     let rd_check = self.rd.check_usize(txx.rd as usize);
                     let q_check = self.q.check_string(&txx.q);
 
-                    // println!("{}", df_check);
-                    // println!("{}", t_check);
-                    // println!("{}", w_check);
-                    // println!("{}", s_check);
-                    // println!("{}", a_check);
-                    // println!("{}", f_check);
-                    // println!("{}", o_check);
-                    // println!("{}", rd_check);
-                    // println!("{}", q_check);
-
-                    let bool_vec = vec![
-                        df_check, t_check, w_check, s_check, a_check, f_check, o_check, rd_check,
-                        q_check,
+                    let mut checks = vec![
+                        (true, mp("R")),
+                        (df_check, mp("DF")),
+                        (t_check, t_points),
+                        (s_check, mp("S")),
+                        (a_check, mp("A")),
+                        (f_check, mp("F")),
+                        (rd_check, mp("RD")),
+                        (q_check, mp("Q")),
                     ];
-                    return (bool_score(bool_vec), 9);
+                    // T1 has no W and O
+                    if name != "T1" {
+                        let w_check = self.w.check_usize(txx.w as usize);
+                        let o_check = self.o.check_string(&txx.o);
+                        checks.push((w_check, mp("W")));
+                        checks.push((o_check, mp("O")));
+                    }
+                    return points_score(checks);
                 }
             }
         }
-        (0, 9)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> TX {
         let r = match map.get("r") {
@@ -858,17 +1144,18 @@ pub struct U1DB {
 }
 
 impl U1DB {
-    pub fn check(&self, u1x: &U1X) -> (usize, usize) {
+    pub fn check(&self, u1x: &U1X, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("U1", attr);
         let r_check = self.r.check_r(&u1x.r);
         if r_check {
             match u1x.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let df_check = self.df.check_string(&u1x.df);
-                    let t_check = if u1x.t > 0 {
-                        self.t.check_usize(u1x.t as usize)
+                    let (t_check, t_points) = if u1x.t > 0 {
+                        (self.t.check_usize(u1x.t as usize), mp("T"))
                     } else {
-                        self.tg.check_usize(u1x.tg as usize)
+                        (self.tg.check_usize(u1x.tg as usize), mp("TG"))
                     };
                     let ipl_check = self.ipl.check_usize(u1x.ipl as usize);
                     let un_check = self.un.check_usize(u1x.un as usize);
@@ -878,32 +1165,23 @@ impl U1DB {
                     let ruck_check = self.ruck.check_string(&u1x.ruck);
                     let rud_check = self.rud.check_string(&u1x.rud);
 
-                    // println!("{}", df_check);
-                    // println!("{}", t_check);
-                    // println!("{}", ipl_check);
-                    // println!("{}", un_check);
-                    // println!("{}", ripl_check);
-                    // println!("{}", rid_check);
-                    // println!("{}", ripck_check);
-                    // println!("{}", ruck_check);
-                    // println!("{}", rud_check);
-
-                    let bool_vec = vec![
-                        df_check,
-                        t_check,
-                        ipl_check,
-                        un_check,
-                        ripl_check,
-                        rid_check,
-                        ripck_check,
-                        ruck_check,
-                        rud_check,
+                    let checks = vec![
+                        (true, mp("R")),
+                        (df_check, mp("DF")),
+                        (t_check, t_points),
+                        (ipl_check, mp("IPL")),
+                        (un_check, mp("UN")),
+                        (ripl_check, mp("RIPL")),
+                        (rid_check, mp("RID")),
+                        (ripck_check, mp("RIPCK")),
+                        (ruck_check, mp("RUCK")),
+                        (rud_check, mp("RUD")),
                     ];
-                    return (bool_score(bool_vec), 9);
+                    return points_score(checks);
                 }
             }
         }
-        (0, 9)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> U1DB {
         let r = match map.get("r") {
@@ -1024,25 +1302,31 @@ pub struct IEDB {
 }
 
 impl IEDB {
-    pub fn check(&self, iex: &IEX) -> (usize, usize) {
+    pub fn check(&self, iex: &IEX, match_points: &MatchPoints) -> (usize, usize) {
+        let mp = |attr: &str| match_points.get("IE", attr);
         let r_check = self.r.check_r(&iex.r);
         if r_check {
             match iex.r.as_str() {
-                "N" => (),
+                "N" => return (mp("R"), mp("R")),
                 _ => {
                     let dfi_check = self.dfi.check_string(&iex.dfi);
-                    let t_check = if iex.t > 0 {
-                        self.t.check_usize(iex.t as usize)
+                    let (t_check, t_points) = if iex.t > 0 {
+                        (self.t.check_usize(iex.t as usize), mp("T"))
                     } else {
-                        self.tg.check_usize(iex.tg as usize)
+                        (self.tg.check_usize(iex.tg as usize), mp("TG"))
                     };
                     let cd_check = self.cd.check_string(&iex.cd);
-                    let bool_vec = vec![dfi_check, t_check, cd_check];
-                    return (bool_score(bool_vec), 3);
+                    let checks = vec![
+                        (true, mp("R")),
+                        (dfi_check, mp("DFI")),
+                        (t_check, t_points),
+                        (cd_check, mp("CD")),
+                    ];
+                    return points_score(checks);
                 }
             }
         }
-        (0, 3)
+        (0, mp("R"))
     }
     pub fn new(map: HashMap<&str, NmapOsDbValueTypes>) -> IEDB {
         let r = match map.get("r") {
@@ -1121,32 +1405,77 @@ pub struct NmapOsDb {
 }
 
 impl NmapOsDb {
-    pub fn check(&self, probe_ret: &PistolFingerprint) -> (usize, usize) {
-        let (seq_score, seq_total) = self.seq.check(&probe_ret.seqx);
+    /// Compare the fingerprint with this one, returns the score and the total points weighted by the `MatchPoints`.
+    /// The skipped tests of the fingerprint are not counted.
+    pub fn check(
+        &self,
+        probe_ret: &PistolFingerprint,
+        match_points: &MatchPoints,
+    ) -> (usize, usize) {
+        let (seq_score, seq_total) = match probe_ret.is_skipped("SEQ") {
+            true => (0, 0),
+            false => self.seq.check(&probe_ret.seqx, match_points),
+        };
         // println!("SEQ: {}", seq_check);
-        let (ops_score, ops_total) = self.ops.check(&probe_ret.opsx);
+        let (ops_score, ops_total) = match probe_ret.is_skipped("OPS") {
+            true => (0, 0),
+            false => self.ops.check(&probe_ret.opsx, match_points),
+        };
         // println!("OPS: {}", ops_check);
-        let (win_score, win_total) = self.win.check(&probe_ret.winx);
+        let (win_score, win_total) = match probe_ret.is_skipped("WIN") {
+            true => (0, 0),
+            false => self.win.check(&probe_ret.winx, match_points),
+        };
         // println!("WIN: {}", win_check);
-        let (ecn_score, ecn_total) = self.ecn.check(&probe_ret.ecnx);
+        let (ecn_score, ecn_total) = match probe_ret.is_skipped("ECN") {
+            true => (0, 0),
+            false => self.ecn.check(&probe_ret.ecnx, match_points),
+        };
         // println!("ECN: {}", ecn_check);
-        let (t1_score, t1_total) = self.t1.check(&probe_ret.t1x, "T1");
+        let (t1_score, t1_total) = match probe_ret.is_skipped("T1") {
+            true => (0, 0),
+            false => self.t1.check(&probe_ret.t1x, "T1", match_points),
+        };
         // println!("T1: {}", t1_check);
-        let (t2_score, t2_total) = self.t2.check(&probe_ret.t2x, "T2");
+        let (t2_score, t2_total) = match probe_ret.is_skipped("T2") {
+            true => (0, 0),
+            false => self.t2.check(&probe_ret.t2x, "T2", match_points),
+        };
         // println!("T2: {}", t2_check);
-        let (t3_score, t3_total) = self.t3.check(&probe_ret.t3x, "T3");
+        let (t3_score, t3_total) = match probe_ret.is_skipped("T3") {
+            true => (0, 0),
+            false => self.t3.check(&probe_ret.t3x, "T3", match_points),
+        };
         // println!("T3: {}", t3_check);
-        let (t4_score, t4_total) = self.t4.check(&probe_ret.t4x, "T4");
+        let (t4_score, t4_total) = match probe_ret.is_skipped("T4") {
+            true => (0, 0),
+            false => self.t4.check(&probe_ret.t4x, "T4", match_points),
+        };
         // println!("T4: {}", t4_check);
-        let (t5_score, t5_total) = self.t5.check(&probe_ret.t5x, "T5");
+        let (t5_score, t5_total) = match probe_ret.is_skipped("T5") {
+            true => (0, 0),
+            false => self.t5.check(&probe_ret.t5x, "T5", match_points),
+        };
         // println!("T5: {}", t5_check);
-        let (t6_score, t6_total) = self.t6.check(&probe_ret.t6x, "T6");
+        let (t6_score, t6_total) = match probe_ret.is_skipped("T6") {
+            true => (0, 0),
+            false => self.t6.check(&probe_ret.t6x, "T6", match_points),
+        };
         // println!("T6: {}", t6_check);
-        let (t7_score, t7_total) = self.t7.check(&probe_ret.t7x, "T7");
+        let (t7_score, t7_total) = match probe_ret.is_skipped("T7") {
+            true => (0, 0),
+            false => self.t7.check(&probe_ret.t7x, "T7", match_points),
+        };
         // println!("T7: {}", t7_check);
-        let (u1_score, u1_total) = self.u1.check(&probe_ret.u1x);
+        let (u1_score, u1_total) = match probe_ret.is_skipped("U1") {
+            true => (0, 0),
+            false => self.u1.check(&probe_ret.u1x, match_points),
+        };
         // println!("U1: {}", u1_check);
-        let (ie_score, ie_total) = self.ie.check(&probe_ret.iex);
+        let (ie_score, ie_total) = match probe_ret.is_skipped("IE") {
+            true => (0, 0),
+            false => self.ie.check(&probe_ret.iex, match_points),
+        };
         // println!("IE: {}", ie_check);
 
         let score = seq_score
@@ -1271,9 +1600,20 @@ pub fn nmap_os_db_parser(lines: Vec<String>) -> Result<Vec<NmapOsDb>> {
     let mut t6 = None;
     let mut t7 = None;
     let mut u1 = None;
+    let mut in_match_points = false;
     for l in lines {
         // println!("{}", l);
         let l = l.trim().to_string();
+        // the MatchPoints section is parsed by MatchPoints::parser
+        if l == "MatchPoints" {
+            in_match_points = true;
+            continue;
+        } else if in_match_points {
+            if l.is_empty() {
+                in_match_points = false;
+            }
+            continue;
+        }
         if l.len() > 0 {
             if l.starts_with("#") {
                 let l = match l1 {
@@ -605,6 +605,25 @@ fn get_tsval(ipv4_response: &[u8]) -> Result<Option<u32>> {
     }
 }
 
+/// The TSvals of the SEQ responses with the index of the probe, the probes are sent 100 ms apart.
+pub fn tcp_tsvals(seqrr: &SEQRR) -> Result<Vec<(usize, u32)>> {
+    let responses = [
+        &seqrr.seq1.response,
+        &seqrr.seq2.response,
+        &seqrr.seq3.response,
+        &seqrr.seq4.response,
+        &seqrr.seq5.response,
+        &seqrr.seq6.response,
+    ];
+    let mut tsvals = Vec::new();
+    for (i, response) in responses.iter().enumerate() {
+        if let Some(tsval) = get_tsval(response)? {
+            tsvals.push((i, tsval));
+        }
+    }
+    Ok(tsvals)
+}
+
 /// TCP timestamp option algorithm (TS)
 pub fn tcp_ts(seqrr: &SEQRR) -> Result<String> {
     let tsval_1 = get_tsval(&seqrr.seq1.response)?;
@@ -829,7 +848,11 @@ pub fn tcp_udp_icmp_t(ipv4_response: &[u8], u1rr: &U1RR) -> Result<u16> {
 }
 
 fn udp_hops(u1rr: &U1RR) -> Result<u8> {
-    let request = get_ipv4_packet(&u1rr.u1.request)?.unwrap(); // must have request
+    let request = match get_ipv4_packet(&u1rr.u1.request)? {
+        Some(r) => r,
+        // The U1 probe is skipped.
+        None => return Ok(0),
+    };
     let ipv4_packet = get_ipv4_packet(&u1rr.u1.response)?;
     match ipv4_packet {
         Some(ipv4_packet) => {
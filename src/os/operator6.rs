@@ -5,6 +5,7 @@ use pnet::packet::tcp::TcpOptionNumbers;
 use pnet::packet::tcp::TcpPacket;
 use pnet::packet::Packet;
 use std::iter::zip;
+use std::time::Duration;
 
 use super::rr::AllPacketRR6;
 use crate::errors::GetIcmpv6PacketFailed;
@@ -21,6 +22,13 @@ const RST_MASK: u8 = 0b00000100;
 const SYN_MASK: u8 = 0b00000010;
 const FIN_MASK: u8 = 0b00000001;
 
+/// The number of the features the nmap logistic regression model uses, the head of the `vectorize` output.
+pub const NMAP_FEATURES_LEN: usize = 695;
+/// The number of the features `vectorize` returns, the nmap features followed by NI, timing and flow label features.
+pub const FEATURES_LEN: usize = 719;
+/// The index of the first timing feature, the nmap and NI features are before it.
+pub const TIMING_FEATURES_START: usize = NMAP_FEATURES_LEN + 5;
+
 fn get_response_by_name(ap: &AllPacketRR6, name: &str) -> Option<Vec<u8>> {
     match name {
         "S1" => Some(ap.seq.seq1.response.to_vec()),
@@ -45,6 +53,44 @@ fn get_response_by_name(ap: &AllPacketRR6, name: &str) -> Option<Vec<u8>> {
     }
 }
 
+const IPV6_PROBE_NAMES: [&str; 17] = [
+    "S1", "S2", "S3", "S4", "S5", "S6", "IE1", "IE2", "NS", "U1", "TECN", "T2", "T3", "T4", "T5",
+    "T6", "T7",
+]; // 17 * 3 + 1 => 51 + 1 features
+const TCP_PROBE_NAMES: [&str; 13] = [
+    "S1", "S2", "S3", "S4", "S5", "S6", "TECN", "T2", "T3", "T4", "T5", "T6", "T7",
+]; // 637 features
+const ICMPV6_PROBE_NAMES: [&str; 3] = ["IE1", "IE2", "NS"]; // 6 features
+const TIMING_PROBE_NAMES: [&str; 18] = [
+    "S1", "S2", "S3", "S4", "S5", "S6", "IE1", "IE2", "NI", "NS", "U1", "TECN", "T2", "T3", "T4",
+    "T5", "T6", "T7",
+];
+
+/// The send and receive time of the probe, relative to the start of the detection.
+fn get_timing_by_name(ap: &AllPacketRR6, name: &str) -> Option<(Duration, Duration)> {
+    match name {
+        "S1" => Some((ap.seq.st1, ap.seq.rt1)),
+        "S2" => Some((ap.seq.st2, ap.seq.rt2)),
+        "S3" => Some((ap.seq.st3, ap.seq.rt3)),
+        "S4" => Some((ap.seq.st4, ap.seq.rt4)),
+        "S5" => Some((ap.seq.st5, ap.seq.rt5)),
+        "S6" => Some((ap.seq.st6, ap.seq.rt6)),
+        "IE1" => Some((ap.ie.st1, ap.ie.rt1)),
+        "IE2" => Some((ap.ie.st2, ap.ie.rt2)),
+        "NI" => Some((ap.nx.sti, ap.nx.rti)),
+        "NS" => Some((ap.nx.sts, ap.nx.rts)),
+        "U1" => Some((ap.u1.st, ap.u1.rt)),
+        "TECN" => Some((ap.tecn.st, ap.tecn.rt)),
+        "T2" => Some((ap.tx.st2, ap.tx.rt2)),
+        "T3" => Some((ap.tx.st3, ap.tx.rt3)),
+        "T4" => Some((ap.tx.st4, ap.tx.rt4)),
+        "T5" => Some((ap.tx.st5, ap.tx.rt5)),
+        "T6" => Some((ap.tx.st6, ap.tx.rt6)),
+        "T7" => Some((ap.tx.st7, ap.tx.rt7)),
+        _ => None,
+    }
+}
+
 fn get_ipv6_packet(ipv6_buff: &[u8]) -> Result<Option<Ipv6Packet>> {
     if ipv6_buff.len() > 0 {
         match Ipv6Packet::new(ipv6_buff) {
@@ -93,6 +139,24 @@ fn ipv6_plen_tc(ipv6_buff: &[u8]) -> Result<(f64, f64)> {
     Ok((plen, tc))
 }
 
+/// IPv6 Flow Label field.
+fn ipv6_flow_label(ipv6_buff: &[u8]) -> Result<Option<u32>> {
+    let ipv6_packet = get_ipv6_packet(ipv6_buff)?;
+    match ipv6_packet {
+        Some(ipv6_packet) => Ok(Some(ipv6_packet.get_flow_label())),
+        None => Ok(None),
+    }
+}
+
+/// The time between sending the probe and receiving the response (RT - ST) in seconds.
+fn probe_rtt(ipv6_buff: &[u8], st: Duration, rt: Duration) -> f64 {
+    if !ipv6_buff.is_empty() {
+        rt.saturating_sub(st).as_secs_f64()
+    } else {
+        -1.0
+    }
+}
+
 /// Get tcp sequence number.
 fn tcp_seq(ipv6_buff: &[u8]) -> Result<u32> {
     let ipv6_packet = get_ipv6_packet(ipv6_buff)?;
@@ -418,18 +482,11 @@ fn icmpv6_type_code(ipv6_buff: &[u8]) -> Result<(f64, f64)> {
     Ok((-1.0, -1.0))
 }
 
+/// The first `NMAP_FEATURES_LEN` features are the ones of the nmap model,
+/// the NI response, the round trip time of every probe and the response flow labels are appended after them.
 pub fn vectorize(ap: &AllPacketRR6) -> Result<Vec<f64>> {
-    let ipv6_probe_names: Vec<&str> = vec![
-        "S1", "S2", "S3", "S4", "S5", "S6", "IE1", "IE2", "NS", "U1", "TECN", "T2", "T3", "T4",
-        "T5", "T6", "T7",
-    ]; // 17 * 3 + 1 => 51 + 1 features
-    let tcp_probe_names: Vec<&str> = vec![
-        "S1", "S2", "S3", "S4", "S5", "S6", "TECN", "T2", "T3", "T4", "T5", "T6", "T7",
-    ]; // 637 features
-    let icmpv6_probe_names: Vec<&str> = vec!["IE1", "IE2", "NS"]; // 6 features
-
     let mut features: Vec<f64> = Vec::new();
-    for name in ipv6_probe_names {
+    for name in IPV6_PROBE_NAMES {
         let ipv6_response = get_response_by_name(ap, name).unwrap();
         let (plen, tc) = ipv6_plen_tc(&ipv6_response)?;
         features.push(plen);
@@ -441,7 +498,7 @@ pub fn vectorize(ap: &AllPacketRR6) -> Result<Vec<f64>> {
     // println!("{}", isr);
     features.push(isr);
 
-    for name in tcp_probe_names {
+    for name in TCP_PROBE_NAMES {
         // Each round will add 49 features.
         let ipv6_response = get_response_by_name(ap, name).unwrap();
         let window = tcp_window(&ipv6_response)?;
@@ -482,17 +539,94 @@ pub fn vectorize(ap: &AllPacketRR6) -> Result<Vec<f64>> {
         }
     }
 
-    for name in icmpv6_probe_names {
+    for name in ICMPV6_PROBE_NAMES {
         let ipv6_response = get_response_by_name(ap, name).unwrap();
         let (t, c) = icmpv6_type_code(&ipv6_response)?;
         features.push(t);
         features.push(c);
     }
 
-    assert_eq!(features.len(), 695);
+    assert_eq!(features.len(), NMAP_FEATURES_LEN);
+
+    // The features below are not used by the nmap model.
+    // NI probe, 5 features.
+    let ipv6_response = get_response_by_name(ap, "NI").unwrap();
+    let (plen, tc) = ipv6_plen_tc(&ipv6_response)?;
+    features.push(plen);
+    features.push(tc);
+    let hlim = ipv6_hlim(&ipv6_response)?;
+    features.push(hlim);
+    let (t, c) = icmpv6_type_code(&ipv6_response)?;
+    features.push(t);
+    features.push(c);
+
+    // ST and RT of every probe, 18 features.
+    let mut flow_labels = Vec::new();
+    for name in TIMING_PROBE_NAMES {
+        let ipv6_response = get_response_by_name(ap, name).unwrap();
+        let (st, rt) = get_timing_by_name(ap, name).unwrap();
+        features.push(probe_rtt(&ipv6_response, st, rt));
+        if let Some(fl) = ipv6_flow_label(&ipv6_response)? {
+            flow_labels.push(fl);
+        }
+    }
+
+    // EXTRA, the ratio of the responses with a non-zero flow label, 1 feature.
+    if !flow_labels.is_empty() {
+        let nonzero = flow_labels.iter().filter(|fl| **fl != 0).count();
+        features.push(nonzero as f64 / flow_labels.len() as f64);
+    } else {
+        features.push(-1.0);
+    }
+
+    assert_eq!(features.len(), FEATURES_LEN);
     Ok(features)
 }
 
+/// The names of the `vectorize` features, the same as the names in the nmap model files, like `S1.PLEN`.
+pub fn feature_names() -> Vec<String> {
+    let mut names = Vec::new();
+    for name in IPV6_PROBE_NAMES {
+        names.push(format!("{}.PLEN", name));
+        names.push(format!("{}.TC", name));
+        names.push(format!("{}.HLIM", name));
+    }
+    names.push(String::from("TCP_ISR"));
+    for name in TCP_PROBE_NAMES {
+        names.push(format!("{}.TCP_WINDOW", name));
+        for flag in ["F", "S", "R", "P", "A", "U", "E", "C"] {
+            names.push(format!("{}.TCP_FLAG_{}", name, flag));
+        }
+        for i in 8..12 {
+            names.push(format!("{}.TCP_FLAG_RES{}", name, i));
+        }
+        for i in 0..16 {
+            names.push(format!("{}.TCP_OPT_{}", name, i));
+        }
+        for i in 0..16 {
+            names.push(format!("{}.TCP_OPTLEN_{}", name, i));
+        }
+        names.push(format!("{}.TCP_MSS", name));
+        names.push(format!("{}.TCP_SACKOK", name));
+        names.push(format!("{}.TCP_WSCALE", name));
+        names.push(format!("{}.TCP_CORR_WINDOW_MSS", name));
+    }
+    for name in ICMPV6_PROBE_NAMES {
+        names.push(format!("{}.ICMPV6_TYPE", name));
+        names.push(format!("{}.ICMPV6_CODE", name));
+    }
+    names.push(String::from("NI.PLEN"));
+    names.push(String::from("NI.TC"));
+    names.push(String::from("NI.HLIM"));
+    names.push(String::from("NI.ICMPV6_TYPE"));
+    names.push(String::from("NI.ICMPV6_CODE"));
+    for name in TIMING_PROBE_NAMES {
+        names.push(format!("{}.RTT", name));
+    }
+    names.push(String::from("EXTRA.FL_NONZERO"));
+    names
+}
+
 pub fn apply_scale(features: &[f64], scale: &[Vec<f64>]) -> Vec<f64> {
     let mut new_features = Vec::new();
     for (f, ab) in zip(features, scale) {
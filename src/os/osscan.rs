@@ -7,7 +7,7 @@ use pnet::datalink::MacAddr;
 use rand::Rng;
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::fmt;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
@@ -16,6 +16,7 @@ use std::thread::sleep;
 use std::time::Duration;
 use std::time::SystemTime;
 
+use crate::errors::FingerprintParseFailed;
 use crate::errors::OsDetectResultsNullError;
 use crate::hop::ipv4_get_hops;
 use crate::layers::layer3_ipv4_send;
@@ -24,12 +25,16 @@ use crate::layers::Layer3Match;
 use crate::layers::Layer4MatchIcmp;
 use crate::layers::Layer4MatchTcpUdp;
 use crate::layers::LayersMatch;
+use crate::os::rank_os_infos;
+use crate::os::OsDetectOptions;
+use crate::os::OsDetectPorts;
 use crate::os::OsInfo;
 use crate::utils::get_threads_pool;
 use crate::utils::random_port;
 use crate::utils::random_port_multi;
 use crate::IpCheckMethods;
 
+use super::dbparser::MatchPoints;
 use super::dbparser::NmapOsDb;
 use super::operator::icmp_cd;
 use super::operator::icmp_dfi;
@@ -47,6 +52,7 @@ use super::operator::tcp_sp;
 use super::operator::tcp_ss;
 use super::operator::tcp_ti_ci_ii;
 use super::operator::tcp_ts;
+use super::operator::tcp_tsvals;
 use super::operator::tcp_udp_df;
 use super::operator::tcp_udp_icmp_r;
 use super::operator::tcp_udp_icmp_t;
@@ -68,6 +74,9 @@ use super::rr::IERR;
 use super::rr::SEQRR;
 use super::rr::TXRR;
 use super::rr::U1RR;
+use super::seqinfo::IsnInfo;
+use super::seqinfo::SeqInfo;
+use super::seqinfo::TcpTsInfo;
 
 // EXAMPLE
 // SCAN(V=5.05BETA1%D=8/23%OT=22%CT=1%CU=42341%PV=N%DS=0%DC=L%G=Y%TM=4A91CB90%P=i686-pc-linux-gnu)
@@ -101,6 +110,19 @@ pub struct PistolFingerprint {
     pub t7x: TXX,
     pub u1x: U1X,
     pub iex: IEX,
+    /// The tests not run for lack of a suitable port, such as `T5`, `T6`, `T7` and `U1` without a closed port.
+    /// They are left out of the output and the matching.
+    pub skipped_tests: Vec<String>,
+    /// The number of probe rounds sent, see `threads_os_probe`.
+    pub tries: usize,
+    /// The tests whose results differ between the rounds, the SEQ and IE are merged from the agreed rounds.
+    pub inconsistent_tests: Vec<String>,
+}
+
+impl PistolFingerprint {
+    pub fn is_skipped(&self, test: &str) -> bool {
+        self.skipped_tests.iter().any(|t| t == test)
+    }
 }
 
 impl fmt::Display for PistolFingerprint {
@@ -119,19 +141,26 @@ impl fmt::Display for PistolFingerprint {
         let t7x_str = format!("\n{}", self.t7x);
         let u1x_str = format!("\n{}", self.u1x);
         let iex_str = format!("\n{}", self.iex);
-        output += &seqx_str;
-        output += &opsx_str;
-        output += &winx_str;
-        output += &ecnx_str;
-        output += &t1x_str;
-        output += &t2x_str;
-        output += &t3x_str;
-        output += &t4x_str;
-        output += &t5x_str;
-        output += &t6x_str;
-        output += &t7x_str;
-        output += &u1x_str;
-        output += &iex_str;
+        let tests = vec![
+            ("SEQ", seqx_str),
+            ("OPS", opsx_str),
+            ("WIN", winx_str),
+            ("ECN", ecnx_str),
+            ("T1", t1x_str),
+            ("T2", t2x_str),
+            ("T3", t3x_str),
+            ("T4", t4x_str),
+            ("T5", t5x_str),
+            ("T6", t6x_str),
+            ("T7", t7x_str),
+            ("U1", u1x_str),
+            ("IE", iex_str),
+        ];
+        for (name, test_str) in tests {
+            if !self.is_skipped(name) {
+                output += &test_str;
+            }
+        }
         write!(f, "{}", output)
     }
 }
@@ -153,13 +182,148 @@ impl PistolFingerprint {
         }
         ret.trim().to_string()
     }
+    /// Parse the fingerprint text from `nmap_format`, the nmap output or the `Display`,
+    /// the `OS:` prefixes and the line wrapping are removed before parsing.
+    /// The tests not in the text are not run, they are in the `skipped_tests`.
+    pub fn parser(fingerprint: &str) -> Result<PistolFingerprint> {
+        let tests = fingerprint_tests(fingerprint)?;
+        let names = [
+            "SEQ", "OPS", "WIN", "ECN", "T1", "T2", "T3", "T4", "T5", "T6", "T7", "U1", "IE",
+        ];
+        let mut skipped_tests = Vec::new();
+        for name in names {
+            if !tests.iter().any(|(n, _)| n == name) {
+                skipped_tests.push(name.to_string());
+            }
+        }
+        let get_test = |name: &str| -> String {
+            for (n, body) in &tests {
+                if n == name {
+                    return body.clone();
+                }
+            }
+            String::from("R=N")
+        };
+
+        let scan = match tests.iter().find(|(n, _)| n == "SCAN") {
+            Some((_, body)) => format!("SCAN({})", body),
+            None => String::new(),
+        };
+        Ok(PistolFingerprint {
+            scan,
+            seqx: SEQX::parser(&get_test("SEQ"))?,
+            opsx: OPSX::parser(&get_test("OPS"))?,
+            winx: WINX::parser(&get_test("WIN"))?,
+            ecnx: ECNX::parser(&get_test("ECN"))?,
+            t1x: TXX::parser("T1", &get_test("T1"))?,
+            t2x: TXX::parser("T2", &get_test("T2"))?,
+            t3x: TXX::parser("T3", &get_test("T3"))?,
+            t4x: TXX::parser("T4", &get_test("T4"))?,
+            t5x: TXX::parser("T5", &get_test("T5"))?,
+            t6x: TXX::parser("T6", &get_test("T6"))?,
+            t7x: TXX::parser("T7", &get_test("T7"))?,
+            u1x: U1X::parser(&get_test("U1"))?,
+            iex: IEX::parser(&get_test("IE"))?,
+            skipped_tests,
+            tries: 1,
+            inconsistent_tests: Vec::new(),
+        })
+    }
 }
 
+/// Split the fingerprint text into `(name, body)` tests, such as `("SEQ", "SP=F8%GCD=1")`.
+/// The `OS:` prefix of each line is removed and the lines are joined, so it works with any wrapping.
+pub fn fingerprint_tests(fingerprint: &str) -> Result<Vec<(String, String)>> {
+    let mut text = String::new();
+    for line in fingerprint.lines() {
+        let line = line.trim();
+        let line = match line.strip_prefix("OS:") {
+            Some(l) => l,
+            None => line,
+        };
+        text += line;
+    }
+
+    let mut tests = Vec::new();
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let (name, body, next) = match rest.find('(') {
+            Some(start) => match rest[start..].find(')') {
+                Some(end) => (
+                    &rest[..start],
+                    &rest[start + 1..start + end],
+                    &rest[start + end + 1..],
+                ),
+                None => return Err(FingerprintParseFailed::new(rest.to_string()).into()),
+            },
+            None => return Err(FingerprintParseFailed::new(rest.to_string()).into()),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(FingerprintParseFailed::new(rest.to_string()).into());
+        }
+        tests.push((name.to_string(), body.to_string()));
+        rest = next;
+    }
+
+    if !tests.is_empty() {
+        Ok(tests)
+    } else {
+        Err(FingerprintParseFailed::new(fingerprint.to_string()).into())
+    }
+}
+
+/// Split the test body `SP=F8%GCD=1` into its values.
+pub fn fingerprint_test_values(body: &str) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for item in body.split('%') {
+        if !item.is_empty() {
+            match item.split_once('=') {
+                Some((k, v)) => {
+                    values.insert(k.to_string(), v.to_string());
+                }
+                None => return Err(FingerprintParseFailed::new(body.to_string()).into()),
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn value_str(values: &HashMap<String, String>, key: &str) -> String {
+    match values.get(key) {
+        Some(v) => v.clone(),
+        None => String::new(),
+    }
+}
+
+/// The numbers in the fingerprint are in hex, 0 if not present.
+fn value_hex<T: TryFrom<u64>>(values: &HashMap<String, String>, key: &str) -> Result<T> {
+    let v = match values.get(key) {
+        Some(v) if !v.is_empty() => match u64::from_str_radix(v, 16) {
+            Ok(v) => v,
+            Err(_) => return Err(FingerprintParseFailed::new(format!("{}={}", key, v)).into()),
+        },
+        _ => 0,
+    };
+    match T::try_from(v) {
+        Ok(v) => Ok(v),
+        Err(_) => Err(FingerprintParseFailed::new(format!("{}={:X}", key, v)).into()),
+    }
+}
+
+/// The `R` test is omitted when it is `Y`.
+fn value_r(values: &HashMap<String, String>) -> String {
+    match values.get("R") {
+        Some(r) => r.clone(),
+        None => String::from("Y"),
+    }
+}
+
+/// The `good_results` is false when the ports are not confirmed by the port scan, written as `G=N`.
 pub fn get_scan_line(
     dst_mac: Option<MacAddr>,
     dst_open_tcp_port: u16,
-    dst_closed_tcp_port: u16,
-    dst_closed_udp_port: u16,
+    dst_closed_tcp_port: Option<u16>,
+    dst_closed_udp_port: Option<u16>,
     dst_addr: IpAddr,
     hops: u8,
     good_results: bool,
@@ -210,51 +374,86 @@ pub fn get_scan_line(
     let p = "RUST";
 
     // SCAN(V=5.05BETA1%D=8/23%OT=22%CT=1%CU=42341%PV=N%DS=0%DC=L%G=Y%TM=4A91CB90%P=i686-pc-linux-gnu)
-    let info_str = match dst_addr {
-        IpAddr::V4(_) => {
-            let info_str = if m.len() > 0 {
-                format!("SCAN(V={v}%D={date}%OT={dst_open_tcp_port}%CT={dst_closed_tcp_port}%CU={dst_closed_udp_port}PV={pv}%DS={ds}%DC={dc}%G={g}%M={m}%TM={tm}%P={p})", )
-            } else {
-                format!("SCAN(V={v}%D={date}%OT={dst_open_tcp_port}%CT={dst_closed_tcp_port}%CU={dst_closed_udp_port}PV={pv}%DS={ds}%DC={dc}%G={g}%TM={tm}%P={p})", )
-            };
-            info_str
-        }
-        IpAddr::V6(_) => {
-            let info_str = if m.len() > 0 {
-                format!("SCAN(V={v}%E=6%D={date}%OT={dst_open_tcp_port}%CT={dst_closed_tcp_port}%CU={dst_closed_udp_port}PV={pv}%DS={ds}%DC={dc}%G={g}%M={m}%TM={tm}%P={p})", )
-            } else {
-                format!("SCAN(V={v}%E=6%D={date}%OT={dst_open_tcp_port}%CT={dst_closed_tcp_port}%CU={dst_closed_udp_port}PV={pv}%DS={ds}%DC={dc}%G={g}%TM={tm}%P={p})", )
-            };
-            info_str
+    let mut fields = vec![format!("V={v}")];
+    match dst_addr {
+        IpAddr::V4(_) => (),
+        IpAddr::V6(_) => fields.push(String::from("E=6")),
+    }
+    fields.push(format!("D={date}"));
+    fields.push(format!("OT={dst_open_tcp_port}"));
+    // The closed ports are omitted if they are unknown.
+    if let Some(p) = dst_closed_tcp_port {
+        fields.push(format!("CT={p}"));
+    }
+    if let Some(p) = dst_closed_udp_port {
+        fields.push(format!("CU={p}"));
+    }
+    fields.push(format!("PV={pv}"));
+    fields.push(format!("DS={ds}"));
+    fields.push(format!("DC={dc}"));
+    fields.push(format!("G={g}"));
+    if !m.is_empty() {
+        fields.push(format!("M={m}"));
+    }
+    fields.push(format!("TM={tm}"));
+    fields.push(format!("P={p}"));
+    format!("SCAN({})", fields.join("%"))
+}
+
+/// The source ports of the six SEQ probes, all of them use the `src_port` if given,
+/// or `src_port` to `src_port + 5` like nmap `-g` with the `port_step`,
+/// the `src_port` is kept for all of them when `src_port + 5` is over 65535.
+fn seq_src_ports(src_port: Option<u16>, port_step: bool) -> Vec<u16> {
+    match src_port {
+        Some(s) => {
+            let stepped: Option<Vec<u16>> = (0..6).map(|i| s.checked_add(i)).collect();
+            match stepped {
+                Some(ports) if port_step => ports,
+                _ => vec![s; 6],
+            }
         }
-    };
-    info_str
+        None => random_port_multi(6),
+    }
+}
+
+fn seq_packets(
+    src_ipv4: Ipv4Addr,
+    src_ports: &[u16],
+    dst_ipv4: Ipv4Addr,
+    dst_open_port: u16,
+    ttl: u8,
+) -> Result<Vec<Vec<u8>>> {
+    let buff_1 = packet::seq_packet_1_layer3(src_ipv4, src_ports[0], dst_ipv4, dst_open_port, ttl)?;
+    let buff_2 = packet::seq_packet_2_layer3(src_ipv4, src_ports[1], dst_ipv4, dst_open_port, ttl)?;
+    let buff_3 = packet::seq_packet_3_layer3(src_ipv4, src_ports[2], dst_ipv4, dst_open_port, ttl)?;
+    let buff_4 = packet::seq_packet_4_layer3(src_ipv4, src_ports[3], dst_ipv4, dst_open_port, ttl)?;
+    let buff_5 = packet::seq_packet_5_layer3(src_ipv4, src_ports[4], dst_ipv4, dst_open_port, ttl)?;
+    let buff_6 = packet::seq_packet_6_layer3(src_ipv4, src_ports[5], dst_ipv4, dst_open_port, ttl)?;
+    Ok(vec![buff_1, buff_2, buff_3, buff_4, buff_5, buff_6])
 }
 
 fn send_seq_probes(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
+    port_step: bool,
+    ttl: u8,
     dst_ipv4: Ipv4Addr,
     dst_open_port: u16,
     timeout: Duration,
 ) -> Result<SEQRR> {
-    // 6 packets with 6 threads
-    let pool = get_threads_pool(6);
-    let (tx, rx) = channel();
-
-    let src_ports = match src_port {
-        Some(s) => vec![s; 6],
-        None => random_port_multi(6),
+    let src_ports = seq_src_ports(src_port, port_step);
+    // 6 packets with 6 threads, or one by one when they share the source port,
+    // the responses to the same port can only be told apart by the order,
+    // the next probe still leaves 100 ms after the last one if the last one is answered in time
+    let same_port = src_ports.iter().all(|p| *p == src_ports[0]);
+    let pool = if same_port {
+        get_threads_pool(1)
+    } else {
+        get_threads_pool(6)
     };
+    let (tx, rx) = channel();
 
-    let buff_1 = packet::seq_packet_1_layer3(src_ipv4, src_ports[0], dst_ipv4, dst_open_port)?;
-    let buff_2 = packet::seq_packet_2_layer3(src_ipv4, src_ports[1], dst_ipv4, dst_open_port)?;
-    let buff_3 = packet::seq_packet_3_layer3(src_ipv4, src_ports[2], dst_ipv4, dst_open_port)?;
-    let buff_4 = packet::seq_packet_4_layer3(src_ipv4, src_ports[3], dst_ipv4, dst_open_port)?;
-    let buff_5 = packet::seq_packet_5_layer3(src_ipv4, src_ports[4], dst_ipv4, dst_open_port)?;
-    let buff_6 = packet::seq_packet_6_layer3(src_ipv4, src_ports[5], dst_ipv4, dst_open_port)?;
-    let buffs = vec![buff_1, buff_2, buff_3, buff_4, buff_5, buff_6];
-    // let buffs = vec![buff_4];
+    let buffs = seq_packets(src_ipv4, &src_ports, dst_ipv4, dst_open_port, ttl)?;
 
     let start = SystemTime::now();
     let mut i = 0;
@@ -323,15 +522,20 @@ fn send_seq_probes(
     Ok(seqrr)
 }
 
-fn send_ie_probes(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, timeout: Duration) -> Result<IERR> {
+fn send_ie_probes(
+    src_ipv4: Ipv4Addr,
+    ttl: u8,
+    dst_ipv4: Ipv4Addr,
+    timeout: Duration,
+) -> Result<IERR> {
     let (tx, rx) = channel();
 
     let mut rng = rand::thread_rng();
     let id_1 = rng.gen();
     // and the ICMP request ID and sequence numbers are incremented by one from the previous query values
     let id_2 = id_1 + 1;
-    let buff_1 = packet::ie_packet_1_layer3(src_ipv4, dst_ipv4, id_1)?;
-    let buff_2 = packet::ie_packet_2_layer3(src_ipv4, dst_ipv4, id_2)?;
+    let buff_1 = packet::ie_packet_1_layer3(src_ipv4, dst_ipv4, id_1, ttl)?;
+    let buff_2 = packet::ie_packet_2_layer3(src_ipv4, dst_ipv4, id_2, ttl)?;
     let buffs = vec![buff_1, buff_2];
 
     let layer3 = Layer3Match {
@@ -390,6 +594,7 @@ fn send_ie_probes(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, timeout: Duration) ->
 fn send_ecn_probe(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
+    ttl: u8,
     dst_ipv4: Ipv4Addr,
     dst_open_port: u16,
     timeout: Duration,
@@ -411,7 +616,7 @@ fn send_ecn_probe(
     };
     let layers_match = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp);
 
-    let buff = packet::ecn_packet_layer3(src_ipv4, src_port, dst_ipv4, dst_open_port)?;
+    let buff = packet::ecn_packet_layer3(src_ipv4, src_port, dst_ipv4, dst_open_port, ttl)?;
     // For those that do not require time, process them in order.
     // Prevent the previous request from receiving response from the later request.
     // ICMPV6 is a stateless protocol, we cannot accurately know the response for each request.
@@ -433,13 +638,18 @@ fn send_ecn_probe(
 fn send_tx_probes(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
+    ttl: u8,
     dst_ipv4: Ipv4Addr,
     dst_open_port: u16,
-    dst_closed_port: u16,
+    dst_closed_port: Option<u16>,
     timeout: Duration,
 ) -> Result<TXRR> {
-    // 6 packets with 6 threads
-    let pool = get_threads_pool(6);
+    // 6 packets with 6 threads, or one by one with the same source port,
+    // the responses to the same port can only be told apart by the order
+    let pool = match src_port {
+        Some(_) => get_threads_pool(1),
+        None => get_threads_pool(6),
+    };
     let (tx, rx) = channel();
     let src_ports = match src_port {
         Some(s) => vec![s; 6],
@@ -451,87 +661,66 @@ fn send_tx_probes(
         src_addr: Some(dst_ipv4.into()),
         dst_addr: Some(src_ipv4.into()),
     };
-    let layer4_tcp_udp_1 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_open_port),
-        dst_port: Some(src_ports[0]),
-    };
-    let layer4_tcp_udp_2 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_open_port),
-        dst_port: Some(src_ports[1]),
-    };
-    let layer4_tcp_udp_3 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_open_port),
-        dst_port: Some(src_ports[2]),
-    };
-    let layer4_tcp_udp_4 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_closed_port),
-        dst_port: Some(src_ports[3]),
-    };
-    let layer4_tcp_udp_5 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_closed_port),
-        dst_port: Some(src_ports[4]),
-    };
-    let layer4_tcp_udp_6 = Layer4MatchTcpUdp {
-        layer3: Some(layer3),
-        src_port: Some(dst_closed_port),
-        dst_port: Some(src_ports[5]),
+    let layers_match = |src_port: u16, dst_port: u16| -> LayersMatch {
+        let layer4_tcp_udp = Layer4MatchTcpUdp {
+            layer3: Some(layer3),
+            src_port: Some(src_port),
+            dst_port: Some(dst_port),
+        };
+        LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp)
     };
-    let layers_match_1 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_1);
-    let layers_match_2 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_2);
-    let layers_match_3 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_3);
-    let layers_match_4 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_4);
-    let layers_match_5 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_5);
-    let layers_match_6 = LayersMatch::Layer4MatchTcpUdp(layer4_tcp_udp_6);
-    let ms = vec![
-        layers_match_1,
-        layers_match_2,
-        layers_match_3,
-        layers_match_4,
-        layers_match_5,
-        layers_match_6,
-    ];
 
     // T2 sends a TCP null (no flags set) packet with the IP DF bit set and a window field of 128 to an open port.
-    let buff_2 = packet::t2_packet_layer3(src_ipv4, src_ports[0], dst_ipv4, dst_open_port)?;
+    let buff_2 = packet::t2_packet_layer3(src_ipv4, src_ports[0], dst_ipv4, dst_open_port, ttl)?;
     // T3 sends a TCP packet with the SYN, FIN, URG, and PSH flags set and a window field of 256 to an open port. The IP DF bit is not set.
-    let buff_3 = packet::t3_packet_layer3(src_ipv4, src_ports[1], dst_ipv4, dst_open_port)?;
+    let buff_3 = packet::t3_packet_layer3(src_ipv4, src_ports[1], dst_ipv4, dst_open_port, ttl)?;
     // T4 sends a TCP ACK packet with IP DF and a window field of 1024 to an open port.
-    let buff_4 = packet::t4_packet_layer3(src_ipv4, src_ports[2], dst_ipv4, dst_open_port)?;
-    // T5 sends a TCP SYN packet without IP DF and a window field of 31337 to a closed port.
-    let buff_5 = packet::t5_packet_layer3(src_ipv4, src_ports[3], dst_ipv4, dst_closed_port)?;
-    // T6 sends a TCP ACK packet with IP DF and a window field of 32768 to a closed port.
-    let buff_6 = packet::t6_packet_layer3(src_ipv4, src_ports[4], dst_ipv4, dst_closed_port)?;
-    // T7 sends a TCP packet with the FIN, PSH, and URG flags set and a window field of 65535 to a closed port. The IP DF bit is not set.
-    let buff_7 = packet::t7_packet_layer3(src_ipv4, src_ports[5], dst_ipv4, dst_closed_port)?;
-    let buffs = vec![buff_2, buff_3, buff_4, buff_5, buff_6, buff_7];
-    // let buffs = vec![buff_5];
+    let buff_4 = packet::t4_packet_layer3(src_ipv4, src_ports[2], dst_ipv4, dst_open_port, ttl)?;
+    let mut buffs = vec![
+        (buff_2, layers_match(dst_open_port, src_ports[0])),
+        (buff_3, layers_match(dst_open_port, src_ports[1])),
+        (buff_4, layers_match(dst_open_port, src_ports[2])),
+    ];
+    // T5, T6 and T7 are skipped when there is no closed port.
+    if let Some(dst_closed_port) = dst_closed_port {
+        // T5 sends a TCP SYN packet without IP DF and a window field of 31337 to a closed port.
+        let buff_5 =
+            packet::t5_packet_layer3(src_ipv4, src_ports[3], dst_ipv4, dst_closed_port, ttl)?;
+        // T6 sends a TCP ACK packet with IP DF and a window field of 32768 to a closed port.
+        let buff_6 =
+            packet::t6_packet_layer3(src_ipv4, src_ports[4], dst_ipv4, dst_closed_port, ttl)?;
+        // T7 sends a TCP packet with the FIN, PSH, and URG flags set and a window field of 65535 to a closed port. The IP DF bit is not set.
+        let buff_7 =
+            packet::t7_packet_layer3(src_ipv4, src_ports[5], dst_ipv4, dst_closed_port, ttl)?;
+        buffs.push((buff_5, layers_match(dst_closed_port, src_ports[3])));
+        buffs.push((buff_6, layers_match(dst_closed_port, src_ports[4])));
+        buffs.push((buff_7, layers_match(dst_closed_port, src_ports[5])));
+    }
 
-    let mut i = 0;
-    for buff in buffs {
+    let recv_size = buffs.len();
+    for (i, (buff, m)) in buffs.into_iter().enumerate() {
         let tx = tx.clone();
-        let m = ms[i];
         pool.execute(move || {
             let ret = layer3_ipv4_send(src_ipv4, dst_ipv4, &buff, vec![m], timeout);
             match tx.send((i, buff.to_vec(), ret)) {
                 _ => (),
             }
         });
-        i += 1;
     }
 
+    // The skipped probes have neither request nor response.
+    let skipped = RequestAndResponse {
+        request: Vec::new(),
+        response: Vec::new(),
+    };
     let mut t2 = None;
     let mut t3 = None;
     let mut t4 = None;
-    let mut t5 = None;
-    let mut t6 = None;
-    let mut t7 = None;
+    let mut t5 = Some(skipped.clone());
+    let mut t6 = Some(skipped.clone());
+    let mut t7 = Some(skipped);
 
-    let iter = rx.into_iter().take(6);
+    let iter = rx.into_iter().take(recv_size);
     for (i, request, ret) in iter {
         let response = match ret? {
             (Some(r), Some(_rtt)) => r,
@@ -564,10 +753,22 @@ fn send_tx_probes(
 fn send_u1_probe(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
+    ttl: u8,
     dst_ipv4: Ipv4Addr,
-    dst_closed_port: u16, //should be an closed port
+    dst_closed_port: Option<u16>, //should be an closed port
     timeout: Duration,
 ) -> Result<U1RR> {
+    // U1 is skipped when there is no closed port.
+    let dst_closed_port = match dst_closed_port {
+        Some(p) => p,
+        None => {
+            let rr = RequestAndResponse {
+                request: Vec::new(),
+                response: Vec::new(),
+            };
+            return Ok(U1RR { u1: rr });
+        }
+    };
     let src_port = match src_port {
         Some(s) => s,
         None => random_port(),
@@ -585,7 +786,7 @@ fn send_u1_probe(
     };
     let layers_match = LayersMatch::Layer4MatchIcmp(layer4_icmp);
 
-    let buff = packet::udp_packet_layer3(src_ipv4, src_port, dst_ipv4, dst_closed_port)?;
+    let buff = packet::udp_packet_layer3(src_ipv4, src_port, dst_ipv4, dst_closed_port, ttl)?;
     // For those that do not require time, process them in order.
     // Prevent the previous request from receiving response from the later request.
     // ICMPV6 is a stateless protocol, we cannot accurately know the response for each request.
@@ -604,27 +805,59 @@ fn send_u1_probe(
     Ok(u1)
 }
 
+/// Every probe uses the `src_port` if given, except the IE probes which have no port,
+/// the six SEQ probes use `src_port` to `src_port + 5` instead with `options.seq_port_step`, see `seq_src_ports`.
+/// Every probe uses the `options.ttl` if given, `packet::TTL` if not.
 fn send_all_probes(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
     dst_ipv4: Ipv4Addr,
-    dst_open_tcp_port: u16,
-    dst_closed_tcp_port: u16,
-    dst_closed_udp_port: u16,
+    ports: &OsDetectPorts,
+    options: &OsDetectOptions,
     timeout: Duration,
 ) -> Result<AllPacketRR> {
-    let seq = send_seq_probes(src_ipv4, src_port, dst_ipv4, dst_open_tcp_port, timeout)?;
-    let ie = send_ie_probes(src_ipv4, dst_ipv4, timeout)?;
-    let ecn = send_ecn_probe(src_ipv4, src_port, dst_ipv4, dst_open_tcp_port, timeout)?;
+    let ttl = match options.ttl {
+        Some(t) => t,
+        None => packet::TTL,
+    };
+    let dst_open_tcp_port = ports.open_tcp_port;
+    let dst_closed_tcp_port = ports.closed_tcp_port;
+    let dst_closed_udp_port = ports.closed_udp_port;
+    let seq = send_seq_probes(
+        src_ipv4,
+        src_port,
+        options.seq_port_step,
+        ttl,
+        dst_ipv4,
+        dst_open_tcp_port,
+        timeout,
+    )?;
+    let ie = send_ie_probes(src_ipv4, ttl, dst_ipv4, timeout)?;
+    let ecn = send_ecn_probe(
+        src_ipv4,
+        src_port,
+        ttl,
+        dst_ipv4,
+        dst_open_tcp_port,
+        timeout,
+    )?;
     let tx = send_tx_probes(
         src_ipv4,
         src_port,
+        ttl,
         dst_ipv4,
         dst_open_tcp_port,
         dst_closed_tcp_port,
         timeout,
     )?;
-    let u1 = send_u1_probe(src_ipv4, src_port, dst_ipv4, dst_closed_udp_port, timeout)?;
+    let u1 = send_u1_probe(
+        src_ipv4,
+        src_port,
+        ttl,
+        dst_ipv4,
+        dst_closed_udp_port,
+        timeout,
+    )?;
 
     let ap = AllPacketRR {
         seq,
@@ -690,6 +923,23 @@ impl fmt::Display for SEQX {
     }
 }
 
+impl SEQX {
+    pub fn parser(body: &str) -> Result<SEQX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(SEQX {
+            sp: value_hex(&values, "SP")?,
+            gcd: value_hex(&values, "GCD")?,
+            isr: value_hex(&values, "ISR")?,
+            ti: value_str(&values, "TI"),
+            ci: value_str(&values, "CI"),
+            ii: value_str(&values, "II"),
+            ss: value_str(&values, "SS"),
+            ts: value_str(&values, "TS"),
+            r: value_r(&values),
+        })
+    }
+}
+
 pub fn seq_fingerprint(ap: &AllPacketRR) -> Result<SEQX> {
     let rynum = |rvec: Vec<String>| -> usize {
         let mut num = 0;
@@ -828,6 +1078,21 @@ impl fmt::Display for OPSX {
     }
 }
 
+impl OPSX {
+    pub fn parser(body: &str) -> Result<OPSX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(OPSX {
+            o1: value_str(&values, "O1"),
+            o2: value_str(&values, "O2"),
+            o3: value_str(&values, "O3"),
+            o4: value_str(&values, "O4"),
+            o5: value_str(&values, "O5"),
+            o6: value_str(&values, "O6"),
+            r: value_r(&values),
+        })
+    }
+}
+
 pub fn ops_fingerprint(ap: &AllPacketRR) -> Result<OPSX> {
     let rops = |rvec: Vec<String>| -> bool {
         let mut flag = true;
@@ -949,6 +1214,21 @@ impl fmt::Display for WINX {
     }
 }
 
+impl WINX {
+    pub fn parser(body: &str) -> Result<WINX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(WINX {
+            w1: value_hex(&values, "W1")?,
+            w2: value_hex(&values, "W2")?,
+            w3: value_hex(&values, "W3")?,
+            w4: value_hex(&values, "W4")?,
+            w5: value_hex(&values, "W5")?,
+            w6: value_hex(&values, "W6")?,
+            r: value_r(&values),
+        })
+    }
+}
+
 pub fn win_fingerprint(ap: &AllPacketRR) -> Result<WINX> {
     let rwin = |rvec: Vec<String>| -> bool {
         let mut flag = true;
@@ -1085,6 +1365,22 @@ impl fmt::Display for ECNX {
     }
 }
 
+impl ECNX {
+    pub fn parser(body: &str) -> Result<ECNX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(ECNX {
+            r: value_r(&values),
+            df: value_str(&values, "DF"),
+            t: value_hex(&values, "T")?,
+            tg: value_hex(&values, "TG")?,
+            w: value_hex(&values, "W")?,
+            o: value_str(&values, "O"),
+            cc: value_str(&values, "CC"),
+            q: value_str(&values, "Q"),
+        })
+    }
+}
+
 pub fn ecn_fingerprint(ap: &AllPacketRR) -> Result<ECNX> {
     let r = tcp_udp_icmp_r(&ap.ecn.ecn.response)?;
     let (df, t, tg, w, o, cc, q) = match r.as_str() {
@@ -1254,6 +1550,26 @@ impl fmt::Display for TXX {
     }
 }
 
+impl TXX {
+    pub fn parser(name: &str, body: &str) -> Result<TXX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(TXX {
+            name: name.to_string(),
+            r: value_r(&values),
+            df: value_str(&values, "DF"),
+            t: value_hex(&values, "T")?,
+            tg: value_hex(&values, "TG")?,
+            w: value_hex(&values, "W")?,
+            s: value_str(&values, "S"),
+            a: value_str(&values, "A"),
+            f: value_str(&values, "F"),
+            o: value_str(&values, "O"),
+            rd: value_hex(&values, "RD")?,
+            q: value_str(&values, "Q"),
+        })
+    }
+}
+
 fn _tx_fingerprint(tx: &RequestAndResponse, u1rr: &U1RR, name: &str) -> Result<TXX> {
     let r = tcp_udp_icmp_r(&tx.response)?;
     let (df, t, tg, w, s, a, f, o, rd, q) = match r.as_str() {
@@ -1365,9 +1681,9 @@ impl fmt::Display for U1X {
             // This TTL guess field is not printed in a subject fingerprint if the actual TTL (T) value was discovered.
             let tg_str = if first_elem {
                 first_elem = false;
-                format!("TG={}", self.tg)
+                format!("TG={:X}", self.tg)
             } else {
-                format!("%TG={}", self.tg)
+                format!("%TG={:X}", self.tg)
             };
             output += &tg_str;
         }
@@ -1437,6 +1753,25 @@ impl fmt::Display for U1X {
     }
 }
 
+impl U1X {
+    pub fn parser(body: &str) -> Result<U1X> {
+        let values = fingerprint_test_values(body)?;
+        Ok(U1X {
+            r: value_r(&values),
+            df: value_str(&values, "DF"),
+            t: value_hex(&values, "T")?,
+            tg: value_hex(&values, "TG")?,
+            ipl: value_hex(&values, "IPL")?,
+            un: value_hex(&values, "UN")?,
+            ripl: value_str(&values, "RIPL"),
+            rid: value_str(&values, "RID"),
+            ripck: value_str(&values, "RIPCK"),
+            ruck: value_str(&values, "RUCK"),
+            rud: value_str(&values, "RUD"),
+        })
+    }
+}
+
 pub fn u1_fingerprint(ap: &AllPacketRR) -> Result<U1X> {
     let r = tcp_udp_icmp_r(&ap.u1.u1.response)?;
     let (df, t, tg, ipl, un, ripl, rid, ripck, ruck, rud) = match r.as_str() {
@@ -1529,9 +1864,9 @@ impl fmt::Display for IEX {
             // This TTL guess field is not printed in a subject fingerprint if the actual TTL (T) value was discovered.
             let tg_str = if first_elem {
                 first_elem = false;
-                format!("TG={}", self.tg)
+                format!("TG={:X}", self.tg)
             } else {
-                format!("%TG={}", self.tg)
+                format!("%TG={:X}", self.tg)
             };
             output += &tg_str;
         }
@@ -1549,6 +1884,19 @@ impl fmt::Display for IEX {
     }
 }
 
+impl IEX {
+    pub fn parser(body: &str) -> Result<IEX> {
+        let values = fingerprint_test_values(body)?;
+        Ok(IEX {
+            r: value_r(&values),
+            dfi: value_str(&values, "DFI"),
+            t: value_hex(&values, "T")?,
+            tg: value_hex(&values, "TG")?,
+            cd: value_str(&values, "CD"),
+        })
+    }
+}
+
 pub fn ie_fingerprint(ap: &AllPacketRR) -> Result<IEX> {
     let r1 = tcp_udp_icmp_r(&ap.ie.ie1.response)?;
     let r2 = tcp_udp_icmp_r(&ap.ie.ie2.response)?;
@@ -1574,32 +1922,253 @@ pub fn ie_fingerprint(ap: &AllPacketRR) -> Result<IEX> {
     Ok(IEX { r, dfi, t, tg, cd })
 }
 
-pub fn threads_os_probe(
+/// The SP and ISR of two SEQ results within this distance are taken as agreed,
+/// both are log scaled, so 8 is about a doubling of the rate.
+const SEQ_RATE_TOLERANCE: u32 = 8;
+
+fn seq_agree(a: &SEQX, b: &SEQX) -> bool {
+    a.r == b.r
+        && a.gcd == b.gcd
+        && a.ti == b.ti
+        && a.ci == b.ci
+        && a.ii == b.ii
+        && a.ss == b.ss
+        && a.ts == b.ts
+        && a.sp.abs_diff(b.sp) <= SEQ_RATE_TOLERANCE
+        && a.isr.abs_diff(b.isr) <= SEQ_RATE_TOLERANCE
+}
+
+/// Merge the SEQ results of several rounds, the largest group of agreed results wins
+/// and its SP and ISR are averaged. Returns None if there is no result,
+/// the bool is true if all the results agree.
+pub fn merge_seqx(seqxs: &[SEQX]) -> Option<(SEQX, bool)> {
+    let mut best: Vec<&SEQX> = Vec::new();
+    for a in seqxs {
+        let group: Vec<&SEQX> = seqxs.iter().filter(|b| seq_agree(a, b)).collect();
+        // the responsive results are preferred
+        let better = match best.first() {
+            Some(b) => match (a.r == "Y", b.r == "Y") {
+                (true, false) => true,
+                (false, true) => false,
+                _ => group.len() > best.len(),
+            },
+            None => true,
+        };
+        if better {
+            best = group;
+        }
+    }
+    match best.first() {
+        Some(first) => {
+            let mut seqx = (*first).clone();
+            let n = best.len() as u64;
+            seqx.sp = (best.iter().map(|s| s.sp as u64).sum::<u64>() / n) as u32;
+            seqx.isr = (best.iter().map(|s| s.isr as u64).sum::<u64>() / n) as u32;
+            Some((seqx, best.len() == seqxs.len()))
+        }
+        None => None,
+    }
+}
+
+/// Merge the IE results of several rounds, the most common result wins and the responsive ones are preferred.
+/// Returns None if there is no result, the bool is true if all the results agree.
+pub fn merge_iex(iexs: &[IEX]) -> Option<(IEX, bool)> {
+    let lines: Vec<String> = iexs.iter().map(|i| format!("{}", i)).collect();
+    let mut best: Option<(usize, usize)> = None; // (index, count)
+    for (i, line) in lines.iter().enumerate() {
+        let count = lines.iter().filter(|l| *l == line).count();
+        let better = match best {
+            Some((b, b_count)) => match (iexs[i].r == "Y", iexs[b].r == "Y") {
+                (true, false) => true,
+                (false, true) => false,
+                _ => count > b_count,
+            },
+            None => true,
+        };
+        if better {
+            best = Some((i, count));
+        }
+    }
+    best.map(|(i, count)| (iexs[i].clone(), count == iexs.len()))
+}
+
+/// Send all the probes once and make the fingerprint of this round.
+/// The SEQ test is returned alone, it is an error if the SEQ failed in this round,
+/// and so is the analysis of the TCP timestamps, see `TcpTsInfo`.
+fn os_probe_round(
     src_ipv4: Ipv4Addr,
     src_port: Option<u16>,
     dst_ipv4: Ipv4Addr,
-    dst_open_tcp_port: u16,
-    dst_closed_tcp_port: u16,
-    dst_closed_udp_port: u16,
-    nmap_os_db: Vec<NmapOsDb>,
-    top_k: usize,
+    ports: &OsDetectPorts,
+    options: &OsDetectOptions,
+    scan: &str,
     timeout: Duration,
-) -> Result<(PistolFingerprint, Vec<OsInfo>)> {
+) -> Result<(Result<SEQX>, Option<TcpTsInfo>, PistolFingerprint)> {
     debug!("send all probes now");
-    let ap = send_all_probes(
-        src_ipv4,
-        src_port,
-        dst_ipv4,
-        dst_open_tcp_port,
-        dst_closed_tcp_port,
-        dst_closed_udp_port,
-        timeout,
-    )?;
+    let start = SystemTime::now();
+    let ap = send_all_probes(src_ipv4, src_port, dst_ipv4, ports, options, timeout)?;
+
+    debug!("parse seqx");
+    let seqx = seq_fingerprint(&ap);
+    let ts_info = TcpTsInfo::new(&tcp_tsvals(&ap.seq)?, start);
+    debug!("parse opsx");
+    let opsx = ops_fingerprint(&ap)?;
+    debug!("parse winx");
+    let winx = win_fingerprint(&ap)?;
+    debug!("parse ecnx");
+    let ecnx = ecn_fingerprint(&ap)?;
+    debug!("parse t1x-t7x");
+    let (t1x, t2x, t3x, t4x, t5x, t6x, t7x) = tx_fingerprint(&ap)?;
+    debug!("parse u1x");
+    let u1x = u1_fingerprint(&ap)?;
+    debug!("parse iex");
+    let iex = ie_fingerprint(&ap)?;
+
+    let fingerprint = PistolFingerprint {
+        scan: scan.to_string(),
+        // replaced by the merged SEQ of all rounds
+        seqx: SEQX::parser("R=N")?,
+        opsx,
+        winx,
+        ecnx,
+        t1x,
+        t2x,
+        t3x,
+        t4x,
+        t5x,
+        t6x,
+        t7x,
+        u1x,
+        iex,
+        skipped_tests: skipped_tests(ports),
+        tries: 1,
+        inconsistent_tests: Vec::new(),
+    };
+    Ok((seqx, ts_info, fingerprint))
+}
+
+/// The tests skipped without the closed ports.
+fn skipped_tests(ports: &OsDetectPorts) -> Vec<String> {
+    let mut skipped_tests = Vec::new();
+    if ports.closed_tcp_port.is_none() {
+        skipped_tests.push(String::from("T5"));
+        skipped_tests.push(String::from("T6"));
+        skipped_tests.push(String::from("T7"));
+    }
+    if ports.closed_udp_port.is_none() {
+        skipped_tests.push(String::from("U1"));
+    }
+    skipped_tests
+}
+
+/// Returns the string of one test in the fingerprint.
+type TestStr = fn(&PistolFingerprint) -> String;
+
+/// Merge the SEQ and IE of all rounds into each round, match them and keep the best one.
+fn best_os_probe_round(
+    seqxs: &[SEQX],
+    rounds: &[PistolFingerprint],
+    nmap_os_db: &[NmapOsDb],
+    match_points: &MatchPoints,
+    top_k: usize,
+) -> Result<(PistolFingerprint, Vec<OsInfo>)> {
+    let (seqx, seq_agreed) = match merge_seqx(seqxs) {
+        Some(s) => s,
+        None => return Err(OsDetectResultsNullError::new().into()),
+    };
+    let iexs: Vec<IEX> = rounds.iter().map(|r| r.iex.clone()).collect();
+    let (iex, ie_agreed) = match merge_iex(&iexs) {
+        Some(i) => i,
+        None => return Err(OsDetectResultsNullError::new().into()),
+    };
+
+    let mut inconsistent_tests = Vec::new();
+    // the failed SEQ rounds also disagree
+    if !seq_agreed || seqxs.len() != rounds.len() {
+        inconsistent_tests.push(String::from("SEQ"));
+    }
+    let tests: Vec<(&str, TestStr)> = vec![
+        ("OPS", |f| format!("{}", f.opsx)),
+        ("WIN", |f| format!("{}", f.winx)),
+        ("ECN", |f| format!("{}", f.ecnx)),
+        ("T1", |f| format!("{}", f.t1x)),
+        ("T2", |f| format!("{}", f.t2x)),
+        ("T3", |f| format!("{}", f.t3x)),
+        ("T4", |f| format!("{}", f.t4x)),
+        ("T5", |f| format!("{}", f.t5x)),
+        ("T6", |f| format!("{}", f.t6x)),
+        ("T7", |f| format!("{}", f.t7x)),
+        ("U1", |f| format!("{}", f.u1x)),
+    ];
+    for (name, test_str) in tests {
+        let first = test_str(&rounds[0]);
+        if rounds.iter().any(|r| test_str(r) != first) {
+            inconsistent_tests.push(name.to_string());
+        }
+    }
+    if !ie_agreed {
+        inconsistent_tests.push(String::from("IE"));
+    }
+
+    let mut best: Option<(PistolFingerprint, Vec<OsInfo>)> = None;
+    for round in rounds {
+        let mut fingerprint = round.clone();
+        fingerprint.seqx = seqx.clone();
+        fingerprint.iex = iex.clone();
+        fingerprint.tries = rounds.len();
+        fingerprint.inconsistent_tests = inconsistent_tests.clone();
+
+        let mut os_infos = Vec::new();
+        for db in nmap_os_db {
+            let (score, total) = db.check(&fingerprint, match_points);
+            os_infos.push(OsInfo::new(db, score, total));
+        }
+        if os_infos.is_empty() {
+            return Err(OsDetectResultsNullError::new().into());
+        }
+        let detect_rets = rank_os_infos(os_infos, top_k);
+        let accuracy = |rets: &[OsInfo]| -> f64 {
+            match rets.first() {
+                Some(r) => r.accuracy,
+                None => 0.0,
+            }
+        };
+        let better = match &best {
+            Some((_, b)) => accuracy(&detect_rets) > accuracy(b),
+            None => true,
+        };
+        if better {
+            best = Some((fingerprint, detect_rets));
+        }
+    }
+    match best {
+        Some(b) => Ok(b),
+        None => Err(OsDetectResultsNullError::new().into()),
+    }
+}
 
+/// Send the probes for up to `options.max_tries` rounds until there is a perfect match, like nmap.
+/// The probes use the `src_port` and `options.ttl` if given, see `send_all_probes`.
+/// The SEQ and IE results of the rounds are merged, and the best matched round is returned
+/// with the analysis of the TCP timestamps and ISNs.
+pub fn threads_os_probe(
+    src_ipv4: Ipv4Addr,
+    src_port: Option<u16>,
+    dst_ipv4: Ipv4Addr,
+    ports: &OsDetectPorts,
+    top_k: usize,
+    options: &OsDetectOptions,
+    timeout: Duration,
+) -> Result<(PistolFingerprint, SeqInfo, Vec<OsInfo>)> {
+    let db = options.get_db()?;
+    let max_tries = options.max_tries;
+    let dst_open_tcp_port = ports.open_tcp_port;
+    let dst_closed_tcp_port = ports.closed_tcp_port;
+    let dst_closed_udp_port = ports.closed_udp_port;
+    let good_results = ports.confirmed;
     // let hops = Some(1);
     let (dst_mac, _interface) = layer3_ipv4_system_route(src_ipv4, dst_ipv4)?;
 
-    let good_results = true;
     // form get_scan_line function
     let need_cal_hops = |dst_addr: IpAddr| -> bool {
         if dst_addr.is_loopback() {
@@ -1638,81 +2207,106 @@ pub fn threads_os_probe(
         }
     };
 
-    // Use seq to judge target is alive or not.
-    debug!("parse seqx");
-    let seqx = seq_fingerprint(&ap);
-    match seqx {
-        Ok(seqx) => {
-            debug!("parse opsx");
-            let opsx = ops_fingerprint(&ap)?;
-            debug!("parse winx");
-            let winx = win_fingerprint(&ap)?;
-            debug!("parse ecnx");
-            let ecnx = ecn_fingerprint(&ap)?;
-            debug!("parse t1x-t7x");
-            let (t1x, t2x, t3x, t4x, t5x, t6x, t7x) = tx_fingerprint(&ap)?;
-            debug!("parse u1x");
-            let u1x = u1_fingerprint(&ap)?;
-            debug!("parse iex");
-            let iex = ie_fingerprint(&ap)?;
-
-            debug!("generate the fingerprint");
-            let fingerprint = PistolFingerprint {
-                scan,
-                seqx,
-                opsx,
-                winx,
-                ecnx,
-                t1x,
-                t2x,
-                t3x,
-                t4x,
-                t5x,
-                t6x,
-                t7x,
-                u1x,
-                iex,
+    let mut seqxs = Vec::new();
+    let mut ts_infos = Vec::new();
+    let mut rounds = Vec::new();
+    let mut best = None;
+    let mut last_error = None;
+    for i in 0..max_tries.max(1) {
+        debug!("os probe round {}", i + 1);
+        let (seqx, ts_info, fingerprint) =
+            match os_probe_round(src_ipv4, src_port, dst_ipv4, ports, options, &scan, timeout) {
+                Ok(r) => r,
+                Err(e) => {
+                    debug!("os probe round {} failed: {}", i + 1, e);
+                    last_error = Some(e);
+                    continue;
+                }
             };
-
-            let mut bm = BTreeMap::new();
-            for db in &nmap_os_db {
-                let (score, total) = db.check(&fingerprint);
-                let os_info = OsInfo {
-                    info: db.info.clone(),
-                    class: db.class.clone(),
-                    score,
-                    total,
-                    db: db.clone(),
-                    cpe: db.cpe.clone(),
-                };
-                bm.insert(score, os_info);
+        // Use seq to judge target is alive or not.
+        match seqx {
+            Ok(seqx) => seqxs.push(seqx),
+            Err(e) => {
+                debug!("os probe round {} seq failed: {}", i + 1, e);
+                last_error = Some(e);
             }
+        }
+        if let Some(ts_info) = ts_info {
+            ts_infos.push(ts_info);
+        }
+        rounds.push(fingerprint);
+        if seqxs.is_empty() {
+            continue;
+        }
 
-            let mut detect_rets = Vec::new();
-            let bm_keys: Vec<&usize> = bm.keys().rev().collect();
-            if bm_keys.len() > 0 {
-                let mut last_score = *bm_keys[0];
-                let mut top_k_count = 0;
-                for (score, os_info) in bm.into_iter().rev() {
-                    debug!("score: {}, cpe: {}", score, os_info.db.class);
-                    debug!("last_score: {}, score: {}", last_score, score);
-                    if last_score != score {
-                        top_k_count += 1;
-                        last_score = score;
-                    }
-                    if top_k_count == top_k {
-                        break;
-                    }
-                    detect_rets.push(os_info);
-                }
+        let (fingerprint, detect_rets) =
+            best_os_probe_round(&seqxs, &rounds, &db.nmap_os_db, &db.match_points, top_k)?;
+        let perfect_match = match detect_rets.first() {
+            Some(r) => r.total > 0 && r.score == r.total,
+            None => false,
+        };
+        best = Some((fingerprint, detect_rets));
+        if perfect_match {
+            break;
+        }
+    }
 
-                debug!("ret len: {}", detect_rets.len());
-                debug!("fingerprint:\n{}", fingerprint.nmap_format());
-                Ok((fingerprint, detect_rets))
-            } else {
-                Err(OsDetectResultsNullError::new().into())
+    match best {
+        Some((fingerprint, detect_rets)) => {
+            for os_info in &detect_rets {
+                debug!(
+                    "rank: {}, accuracy: {:.1}%, class: {}",
+                    os_info.rank, os_info.accuracy, os_info.class
+                );
             }
+            debug!("ret len: {}", detect_rets.len());
+            debug!("tries: {}", fingerprint.tries);
+            debug!("fingerprint:\n{}", fingerprint.nmap_format());
+            // the latest round with an uptime is the most accurate
+            let ts = match ts_infos.iter().rev().find(|t| t.uptime.is_some()) {
+                Some(t) => Some(t.clone()),
+                None => ts_infos.pop(),
+            };
+            let seq_info = SeqInfo {
+                ts,
+                isn: IsnInfo::new(&fingerprint.seqx),
+            };
+            debug!("seq info:\n{}", seq_info);
+            Ok((fingerprint, seq_info, detect_rets))
+        }
+        None => match last_error {
+            Some(e) => Err(e),
+            None => Err(OsDetectResultsNullError::new().into()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ipv4::Ipv4Packet;
+    use pnet::packet::tcp::TcpPacket;
+    use pnet::packet::Packet;
+    #[test]
+    fn test_seq_src_ports() -> Result<()> {
+        assert_eq!(seq_src_ports(Some(53), false), vec![53; 6]);
+        assert_eq!(seq_src_ports(Some(53), true), vec![53, 54, 55, 56, 57, 58]);
+        // no wrap around to port 0
+        assert_eq!(seq_src_ports(Some(65534), true), vec![65534; 6]);
+        assert_eq!(seq_src_ports(None, false).len(), 6);
+
+        // every SEQ probe leaves from the given port
+        let src_ipv4 = Ipv4Addr::new(192, 168, 1, 2);
+        let dst_ipv4 = Ipv4Addr::new(192, 168, 1, 3);
+        let src_ports = seq_src_ports(Some(53), false);
+        let buffs = seq_packets(src_ipv4, &src_ports, dst_ipv4, 22, packet::TTL)?;
+        assert_eq!(buffs.len(), 6);
+        for buff in buffs {
+            let ipv4_packet = Ipv4Packet::new(&buff).unwrap();
+            let tcp_packet = TcpPacket::new(ipv4_packet.payload()).unwrap();
+            assert_eq!(tcp_packet.get_source(), 53);
+            assert_eq!(tcp_packet.get_destination(), 22);
         }
-        Err(e) => Err(e),
+        Ok(())
     }
 }
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use log::debug;
+use pnet::packet::ipv6::Ipv6Packet;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt;
@@ -12,6 +13,8 @@ use std::time::Duration;
 use std::time::Instant;
 use std::time::SystemTime;
 
+use crate::errors::FingerprintParseFailed;
+use crate::errors::OsDetectResultsNullError;
 use crate::hop::ipv6_get_hops;
 use crate::layers::layer3_ipv6_send;
 use crate::layers::layer3_ipv6_system_route;
@@ -24,8 +27,12 @@ use crate::utils::random_port;
 use crate::utils::random_port_multi;
 use crate::IpCheckMethods;
 
+use super::cpe::CpeName;
+use super::cpe::OsClass;
 use super::operator6::apply_scale;
 use super::operator6::vectorize;
+use super::osscan::fingerprint_test_values;
+use super::osscan::fingerprint_tests;
 use super::osscan::get_scan_line;
 use super::packet6;
 use super::rr::AllPacketRR6;
@@ -37,6 +44,7 @@ use super::rr::TECNRR6;
 use super::rr::TXRR6;
 use super::rr::U1RR6;
 use super::Linear;
+use super::OsDetectStatus6;
 use super::OsInfo6;
 
 // EXAMPLE
@@ -59,71 +67,44 @@ use super::OsInfo6;
 // EXTRA(FL=12345)
 
 fn p_reduce(p: &str) -> String {
+    // Compress the repeated bytes (two chars), like 00000000 to 00{4}.
+    let p_chars: Vec<char> = p.chars().collect();
+    let units: Vec<String> = p_chars.chunks(2).map(|c| c.iter().collect()).collect();
     let mut new_p = String::new();
-    let mut count = 0;
 
-    let p_chars: Vec<char> = p.chars().collect();
-    // println!("1 {}", p);
     let mut i = 0;
-
-    loop {
-        // 000001
-        if i + 1 > p_chars.len() {
-            break;
-        }
-
-        let ch_i = p_chars[i];
-        let ch_i_next = if i + 1 >= p_chars.len() {
-            0 as char
-        } else {
-            p_chars[i + 1]
-        };
-
-        let ch_i_p_2 = if i + 2 >= p_chars.len() {
-            0 as char
-        } else {
-            p_chars[i + 2]
-        };
-        let ch_i_next_p_2 = if i + 3 >= p_chars.len() {
-            0 as char
-        } else {
-            p_chars[i + 3]
-        };
-
-        if ch_i == ch_i_p_2 && ch_i_next == ch_i_next_p_2 {
+    while i < units.len() {
+        let mut count = 1;
+        while i + count < units.len() && units[i + count] == units[i] {
             count += 1;
-            i += 2;
+        }
+        // nmap keeps the short runs such as 0101
+        if count > 2 && units[i].len() == 2 {
+            new_p += &format!("{}{{{}}}", units[i], count);
         } else {
-            if count != 0 {
-                i += 2;
-            } else {
-                i += 1;
+            for _ in 0..count {
+                new_p += &units[i];
             }
-            if count == 0 {
-                new_p += &format!("{}", ch_i);
-            } else {
-                new_p += &format!("{}{}{{{}}}", ch_i, ch_i_next, count + 1);
-            }
-            count = 0;
         }
+        i += count;
     }
 
-    // println!("2 {}", new_p.trim());
     new_p.trim().to_string()
 }
 
 fn p_as_nmap_format(input: &[u8]) -> String {
     let mut p = String::new();
-    let ip_start = 16;
-    let ip_end = ip_start + 32 * 2;
+    // The source and destination addresses in the IPv6 header.
+    let ip_start = 8;
+    let ip_end = ip_start + 32;
 
     for (i, b) in input.iter().enumerate() {
         if i >= ip_start && i < ip_end {
             // The characters XX are put in place of source and destination addresses,
             // which are private and anyway not useful for training the classifier.
-            p += "X";
+            p += "XX";
         } else {
-            p += &format!("{:x}", b);
+            p += &format!("{:02x}", b);
         }
     }
 
@@ -131,6 +112,80 @@ fn p_as_nmap_format(input: &[u8]) -> String {
     new_p
 }
 
+/// Restore the packet from the `P` value, the addresses replaced by `XX` are restored as zero.
+fn p_from_nmap_format(p: &str) -> Result<Vec<u8>> {
+    // expand the repeat `00{4}` first
+    let mut hex: Vec<char> = Vec::new();
+    let mut chars = p.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => {
+                let mut num = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => num.push(c),
+                        None => return Err(FingerprintParseFailed::new(p.to_string()).into()),
+                    }
+                }
+                let count: usize = match num.parse() {
+                    Ok(c) => c,
+                    Err(_) => return Err(FingerprintParseFailed::new(p.to_string()).into()),
+                };
+                if hex.len() < 2 || count == 0 {
+                    return Err(FingerprintParseFailed::new(p.to_string()).into());
+                }
+                let unit = [hex[hex.len() - 2], hex[hex.len() - 1]];
+                for _ in 1..count {
+                    hex.extend_from_slice(&unit);
+                }
+            }
+            c if !c.is_whitespace() => hex.push(c),
+            _ => (),
+        }
+    }
+
+    if !hex.len().is_multiple_of(2) {
+        return Err(FingerprintParseFailed::new(p.to_string()).into());
+    }
+    let mut packet = Vec::new();
+    for b in hex.chunks(2) {
+        let b: String = b.iter().collect();
+        if b == "XX" {
+            packet.push(0);
+        } else {
+            match u8::from_str_radix(&b, 16) {
+                Ok(b) => packet.push(b),
+                Err(_) => return Err(FingerprintParseFailed::new(p.to_string()).into()),
+            }
+        }
+    }
+    Ok(packet)
+}
+
+/// Parse the `P`, `ST` and `RT` values of the test, the request is not in the fingerprint and left empty.
+fn rr_from_test(body: &str) -> Result<(RequestAndResponse, Duration, Duration)> {
+    let values = fingerprint_test_values(body)?;
+    let response = match values.get("P") {
+        Some(p) => p_from_nmap_format(p)?,
+        None => Vec::new(),
+    };
+    let secs = |key: &str| -> Result<Duration> {
+        match values.get(key) {
+            Some(v) => match v.parse::<f64>() {
+                Ok(v) if v >= 0.0 => Ok(Duration::from_secs_f64(v)),
+                _ => Err(FingerprintParseFailed::new(format!("{}={}", key, v)).into()),
+            },
+            None => Ok(Duration::new(0, 0)),
+        }
+    };
+    let rr = RequestAndResponse {
+        request: Vec::new(),
+        response,
+    };
+    Ok((rr, secs("ST")?, secs("RT")?))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SEQX6 {
     pub name: String,
@@ -307,6 +362,8 @@ pub struct PistolFingerprint6 {
     pub t7x: TX6,
     pub extra: String,
     pub novelty: f64,
+    /// The relative score margin between the top two OS classes, `(top1 - top2) / top1`.
+    pub margin: f64,
     pub status: bool,
 }
 
@@ -327,6 +384,173 @@ impl PistolFingerprint6 {
         }
         ret.trim().to_string()
     }
+    /// The time the SEQ probes take, the probes without response have no time in the text.
+    fn seq_elapsed(&self) -> f64 {
+        let seqx = [
+            &self.s1x, &self.s2x, &self.s3x, &self.s4x, &self.s5x, &self.s6x,
+        ];
+        let mut rt = Duration::new(0, 0);
+        for x in seqx {
+            rt = rt.max(x.rt);
+        }
+        let elapsed = rt.saturating_sub(self.s1x.st).as_secs_f64();
+        if elapsed > 0.0 {
+            elapsed
+        } else {
+            // the probes are sent 100 ms apart
+            0.6
+        }
+    }
+    /// Rebuild the probes and responses of the fingerprint, the requests are not kept.
+    pub fn all_packet_rr(&self) -> AllPacketRR6 {
+        AllPacketRR6 {
+            seq: SEQRR6 {
+                seq1: self.s1x.rr.clone(),
+                seq2: self.s2x.rr.clone(),
+                seq3: self.s3x.rr.clone(),
+                seq4: self.s4x.rr.clone(),
+                seq5: self.s5x.rr.clone(),
+                seq6: self.s6x.rr.clone(),
+                elapsed: self.seq_elapsed(),
+                st1: self.s1x.st,
+                rt1: self.s1x.rt,
+                st2: self.s2x.st,
+                rt2: self.s2x.rt,
+                st3: self.s3x.st,
+                rt3: self.s3x.rt,
+                st4: self.s4x.st,
+                rt4: self.s4x.rt,
+                st5: self.s5x.st,
+                rt5: self.s5x.rt,
+                st6: self.s6x.st,
+                rt6: self.s6x.rt,
+            },
+            ie: IERR6 {
+                ie1: self.ie1x.rr.clone(),
+                ie2: self.ie2x.rr.clone(),
+                st1: self.ie1x.st,
+                rt1: self.ie1x.rt,
+                st2: self.ie2x.st,
+                rt2: self.ie2x.rt,
+            },
+            nx: NXRR6 {
+                ni: self.ni.rr.clone(),
+                ns: self.ns.rr.clone(),
+                sti: self.ni.st,
+                rti: self.ni.rt,
+                sts: self.ns.st,
+                rts: self.ns.rt,
+            },
+            u1: U1RR6 {
+                u1: self.u1x.rr.clone(),
+                st: self.u1x.st,
+                rt: self.u1x.rt,
+            },
+            tecn: TECNRR6 {
+                tecn: self.tecnx.rr.clone(),
+                st: self.tecnx.st,
+                rt: self.tecnx.rt,
+            },
+            tx: TXRR6 {
+                t2: self.t2x.rr.clone(),
+                t3: self.t3x.rr.clone(),
+                t4: self.t4x.rr.clone(),
+                t5: self.t5x.rr.clone(),
+                t6: self.t6x.rr.clone(),
+                t7: self.t7x.rr.clone(),
+                st2: self.t2x.st,
+                rt2: self.t2x.rt,
+                st3: self.t3x.st,
+                rt3: self.t3x.rt,
+                st4: self.t4x.st,
+                rt4: self.t4x.rt,
+                st5: self.t5x.st,
+                rt5: self.t5x.rt,
+                st6: self.t6x.st,
+                rt6: self.t6x.rt,
+                st7: self.t7x.st,
+                rt7: self.t7x.rt,
+            },
+        }
+    }
+    /// The feature vector of the fingerprint, including the NI, ST/RT and EXTRA features, see `operator6::vectorize`.
+    pub fn features(&self) -> Result<Vec<f64>> {
+        vectorize(&self.all_packet_rr())
+    }
+    /// Parse the fingerprint text from `nmap_format` or the nmap output, see `PistolFingerprint::parser`.
+    /// The tests not in the text have no response, the `novelty`, `margin` and `status` are not part of the text.
+    pub fn parser(fingerprint: &str) -> Result<PistolFingerprint6> {
+        let tests = fingerprint_tests(fingerprint)?;
+        let get_test = |name: &str| -> Result<(RequestAndResponse, Duration, Duration)> {
+            for (n, body) in &tests {
+                if n == name {
+                    return rr_from_test(body);
+                }
+            }
+            rr_from_test("")
+        };
+        let seqx6 = |name: &str| -> Result<SEQX6> {
+            let (rr, st, rt) = get_test(name)?;
+            let name = name.to_string();
+            Ok(SEQX6 { name, rr, st, rt })
+        };
+        let iex6 = |name: &str| -> Result<IEX6> {
+            let (rr, st, rt) = get_test(name)?;
+            let name = name.to_string();
+            Ok(IEX6 { name, rr, st, rt })
+        };
+        let nx6 = |name: &str| -> Result<NX6> {
+            let (rr, st, rt) = get_test(name)?;
+            let name = name.to_string();
+            Ok(NX6 { name, rr, st, rt })
+        };
+        let tx6 = |name: &str| -> Result<TX6> {
+            let (rr, st, rt) = get_test(name)?;
+            let name = name.to_string();
+            Ok(TX6 { name, rr, st, rt })
+        };
+
+        let scan = match tests.iter().find(|(n, _)| n == "SCAN") {
+            Some((_, body)) => format!("SCAN({})", body),
+            None => String::new(),
+        };
+        let extra = match tests.iter().find(|(n, _)| n == "EXTRA") {
+            Some((_, body)) => match fingerprint_test_values(body)?.get("FL") {
+                Some(fl) => fl.clone(),
+                None => String::new(),
+            },
+            None => String::new(),
+        };
+        let (rr, st, rt) = get_test("U1")?;
+        let u1x = U1X6 { rr, st, rt };
+        let (rr, st, rt) = get_test("TECN")?;
+        let tecnx = TECNX6 { rr, st, rt };
+        Ok(PistolFingerprint6 {
+            scan,
+            s1x: seqx6("S1")?,
+            s2x: seqx6("S2")?,
+            s3x: seqx6("S3")?,
+            s4x: seqx6("S4")?,
+            s5x: seqx6("S5")?,
+            s6x: seqx6("S6")?,
+            ie1x: iex6("IE1")?,
+            ie2x: iex6("IE2")?,
+            ni: nx6("NI")?,
+            ns: nx6("NS")?,
+            u1x,
+            tecnx,
+            t2x: tx6("T2")?,
+            t3x: tx6("T3")?,
+            t4x: tx6("T4")?,
+            t5x: tx6("T5")?,
+            t6x: tx6("T6")?,
+            t7x: tx6("T7")?,
+            extra,
+            novelty: 0.0,
+            margin: 0.0,
+            status: false,
+        })
+    }
 }
 
 impl fmt::Display for PistolFingerprint6 {
@@ -338,6 +562,10 @@ impl fmt::Display for PistolFingerprint6 {
         let s4x_str = format!("\n{}", self.s4x);
         let s5x_str = format!("\n{}", self.s5x);
         let s6x_str = format!("\n{}", self.s6x);
+        let ie1x_str = format!("\n{}", self.ie1x);
+        let ie2x_str = format!("\n{}", self.ie2x);
+        let ns_str = format!("\n{}", self.ns);
+        let ni_str = format!("\n{}", self.ni);
         let u1x_str = format!("\n{}", self.u1x);
         let tecnx_str = format!("\n{}", self.tecnx);
         let t2x_str = format!("\n{}", self.t2x);
@@ -365,6 +593,18 @@ impl fmt::Display for PistolFingerprint6 {
         if s6x_str.trim().len() > 0 {
             output += &s6x_str;
         }
+        if !ie1x_str.trim().is_empty() {
+            output += &ie1x_str;
+        }
+        if !ie2x_str.trim().is_empty() {
+            output += &ie2x_str;
+        }
+        if !ns_str.trim().is_empty() {
+            output += &ns_str;
+        }
+        if !ni_str.trim().is_empty() {
+            output += &ni_str;
+        }
         if u1x_str.trim().len() > 0 {
             output += &u1x_str;
         }
@@ -1026,7 +1266,8 @@ fn send_all_probes(
     Ok(ap)
 }
 
-fn predict_value(features: &[f64], wvec: &[Vec<f64>]) -> Vec<f64> {
+/// The probability of every OS class, the `features` are scaled by `apply_scale`.
+pub fn predict_value(features: &[f64], wvec: &[Vec<f64>]) -> Vec<f64> {
     /*
        features [695]
        wvec [92, 695]
@@ -1041,22 +1282,24 @@ fn predict_value(features: &[f64], wvec: &[Vec<f64>]) -> Vec<f64> {
         sum
     };
 
-    let mut dec_value = [0f64; 92];
-    for (idx, w) in wvec.iter().enumerate() {
-        dec_value[idx] = vec_time(features, w);
+    let mut dec_value = Vec::new();
+    for w in wvec {
+        dec_value.push(vec_time(features, w));
     }
 
-    let dec_value = dec_value.map(|x| 1.0 / (1.0 + (-x as f64).exp()));
-    dec_value.to_vec()
+    dec_value
+        .into_iter()
+        .map(|x| 1.0 / (1.0 + (-x).exp()))
+        .collect()
 }
 
-fn novelty_of(features: &[f64], mean: &[f64], variance: &[f64]) -> f64 {
-    assert_eq!(features.len(), 695);
-    assert_eq!(mean.len(), 695);
-    assert_eq!(variance.len(), 695);
+/// The distance between the scaled `features` and the `mean` of the OS class, weighted by the `variance`.
+pub fn novelty_of(features: &[f64], mean: &[f64], variance: &[f64]) -> f64 {
+    assert_eq!(features.len(), mean.len());
+    assert_eq!(features.len(), variance.len());
 
     let mut sum = 0.0;
-    for i in 0..695 {
+    for i in 0..features.len() {
         let d = features[i] - mean[i];
         // print!("{:.3}, ", d);
         let mut v = variance[i];
@@ -1105,8 +1348,9 @@ pub fn threads_os_probe6(
     dst_open_tcp_port: u16,
     dst_closed_tcp_port: u16,
     dst_closed_udp_port: u16,
+    good_results: bool,
     top_k: usize,
-    linear: Linear,
+    linear: &Linear,
     timeout: Duration,
 ) -> Result<(PistolFingerprint6, Vec<OsInfo6>)> {
     debug!("send all probes now");
@@ -1122,7 +1366,6 @@ pub fn threads_os_probe6(
 
     let (dst_mac, _interface) = layer3_ipv6_system_route(src_ipv6, dst_ipv6)?;
 
-    let good_results = true;
     // form get_scan_line function
     let need_cal_hops = |dst_addr: IpAddr| -> bool {
         if dst_addr.is_loopback() {
@@ -1140,8 +1383,8 @@ pub fn threads_os_probe6(
             get_scan_line(
                 Some(dst_mac),
                 dst_open_tcp_port,
-                dst_closed_tcp_port,
-                dst_closed_udp_port,
+                Some(dst_closed_tcp_port),
+                Some(dst_closed_udp_port),
                 dst_ipv6.into(),
                 hops,
                 good_results,
@@ -1152,8 +1395,8 @@ pub fn threads_os_probe6(
             get_scan_line(
                 Some(dst_mac),
                 dst_open_tcp_port,
-                dst_closed_tcp_port,
-                dst_closed_udp_port,
+                Some(dst_closed_tcp_port),
+                Some(dst_closed_udp_port),
                 dst_ipv6.into(),
                 hops,
                 good_results,
@@ -1162,13 +1405,29 @@ pub fn threads_os_probe6(
     };
 
     let features = vectorize(&ap)?;
-    let features = apply_scale(&features, &linear.scale);
+    // The nmap model only knows the first NMAP_FEATURES_LEN features, the NI, timing and flow label ones are not used,
+    // a model trained by `train6::train_linear` may use all of them.
+    let features = apply_scale(&features[..linear.features_len()], &linear.scale);
     let predict = predict_value(&features, &linear.w);
 
     let mut detect_rets = Vec::new();
     for (i, (info, score)) in zip(&linear.infolist, &predict).into_iter().enumerate() {
         let class = &linear.cpe[i].osclass;
         if class.len() > 0 {
+            let mut classes = Vec::new();
+            for c in class {
+                match OsClass::from_fields(c) {
+                    Ok(c) => classes.push(c),
+                    Err(e) => debug!("{}", e),
+                }
+            }
+            let mut cpes = Vec::new();
+            for c in &linear.cpe[i].cpe {
+                match CpeName::parser(c) {
+                    Ok(c) => cpes.push(c),
+                    Err(e) => debug!("{}", e),
+                }
+            }
             let class = class[0].join(" | ");
             let cpe = linear.cpe[i].cpe.join(" ");
             // debug!("linear cpe class: {}", class);
@@ -1177,6 +1436,8 @@ pub fn threads_os_probe6(
                 info: info.to_string(),
                 class,
                 cpe,
+                classes,
+                cpes,
                 score: *score,
                 label: i,
             };
@@ -1185,27 +1446,24 @@ pub fn threads_os_probe6(
     }
 
     let detect_rets_sort = isort(&detect_rets);
-    let mut perfect_match = 1;
-    for i in 1..36 {
-        if detect_rets_sort[i].score >= 0.9 * detect_rets_sort[0].score {
-            perfect_match += 1;
-        }
+    if detect_rets_sort.is_empty() {
+        return Err(OsDetectResultsNullError::new().into());
     }
+    let margin = if detect_rets_sort.len() > 1 && detect_rets_sort[0].score > 0.0 {
+        (detect_rets_sort[0].score - detect_rets_sort[1].score) / detect_rets_sort[0].score
+    } else {
+        1.0
+    };
 
-    // println!("{}", perfect_match);
     let label = detect_rets_sort[0].label;
     let novelty = novelty_of(&features, &linear.mean[label], &linear.variance[label]);
+    let match_status = OsDetectStatus6::new(novelty, margin) == OsDetectStatus6::Matched;
+    debug!("novelty: {:.2}, margin: {:.3}", novelty, margin);
 
-    let match_status = if perfect_match == 1 {
-        const FP_NOVELTY_THRESHOLD: f64 = 15.0;
-        // println!("{}", novelty);
-        if novelty < FP_NOVELTY_THRESHOLD {
-            true
-        } else {
-            false
-        }
-    } else {
-        false
+    // The IPv6 flow label of the probes, 0 on the platforms not allowing to set it.
+    let extra = match Ipv6Packet::new(&ap.seq.seq1.request) {
+        Some(ipv6_packet) => format!("{:x}", ipv6_packet.get_flow_label()),
+        None => String::from("12345"),
     };
 
     let s1x = SEQX6 {
@@ -1335,13 +1593,14 @@ pub fn threads_os_probe6(
         t5x,
         t6x,
         t7x,
-        extra: String::from("12345"), // IPv6 flow label
+        extra,
         novelty,
+        margin,
         status: match_status,
     };
 
     let ret = if match_status {
-        let ret = detect_rets_sort[0..top_k].to_vec();
+        let ret = detect_rets_sort[0..top_k.min(detect_rets_sort.len())].to_vec();
         ret
     } else {
         let ret = vec![];
@@ -1354,6 +1613,8 @@ pub fn threads_os_probe6(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::os::operator6::FEATURES_LEN;
+    use crate::os::operator6::NMAP_FEATURES_LEN;
     #[test]
     fn test_something() {
         let p = "000XXXXXX";
@@ -1368,4 +1629,49 @@ mod tests {
         let ret = p_reduce(p);
         println!("{}", ret);
     }
+    #[test]
+    fn test_fingerprint6_parser() -> Result<()> {
+        let mut packet = vec![0x60, 0x00, 0x00, 0x00, 0x00, 0x14, 0x06, 0x3c];
+        packet.extend_from_slice(&[0xfe; 32]);
+        packet.extend_from_slice(&[0x00, 0x16, 0xc1, 0xb9, 0x00, 0x00, 0x00, 0x00, 0x05]);
+        let p = p_as_nmap_format(&packet);
+        assert_eq!(p, "6000{4}14063cXX{32}0016c1b900{4}05");
+        // the addresses are not in the fingerprint
+        let mut expect = packet.clone();
+        expect[8..40].copy_from_slice(&[0; 32]);
+        assert_eq!(p_from_nmap_format(&p)?, expect);
+
+        let text = format!(
+            "OS:SCAN(V=PISTOL%E=6)S1(P={}%ST=0.021271%RT=0.041661)T4(R=N)EXTRA(FL=12345)",
+            p
+        );
+        let ret = PistolFingerprint6::parser(&text)?;
+        assert_eq!(ret.s1x.rr.response, expect);
+        assert_eq!(ret.s1x.st, Duration::from_secs_f64(0.021271));
+        assert_eq!(ret.s2x.rr.response.len(), 0);
+        assert_eq!(ret.t4x.rr.response.len(), 0);
+        assert_eq!(ret.extra, "12345");
+
+        assert!(p_from_nmap_format("60{4").is_err());
+        assert!(p_from_nmap_format("600").is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_fingerprint6_features() -> Result<()> {
+        let text = "OS:SCAN(V=PISTOL%E=6)S1(P=6000{4}28063cXX{32}0016c1b002bbd213c57562f5a01212e0f8880000020404c40402080a5be177f2ff{4}01030307%ST=0.021271%RT=0.041661)T4(P=6000{4}14063cXX{32}0016c1b93a67fc8a00{4}500400000c7c0000%ST=0.912394%RT=0.93247)EXTRA(FL=12345)";
+        let ret = PistolFingerprint6::parser(text)?;
+        let features = ret.features()?;
+        assert_eq!(features.len(), FEATURES_LEN);
+        // no NI response
+        assert_eq!(
+            features[NMAP_FEATURES_LEN..NMAP_FEATURES_LEN + 5],
+            [-1.0; 5]
+        );
+        // the round trip time of S1, S2 has no response
+        assert!((features[NMAP_FEATURES_LEN + 5] - 0.02039).abs() < 1e-6);
+        assert_eq!(features[NMAP_FEATURES_LEN + 6], -1.0);
+        // the flow labels of the responses are 0
+        assert_eq!(features[FEATURES_LEN - 1], 0.0);
+        Ok(())
+    }
 }
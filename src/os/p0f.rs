@@ -0,0 +1,831 @@
+use anyhow::Result;
+use log::debug;
+use once_cell::sync::OnceCell;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Flags;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::TcpFlags;
+use pnet::packet::tcp::TcpPacket;
+use pnet::packet::Packet;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::errors::P0fParseFailed;
+use crate::errors::PcapLinkTypeUnsupported;
+use crate::errors::PcapParseFailed;
+
+static EMBEDDED_P0F_DB: OnceCell<Arc<P0fDb>> = OnceCell::new();
+
+/// The longest distance in hops between the initial TTL of a signature and the observed TTL.
+const P0F_MAX_DIST: u8 = 35;
+/// The initial TTLs used to guess the distance of the packets without a matched signature.
+const COMMON_TTLS: [u8; 4] = [32, 64, 128, 255];
+/// The quirks which are allowed to differ in a fuzzy match, they are often changed by the middleboxes.
+const FUZZY_QUIRKS: [&str; 4] = ["df", "id+", "id-", "ecn"];
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NS: u32 = 0xa1b23c4d;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum P0fDirection {
+    /// The SYN sent by a client.
+    Request,
+    /// The SYN+ACK sent by a server.
+    Response,
+}
+
+impl fmt::Display for P0fDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            P0fDirection::Request => write!(f, "syn"),
+            P0fDirection::Response => write!(f, "syn+ack"),
+        }
+    }
+}
+
+/// The `label` line of the p0f database, such as `s:unix:Linux:3.11 and newer`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct P0fLabel {
+    /// `g`, the signature is generic and less specific than the `s` ones.
+    pub generic: bool,
+    /// Such as `unix` and `win`, `!` means it is not an OS.
+    pub class: String,
+    pub name: String,
+    pub flavor: String,
+}
+
+impl fmt::Display for P0fLabel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.flavor.is_empty() {
+            write!(f, "{} {}", self.name, self.flavor)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+impl P0fLabel {
+    pub fn parser(label: &str) -> Result<P0fLabel> {
+        let fields: Vec<&str> = label.splitn(4, ':').collect();
+        if fields.len() != 4 || (fields[0] != "s" && fields[0] != "g") {
+            return Err(P0fParseFailed::new(label.to_string()).into());
+        }
+        Ok(P0fLabel {
+            generic: fields[0] == "g",
+            class: fields[1].to_string(),
+            name: fields[2].to_string(),
+            flavor: fields[3].to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum P0fWindow {
+    Any,
+    Value(u16),
+    /// A multiple of the MSS.
+    Mss(u16),
+    /// A multiple of the MTU, the MSS with the IP and TCP headers.
+    Mtu(u16),
+    /// Any multiple of the value.
+    Mod(u16),
+}
+
+impl P0fWindow {
+    fn parser(wsize: &str) -> Option<P0fWindow> {
+        if wsize == "*" {
+            Some(P0fWindow::Any)
+        } else if let Some(n) = wsize.strip_prefix("mss*") {
+            n.parse().ok().map(P0fWindow::Mss)
+        } else if let Some(n) = wsize.strip_prefix("mtu*") {
+            n.parse().ok().map(P0fWindow::Mtu)
+        } else if let Some(n) = wsize.strip_prefix('%') {
+            n.parse().ok().map(P0fWindow::Mod)
+        } else {
+            wsize.parse().ok().map(P0fWindow::Value)
+        }
+    }
+    fn do_match(&self, packet: &P0fPacket) -> bool {
+        let win = packet.wsize as u32;
+        let mss = match packet.mss {
+            Some(m) => m as u32,
+            None => 0,
+        };
+        match self {
+            P0fWindow::Any => true,
+            P0fWindow::Value(v) => win == *v as u32,
+            P0fWindow::Mss(n) => mss > 0 && win == mss * *n as u32,
+            P0fWindow::Mtu(n) => {
+                let headers = if packet.ver == 4 { 40 } else { 60 };
+                mss > 0 && win == (mss + headers) * *n as u32
+            }
+            P0fWindow::Mod(n) => *n > 0 && win.is_multiple_of(*n as u32),
+        }
+    }
+}
+
+/// One `sig` line of the p0f database, `ver:ittl:olen:mss:wsize,scale:olayout:quirks:pclass`.
+/// The fields of None match any value (`*`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P0fSignature {
+    pub label: P0fLabel,
+    pub direction: P0fDirection,
+    pub ver: Option<u8>,
+    pub ittl: u8,
+    /// The `-` suffix of the ittl, the TTL is not used to calculate the distance.
+    pub bad_ttl: bool,
+    pub olen: u8,
+    pub mss: Option<u16>,
+    pub wsize: P0fWindow,
+    pub scale: Option<u8>,
+    pub olayout: Vec<String>,
+    pub quirks: Vec<String>,
+    /// Some(true) if the packet has no payload, Some(false) if it has.
+    pub pclass_zero: Option<bool>,
+}
+
+impl P0fSignature {
+    pub fn parser(label: &P0fLabel, direction: P0fDirection, sig: &str) -> Result<P0fSignature> {
+        let invalid = || P0fParseFailed::new(sig.to_string());
+        let fields: Vec<&str> = sig.split(':').collect();
+        if fields.len() != 8 {
+            return Err(invalid().into());
+        }
+        let any = |v: &str| -> Result<Option<u16>> {
+            match v {
+                "*" => Ok(None),
+                _ => match v.parse() {
+                    Ok(v) => Ok(Some(v)),
+                    Err(_) => Err(invalid().into()),
+                },
+            }
+        };
+        let ver = any(fields[0])?.map(|v| v as u8);
+        let (ittl, bad_ttl) = match fields[1].strip_suffix('-') {
+            Some(t) => (t, true),
+            // the ttl of some signatures is `64+1`, the distance is known
+            None => (fields[1].split('+').next().unwrap_or(""), false),
+        };
+        let ittl = match ittl.parse() {
+            Ok(t) => t,
+            Err(_) => return Err(invalid().into()),
+        };
+        let olen = match fields[2].parse() {
+            Ok(o) => o,
+            Err(_) => return Err(invalid().into()),
+        };
+        let mss = any(fields[3])?;
+        let (wsize, scale) = match fields[4].split_once(',') {
+            Some((w, s)) => match P0fWindow::parser(w) {
+                Some(w) => (w, any(s)?.map(|s| s as u8)),
+                None => return Err(invalid().into()),
+            },
+            None => return Err(invalid().into()),
+        };
+        let list = |v: &str| -> Vec<String> {
+            v.split(',')
+                .filter(|x| !x.is_empty())
+                .map(|x| x.to_string())
+                .collect()
+        };
+        let pclass_zero = match fields[7] {
+            "0" => Some(true),
+            "+" => Some(false),
+            _ => None,
+        };
+        Ok(P0fSignature {
+            label: label.clone(),
+            direction,
+            ver,
+            ittl,
+            bad_ttl,
+            olen,
+            mss,
+            wsize,
+            scale,
+            olayout: list(fields[5]),
+            quirks: list(fields[6]),
+            pclass_zero,
+        })
+    }
+    /// The distance in hops if the packet matches this signature.
+    /// With `fuzzy`, the quirks in `FUZZY_QUIRKS` are not compared.
+    pub fn do_match(&self, packet: &P0fPacket, fuzzy: bool) -> Option<u8> {
+        if self.direction != packet.direction || self.olen != packet.olen {
+            return None;
+        }
+        match self.ver {
+            Some(v) if v != packet.ver => return None,
+            _ => (),
+        }
+        if self.olayout != packet.olayout {
+            return None;
+        }
+        let quirks_match = if fuzzy {
+            let strip = |q: &[String]| -> Vec<String> {
+                q.iter()
+                    .filter(|x| !FUZZY_QUIRKS.contains(&x.as_str()))
+                    .cloned()
+                    .collect()
+            };
+            strip(&self.quirks) == strip(&packet.quirks)
+        } else {
+            self.quirks == packet.quirks
+        };
+        if !quirks_match {
+            return None;
+        }
+        match self.pclass_zero {
+            Some(z) if z != packet.pclass_zero => return None,
+            _ => (),
+        }
+        match self.mss {
+            Some(m) if Some(m) != packet.mss => return None,
+            _ => (),
+        }
+        match self.scale {
+            Some(s) if Some(s) != packet.scale => return None,
+            _ => (),
+        }
+        if !self.wsize.do_match(packet) {
+            return None;
+        }
+        if self.bad_ttl {
+            if self.ittl < packet.ttl {
+                return None;
+            }
+            Some(guess_distance(packet.ttl))
+        } else {
+            if self.ittl < packet.ttl || self.ittl - packet.ttl > P0F_MAX_DIST {
+                return None;
+            }
+            Some(self.ittl - packet.ttl)
+        }
+    }
+}
+
+/// The distance from the nearest common initial TTL.
+pub fn guess_distance(ttl: u8) -> u8 {
+    for c in COMMON_TTLS {
+        if ttl <= c {
+            return c - ttl;
+        }
+    }
+    0
+}
+
+/// The TCP signatures of the p0f database (p0f.fp).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P0fDb {
+    pub signatures: Vec<P0fSignature>,
+}
+
+impl P0fDb {
+    /// The embedded subset of the p0f signatures, parsed at the first call and cached after that.
+    pub fn embedded() -> Result<Arc<P0fDb>> {
+        let db = EMBEDDED_P0F_DB.get_or_try_init(|| -> Result<Arc<P0fDb>> {
+            let db = P0fDb::parser(include_str!("../db/p0f.fp"))?;
+            Ok(Arc::new(db))
+        })?;
+        Ok(db.clone())
+    }
+    /// Parse the content of a p0f.fp file, only the `tcp:request` and `tcp:response` sections are used.
+    pub fn parser(p0f_fp: &str) -> Result<P0fDb> {
+        let mut signatures = Vec::new();
+        let mut direction = None;
+        let mut label = None;
+        for line in p0f_fp.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                direction = match line {
+                    "[tcp:request]" => Some(P0fDirection::Request),
+                    "[tcp:response]" => Some(P0fDirection::Response),
+                    _ => None,
+                };
+                label = None;
+                continue;
+            }
+            let direction = match direction {
+                Some(d) => d,
+                None => continue,
+            };
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => return Err(P0fParseFailed::new(line.to_string()).into()),
+            };
+            match key {
+                "label" => label = Some(P0fLabel::parser(value)?),
+                "sig" => match &label {
+                    Some(l) => signatures.push(P0fSignature::parser(l, direction, value)?),
+                    None => return Err(P0fParseFailed::new(line.to_string()).into()),
+                },
+                // sys, classes and ua_os are not used
+                _ => (),
+            }
+        }
+        debug!("p0f db parse finish, {} signatures", signatures.len());
+        Ok(P0fDb { signatures })
+    }
+    /// Load a p0f.fp file, such as the full one from the p0f release.
+    pub fn from_file(path: &str) -> Result<P0fDb> {
+        let p0f_fp = fs::read_to_string(path)?;
+        P0fDb::parser(&p0f_fp)
+    }
+    /// Match the packet like p0f, the specific signatures first, then the generic ones,
+    /// then the fuzzy matches of both.
+    pub fn do_match(&self, packet: &P0fPacket) -> Option<P0fMatch> {
+        for fuzzy in [false, true] {
+            for generic in [false, true] {
+                for sig in &self.signatures {
+                    if sig.label.generic != generic {
+                        continue;
+                    }
+                    if let Some(distance) = sig.do_match(packet, fuzzy) {
+                        return Some(P0fMatch {
+                            label: sig.label.clone(),
+                            fuzzy,
+                            distance,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct P0fMatch {
+    pub label: P0fLabel,
+    /// Some quirks differ from the signature.
+    pub fuzzy: bool,
+    pub distance: u8,
+}
+
+/// The fields of a SYN or SYN+ACK packet used by the p0f signatures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct P0fPacket {
+    pub src_addr: IpAddr,
+    pub dst_addr: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub direction: P0fDirection,
+    pub ver: u8,
+    pub ttl: u8,
+    /// The length of the IPv4 options.
+    pub olen: u8,
+    pub mss: Option<u16>,
+    pub wsize: u16,
+    pub scale: Option<u8>,
+    pub olayout: Vec<String>,
+    pub quirks: Vec<String>,
+    pub pclass_zero: bool,
+}
+
+impl fmt::Display for P0fPacket {
+    /// The signature of the packet in the p0f format, it can be added to the database.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mss = match self.mss {
+            Some(m) => format!("{}", m),
+            None => String::from("*"),
+        };
+        let scale = match self.scale {
+            Some(s) => format!("{}", s),
+            None => String::from("0"),
+        };
+        let pclass = if self.pclass_zero { "0" } else { "+" };
+        write!(
+            f,
+            "{}:{}+{}:{}:{}:{},{}:{}:{}:{}",
+            self.ver,
+            self.ttl as u16 + guess_distance(self.ttl) as u16,
+            guess_distance(self.ttl),
+            self.olen,
+            mss,
+            self.wsize,
+            scale,
+            self.olayout.join(","),
+            self.quirks.join(","),
+            pclass
+        )
+    }
+}
+
+impl P0fPacket {
+    /// Parse the SYN or SYN+ACK in the ethernet frame, None for the other frames.
+    pub fn parse_frame(ethernet_buff: &[u8]) -> Option<P0fPacket> {
+        let ethernet_packet = EthernetPacket::new(ethernet_buff)?;
+        match ethernet_packet.get_ethertype() {
+            EtherTypes::Ipv4 | EtherTypes::Ipv6 => P0fPacket::parse_ip(ethernet_packet.payload()),
+            _ => None,
+        }
+    }
+    /// Parse the SYN or SYN+ACK in the IPv4 or IPv6 packet, None for the other packets.
+    pub fn parse_ip(ip_buff: &[u8]) -> Option<P0fPacket> {
+        if ip_buff.is_empty() {
+            return None;
+        }
+        let mut quirks = Vec::new();
+        let (src_addr, dst_addr, ver, ttl, olen, tcp_buff) = match ip_buff[0] >> 4 {
+            4 => {
+                let ipv4_packet = Ipv4Packet::new(ip_buff)?;
+                if ipv4_packet.get_next_level_protocol() != IpNextHeaderProtocols::Tcp {
+                    return None;
+                }
+                let flags = ipv4_packet.get_flags();
+                let df = flags & Ipv4Flags::DontFragment != 0;
+                let id = ipv4_packet.get_identification();
+                if df {
+                    quirks.push("df");
+                    if id != 0 {
+                        quirks.push("id+");
+                    }
+                } else if id == 0 {
+                    quirks.push("id-");
+                }
+                if ipv4_packet.get_ecn() != 0 {
+                    quirks.push("ecn");
+                }
+                // the reserved flag, must be zero
+                if flags & 0b100 != 0 {
+                    quirks.push("0+");
+                }
+                let header_length = ipv4_packet.get_header_length() as usize * 4;
+                let olen = header_length.saturating_sub(20) as u8;
+                let total_length = (ipv4_packet.get_total_length() as usize).min(ip_buff.len());
+                if header_length > total_length {
+                    return None;
+                }
+                let tcp_buff = &ip_buff[header_length..total_length];
+                (
+                    IpAddr::V4(ipv4_packet.get_source()),
+                    IpAddr::V4(ipv4_packet.get_destination()),
+                    4,
+                    ipv4_packet.get_ttl(),
+                    olen,
+                    tcp_buff.to_vec(),
+                )
+            }
+            6 => {
+                let ipv6_packet = Ipv6Packet::new(ip_buff)?;
+                if ipv6_packet.get_next_header() != IpNextHeaderProtocols::Tcp {
+                    return None;
+                }
+                if ipv6_packet.get_flow_label() != 0 {
+                    quirks.push("flow");
+                }
+                if ipv6_packet.get_traffic_class() & 0b11 != 0 {
+                    quirks.push("ecn");
+                }
+                (
+                    IpAddr::V6(ipv6_packet.get_source()),
+                    IpAddr::V6(ipv6_packet.get_destination()),
+                    6,
+                    ipv6_packet.get_hop_limit(),
+                    0,
+                    ipv6_packet.payload().to_vec(),
+                )
+            }
+            _ => return None,
+        };
+
+        let tcp_packet = TcpPacket::new(&tcp_buff)?;
+        let flags = tcp_packet.get_flags();
+        let direction = if flags & TcpFlags::SYN == 0 || flags & TcpFlags::RST != 0 {
+            return None;
+        } else if flags & TcpFlags::ACK != 0 {
+            P0fDirection::Response
+        } else {
+            P0fDirection::Request
+        };
+
+        if tcp_packet.get_sequence() == 0 {
+            quirks.push("seq-");
+        }
+        let ack = flags & TcpFlags::ACK != 0;
+        if !ack && tcp_packet.get_acknowledgement() != 0 {
+            quirks.push("ack+");
+        }
+        if ack && tcp_packet.get_acknowledgement() == 0 {
+            quirks.push("ack-");
+        }
+        let urg = flags & TcpFlags::URG != 0;
+        if !urg && tcp_packet.get_urgent_ptr() != 0 {
+            quirks.push("uptr+");
+        }
+        if urg {
+            quirks.push("urgf+");
+        }
+        if flags & TcpFlags::PSH != 0 {
+            quirks.push("pushf+");
+        }
+
+        let data_offset = tcp_packet.get_data_offset() as usize * 4;
+        if data_offset < 20 || data_offset > tcp_buff.len() {
+            return None;
+        }
+        let options = &tcp_buff[20..data_offset];
+        let (olayout, mss, scale, option_quirks) = parse_tcp_options(options, direction);
+        quirks.extend(option_quirks);
+        // keep the order of the p0f quirks
+        let order = [
+            "df", "id+", "id-", "ecn", "0+", "flow", "seq-", "ack+", "ack-", "uptr+", "urgf+",
+            "pushf+", "ts1-", "ts2+", "opt+", "exws", "bad",
+        ];
+        let mut quirks: Vec<String> = order
+            .iter()
+            .filter(|q| quirks.contains(q))
+            .map(|q| q.to_string())
+            .collect();
+        quirks.dedup();
+
+        Some(P0fPacket {
+            src_addr,
+            dst_addr,
+            src_port: tcp_packet.get_source(),
+            dst_port: tcp_packet.get_destination(),
+            direction,
+            ver,
+            ttl,
+            olen,
+            mss,
+            wsize: tcp_packet.get_window(),
+            scale,
+            olayout,
+            quirks,
+            pclass_zero: data_offset == tcp_buff.len(),
+        })
+    }
+}
+
+/// The option layout, the MSS, the window scale and the quirks of the raw TCP options.
+fn parse_tcp_options(
+    options: &[u8],
+    direction: P0fDirection,
+) -> (Vec<String>, Option<u16>, Option<u8>, Vec<&'static str>) {
+    let mut olayout = Vec::new();
+    let mut mss = None;
+    let mut scale = None;
+    let mut quirks = Vec::new();
+    let mut i = 0;
+    while i < options.len() {
+        let kind = options[i];
+        match kind {
+            0 => {
+                // eol, the rest is padding
+                let rest = &options[i + 1..];
+                olayout.push(format!("eol+{}", rest.len()));
+                if rest.iter().any(|b| *b != 0) {
+                    quirks.push("opt+");
+                }
+                break;
+            }
+            1 => {
+                olayout.push(String::from("nop"));
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+        if i + 1 >= options.len() {
+            quirks.push("bad");
+            break;
+        }
+        let len = options[i + 1] as usize;
+        if len < 2 || i + len > options.len() {
+            quirks.push("bad");
+            break;
+        }
+        let data = &options[i + 2..i + len];
+        match (kind, data.len()) {
+            (2, 2) => {
+                olayout.push(String::from("mss"));
+                mss = Some(u16::from_be_bytes([data[0], data[1]]));
+            }
+            (3, 1) => {
+                olayout.push(String::from("ws"));
+                scale = Some(data[0]);
+                if data[0] > 14 {
+                    quirks.push("exws");
+                }
+            }
+            (4, 0) => olayout.push(String::from("sok")),
+            (5, _) => olayout.push(String::from("sack")),
+            (8, 8) => {
+                olayout.push(String::from("ts"));
+                let tsval = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                let tsecr = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+                if tsval == 0 {
+                    quirks.push("ts1-");
+                }
+                if tsecr != 0 && direction == P0fDirection::Request {
+                    quirks.push("ts2+");
+                }
+            }
+            (2, _) | (3, _) | (4, _) | (8, _) => {
+                quirks.push("bad");
+                break;
+            }
+            _ => olayout.push(format!("?{}", kind)),
+        }
+        i += len;
+    }
+    (olayout, mss, scale, quirks)
+}
+
+/// Read the IP packets from a pcap file, the pcapng format is not supported.
+pub fn pcap_ip_packets(path: &str) -> Result<Vec<Vec<u8>>> {
+    let data = fs::read(path)?;
+    let invalid = || PcapParseFailed::new(path.to_string());
+    if data.len() < 24 {
+        return Err(invalid().into());
+    }
+    let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let little_endian = if magic == PCAP_MAGIC || magic == PCAP_MAGIC_NS {
+        true
+    } else if magic.swap_bytes() == PCAP_MAGIC || magic.swap_bytes() == PCAP_MAGIC_NS {
+        false
+    } else {
+        return Err(invalid().into());
+    };
+    let read_u32 = |b: &[u8]| -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    };
+    let linktype = read_u32(&data[20..24]);
+
+    let mut ret = Vec::new();
+    let mut i = 24;
+    while i + 16 <= data.len() {
+        let incl_len = read_u32(&data[i + 8..i + 12]) as usize;
+        i += 16;
+        if i + incl_len > data.len() {
+            return Err(invalid().into());
+        }
+        let frame = &data[i..i + incl_len];
+        i += incl_len;
+        let ip_buff = match linktype {
+            LINKTYPE_ETHERNET => match EthernetPacket::new(frame) {
+                Some(e) => match e.get_ethertype() {
+                    EtherTypes::Ipv4 | EtherTypes::Ipv6 => e.payload().to_vec(),
+                    _ => continue,
+                },
+                None => continue,
+            },
+            LINKTYPE_RAW => frame.to_vec(),
+            // the 16 bytes cooked header of `tcpdump -i any`
+            LINKTYPE_LINUX_SLL => {
+                if frame.len() < 16 {
+                    continue;
+                }
+                frame[16..].to_vec()
+            }
+            _ => return Err(PcapLinkTypeUnsupported::new(linktype).into()),
+        };
+        ret.push(ip_buff);
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ipv4::MutableIpv4Packet;
+    use pnet::packet::tcp::MutableTcpPacket;
+    use std::env;
+    use std::net::Ipv4Addr;
+
+    fn syn_packet(ttl: u8, window: u16, options: &[u8], flags: u8) -> Vec<u8> {
+        let tcp_len = 20 + options.len();
+        let mut buff = vec![0u8; 20 + tcp_len];
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut buff).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + tcp_len) as u16);
+        ipv4_packet.set_identification(0x1234);
+        ipv4_packet.set_flags(Ipv4Flags::DontFragment);
+        ipv4_packet.set_ttl(ttl);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
+        ipv4_packet.set_source(Ipv4Addr::new(192, 168, 1, 10));
+        ipv4_packet.set_destination(Ipv4Addr::new(192, 168, 1, 1));
+        let mut tcp_packet = MutableTcpPacket::new(&mut buff[20..]).unwrap();
+        tcp_packet.set_source(40000);
+        tcp_packet.set_destination(80);
+        tcp_packet.set_sequence(0x11223344);
+        if flags & TcpFlags::ACK != 0 {
+            tcp_packet.set_acknowledgement(0x55667788);
+        }
+        tcp_packet.set_data_offset((tcp_len / 4) as u8);
+        tcp_packet.set_flags(flags);
+        tcp_packet.set_window(window);
+        buff[40..].copy_from_slice(options);
+        buff
+    }
+    #[test]
+    fn test_p0f_linux_syn() -> Result<()> {
+        // mss 1460, sok, ts, nop, ws 7
+        let options = [
+            2, 4, 0x05, 0xb4, 4, 2, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0, 1, 3, 3, 7,
+        ];
+        let buff = syn_packet(61, 1460 * 20, &options, TcpFlags::SYN);
+        let packet = P0fPacket::parse_ip(&buff).unwrap();
+        assert_eq!(packet.direction, P0fDirection::Request);
+        assert_eq!(packet.olayout, vec!["mss", "sok", "ts", "nop", "ws"]);
+        assert_eq!(packet.quirks, vec!["df", "id+"]);
+        assert_eq!(packet.mss, Some(1460));
+        assert_eq!(packet.scale, Some(7));
+        assert_eq!(
+            format!("{}", packet),
+            "4:64+3:0:1460:29200,7:mss,sok,ts,nop,ws:df,id+:0"
+        );
+
+        let db = P0fDb::embedded()?;
+        let m = db.do_match(&packet).unwrap();
+        assert_eq!(m.label.name, "Linux");
+        assert_eq!(m.label.flavor, "3.11 and newer");
+        assert_eq!(m.distance, 3);
+        assert!(!m.fuzzy);
+        Ok(())
+    }
+    #[test]
+    fn test_p0f_windows_syn_ack() -> Result<()> {
+        // mss 1460, nop, ws 8, nop, nop, sok
+        let options = [2, 4, 0x05, 0xb4, 1, 3, 3, 8, 1, 1, 4, 2];
+        let buff = syn_packet(120, 8192, &options, TcpFlags::SYN | TcpFlags::ACK);
+        let packet = P0fPacket::parse_ip(&buff).unwrap();
+        assert_eq!(packet.direction, P0fDirection::Response);
+
+        let db = P0fDb::embedded()?;
+        let m = db.do_match(&packet).unwrap();
+        assert_eq!(m.label.class, "win");
+        assert_eq!(m.label.flavor, "7 or 8");
+        assert_eq!(m.distance, 8);
+
+        // not a syn
+        let buff = syn_packet(120, 8192, &options, TcpFlags::ACK);
+        assert!(P0fPacket::parse_ip(&buff).is_none());
+        Ok(())
+    }
+    #[test]
+    fn test_p0f_db_parser() -> Result<()> {
+        let db = P0fDb::embedded()?;
+        assert!(!db.signatures.is_empty());
+        let sig = db
+            .signatures
+            .iter()
+            .find(|s| s.label.name == "NMap")
+            .unwrap();
+        assert!(sig.bad_ttl);
+        assert_eq!(sig.mss, Some(1460));
+
+        let text = "[tcp:request]\nsig = *:64:0:*:*,*:mss::0\n";
+        assert!(P0fDb::parser(text).is_err());
+        let text = "[tcp:request]\nlabel = s:unix:Linux:3.x\nsig = *:64:0:*:*:mss::0\n";
+        assert!(P0fDb::parser(text).is_err());
+        // the other sections are ignored
+        let text = "[http:request]\nlabel = s:unix:Linux:3.x\nsig = *:Host,User-Agent::\n";
+        assert_eq!(P0fDb::parser(text)?.signatures.len(), 0);
+        Ok(())
+    }
+    #[test]
+    fn test_pcap_ip_packets() -> Result<()> {
+        let options = [2, 4, 0x05, 0xb4, 1, 3, 3, 8, 1, 1, 4, 2];
+        let ip_buff = syn_packet(120, 8192, &options, TcpFlags::SYN | TcpFlags::ACK);
+        let mut data = Vec::new();
+        data.extend(PCAP_MAGIC.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(4u16.to_le_bytes());
+        data.extend([0u8; 8]);
+        data.extend(65535u32.to_le_bytes());
+        data.extend(LINKTYPE_RAW.to_le_bytes());
+        data.extend([0u8; 8]);
+        data.extend((ip_buff.len() as u32).to_le_bytes());
+        data.extend((ip_buff.len() as u32).to_le_bytes());
+        data.extend(&ip_buff);
+
+        let path = env::temp_dir().join("pistol_test_p0f.pcap");
+        let path = path.to_str().unwrap();
+        fs::write(path, &data)?;
+        let packets = pcap_ip_packets(path)?;
+        fs::remove_file(path)?;
+        assert_eq!(packets, vec![ip_buff]);
+        Ok(())
+    }
+}
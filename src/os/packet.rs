@@ -101,13 +101,15 @@ const MSS_SIZE: usize = 4;
 const WSCALE_SIZE: usize = 3;
 const TIMESTAMP_SIZE: usize = 10;
 const SACK_PERM_SIZE: usize = 2;
-const TTL: u8 = 64;
+/// The default TTL of the probes.
+pub const TTL: u8 = 64;
 
 pub fn seq_packet_1_layer3(
     src_ipv4: Ipv4Addr,
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -124,7 +126,7 @@ pub fn seq_packet_1_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -168,6 +170,7 @@ pub fn seq_packet_2_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -183,7 +186,7 @@ pub fn seq_packet_2_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -226,6 +229,7 @@ pub fn seq_packet_3_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -242,7 +246,7 @@ pub fn seq_packet_3_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -287,6 +291,7 @@ pub fn seq_packet_4_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -302,7 +307,7 @@ pub fn seq_packet_4_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -344,6 +349,7 @@ pub fn seq_packet_5_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -359,7 +365,7 @@ pub fn seq_packet_5_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -402,6 +408,7 @@ pub fn seq_packet_6_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -417,7 +424,7 @@ pub fn seq_packet_6_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
@@ -454,7 +461,12 @@ pub fn seq_packet_6_layer3(
     Ok(buff.to_vec())
 }
 
-pub fn ie_packet_1_layer3(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, idtf: u16) -> Result<Vec<u8>> {
+pub fn ie_packet_1_layer3(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    idtf: u16,
+    ttl: u8,
+) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const ICMP_DATA_SIZE: usize = 120; // and 120 bytes of 0x00 for the data payload
 
@@ -469,7 +481,7 @@ pub fn ie_packet_1_layer3(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, idtf: u16) ->
     ip_header.set_identification(id);
     // the first one has the IP DF bit set
     ip_header.set_flags(Ipv4Flags::DontFragment);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     // a type-of-service (TOS) byte value of zero
     ip_header.set_dscp(0);
     ip_header.set_ecn(0);
@@ -498,7 +510,12 @@ pub fn ie_packet_1_layer3(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, idtf: u16) ->
     Ok(buff.to_vec())
 }
 
-pub fn ie_packet_2_layer3(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, idtf: u16) -> Result<Vec<u8>> {
+pub fn ie_packet_2_layer3(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    idtf: u16,
+    ttl: u8,
+) -> Result<Vec<u8>> {
     // 150 bytes of data is sent
     let mut rng = rand::thread_rng();
     const ICMP_DATA_SIZE: usize = 150; // 150 bytes of data is sent
@@ -513,7 +530,7 @@ pub fn ie_packet_2_layer3(src_ipv4: Ipv4Addr, dst_ipv4: Ipv4Addr, idtf: u16) ->
     ip_header.set_identification(id);
     // the first one has the IP DF bit set
     ip_header.set_flags(Ipv4Flags::DontFragment);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     // a TOS of four (IP_TOS_RELIABILITY) is used
     // 000001|00
     ip_header.set_dscp(1);
@@ -547,6 +564,7 @@ pub fn ecn_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -572,7 +590,7 @@ pub fn ecn_packet_layer3(
     );
     let id = rng.gen();
     ip_header.set_identification(id);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_ecn(0);
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
@@ -622,6 +640,7 @@ pub fn t2_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -642,7 +661,7 @@ pub fn t2_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -681,6 +700,7 @@ pub fn t3_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -701,7 +721,7 @@ pub fn t3_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -740,6 +760,7 @@ pub fn t4_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -760,7 +781,7 @@ pub fn t4_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -799,6 +820,7 @@ pub fn t5_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -819,7 +841,7 @@ pub fn t5_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -858,6 +880,7 @@ pub fn t6_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -878,7 +901,7 @@ pub fn t6_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -917,6 +940,7 @@ pub fn t7_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     let mut rng = rand::thread_rng();
     const TCP_DATA_SIZE: usize = 0;
@@ -937,7 +961,7 @@ pub fn t7_packet_layer3(
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
     ip_header.set_source(src_ipv4);
     ip_header.set_destination(dst_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
 
@@ -978,6 +1002,7 @@ pub fn udp_packet_layer3(
     src_port: u16,
     dst_ipv4: Ipv4Addr,
     dst_port: u16,
+    ttl: u8,
 ) -> Result<Vec<u8>> {
     const UDP_DATA_SIZE: usize = 300;
     let mut buff = [0u8; IPV4_HEADER_SIZE + UDP_HEADER_SIZE + UDP_DATA_SIZE];
@@ -991,7 +1016,7 @@ pub fn udp_packet_layer3(
     // ip_header.set_flags(Ipv4Flags::DontFragment); // IP DF not set
     ip_header.set_next_level_protocol(IpNextHeaderProtocols::Udp);
     ip_header.set_source(src_ipv4);
-    ip_header.set_ttl(TTL);
+    ip_header.set_ttl(ttl);
     ip_header.set_destination(dst_ipv4);
     let c = ipv4::checksum(&ip_header.to_immutable());
     ip_header.set_checksum(c);
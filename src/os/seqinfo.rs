@@ -0,0 +1,218 @@
+use chrono::DateTime;
+use chrono::Local;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use super::osscan::SEQX;
+
+/// The analysis of the TCP timestamp clock from the TSvals of the SEQ responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpTsInfo {
+    /// The measured TSval increments per second.
+    pub hz: f64,
+    /// The clock frequency the target most likely uses, 2, 100, 200, 1000 or the measured rate, the same buckets as nmap.
+    pub nominal_hz: u64,
+    /// The TSvals never go backwards between the probes.
+    pub monotonic: bool,
+    /// The time since the clock started, None if the clock is not monotonic.
+    pub uptime: Option<Duration>,
+    /// The estimated boot time, nmap's "last boot", None if the clock is not monotonic.
+    pub last_boot: Option<SystemTime>,
+}
+
+impl TcpTsInfo {
+    /// The `tsvals` are `(probe index, TSval)` from `tcp_tsvals`, `start` is the time the first SEQ probe was sent.
+    /// Returns None if there are less than two timestamps or any of them is zero.
+    pub fn new(tsvals: &[(usize, u32)], start: SystemTime) -> Option<TcpTsInfo> {
+        if tsvals.len() < 2 || tsvals.iter().any(|(_, t)| *t == 0) {
+            return None;
+        }
+
+        let mut monotonic = true;
+        let mut sum = 0.0;
+        for w in tsvals.windows(2) {
+            let (i, x) = w[0];
+            let (j, y) = w[1];
+            if y < x {
+                monotonic = false;
+            }
+            // the probes are sent 100 ms apart
+            let elapsed = (j - i) as f64 * 0.1;
+            sum += y.wrapping_sub(x) as f64 / elapsed;
+        }
+        let hz = sum / (tsvals.len() - 1) as f64;
+        // the ranges nmap uses for the uptime guess
+        let nominal_hz = if hz <= 0.0 {
+            0
+        } else if hz < 5.66 {
+            2
+        } else if hz > 70.0 && hz < 150.0 {
+            100
+        } else if (150.0..=350.0).contains(&hz) {
+            200
+        } else if hz > 724.0 && hz < 1448.0 {
+            1000
+        } else {
+            hz.round() as u64
+        };
+
+        let (uptime, last_boot) = if monotonic && nominal_hz > 0 {
+            let (k, tsval) = tsvals[tsvals.len() - 1];
+            let uptime = Duration::from_secs_f64(tsval as f64 / nominal_hz as f64);
+            let sent = start + Duration::from_millis(100 * k as u64);
+            (Some(uptime), sent.checked_sub(uptime))
+        } else {
+            (None, None)
+        };
+        Some(TcpTsInfo {
+            hz,
+            nominal_hz,
+            monotonic,
+            uptime,
+            last_boot,
+        })
+    }
+}
+
+/// The analysis of the TCP ISN generator from the SEQ test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IsnInfo {
+    pub gcd: u32,
+    pub isr: u32,
+    /// The SP is the difficulty of the ISN prediction nmap reports.
+    pub sp: u32,
+    /// The average ISN increments per second, the ISR is `8 * log2(rate)`.
+    pub rate: f64,
+    /// The nmap's rating of the difficulty, from `Trivial joke` to `Good luck!`.
+    pub rating: String,
+}
+
+impl IsnInfo {
+    /// Returns None if the target did not respond to the SEQ probes.
+    pub fn new(seqx: &SEQX) -> Option<IsnInfo> {
+        if seqx.r != "Y" {
+            return None;
+        }
+        let rate = 2f64.powf(seqx.isr as f64 / 8.0);
+        let rating = if seqx.sp < 3 {
+            "Trivial joke"
+        } else if seqx.sp < 6 {
+            "Easy"
+        } else if seqx.sp < 11 {
+            "Medium"
+        } else if seqx.sp < 12 {
+            "Formidable"
+        } else if seqx.sp < 16 {
+            "Worthy challenge"
+        } else {
+            "Good luck!"
+        };
+        Some(IsnInfo {
+            gcd: seqx.gcd,
+            isr: seqx.isr,
+            sp: seqx.sp,
+            rate,
+            rating: rating.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeqInfo {
+    /// None if the target does not support the TCP timestamp option.
+    pub ts: Option<TcpTsInfo>,
+    /// None if the target did not respond to the SEQ probes.
+    pub isn: Option<IsnInfo>,
+}
+
+impl fmt::Display for SeqInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = Vec::new();
+        if let Some(ts) = &self.ts {
+            match (ts.uptime, ts.last_boot) {
+                (Some(uptime), Some(last_boot)) => {
+                    let days = uptime.as_secs_f64() / 86400.0;
+                    let last_boot: DateTime<Local> = last_boot.into();
+                    lines.push(format!(
+                        "Uptime guess: {:.3} days (since {})",
+                        days,
+                        last_boot.format("%a %b %e %H:%M:%S %Y")
+                    ));
+                }
+                _ => lines.push(format!(
+                    "TCP timestamp: {} Hz, not monotonic",
+                    ts.nominal_hz
+                )),
+            }
+        }
+        if let Some(isn) = &self.isn {
+            lines.push(format!(
+                "TCP Sequence Prediction: Difficulty={} ({})",
+                isn.sp, isn.rating
+            ));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    #[test]
+    fn test_tcp_ts_info() {
+        let start = SystemTime::now();
+        // 1000 Hz, up for 10 days, the third response is lost
+        let base = 864_000_000;
+        let tsvals = vec![
+            (0, base),
+            (1, base + 100),
+            (3, base + 301),
+            (4, base + 400),
+            (5, base + 500),
+        ];
+        let info = TcpTsInfo::new(&tsvals, start).unwrap();
+        assert!(info.monotonic);
+        assert_eq!(info.nominal_hz, 1000);
+        assert!((info.hz - 1000.0).abs() < 10.0);
+        assert_eq!(info.uptime.unwrap().as_secs(), 10 * 86400);
+        assert!(info.last_boot.unwrap() < start);
+
+        // 100 Hz and an unusual 500 Hz clock
+        let tsvals = vec![(0, 50_000), (1, 50_010), (2, 50_020)];
+        let info = TcpTsInfo::new(&tsvals, start).unwrap();
+        assert_eq!(info.nominal_hz, 100);
+        assert_eq!(info.uptime.unwrap().as_secs(), 500);
+        let tsvals = vec![(0, 50_000), (1, 50_050), (2, 50_100)];
+        let info = TcpTsInfo::new(&tsvals, start).unwrap();
+        assert_eq!(info.nominal_hz, 500);
+
+        let tsvals = vec![(0, 1000), (1, 1010), (2, 990)];
+        let info = TcpTsInfo::new(&tsvals, start).unwrap();
+        assert!(!info.monotonic);
+        assert!(info.uptime.is_none());
+
+        assert!(TcpTsInfo::new(&[(0, 1000)], start).is_none());
+        assert!(TcpTsInfo::new(&[(0, 0), (1, 0)], start).is_none());
+    }
+    #[test]
+    fn test_isn_info() -> Result<()> {
+        let seqx = SEQX::parser("SP=106%GCD=1%ISR=10A%TI=Z%CI=Z%II=I%TS=A")?;
+        let isn = IsnInfo::new(&seqx).unwrap();
+        assert_eq!(isn.sp, 262);
+        assert_eq!(isn.rating, "Good luck!");
+        assert!((isn.rate - 2f64.powf(266.0 / 8.0)).abs() < 1.0);
+
+        let seqx = SEQX::parser("SP=0%GCD=FA00%ISR=9C%TI=I%TS=U")?;
+        let isn = IsnInfo::new(&seqx).unwrap();
+        assert_eq!(isn.gcd, 0xfa00);
+        assert_eq!(isn.rating, "Trivial joke");
+
+        let seqx = SEQX::parser("R=N")?;
+        assert!(IsnInfo::new(&seqx).is_none());
+        Ok(())
+    }
+}
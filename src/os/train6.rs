@@ -0,0 +1,384 @@
+use anyhow::Result;
+use log::debug;
+use serde::Deserialize;
+use serde::Serialize;
+use std::iter::zip;
+
+use crate::errors::LinearTrainFailed;
+
+use super::operator6::apply_scale;
+use super::operator6::FEATURES_LEN;
+use super::operator6::TIMING_FEATURES_START;
+use super::osscan6::PistolFingerprint6;
+use super::Linear;
+use super::CPE;
+
+/// The parameters of the gradient descent used to train the logistic regression of every OS class.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LinearTrainParams {
+    pub epochs: usize,
+    pub learning_rate: f64,
+    /// The L2 regularization strength.
+    pub l2: f64,
+    /// Train `train_linear` with the timing and flow label features too, off by default,
+    /// the round trip times depend on the network path more than the OS and overfit to the capture place.
+    pub timing: bool,
+}
+
+impl Default for LinearTrainParams {
+    fn default() -> Self {
+        LinearTrainParams {
+            epochs: 500,
+            learning_rate: 0.5,
+            l2: 0.001,
+            timing: false,
+        }
+    }
+}
+
+/// A fingerprint captured from a host of known OS.
+/// The `label` is the OS class it belongs to, the captures with the same `label.name` are the same class,
+/// at least one `osclass` is needed, the classes without it are never reported by the detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledFingerprint6 {
+    pub fingerprint: PistolFingerprint6,
+    pub label: CPE,
+}
+
+impl LabeledFingerprint6 {
+    pub fn new(fingerprint: PistolFingerprint6, label: CPE) -> LabeledFingerprint6 {
+        LabeledFingerprint6 { fingerprint, label }
+    }
+}
+
+/// The distinct labels in the order they first appear, and the label index of every sample.
+fn group_labels(samples: &[LabeledFingerprint6]) -> Result<(Vec<CPE>, Vec<usize>)> {
+    let mut labels: Vec<CPE> = Vec::new();
+    let mut indexes = Vec::new();
+    for s in samples {
+        if s.label.osclass.is_empty() {
+            return Err(LinearTrainFailed::new(format!("{} has no os class", s.label.name)).into());
+        }
+        match labels.iter().position(|l| l.name == s.label.name) {
+            Some(i) => indexes.push(i),
+            None => {
+                indexes.push(labels.len());
+                labels.push(s.label.clone());
+            }
+        }
+    }
+    Ok((labels, indexes))
+}
+
+/// The `[a, b]` of every feature, `(f + a) * b` maps the seen values to [1, 2].
+/// Unlike the nmap scale to [0, 1] the values are kept away from 0, the model has no intercept.
+/// The negative values mean no response and are not scaled.
+fn fit_scale(features: &[Vec<f64>], dims: usize) -> Vec<Vec<f64>> {
+    let mut scale = Vec::new();
+    for i in 0..dims {
+        let mut min = f64::MAX;
+        let mut max = f64::MIN;
+        for f in features {
+            if f[i] >= 0.0 {
+                min = min.min(f[i]);
+                max = max.max(f[i]);
+            }
+        }
+        if min > max {
+            scale.push(vec![0.0, 1.0]);
+        } else if max > min {
+            scale.push(vec![max - min - min, 1.0 / (max - min)]);
+        } else {
+            scale.push(vec![1.0 - min, 1.0]);
+        }
+    }
+    scale
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Train the one-vs-rest logistic regression of one class, `targets` is true for the samples of the class.
+/// The positive and the negative samples have the same total weight, a class usually has only a few captures.
+fn fit_class(features: &[Vec<f64>], targets: &[bool], params: &LinearTrainParams) -> Vec<f64> {
+    let dims = features[0].len();
+    let positive = targets.iter().filter(|t| **t).count().max(1) as f64;
+    let negative = targets.iter().filter(|t| !**t).count().max(1) as f64;
+
+    let mut w = vec![0.0; dims];
+    for _ in 0..params.epochs {
+        let mut grad: Vec<f64> = w.iter().map(|x| params.l2 * x).collect();
+        for (x, t) in zip(features, targets) {
+            let z: f64 = zip(x, &w).map(|(a, b)| a * b).sum();
+            let (y, weight) = match t {
+                true => (1.0, 0.5 / positive),
+                false => (0.0, 0.5 / negative),
+            };
+            let d = (sigmoid(z) - y) * weight;
+            for (g, a) in zip(&mut grad, x) {
+                *g += d * a;
+            }
+        }
+        for (v, g) in zip(&mut w, &grad) {
+            *v -= params.learning_rate * g;
+        }
+    }
+    w
+}
+
+/// The mean and the variance of every feature of the samples.
+fn mean_variance(features: &[&Vec<f64>]) -> (Vec<f64>, Vec<f64>) {
+    let dims = features[0].len();
+    let n = features.len() as f64;
+    let mut mean = vec![0.0; dims];
+    for f in features {
+        for (m, x) in zip(&mut mean, f.iter()) {
+            *m += x / n;
+        }
+    }
+    let mut variance = vec![0.0; dims];
+    for f in features {
+        for ((v, m), x) in zip(zip(&mut variance, &mean), f.iter()) {
+            *v += (x - m) * (x - m) / n;
+        }
+    }
+    (mean, variance)
+}
+
+fn sample_features(samples: &[LabeledFingerprint6], dims: usize) -> Result<Vec<Vec<f64>>> {
+    let mut features = Vec::new();
+    for s in samples {
+        let mut f = s.fingerprint.features()?;
+        f.truncate(dims);
+        features.push(f);
+    }
+    Ok(features)
+}
+
+/// Train a new model from the local captures only, with the nmap and NI features,
+/// or all the `operator6::FEATURES_LEN` features if `params.timing` is set,
+/// the timing and flow label ones are the last features so the model stops before them by default.
+/// At least two distinct labels are needed.
+pub fn train_linear(samples: &[LabeledFingerprint6], params: &LinearTrainParams) -> Result<Linear> {
+    let (labels, indexes) = group_labels(samples)?;
+    if labels.len() < 2 {
+        return Err(LinearTrainFailed::new(format!(
+            "at least 2 os labels are needed, got {}",
+            labels.len()
+        ))
+        .into());
+    }
+
+    let dims = match params.timing {
+        true => FEATURES_LEN,
+        false => TIMING_FEATURES_START,
+    };
+    let features = sample_features(samples, dims)?;
+    let scale = fit_scale(&features, dims);
+    let features: Vec<Vec<f64>> = features.iter().map(|f| apply_scale(f, &scale)).collect();
+
+    let mut linear = Linear {
+        infolist: Vec::new(),
+        w: Vec::new(),
+        scale,
+        mean: Vec::new(),
+        variance: Vec::new(),
+        cpe: Vec::new(),
+    };
+    for (i, label) in labels.into_iter().enumerate() {
+        debug!("train os class: {}", label.name);
+        let targets: Vec<bool> = indexes.iter().map(|x| *x == i).collect();
+        let w = fit_class(&features, &targets, params);
+        let class_features: Vec<&Vec<f64>> = zip(&features, &targets)
+            .filter(|(_, t)| **t)
+            .map(|(f, _)| f)
+            .collect();
+        let (mean, variance) = mean_variance(&class_features);
+        linear.infolist.push(label.name.clone());
+        linear.w.push(w);
+        linear.mean.push(mean);
+        linear.variance.push(variance);
+        linear.cpe.push(label);
+    }
+    Ok(linear)
+}
+
+/// Add the local captures to an existing model, such as the embedded nmap one, keeping its features and scale.
+/// The class of every label in the samples is trained again, or appended if the model does not know it.
+/// The nmap training data is not available, so the only negatives are the other samples
+/// and one vector per other class of the model, its mean: the class is separated from the centers
+/// of the other classes, not from their spread, and their outliers may be taken as this class.
+/// The weights of the other classes, and the mean and variance of the classes the model knows, are kept.
+pub fn extend_linear(
+    linear: &Linear,
+    samples: &[LabeledFingerprint6],
+    params: &LinearTrainParams,
+) -> Result<Linear> {
+    let (labels, indexes) = group_labels(samples)?;
+    if labels.is_empty() {
+        return Err(LinearTrainFailed::new(String::from("no samples")).into());
+    }
+
+    let dims = linear.features_len();
+    let features = sample_features(samples, dims)?;
+    let features: Vec<Vec<f64>> = features
+        .iter()
+        .map(|f| apply_scale(f, &linear.scale))
+        .collect();
+
+    let mut new_linear = linear.clone();
+    for (i, label) in labels.into_iter().enumerate() {
+        let known = linear.infolist.iter().position(|n| *n == label.name);
+        let mut x = Vec::new();
+        let mut targets = Vec::new();
+        for (f, j) in zip(&features, &indexes) {
+            x.push(f.clone());
+            targets.push(*j == i);
+        }
+        for (k, mean) in linear.mean.iter().enumerate() {
+            x.push(mean.clone());
+            targets.push(Some(k) == known);
+        }
+        debug!("train os class: {}, known: {}", label.name, known.is_some());
+        let w = fit_class(&x, &targets, params);
+        match known {
+            Some(k) => new_linear.w[k] = w,
+            None => {
+                let class_features: Vec<&Vec<f64>> = zip(&features, &indexes)
+                    .filter(|(_, j)| **j == i)
+                    .map(|(f, _)| f)
+                    .collect();
+                let (mean, variance) = mean_variance(&class_features);
+                new_linear.infolist.push(label.name.clone());
+                new_linear.w.push(w);
+                new_linear.mean.push(mean);
+                new_linear.variance.push(variance);
+                new_linear.cpe.push(label);
+            }
+        }
+    }
+    Ok(new_linear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os::gen_linear;
+    use crate::os::operator6::feature_names;
+    use crate::os::operator6::NMAP_FEATURES_LEN;
+    use crate::os::osscan6::predict_value;
+    use std::env;
+    use std::fs;
+    fn fingerprint(hlim: &str, window: &str, rt: f64) -> Result<PistolFingerprint6> {
+        let text = format!(
+            "OS:SCAN(V=PISTOL%E=6)S1(P=6000{{4}}2806{}XX{{32}}0016c1b002bbd213c57562f5a012{}f8880000020404c40402080a5be177f2ff{{4}}01030307%ST=0.021271%RT={})T4(P=6000{{4}}1406{}XX{{32}}0016c1b93a67fc8a00{{4}}500400000c7c0000%ST=0.912394%RT=0.93247)",
+            hlim, window, rt, hlim
+        );
+        PistolFingerprint6::parser(&text)
+    }
+    fn label(name: &str, family: &str) -> CPE {
+        CPE {
+            name: name.to_string(),
+            osclass: vec![vec![
+                family.to_string(),
+                family.to_string(),
+                String::new(),
+                String::from("general purpose"),
+            ]],
+            cpe: vec![],
+        }
+    }
+    fn samples() -> Result<Vec<LabeledFingerprint6>> {
+        let mut samples = Vec::new();
+        for rt in [0.04, 0.05, 0.06] {
+            samples.push(LabeledFingerprint6::new(
+                fingerprint("40", "7210", rt)?,
+                label("Linux 6.X", "Linux"),
+            ));
+            samples.push(LabeledFingerprint6::new(
+                fingerprint("80", "2000", rt + 0.1)?,
+                label("Windows 11", "Windows"),
+            ));
+        }
+        Ok(samples)
+    }
+    fn predict(linear: &Linear, fp: &PistolFingerprint6) -> Result<String> {
+        let features = fp.features()?;
+        let features = apply_scale(&features[..linear.features_len()], &linear.scale);
+        let scores = predict_value(&features, &linear.w);
+        let mut best = 0;
+        for (i, s) in scores.iter().enumerate() {
+            if *s > scores[best] {
+                best = i;
+            }
+        }
+        Ok(linear.infolist[best].clone())
+    }
+    #[test]
+    fn test_feature_names() -> Result<()> {
+        let names = feature_names();
+        assert_eq!(names.len(), FEATURES_LEN);
+        let scale: Vec<crate::os::NmapJsonParameters> =
+            serde_json::from_str(include_str!("../db/nmap-os-db-ipv6/scale.json"))?;
+        let nmap_names: Vec<String> = scale.into_iter().map(|s| s.name).collect();
+        assert_eq!(names[..NMAP_FEATURES_LEN], nmap_names);
+        Ok(())
+    }
+    #[test]
+    fn test_train_linear() -> Result<()> {
+        let samples = samples()?;
+        let linear = train_linear(&samples, &LinearTrainParams::default())?;
+        assert_eq!(linear.infolist, vec!["Linux 6.X", "Windows 11"]);
+        // the timing features are not used by default
+        assert_eq!(linear.features_len(), TIMING_FEATURES_START);
+        assert_eq!(
+            predict(&linear, &fingerprint("40", "7210", 0.045)?)?,
+            "Linux 6.X"
+        );
+        assert_eq!(
+            predict(&linear, &fingerprint("80", "2000", 0.155)?)?,
+            "Windows 11"
+        );
+
+        // the saved files are loaded back the same
+        let dir = env::temp_dir().join("pistol_test_train_linear");
+        let dir = dir.to_str().unwrap();
+        linear.save_json(dir)?;
+        let loaded = Linear::from_dir(dir)?;
+        fs::remove_dir_all(dir)?;
+        assert_eq!(loaded.infolist, linear.infolist);
+        for (a, b) in zip(loaded.w.concat(), linear.w.concat()) {
+            assert!((a - b).abs() < 1e-9);
+        }
+        assert_eq!(loaded.features_len(), linear.features_len());
+
+        let params = LinearTrainParams {
+            timing: true,
+            ..Default::default()
+        };
+        let linear = train_linear(&samples, &params)?;
+        assert_eq!(linear.features_len(), FEATURES_LEN);
+        assert_eq!(
+            predict(&linear, &fingerprint("80", "2000", 0.155)?)?,
+            "Windows 11"
+        );
+
+        assert!(train_linear(&samples[..1], &LinearTrainParams::default()).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_extend_linear() -> Result<()> {
+        let nmap_linear = gen_linear()?;
+        let samples = samples()?;
+        let linear = extend_linear(&nmap_linear, &samples, &LinearTrainParams::default())?;
+        assert_eq!(linear.infolist.len(), nmap_linear.infolist.len() + 2);
+        assert_eq!(linear.features_len(), NMAP_FEATURES_LEN);
+        assert_eq!(linear.w[0], nmap_linear.w[0]);
+        assert_eq!(
+            predict(&linear, &fingerprint("40", "7210", 0.045)?)?,
+            "Linux 6.X"
+        );
+        Ok(())
+    }
+}
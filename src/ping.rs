@@ -16,7 +16,11 @@ use std::time::Duration;
 
 pub mod icmp;
 pub mod icmpv6;
+pub mod ipproto;
+pub mod ipproto6;
 
+use crate::dns::addr_with_hostname;
+use crate::dns::ResolveHostnames;
 use crate::errors::CanNotFoundSourceAddress;
 use crate::scan::tcp;
 use crate::scan::tcp6;
@@ -33,6 +37,10 @@ use crate::Target;
 const SYN_PING_DEFAULT_PORT: u16 = 80;
 const ACK_PING_DEFAULT_PORT: u16 = 80;
 const UDP_PING_DEFAULT_PORT: u16 = 125;
+// icmp, igmp and ip-in-ip, same as nmap -PO
+const IP_PROTOCOL_PING_DEFAULT_PROTOCOLS: [u8; 3] = [1, 2, 4];
+// icmpv6 and ipv6-in-ipv6
+const IP_PROTOCOL_PING6_DEFAULT_PROTOCOLS: [u8; 2] = [58, 41];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PingStatus {
@@ -47,6 +55,7 @@ pub struct PingResults {
     pub rtts: HashMap<IpAddr, Vec<Duration>>,
     pub avg_rtt: Option<Duration>,
     pub alive_hosts: usize,
+    pub hostnames: HashMap<IpAddr, String>,
 }
 
 impl PingResults {
@@ -56,6 +65,7 @@ impl PingResults {
             rtts: HashMap::new(),
             avg_rtt: None,
             alive_hosts: 0,
+            hostnames: HashMap::new(),
         }
     }
     pub fn get_ping_status(&self, k: &IpAddr) -> Option<&Vec<PingStatus>> {
@@ -123,6 +133,15 @@ impl PingResults {
     }
 }
 
+impl ResolveHostnames for PingResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.pings.keys().copied().collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
 impl fmt::Display for PingResults {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new();
@@ -134,6 +153,7 @@ impl fmt::Display for PingResults {
         let pings: BTreeMap<IpAddr, &Vec<PingStatus>> =
             pings.into_iter().map(|(i, p)| (*i, p)).collect();
         for (ip, status) in pings {
+            let ip_str = addr_with_hostname(&ip, &self.hostnames);
             let mut status_str_vec = Vec::new();
             for s in status {
                 let s_str = match s {
@@ -144,7 +164,7 @@ impl fmt::Display for PingResults {
                 status_str_vec.push(s_str);
             }
             let status_str = status_str_vec.join("|");
-            table.add_row(row![c -> ip, c -> status_str]);
+            table.add_row(row![c -> ip_str, c -> status_str]);
         }
         let avg_rtt = match self.avg_rtt {
             Some(avg_rtt) => avg_rtt,
@@ -166,10 +186,57 @@ pub enum PingMethods {
     Ack,
     Udp,
     Icmp,
+    /// Send the default IP protocols, use `ip_protocol_ping` to choose them.
+    IpProtocol,
+}
+
+fn send_ip_protocol_probes(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    protocols: &[u8],
+    timeout: Duration,
+) -> Result<(PingStatus, Option<Duration>)> {
+    let protocols = if !protocols.is_empty() {
+        protocols.to_vec()
+    } else {
+        IP_PROTOCOL_PING_DEFAULT_PROTOCOLS.to_vec()
+    };
+    for protocol in protocols {
+        let (ret, rtt) =
+            ipproto::send_ip_protocol_ping_packet(src_ipv4, dst_ipv4, protocol, timeout)?;
+        debug!("ip protocol {} ret: {:?}", protocol, ret);
+        if ret == PingStatus::Up {
+            return Ok((ret, rtt));
+        }
+    }
+    Ok((PingStatus::Down, None))
+}
+
+fn send_ip_protocol_probes6(
+    src_ipv6: Ipv6Addr,
+    dst_ipv6: Ipv6Addr,
+    protocols: &[u8],
+    timeout: Duration,
+) -> Result<(PingStatus, Option<Duration>)> {
+    let protocols = if !protocols.is_empty() {
+        protocols.to_vec()
+    } else {
+        IP_PROTOCOL_PING6_DEFAULT_PROTOCOLS.to_vec()
+    };
+    for protocol in protocols {
+        let (ret, rtt) =
+            ipproto6::send_ip_protocol_ping_packet(src_ipv6, dst_ipv6, protocol, timeout)?;
+        debug!("ipv6 protocol {} ret: {:?}", protocol, ret);
+        if ret == PingStatus::Up {
+            return Ok((ret, rtt));
+        }
+    }
+    Ok((PingStatus::Down, None))
 }
 
 fn threads_ping(
     method: PingMethods,
+    protocols: &[u8],
     src_ipv4: Ipv4Addr,
     src_port: u16,
     dst_ipv4: Ipv4Addr,
@@ -229,12 +296,14 @@ fn threads_ping(
             debug!("icmp ret: {:?}", ret);
             (ret, rtt)
         }
+        PingMethods::IpProtocol => send_ip_protocol_probes(src_ipv4, dst_ipv4, protocols, timeout)?,
     };
     Ok((ping_status, rtt))
 }
 
 fn threads_ping6(
     method: PingMethods,
+    protocols: &[u8],
     src_ipv6: Ipv6Addr,
     src_port: u16,
     dst_ipv6: Ipv6Addr,
@@ -287,12 +356,16 @@ fn threads_ping6(
             }
         }
         PingMethods::Icmp => icmpv6::send_icmpv6_ping_packet(src_ipv6, dst_ipv6, timeout)?,
+        PingMethods::IpProtocol => {
+            send_ip_protocol_probes6(src_ipv6, dst_ipv6, protocols, timeout)?
+        }
     };
     Ok((ping_status, rtt))
 }
 
 fn ipv4_ping(
     method: PingMethods,
+    protocols: &[u8],
     src_addr: Option<IpAddr>,
     src_port: u16,
     dst_ipv4: Ipv4Addr,
@@ -304,18 +377,20 @@ fn ipv4_ping(
         None => return Err(CanNotFoundSourceAddress::new().into()),
     };
 
-    let dst_port = if method != PingMethods::Icmp {
-        dst_port
-    } else {
-        None
+    let dst_port = match method {
+        PingMethods::Icmp | PingMethods::IpProtocol => None,
+        _ => dst_port,
     };
 
-    let ret = threads_ping(method, src_ipv4, src_port, dst_ipv4, dst_port, timeout);
+    let ret = threads_ping(
+        method, protocols, src_ipv4, src_port, dst_ipv4, dst_port, timeout,
+    );
     ret
 }
 
 fn ipv6_ping(
     method: PingMethods,
+    protocols: &[u8],
     src_addr: Option<IpAddr>,
     src_port: u16,
     dst_ipv6: Ipv6Addr,
@@ -327,13 +402,14 @@ fn ipv6_ping(
         None => return Err(CanNotFoundSourceAddress::new().into()),
     };
 
-    let dst_port = if method != PingMethods::Icmp {
-        dst_port
-    } else {
-        None
+    let dst_port = match method {
+        PingMethods::Icmp | PingMethods::IpProtocol => None,
+        _ => dst_port,
     };
 
-    let ret = threads_ping6(method, src_ipv6, src_port, dst_ipv6, dst_port, timeout);
+    let ret = threads_ping6(
+        method, protocols, src_ipv6, src_port, dst_ipv6, dst_port, timeout,
+    );
     ret
 }
 
@@ -346,6 +422,39 @@ pub fn ping(
     timeout: Option<Duration>,
     tests: usize,
 ) -> Result<PingResults> {
+    let probe = PingProbe {
+        method,
+        protocols: Vec::new(),
+    };
+    ping_with_protocols(
+        target,
+        probe,
+        src_addr,
+        src_port,
+        threads_num,
+        timeout,
+        tests,
+    )
+}
+
+/// The method of the ping and the protocols of `PingMethods::IpProtocol`.
+struct PingProbe {
+    method: PingMethods,
+    /// Only used by `PingMethods::IpProtocol`, an empty list means the default protocols.
+    protocols: Vec<u8>,
+}
+
+/// Same as `ping`, with the `protocols` of the `probe`.
+fn ping_with_protocols(
+    target: Target,
+    probe: PingProbe,
+    src_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+    tests: usize,
+) -> Result<PingResults> {
+    let PingProbe { method, protocols } = probe;
     let src_port = match src_port {
         Some(p) => p,
         None => random_port(),
@@ -371,9 +480,11 @@ pub fn ping(
                     } else {
                         None
                     };
+                    let protocols = protocols.clone();
                     pool.execute(move || {
-                        let ret =
-                            ipv4_ping(method, src_addr, src_port, dst_ipv4, dst_port, timeout);
+                        let ret = ipv4_ping(
+                            method, &protocols, src_addr, src_port, dst_ipv4, dst_port, timeout,
+                        );
                         match tx.send((dst_addr, ret)) {
                             _ => (),
                         }
@@ -389,9 +500,11 @@ pub fn ping(
                     } else {
                         None
                     };
+                    let protocols = protocols.clone();
                     pool.execute(move || {
-                        let ret =
-                            ipv6_ping(method, src_addr, src_port, dst_ipv6, dst_port, timeout);
+                        let ret = ipv6_ping(
+                            method, &protocols, src_addr, src_port, dst_ipv6, dst_port, timeout,
+                        );
                         match tx.send((dst_addr, ret)) {
                             _ => (),
                         }
@@ -688,6 +801,53 @@ pub fn icmp_ping_raw(
     }
 }
 
+/// IP Protocol Ping.
+/// A newer host discovery option is the IPProto ping, which sends IP packets with the specified protocol number set in their IP header.
+/// The default is to send multiple IP packets for ICMP (protocol 1), IGMP (protocol 2), and IP-in-IP (protocol 4).
+/// For IPv6 the default is ICMPv6 (protocol 58) and IPv6-in-IPv6 (protocol 41).
+/// The ICMP, IGMP and UDP packets are sent with the proper protocol headers, others are sent with no additional data beyond the IP header.
+/// This host discovery method looks for either responses using the same protocol as a probe, or ICMP protocol unreachable messages which signify that the given protocol isn't supported on the destination host.
+/// Either type of response signifies that the target host is alive.
+pub fn ip_protocol_ping(
+    target: Target,
+    protocols: Option<Vec<u8>>,
+    src_addr: Option<IpAddr>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+    tests: usize,
+) -> Result<PingResults> {
+    let protocols = protocols.unwrap_or_default();
+    let probe = PingProbe {
+        method: PingMethods::IpProtocol,
+        protocols,
+    };
+    ping_with_protocols(target, probe, src_addr, None, threads_num, timeout, tests)
+}
+
+/// IP Protocol Ping, raw version.
+pub fn ip_protocol_ping_raw(
+    dst_addr: IpAddr,
+    protocols: Option<Vec<u8>>,
+    src_addr: Option<IpAddr>,
+    timeout: Option<Duration>,
+) -> Result<(PingStatus, Option<Duration>)> {
+    let protocols = protocols.unwrap_or_default();
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    match dst_addr {
+        IpAddr::V4(dst_ipv4) => match find_source_addr(src_addr, dst_ipv4)? {
+            Some(src_ipv4) => send_ip_protocol_probes(src_ipv4, dst_ipv4, &protocols, timeout),
+            None => Err(CanNotFoundSourceAddress::new().into()),
+        },
+        IpAddr::V6(dst_ipv6) => match find_source_addr6(src_addr, dst_ipv6)? {
+            Some(src_ipv6) => send_ip_protocol_probes6(src_ipv6, dst_ipv6, &protocols, timeout),
+            None => Err(CanNotFoundSourceAddress::new().into()),
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -766,4 +926,18 @@ mod tests {
         println!("{}", ret);
         Ok(())
     }
+    #[test]
+    fn test_ip_protocol_ping() -> Result<()> {
+        // Logger::init_debug_logging()?;
+        let src_ipv4 = None;
+        let threads_num: usize = 8;
+        let timeout = Some(Duration::new(1, 0));
+        let host = Host::new(TEST_IPV4_REMOTE.into(), None);
+        let target: Target = Target::new(vec![host]);
+        let tests = 2;
+        let protocols = Some(vec![1, 2, 4]);
+        let ret = ip_protocol_ping(target, protocols, src_ipv4, threads_num, timeout, tests)?;
+        println!("{}", ret);
+        Ok(())
+    }
 }
@@ -0,0 +1,144 @@
+use anyhow::Result;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::icmp;
+use pnet::packet::icmp::echo_request::MutableEchoRequestPacket;
+use pnet::packet::icmp::IcmpCode;
+use pnet::packet::icmp::IcmpType;
+use pnet::packet::icmp::MutableIcmpPacket;
+use pnet::packet::ip::IpNextHeaderProtocol;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4;
+use pnet::packet::ipv4::Ipv4Flags;
+use pnet::packet::ipv4::MutableIpv4Packet;
+use pnet::packet::udp;
+use pnet::packet::udp::MutableUdpPacket;
+use pnet::util;
+use rand::Rng;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::layers::layer3_ipv4_send;
+use crate::layers::Layer2Match;
+use crate::layers::Layer3Match;
+use crate::layers::Layer3MatchProtocol;
+use crate::layers::Layer4MatchIcmpError;
+use crate::layers::LayersMatch;
+use crate::layers::ICMP_HEADER_SIZE;
+use crate::layers::IPV4_HEADER_SIZE;
+use crate::layers::UDP_HEADER_SIZE;
+use crate::ping::PingStatus;
+
+const TTL: u8 = 64;
+const IGMP_HEADER_SIZE: usize = 8;
+const UDP_DST_PORT: u16 = 40125;
+
+fn protocol_payload_size(protocol: u8) -> usize {
+    match IpNextHeaderProtocol(protocol) {
+        IpNextHeaderProtocols::Icmp => ICMP_HEADER_SIZE,
+        IpNextHeaderProtocols::Igmp => IGMP_HEADER_SIZE,
+        IpNextHeaderProtocols::Udp => UDP_HEADER_SIZE,
+        // other protocols are sent with an empty payload
+        _ => 0,
+    }
+}
+
+/// Send one IP packet with the given protocol number,
+/// a response from the target with the same protocol, or an icmp error quoted the probe
+/// (include protocol unreachable) means the host is up.
+pub fn send_ip_protocol_ping_packet(
+    src_ipv4: Ipv4Addr,
+    dst_ipv4: Ipv4Addr,
+    protocol: u8,
+    timeout: Duration,
+) -> Result<(PingStatus, Option<Duration>)> {
+    let mut rng = rand::thread_rng();
+    let payload_size = protocol_payload_size(protocol);
+    // ip header
+    let mut ip_buff = vec![0u8; IPV4_HEADER_SIZE + payload_size];
+    let mut ip_header = MutableIpv4Packet::new(&mut ip_buff).unwrap();
+    ip_header.set_version(4);
+    ip_header.set_header_length(5);
+    ip_header.set_source(src_ipv4);
+    ip_header.set_destination(dst_ipv4);
+    ip_header.set_total_length((IPV4_HEADER_SIZE + payload_size) as u16);
+    let id = rng.gen();
+    ip_header.set_identification(id);
+    ip_header.set_flags(Ipv4Flags::DontFragment);
+    ip_header.set_ttl(TTL);
+    ip_header.set_next_level_protocol(IpNextHeaderProtocol(protocol));
+    let c = ipv4::checksum(&ip_header.to_immutable());
+    ip_header.set_checksum(c);
+
+    match IpNextHeaderProtocol(protocol) {
+        IpNextHeaderProtocols::Icmp => {
+            // icmp echo request
+            let mut icmp_header =
+                MutableEchoRequestPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+            icmp_header.set_icmp_type(IcmpType(8));
+            icmp_header.set_icmp_code(IcmpCode(0));
+            icmp_header.set_sequence_number(1);
+            icmp_header.set_identifier(rng.gen());
+            let mut icmp_header = MutableIcmpPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+            let checksum = icmp::checksum(&icmp_header.to_immutable());
+            icmp_header.set_checksum(checksum);
+        }
+        IpNextHeaderProtocols::Igmp => {
+            // igmp membership query (type 0x11) for all groups
+            let igmp_buff = &mut ip_buff[IPV4_HEADER_SIZE..];
+            igmp_buff[0] = 0x11;
+            let checksum = util::checksum(igmp_buff, 1);
+            igmp_buff[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+        IpNextHeaderProtocols::Udp => {
+            let mut udp_header = MutableUdpPacket::new(&mut ip_buff[IPV4_HEADER_SIZE..]).unwrap();
+            udp_header.set_source(rng.gen_range(1024..65535));
+            udp_header.set_destination(UDP_DST_PORT);
+            udp_header.set_length(UDP_HEADER_SIZE as u16);
+            let checksum = udp::ipv4_checksum(&udp_header.to_immutable(), &src_ipv4, &dst_ipv4);
+            udp_header.set_checksum(checksum);
+        }
+        _ => (),
+    }
+
+    let layer2 = Layer2Match {
+        src_mac: None,
+        dst_mac: None,
+        ethernet_type: Some(EtherTypes::Ipv4),
+    };
+    let layer3 = Layer3Match {
+        layer2: Some(layer2),
+        src_addr: Some(dst_ipv4.into()),
+        dst_addr: Some(src_ipv4.into()),
+    };
+    // the response with the same protocol as the probe
+    let layer3_protocol = Layer3MatchProtocol {
+        layer3: Some(layer3),
+        protocol: IpNextHeaderProtocol(protocol),
+    };
+    // or the error message quoted the probe, e.g. protocol unreachable
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv4.into()),
+        quoted_dst_addr: Some(dst_ipv4.into()),
+        quoted_protocol: Some(IpNextHeaderProtocol(protocol)),
+        quoted_src_port: None,
+        quoted_dst_port: None,
+        quoted_id: None,
+    };
+    let layers_match_1 = LayersMatch::Layer3MatchProtocol(layer3_protocol);
+    let layers_match_2 = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    let (ret, rtt) = layer3_ipv4_send(
+        src_ipv4,
+        dst_ipv4,
+        &ip_buff,
+        vec![layers_match_1, layers_match_2],
+        timeout,
+    )?;
+    match ret {
+        // echo reply, igmp report, icmp protocol unreachable (type 3, code 2) or other icmp errors
+        Some(_) => Ok((PingStatus::Up, rtt)),
+        // no response received (even after retransmissions)
+        None => Ok((PingStatus::Down, rtt)),
+    }
+}
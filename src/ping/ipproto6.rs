@@ -0,0 +1,128 @@
+use anyhow::Result;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::icmpv6;
+use pnet::packet::icmpv6::echo_request::MutableEchoRequestPacket;
+use pnet::packet::icmpv6::Icmpv6Code;
+use pnet::packet::icmpv6::Icmpv6Type;
+use pnet::packet::icmpv6::MutableIcmpv6Packet;
+use pnet::packet::ip::IpNextHeaderProtocol;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::udp;
+use pnet::packet::udp::MutableUdpPacket;
+use rand::Rng;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use crate::layers::layer3_ipv6_send;
+use crate::layers::Layer2Match;
+use crate::layers::Layer3Match;
+use crate::layers::Layer3MatchProtocol;
+use crate::layers::Layer4MatchIcmpError;
+use crate::layers::LayersMatch;
+use crate::layers::ICMPV6_ER_HEADER_SIZE;
+use crate::layers::IPV6_HEADER_SIZE;
+use crate::layers::UDP_HEADER_SIZE;
+use crate::ping::PingStatus;
+
+const TTL: u8 = 255;
+const UDP_DST_PORT: u16 = 40125;
+
+fn protocol_payload_size(protocol: u8) -> usize {
+    match IpNextHeaderProtocol(protocol) {
+        IpNextHeaderProtocols::Icmpv6 => ICMPV6_ER_HEADER_SIZE,
+        IpNextHeaderProtocols::Udp => UDP_HEADER_SIZE,
+        // other protocols are sent with an empty payload
+        _ => 0,
+    }
+}
+
+/// Send one IPv6 packet with the given next header value,
+/// a response from the target with the same next header, or an icmpv6 error quoted the probe
+/// (include unrecognized next header) means the host is up.
+pub fn send_ip_protocol_ping_packet(
+    src_ipv6: Ipv6Addr,
+    dst_ipv6: Ipv6Addr,
+    protocol: u8,
+    timeout: Duration,
+) -> Result<(PingStatus, Option<Duration>)> {
+    let mut rng = rand::thread_rng();
+    let payload_size = protocol_payload_size(protocol);
+    // ipv6 header
+    let mut ipv6_buff = vec![0u8; IPV6_HEADER_SIZE + payload_size];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_flow_label(0x12345);
+    ipv6_header.set_payload_length(payload_size as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocol(protocol));
+    ipv6_header.set_hop_limit(TTL);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(dst_ipv6);
+
+    match IpNextHeaderProtocol(protocol) {
+        IpNextHeaderProtocols::Icmpv6 => {
+            // icmpv6 echo request
+            let mut icmpv6_header =
+                MutableEchoRequestPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+            icmpv6_header.set_icmpv6_type(Icmpv6Type(128));
+            icmpv6_header.set_icmpv6_code(Icmpv6Code(0));
+            icmpv6_header.set_sequence_number(1);
+            icmpv6_header.set_identifier(rng.gen());
+            let mut icmpv6_header =
+                MutableIcmpv6Packet::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+            let checksum = icmpv6::checksum(&icmpv6_header.to_immutable(), &src_ipv6, &dst_ipv6);
+            icmpv6_header.set_checksum(checksum);
+        }
+        IpNextHeaderProtocols::Udp => {
+            let mut udp_header = MutableUdpPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+            udp_header.set_source(rng.gen_range(1024..65535));
+            udp_header.set_destination(UDP_DST_PORT);
+            udp_header.set_length(UDP_HEADER_SIZE as u16);
+            let checksum = udp::ipv6_checksum(&udp_header.to_immutable(), &src_ipv6, &dst_ipv6);
+            udp_header.set_checksum(checksum);
+        }
+        _ => (),
+    }
+
+    let layer2 = Layer2Match {
+        src_mac: None,
+        dst_mac: None,
+        ethernet_type: Some(EtherTypes::Ipv6),
+    };
+    let layer3 = Layer3Match {
+        layer2: Some(layer2),
+        src_addr: Some(dst_ipv6.into()),
+        dst_addr: Some(src_ipv6.into()),
+    };
+    // the response with the same protocol as the probe
+    let layer3_protocol = Layer3MatchProtocol {
+        layer3: Some(layer3),
+        protocol: IpNextHeaderProtocol(protocol),
+    };
+    // or the error message quoted the probe, e.g. protocol unreachable
+    let layer4_icmp_error = Layer4MatchIcmpError {
+        layer3: Some(layer3),
+        quoted_src_addr: Some(src_ipv6.into()),
+        quoted_dst_addr: Some(dst_ipv6.into()),
+        quoted_protocol: Some(IpNextHeaderProtocol(protocol)),
+        quoted_src_port: None,
+        quoted_dst_port: None,
+        quoted_id: None,
+    };
+    let layers_match_1 = LayersMatch::Layer3MatchProtocol(layer3_protocol);
+    let layers_match_2 = LayersMatch::Layer4MatchIcmpError(layer4_icmp_error);
+
+    let (ret, rtt) = layer3_ipv6_send(
+        src_ipv6,
+        dst_ipv6,
+        &ipv6_buff,
+        vec![layers_match_1, layers_match_2],
+        timeout,
+    )?;
+    match ret {
+        // echo reply, parameter problem (type 4, code 1) or other icmpv6 errors
+        Some(_) => Ok((PingStatus::Up, rtt)),
+        // no response received (even after retransmissions)
+        None => Ok((PingStatus::Down, rtt)),
+    }
+}
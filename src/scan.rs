@@ -18,15 +18,26 @@ use std::sync::mpsc::channel;
 use std::time::Duration;
 
 pub mod arp;
+pub mod discovery;
+pub mod ndp;
+pub mod passive;
 pub mod tcp;
 pub mod tcp6;
 pub mod udp;
 pub mod udp6;
 
+use crate::dns::addr_with_hostname;
+use crate::dns::ResolveHostnames;
 use crate::errors::CanNotFoundInterface;
 use crate::errors::CanNotFoundMacAddress;
 use crate::errors::CanNotFoundSourceAddress;
+use crate::layers::layer2_capture;
+use crate::scan::discovery::DiscoveryMethod;
+use crate::scan::discovery::DiscoveryRecord;
+use crate::scan::passive::PassiveProtocol;
+use crate::scan::passive::PassiveRecord;
 use crate::utils::find_interface_by_ip;
+use crate::utils::find_interface_by_name;
 use crate::utils::find_source_addr;
 use crate::utils::find_source_addr6;
 use crate::utils::get_default_timeout;
@@ -66,6 +77,9 @@ pub struct PortScanResults {
     pub rtts: HashMap<IpAddr, HashMap<u16, Vec<Duration>>>,
     pub avg_rtt: Option<Duration>,
     pub open_ports: usize,
+    pub hostnames: HashMap<IpAddr, String>,
+    /// The scan method of the results, which tells the protocol of the ports.
+    pub method: Option<ScanMethod>,
 }
 
 impl PortScanResults {
@@ -75,6 +89,8 @@ impl PortScanResults {
             rtts: HashMap::new(),
             avg_rtt: None,
             open_ports: 0,
+            hostnames: HashMap::new(),
+            method: None,
         }
     }
     pub fn get(&self, k: &IpAddr) -> Option<&HashMap<u16, Vec<PortStatus>>> {
@@ -164,6 +180,15 @@ impl PortScanResults {
     }
 }
 
+impl ResolveHostnames for PortScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.scans.keys().copied().collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
 impl fmt::Display for PortScanResults {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new();
@@ -176,6 +201,7 @@ impl fmt::Display for PortScanResults {
         let scans: BTreeMap<IpAddr, &HashMap<u16, Vec<PortStatus>>> =
             scans.into_iter().map(|(i, h)| (*i, h)).collect();
         for (ip, ports_status) in scans {
+            let ip_str = addr_with_hostname(&ip, &self.hostnames);
             let ports_status: BTreeMap<u16, &Vec<PortStatus>> =
                 ports_status.into_iter().map(|(p, s)| (*p, s)).collect();
             for (port, status) in ports_status {
@@ -194,7 +220,7 @@ impl fmt::Display for PortScanResults {
                     status_str_vec.push(s_str);
                 }
                 let status_str = status_str_vec.join("|");
-                table.add_row(row![c -> ip, c-> port, c -> status_str]);
+                table.add_row(row![c -> ip_str, c-> port, c -> status_str]);
             }
         }
         let avg_rtt = match self.avg_rtt {
@@ -227,6 +253,7 @@ pub struct ArpAliveHost {
 pub struct ArpScanResults {
     pub alive_hosts: HashMap<Ipv4Addr, ArpAliveHost>,
     pub alive_host_num: usize,
+    pub hostnames: HashMap<IpAddr, String>,
 }
 
 impl ArpScanResults {
@@ -234,6 +261,7 @@ impl ArpScanResults {
         ArpScanResults {
             alive_hosts: HashMap::new(),
             alive_host_num: 0,
+            hostnames: HashMap::new(),
         }
     }
     pub fn get(&self, k: &Ipv4Addr) -> Option<&ArpAliveHost> {
@@ -245,6 +273,15 @@ impl ArpScanResults {
     }
 }
 
+impl ResolveHostnames for ArpScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.alive_hosts.keys().map(|a| (*a).into()).collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
 impl fmt::Display for ArpScanResults {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new();
@@ -255,7 +292,8 @@ impl fmt::Display for ArpScanResults {
         let ah = &self.alive_hosts;
         let ah: BTreeMap<Ipv4Addr, &ArpAliveHost> = ah.into_iter().map(|(i, a)| (*i, a)).collect();
         for (ip, aah) in ah {
-            table.add_row(row![c -> ip, c -> aah.mac_addr, c -> aah.ouis]);
+            let ip_str = addr_with_hostname(&ip.into(), &self.hostnames);
+            table.add_row(row![c -> ip_str, c -> aah.mac_addr, c -> aah.ouis]);
         }
 
         let summary = format!("Summary:\nalive hosts: {}", self.alive_host_num);
@@ -265,13 +303,389 @@ impl fmt::Display for ArpScanResults {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct NdpAliveHost {
+    pub mac_addr: MacAddr,
+    pub ouis: String,
+    pub rtt: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NdpScanResults {
+    pub alive_hosts: HashMap<Ipv6Addr, NdpAliveHost>,
+    pub alive_host_num: usize,
+    pub hostnames: HashMap<IpAddr, String>,
+}
+
+impl Default for NdpScanResults {
+    fn default() -> NdpScanResults {
+        NdpScanResults::new()
+    }
+}
+
+impl NdpScanResults {
+    pub fn new() -> NdpScanResults {
+        NdpScanResults {
+            alive_hosts: HashMap::new(),
+            alive_host_num: 0,
+            hostnames: HashMap::new(),
+        }
+    }
+    pub fn get(&self, k: &Ipv6Addr) -> Option<&NdpAliveHost> {
+        self.alive_hosts.get(k)
+    }
+    pub fn enrichment(&mut self) {
+        // alive hosts
+        self.alive_host_num = self.alive_hosts.len();
+    }
+    fn insert(&mut self, ipv6: Ipv6Addr, mac: MacAddr, ouis: String, rtt: Option<Duration>) {
+        // keep the first (fastest) response
+        match self.alive_hosts.get(&ipv6) {
+            Some(_) => (),
+            None => {
+                let nah = NdpAliveHost {
+                    mac_addr: mac,
+                    ouis,
+                    rtt,
+                };
+                self.alive_hosts.insert(ipv6, nah);
+            }
+        }
+    }
+}
+
+impl ResolveHostnames for NdpScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.alive_hosts.keys().map(|a| (*a).into()).collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
+impl fmt::Display for NdpScanResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("NDP Scan Results")
+            .style_spec("c")
+            .with_hspan(4)]));
+
+        let ah = &self.alive_hosts;
+        let ah: BTreeMap<Ipv6Addr, &NdpAliveHost> = ah.iter().map(|(i, a)| (*i, a)).collect();
+        for (ip, nah) in ah {
+            let rtt_str = match nah.rtt {
+                Some(rtt) => format!("{:.1}ms", rtt.as_secs_f64() * 1000.0),
+                None => String::new(),
+            };
+            let ip_str = addr_with_hostname(&ip.into(), &self.hostnames);
+            table.add_row(row![c -> ip_str, c -> nah.mac_addr, c -> nah.ouis, c -> rtt_str]);
+        }
+
+        let summary = format!("Summary:\nalive hosts: {}", self.alive_host_num);
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(4)]));
+
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveAliveHost {
+    pub mac_addr: MacAddr,
+    pub ouis: String,
+    pub addrs: Vec<IpAddr>,
+    pub hostnames: Vec<String>,
+    pub protocols: Vec<PassiveProtocol>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveScanResults {
+    pub alive_hosts: HashMap<MacAddr, PassiveAliveHost>,
+    pub alive_host_num: usize,
+    pub hostnames: HashMap<IpAddr, String>,
+}
+
+impl Default for PassiveScanResults {
+    fn default() -> PassiveScanResults {
+        PassiveScanResults::new()
+    }
+}
+
+impl PassiveScanResults {
+    pub fn new() -> PassiveScanResults {
+        PassiveScanResults {
+            alive_hosts: HashMap::new(),
+            alive_host_num: 0,
+            hostnames: HashMap::new(),
+        }
+    }
+    pub fn get(&self, k: &MacAddr) -> Option<&PassiveAliveHost> {
+        self.alive_hosts.get(k)
+    }
+    /// Search the host by one of its addresses.
+    pub fn get_by_addr(&self, addr: &IpAddr) -> Option<&PassiveAliveHost> {
+        self.alive_hosts
+            .values()
+            .find(|&pah| pah.addrs.contains(addr))
+            .map(|v| v as _)
+    }
+    pub fn enrichment(&mut self) {
+        // alive hosts
+        self.alive_host_num = self.alive_hosts.len();
+    }
+    fn insert(&mut self, record: PassiveRecord, nmap_mac_prefixes: &[NmapMacPrefix]) {
+        let mac_addr = record.mac_addr;
+        if mac_addr == MacAddr::zero() || mac_addr == MacAddr::broadcast() {
+            return;
+        }
+        let pah = match self.alive_hosts.get_mut(&mac_addr) {
+            Some(pah) => pah,
+            None => {
+                let pah = PassiveAliveHost {
+                    mac_addr,
+                    ouis: get_mac_ouis(mac_addr, nmap_mac_prefixes),
+                    addrs: Vec::new(),
+                    hostnames: Vec::new(),
+                    protocols: Vec::new(),
+                };
+                self.alive_hosts.insert(mac_addr, pah);
+                self.alive_hosts.get_mut(&mac_addr).unwrap()
+            }
+        };
+        if let Some(addr) = record.addr {
+            if !pah.addrs.contains(&addr) {
+                pah.addrs.push(addr);
+            }
+        }
+        if let Some(hostname) = record.hostname {
+            if !hostname.is_empty() && !pah.hostnames.contains(&hostname) {
+                pah.hostnames.push(hostname);
+            }
+        }
+        if !pah.protocols.contains(&record.protocol) {
+            pah.protocols.push(record.protocol);
+        }
+    }
+}
+
+impl ResolveHostnames for PassiveScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        let mut addrs = Vec::new();
+        for pah in self.alive_hosts.values() {
+            for addr in &pah.addrs {
+                if !addrs.contains(addr) {
+                    addrs.push(*addr);
+                }
+            }
+        }
+        addrs
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
+impl fmt::Display for PassiveScanResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Passive Scan Results")
+            .style_spec("c")
+            .with_hspan(5)]));
+
+        let ah = &self.alive_hosts;
+        let ah: BTreeMap<MacAddr, &PassiveAliveHost> = ah.iter().map(|(m, p)| (*m, p)).collect();
+        for (mac, pah) in ah {
+            let addrs_str: Vec<String> = pah
+                .addrs
+                .iter()
+                .map(|a| addr_with_hostname(a, &self.hostnames))
+                .collect();
+            let addrs_str = addrs_str.join("\n");
+            let hostnames_str = pah.hostnames.join("\n");
+            let protocols_str: Vec<String> = pah.protocols.iter().map(|p| p.to_string()).collect();
+            let protocols_str = protocols_str.join("|");
+            table.add_row(
+                row![c -> mac, c -> pah.ouis, c -> addrs_str, c -> hostnames_str, c -> protocols_str],
+            );
+        }
+
+        let summary = format!("Summary:\nalive hosts: {}", self.alive_host_num);
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(5)]));
+
+        write!(f, "{}", table)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryAliveHost {
+    pub mac_addr: Option<MacAddr>,
+    pub ouis: String,
+    pub names: Vec<String>,
+    pub services: Vec<String>,
+    pub model: Option<String>,
+    pub methods: Vec<DiscoveryMethod>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryScanResults {
+    pub alive_hosts: HashMap<IpAddr, DiscoveryAliveHost>,
+    pub alive_host_num: usize,
+    pub hostnames: HashMap<IpAddr, String>,
+}
+
+impl Default for DiscoveryScanResults {
+    fn default() -> DiscoveryScanResults {
+        DiscoveryScanResults::new()
+    }
+}
+
+impl DiscoveryScanResults {
+    pub fn new() -> DiscoveryScanResults {
+        DiscoveryScanResults {
+            alive_hosts: HashMap::new(),
+            alive_host_num: 0,
+            hostnames: HashMap::new(),
+        }
+    }
+    pub fn get(&self, k: &IpAddr) -> Option<&DiscoveryAliveHost> {
+        self.alive_hosts.get(k)
+    }
+    pub fn enrichment(&mut self) {
+        // alive hosts
+        self.alive_host_num = self.alive_hosts.len();
+    }
+    fn get_or_insert(&mut self, addr: IpAddr) -> &mut DiscoveryAliveHost {
+        self.alive_hosts.entry(addr).or_insert(DiscoveryAliveHost {
+            mac_addr: None,
+            ouis: String::new(),
+            names: Vec::new(),
+            services: Vec::new(),
+            model: None,
+            methods: Vec::new(),
+        })
+    }
+    fn insert(&mut self, record: DiscoveryRecord, nmap_mac_prefixes: &[NmapMacPrefix]) {
+        let dah = self.get_or_insert(record.addr);
+        if let Some(mac_addr) = record.mac_addr {
+            if dah.mac_addr.is_none() {
+                dah.mac_addr = Some(mac_addr);
+                dah.ouis = get_mac_ouis(mac_addr, nmap_mac_prefixes);
+            }
+        }
+        if let Some(name) = record.name {
+            if !name.is_empty() && !dah.names.contains(&name) {
+                dah.names.push(name);
+            }
+        }
+        for service in record.services {
+            if !dah.services.contains(&service) {
+                dah.services.push(service);
+            }
+        }
+        if let Some(model) = record.model {
+            if dah.model.is_none() {
+                dah.model = Some(model);
+            }
+        }
+        if !dah.methods.contains(&record.method) {
+            dah.methods.push(record.method);
+        }
+    }
+    /// Merge the arp scan results by ip, the hosts only found by arp are added too.
+    pub fn merge_arp(&mut self, arp_scan_results: &ArpScanResults) {
+        for (ipv4, aah) in &arp_scan_results.alive_hosts {
+            let dah = self.get_or_insert((*ipv4).into());
+            // the mac from arp is more reliable than the netbios unit id
+            dah.mac_addr = Some(aah.mac_addr);
+            dah.ouis = aah.ouis.clone();
+            if !dah.methods.contains(&DiscoveryMethod::Arp) {
+                dah.methods.push(DiscoveryMethod::Arp);
+            }
+        }
+        self.enrichment();
+    }
+    /// Merge other discovery scan results.
+    pub fn merge(&mut self, other: DiscoveryScanResults) {
+        for (addr, hostname) in other.hostnames {
+            self.hostnames.entry(addr).or_insert(hostname);
+        }
+        for (addr, odah) in other.alive_hosts {
+            let dah = self.get_or_insert(addr);
+            if dah.mac_addr.is_none() {
+                dah.mac_addr = odah.mac_addr;
+                dah.ouis = odah.ouis;
+            }
+            for name in odah.names {
+                if !dah.names.contains(&name) {
+                    dah.names.push(name);
+                }
+            }
+            for service in odah.services {
+                if !dah.services.contains(&service) {
+                    dah.services.push(service);
+                }
+            }
+            if dah.model.is_none() {
+                dah.model = odah.model;
+            }
+            for method in odah.methods {
+                if !dah.methods.contains(&method) {
+                    dah.methods.push(method);
+                }
+            }
+        }
+        self.enrichment();
+    }
+}
+
+impl ResolveHostnames for DiscoveryScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.alive_hosts.keys().copied().collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
+impl fmt::Display for DiscoveryScanResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![Cell::new("Discovery Scan Results")
+            .style_spec("c")
+            .with_hspan(7)]));
+
+        let ah = &self.alive_hosts;
+        let ah: BTreeMap<IpAddr, &DiscoveryAliveHost> = ah.iter().map(|(i, d)| (*i, d)).collect();
+        for (ip, dah) in ah {
+            let mac_str = match dah.mac_addr {
+                Some(m) => m.to_string(),
+                None => String::new(),
+            };
+            let names_str = dah.names.join("\n");
+            let services_str = dah.services.join("\n");
+            let model_str = match &dah.model {
+                Some(m) => m.to_string(),
+                None => String::new(),
+            };
+            let methods_str: Vec<String> = dah.methods.iter().map(|m| m.to_string()).collect();
+            let methods_str = methods_str.join("|");
+            let ip_str = addr_with_hostname(&ip, &self.hostnames);
+            table.add_row(row![c -> ip_str, c -> mac_str, c -> dah.ouis, c -> names_str, c -> services_str, c -> model_str, c -> methods_str]);
+        }
+
+        let summary = format!("Summary:\nalive hosts: {}", self.alive_host_num);
+        table.add_row(Row::new(vec![Cell::new(&summary).with_hspan(7)]));
+
+        write!(f, "{}", table)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NmapMacPrefix {
     pub prefix: String,
     pub ouis: String,
 }
 
-fn get_nmap_mac_prefixes() -> Vec<NmapMacPrefix> {
+pub fn get_nmap_mac_prefixes() -> Vec<NmapMacPrefix> {
     let nmap_mac_prefixes_file = include_str!("./db/nmap-mac-prefixes");
     let mut nmap_mac_prefixes = Vec::new();
     for l in nmap_mac_prefixes_file.lines() {
@@ -295,6 +709,18 @@ fn get_nmap_mac_prefixes() -> Vec<NmapMacPrefix> {
     ret
 }
 
+/// Search the vendor of the mac address in nmap-mac-prefixes.
+pub fn get_mac_ouis(mac: MacAddr, nmap_mac_prefixes: &[NmapMacPrefix]) -> String {
+    let mac_prefix = format!("{:02X}{:02X}{:02X}", mac.0, mac.1, mac.2);
+    let mut ouis = String::new();
+    for p in nmap_mac_prefixes {
+        if mac_prefix == p.prefix {
+            ouis = p.ouis.to_string();
+        }
+    }
+    ouis
+}
+
 fn ipv4_arp_scan(
     dst_ipv4: Ipv4Addr,
     dst_mac: MacAddr,
@@ -385,41 +811,7 @@ pub fn arp_scan(
         match v {
             Ok((target_ipv4, target_mac)) => match target_mac? {
                 (Some(m), Some(_rtt)) => {
-                    let mut ouis = String::new();
-                    let mut mac_prefix = String::new();
-                    let m0 = format!("{:X}", m.0);
-                    let m1 = format!("{:X}", m.1);
-                    let m2 = format!("{:X}", m.2);
-                    // m0
-                    let i = if m0.len() < 2 { 2 - m0.len() } else { 0 };
-                    if i > 0 {
-                        for _ in 0..i {
-                            mac_prefix += "0";
-                        }
-                    }
-                    mac_prefix += &m0;
-                    // m1
-                    let i = if m1.len() < 2 { 2 - m1.len() } else { 0 };
-                    if i > 0 {
-                        for _ in 0..i {
-                            mac_prefix += "0";
-                        }
-                    }
-                    mac_prefix += &m1;
-                    // m2
-                    let i = if m2.len() < 2 { 2 - m2.len() } else { 0 };
-                    if i > 0 {
-                        for _ in 0..i {
-                            mac_prefix += "0";
-                        }
-                    }
-                    mac_prefix += &m2;
-                    // println!("{}", mac_prefix);
-                    for p in &nmap_mac_prefixes {
-                        if mac_prefix == p.prefix {
-                            ouis = p.ouis.to_string();
-                        }
-                    }
+                    let ouis = get_mac_ouis(m, &nmap_mac_prefixes);
                     let aah = ArpAliveHost { mac_addr: m, ouis };
                     ret.alive_hosts.insert(target_ipv4, aah);
                 }
@@ -432,6 +824,288 @@ pub fn arp_scan(
     Ok(ret)
 }
 
+fn ipv6_ndp_scan(
+    dst_ipv6: Ipv6Addr,
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<(Option<MacAddr>, Option<Duration>)> {
+    let src_ipv6 = match find_source_addr6(src_addr, dst_ipv6)? {
+        Some(s) => s,
+        None => return Err(CanNotFoundSourceAddress::new().into()),
+    };
+    let interface = match find_interface_by_ip(src_ipv6.into()) {
+        Some(i) => i,
+        None => return Err(CanNotFoundInterface::new().into()),
+    };
+    let src_mac = match interface.mac {
+        Some(m) => m,
+        None => return Err(CanNotFoundMacAddress::new().into()),
+    };
+    ndp::send_ndp_ns_scan_packet(dst_ipv6, src_ipv6, src_mac, interface, timeout)
+}
+
+pub fn ndp_scan_raw(
+    dst_ipv6: Ipv6Addr,
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<Option<MacAddr>> {
+    match ipv6_ndp_scan(dst_ipv6, src_addr, timeout) {
+        Ok((mac, _)) => Ok(mac),
+        Err(e) => Err(e),
+    }
+}
+
+/// NDP Scan.
+/// The IPv6 version of the ARP scan, this will sends neighbor solicitation packets to hosts on the local network and displays any neighbor advertisement that are received.
+pub fn ndp_scan(
+    target: Target,
+    src_addr: Option<IpAddr>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<NdpScanResults> {
+    let nmap_mac_prefixes = get_nmap_mac_prefixes();
+    let mut ret = NdpScanResults::new();
+
+    let pool = get_threads_pool(threads_num);
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+
+    let (tx, rx) = channel();
+    let mut recv_size = 0;
+    for host in target.hosts {
+        let dst_addr = host.addr;
+        match dst_addr {
+            IpAddr::V4(_) => {
+                warn!("ndp scan not support the ipv4 address");
+            }
+            IpAddr::V6(dst_ipv6) => {
+                let tx = tx.clone();
+                recv_size += 1;
+                pool.execute(move || {
+                    let scan_ret = ipv6_ndp_scan(dst_ipv6, src_addr, timeout);
+                    let _ = tx.send((dst_ipv6, scan_ret));
+                });
+            }
+        }
+    }
+    let iter = rx.into_iter().take(recv_size);
+    for (target_ipv6, target_mac) in iter {
+        if let (Some(m), Some(rtt)) = target_mac? {
+            let ouis = get_mac_ouis(m, &nmap_mac_prefixes);
+            ret.insert(target_ipv6, m, ouis, Some(rtt));
+        }
+    }
+    ret.enrichment();
+    Ok(ret)
+}
+
+/// NDP Multicast Scan.
+/// Find the link-local hosts without a target list,
+/// sends an ICMPv6 echo request to all-nodes (ff02::1) and a router solicitation to all-routers (ff02::2) from the interface,
+/// and collects every echo reply and router advertisement received before the timeout.
+/// If `src_addr` is none, the link-local address of the interface is used.
+pub fn ndp_multicast_scan(
+    iface: &str,
+    src_addr: Option<IpAddr>,
+    timeout: Option<Duration>,
+) -> Result<NdpScanResults> {
+    let nmap_mac_prefixes = get_nmap_mac_prefixes();
+    let mut ret = NdpScanResults::new();
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+
+    let interface = match find_interface_by_name(iface) {
+        Some(i) => i,
+        None => return Err(CanNotFoundInterface::new().into()),
+    };
+    let src_mac = match interface.mac {
+        Some(m) => m,
+        None => return Err(CanNotFoundMacAddress::new().into()),
+    };
+    let src_ipv6 = match src_addr {
+        Some(IpAddr::V6(src_ipv6)) => Some(src_ipv6),
+        _ => {
+            let mut src_ipv6 = None;
+            for ipnetwork in &interface.ips {
+                if let IpAddr::V6(ipv6) = ipnetwork.ip() {
+                    if ipv6.segments()[0] & 0xffc0 == 0xfe80 {
+                        src_ipv6 = Some(ipv6);
+                        break;
+                    }
+                }
+            }
+            src_ipv6
+        }
+    };
+    let src_ipv6 = match src_ipv6 {
+        Some(s) => s,
+        None => return Err(CanNotFoundSourceAddress::new().into()),
+    };
+
+    let mut responses = ndp::send_ndp_multicast_echo_packet(src_ipv6, interface.clone(), timeout)?;
+    let rs_responses = ndp::send_ndp_multicast_rs_packet(src_ipv6, src_mac, interface, timeout)?;
+    responses.extend(rs_responses);
+    for (ipv6, mac, rtt) in responses {
+        if ipv6 != src_ipv6 {
+            let ouis = get_mac_ouis(mac, &nmap_mac_prefixes);
+            ret.insert(ipv6, mac, ouis, Some(rtt));
+        }
+    }
+    ret.enrichment();
+    Ok(ret)
+}
+
+/// Passive Scan.
+/// Nothing is sent, this will listen on the interface for the duration and watches the ARP, NDP, DHCP, mDNS, LLMNR, NetBIOS, STP and LLDP frames,
+/// every host found in them is returned with its addresses and the hostnames it announced.
+pub fn passive_scan(iface: &str, duration: Duration) -> Result<PassiveScanResults> {
+    let nmap_mac_prefixes = get_nmap_mac_prefixes();
+    let mut ret = PassiveScanResults::new();
+    let interface = match find_interface_by_name(iface) {
+        Some(i) => i,
+        None => return Err(CanNotFoundInterface::new().into()),
+    };
+    layer2_capture(interface, duration, |buff| {
+        for record in passive::parse_frame(buff) {
+            ret.insert(record, &nmap_mac_prefixes);
+        }
+    })?;
+    ret.enrichment();
+    Ok(ret)
+}
+
+fn records_to_results(records: Vec<DiscoveryRecord>) -> DiscoveryScanResults {
+    let nmap_mac_prefixes = get_nmap_mac_prefixes();
+    let mut ret = DiscoveryScanResults::new();
+    for record in records {
+        ret.insert(record, &nmap_mac_prefixes);
+    }
+    ret.enrichment();
+    ret
+}
+
+/// mDNS Discovery Scan.
+/// Ask the DNS-SD service types on the link (224.0.0.251) and then the instances of every type,
+/// the responders are returned with the host name, the advertised services and the model in the TXT records.
+pub fn mdns_discovery_scan(
+    src_addr: Option<IpAddr>,
+    timeout: Option<Duration>,
+) -> Result<DiscoveryScanResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let records = discovery::send_mdns_discovery_packet(src_addr, timeout)?;
+    Ok(records_to_results(records))
+}
+
+/// SSDP Discovery Scan.
+/// Send the M-SEARCH (ssdp:all) to 239.255.255.250, then fetch the UPnP description of every responder
+/// to get its friendly name and model.
+pub fn ssdp_discovery_scan(
+    src_addr: Option<IpAddr>,
+    timeout: Option<Duration>,
+) -> Result<DiscoveryScanResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let records = discovery::send_ssdp_discovery_packet(src_addr, timeout)?;
+    Ok(records_to_results(records))
+}
+
+/// LLMNR Discovery Scan.
+/// Send the LLMNR reverse (PTR) query of every host to the host itself by unicast,
+/// the windows hosts answer the query of its own address with its name.
+pub fn llmnr_discovery_scan(
+    target: Target,
+    src_addr: Option<IpAddr>,
+    timeout: Option<Duration>,
+) -> Result<DiscoveryScanResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let dst_addrs: Vec<IpAddr> = target.hosts.iter().map(|h| h.addr).collect();
+    let records = discovery::send_llmnr_discovery_packet(&dst_addrs, src_addr, timeout)?;
+    Ok(records_to_results(records))
+}
+
+fn threads_netbios_node_status(
+    responders: Vec<DiscoveryRecord>,
+    threads_num: usize,
+    timeout: Duration,
+) -> Result<Vec<DiscoveryRecord>> {
+    let pool = get_threads_pool(threads_num);
+    let (tx, rx) = channel();
+    let mut recv_size = 0;
+    for record in responders {
+        match record.addr {
+            IpAddr::V4(dst_ipv4) => {
+                let tx = tx.clone();
+                recv_size += 1;
+                pool.execute(move || {
+                    let scan_ret = discovery::send_netbios_node_status_packet(dst_ipv4, timeout);
+                    // keep the responder even if it does not answer the node status request
+                    let scan_ret = match scan_ret {
+                        Ok(Some(r)) => Ok(r),
+                        Ok(None) => Ok(record),
+                        Err(e) => Err(e),
+                    };
+                    let _ = tx.send(scan_ret);
+                });
+            }
+            IpAddr::V6(_) => (),
+        }
+    }
+    let mut ret = Vec::new();
+    let iter = rx.into_iter().take(recv_size);
+    for v in iter {
+        ret.push(v?);
+    }
+    Ok(ret)
+}
+
+/// NetBIOS Discovery Scan.
+/// Broadcast the NetBIOS name query of the wildcard name "*", then send the node status request (nbtstat -A)
+/// to every responder, like `nmblookup -S '*'`,
+/// the names table tells the host name, the services (workstation, file server, domain controller...) and the mac address.
+pub fn netbios_discovery_scan(
+    src_addr: Option<IpAddr>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<DiscoveryScanResults> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => get_default_timeout(),
+    };
+    let responders = discovery::send_netbios_discovery_packet(src_addr, timeout)?;
+    let records = threads_netbios_node_status(responders, threads_num, timeout)?;
+    Ok(records_to_results(records))
+}
+
+/// Discovery Scan.
+/// Run the mDNS, SSDP, LLMNR and NetBIOS discovery and the ARP scan, all the results are merged by ip.
+/// The multicast responders which not in the target are kept too.
+pub fn discovery_scan(
+    target: Target,
+    src_addr: Option<IpAddr>,
+    threads_num: usize,
+    timeout: Option<Duration>,
+) -> Result<DiscoveryScanResults> {
+    let mut ret = mdns_discovery_scan(src_addr, timeout)?;
+    ret.merge(ssdp_discovery_scan(src_addr, timeout)?);
+    ret.merge(llmnr_discovery_scan(target.clone(), src_addr, timeout)?);
+    ret.merge(netbios_discovery_scan(src_addr, threads_num, timeout)?);
+    let arp_scan_results = arp_scan(target, src_addr, threads_num, timeout)?;
+    ret.merge_arp(&arp_scan_results);
+    Ok(ret)
+}
+
 fn threads_scan(
     method: ScanMethod,
     dst_ipv4: Ipv4Addr,
@@ -653,6 +1327,7 @@ pub fn scan(
 
     let iter = rx.into_iter().take(recv_size);
     let mut port_scan_ret = PortScanResults::new();
+    port_scan_ret.method = Some(method);
 
     for (dst_ipv4, dst_port, v) in iter {
         match v {
@@ -1216,6 +1891,7 @@ mod tests {
     use crate::Host;
     use crate::Target;
     use crate::TEST_IPV4_LOCAL;
+    use crate::TEST_IPV6_LOCAL;
     use subnetwork::Ipv4Pool;
     #[test]
     fn test_arp_scan_subnet() -> Result<()> {
@@ -1246,6 +1922,102 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn test_get_mac_ouis() {
+        let nmap_mac_prefixes = get_nmap_mac_prefixes();
+        let mac = MacAddr::new(0x00, 0x00, 0x01, 0x12, 0x34, 0x56);
+        let ouis = get_mac_ouis(mac, &nmap_mac_prefixes);
+        assert_eq!(ouis, "Xerox");
+    }
+    #[test]
+    fn test_ndp_scan() -> Result<()> {
+        let host = Host::new(TEST_IPV6_LOCAL.into(), None);
+        let target: Target = Target::new(vec![host]);
+        let threads_num = 8;
+        let timeout = Some(Duration::new(1, 0));
+        let src_ipv6 = None;
+        let ret: NdpScanResults = ndp_scan(target, src_ipv6, threads_num, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_ndp_multicast_scan() -> Result<()> {
+        let timeout = Some(Duration::new(3, 0));
+        let ret: NdpScanResults = ndp_multicast_scan("ens33", None, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_passive_scan() -> Result<()> {
+        let duration = Duration::new(10, 0);
+        let ret: PassiveScanResults = passive_scan("ens33", duration)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_passive_resolve_hostnames() -> Result<()> {
+        let mac_addr = MacAddr::new(0x00, 0x0c, 0x29, 0x01, 0x02, 0x03);
+        let ipv4: IpAddr = Ipv4Addr::new(192, 168, 1, 20).into();
+        let mut ret = PassiveScanResults::new();
+        for protocol in [PassiveProtocol::Arp, PassiveProtocol::Mdns] {
+            let record = PassiveRecord {
+                mac_addr,
+                addr: Some(ipv4),
+                hostname: None,
+                protocol,
+            };
+            ret.insert(record, &[]);
+        }
+        assert_eq!(ret.addrs(), vec![ipv4]);
+        ret.hostnames.insert(ipv4, String::from("nas.lan"));
+        assert!(ret.to_string().contains("192.168.1.20 (nas.lan)"));
+        // no_resolve skips the queries
+        ret.resolve_hostnames(None, true, 1, None)?;
+        assert_eq!(ret.hostnames.len(), 0);
+        Ok(())
+    }
+    #[test]
+    fn test_discovery_scan() -> Result<()> {
+        let src_addr = None;
+        let threads_num = 8;
+        let timeout = Some(Duration::new(2, 0));
+        let target: Target = Target::from_subnet("192.168.1.1/24", None)?;
+        let ret: DiscoveryScanResults = discovery_scan(target, src_addr, threads_num, timeout)?;
+        println!("{}", ret);
+        Ok(())
+    }
+    #[test]
+    fn test_discovery_merge_arp() {
+        let ipv4 = Ipv4Addr::new(192, 168, 1, 20);
+        let mac_addr = MacAddr::new(0x00, 0x0c, 0x29, 0x01, 0x02, 0x03);
+        let mut arp_scan_results = ArpScanResults::new();
+        let aah = ArpAliveHost {
+            mac_addr,
+            ouis: String::from("VMware"),
+        };
+        arp_scan_results.alive_hosts.insert(ipv4, aah);
+        arp_scan_results.enrichment();
+
+        let mut ret = DiscoveryScanResults::new();
+        let record = DiscoveryRecord {
+            addr: ipv4.into(),
+            mac_addr: None,
+            name: Some(String::from("apple-tv")),
+            services: vec![String::from("_airplay._tcp")],
+            model: Some(String::from("AppleTV")),
+            method: DiscoveryMethod::Mdns,
+        };
+        ret.insert(record, &[]);
+        ret.merge_arp(&arp_scan_results);
+        assert_eq!(ret.alive_host_num, 1);
+        let dah = ret.get(&ipv4.into()).unwrap();
+        assert_eq!(dah.mac_addr, Some(mac_addr));
+        assert_eq!(dah.names, vec!["apple-tv"]);
+        assert_eq!(
+            dah.methods,
+            vec![DiscoveryMethod::Mdns, DiscoveryMethod::Arp]
+        );
+    }
+    #[test]
     fn test_tcp_connect_scan() -> Result<()> {
         let src_ipv4 = None;
         let src_port = None;
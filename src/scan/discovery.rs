@@ -0,0 +1,761 @@
+use anyhow::Result;
+use log::debug;
+use pnet::datalink::MacAddr;
+use rand::Rng;
+use std::fmt;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::net::SocketAddrV6;
+use std::net::TcpStream;
+use std::net::UdpSocket;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::dns::read_name;
+use crate::dns::read_txt;
+use crate::dns::reverse_name;
+use crate::dns::DnsMessage;
+use crate::dns::DnsQuestion;
+use crate::dns::DNS_CLASS_IN;
+use crate::dns::DNS_TYPE_A;
+use crate::dns::DNS_TYPE_AAAA;
+use crate::dns::DNS_TYPE_PTR;
+use crate::dns::DNS_TYPE_SRV;
+use crate::dns::DNS_TYPE_TXT;
+use crate::utils::find_interface_by_ip;
+use crate::utils::find_source_addr6;
+use crate::utils::random_port;
+use crate::Ipv6CheckMethods;
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+const UPNP_HTTP_PORT: u16 = 80;
+const LLMNR_PORT: u16 = 5355;
+const NETBIOS_BROADCAST_ADDR: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 255);
+const NETBIOS_NS_PORT: u16 = 137;
+const UDP_BUFF_SIZE: usize = 9000;
+// the max size of the upnp device description we read
+const HTTP_BUFF_SIZE: u64 = 65536;
+// DNS-SD service type enumeration (RFC 6763 section 9)
+const DNS_SD_SERVICES: &str = "_services._dns-sd._udp.local";
+// class IN with the unicast-response bit set, so the responders answer to our port
+const MDNS_CLASS_QU: u16 = 0x8001;
+// how many questions we put into one mdns query
+const MDNS_MAX_QUESTIONS: usize = 16;
+// NB, the netbios name query
+const NETBIOS_TYPE_NB: u16 = 0x20;
+// NBSTAT, the netbios node status request
+const NETBIOS_TYPE_NBSTAT: u16 = 0x21;
+// the B (broadcast) flag of the netbios name query
+const NETBIOS_FLAG_BROADCAST: u16 = 0x0010;
+// the encoded wildcard name "*"
+const NETBIOS_WILDCARD_NAME: &str = "CKAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const NETBIOS_NAME_SIZE: usize = 18;
+const NETBIOS_GROUP_NAME: u16 = 0x8000;
+// the txt keys which some device describe its model with
+const TXT_MODEL_KEYS: [&str; 5] = ["md", "model", "ty", "usb_MDL", "am"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DiscoveryMethod {
+    Arp,
+    Mdns,
+    Ssdp,
+    Llmnr,
+    Netbios,
+}
+
+impl fmt::Display for DiscoveryMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            DiscoveryMethod::Arp => "arp",
+            DiscoveryMethod::Mdns => "mdns",
+            DiscoveryMethod::Ssdp => "ssdp",
+            DiscoveryMethod::Llmnr => "llmnr",
+            DiscoveryMethod::Netbios => "netbios",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One responder found by the discovery protocols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryRecord {
+    pub addr: IpAddr,
+    pub mac_addr: Option<MacAddr>,
+    pub name: Option<String>,
+    pub services: Vec<String>,
+    pub model: Option<String>,
+    pub method: DiscoveryMethod,
+}
+
+impl DiscoveryRecord {
+    fn new(addr: IpAddr, method: DiscoveryMethod) -> DiscoveryRecord {
+        DiscoveryRecord {
+            addr,
+            mac_addr: None,
+            name: None,
+            services: Vec::new(),
+            model: None,
+            method,
+        }
+    }
+    fn push_service(&mut self, service: String) {
+        if !service.is_empty() && !self.services.contains(&service) {
+            self.services.push(service);
+        }
+    }
+}
+
+fn bind_socket(src_addr: Option<IpAddr>, timeout: Duration) -> Result<UdpSocket> {
+    let src_addr = match src_addr {
+        Some(IpAddr::V4(src_ipv4)) => src_ipv4,
+        _ => Ipv4Addr::new(0, 0, 0, 0),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(src_addr.into(), random_port()))?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.set_write_timeout(Some(timeout))?;
+    Ok(socket)
+}
+
+fn bind_socket6(src_addr: Option<IpAddr>, timeout: Duration) -> Result<UdpSocket> {
+    let src_addr = match src_addr {
+        Some(IpAddr::V6(src_ipv6)) => src_ipv6,
+        _ => Ipv6Addr::UNSPECIFIED,
+    };
+    // the link local address can only be bound with the scope id of its interface
+    let scope_id = match find_interface_by_ip(src_addr.into()) {
+        Some(interface) if !src_addr.is_global_x() => interface.index,
+        _ => 0,
+    };
+    let socket = UdpSocket::bind(SocketAddrV6::new(src_addr, random_port(), 0, scope_id))?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.set_write_timeout(Some(timeout))?;
+    Ok(socket)
+}
+
+/// Receive all the responses until the timeout.
+fn recv_all(socket: &UdpSocket, timeout: Duration) -> Vec<(IpAddr, Vec<u8>)> {
+    let mut ret = Vec::new();
+    let start_time = Instant::now();
+    let mut recv_buff = [0u8; UDP_BUFF_SIZE];
+    loop {
+        let elapsed = start_time.elapsed();
+        if elapsed >= timeout {
+            break;
+        }
+        match socket.set_read_timeout(Some(timeout - elapsed)) {
+            Ok(_) => (),
+            Err(_) => break,
+        }
+        match socket.recv_from(&mut recv_buff) {
+            Ok((n, addr)) => ret.push((addr.ip(), recv_buff[..n].to_vec())),
+            Err(_) => break,
+        }
+    }
+    ret
+}
+
+fn strip_local(name: &str) -> String {
+    let name = name.trim_end_matches('.');
+    match name.strip_suffix(".local") {
+        Some(n) => n.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Parse one mdns response into the record of its sender.
+pub fn parse_mdns_response(src_addr: IpAddr, buff: &[u8]) -> Option<DiscoveryRecord> {
+    let message = match DnsMessage::parse(buff) {
+        Ok(m) => m,
+        Err(_) => return None,
+    };
+    if !message.is_response() {
+        return None;
+    }
+    let mut ret = DiscoveryRecord::new(src_addr, DiscoveryMethod::Mdns);
+    let mut hostname = None;
+    let mut srv_target = None;
+    let records = message.answers.iter().chain(message.additionals.iter());
+    for rr in records {
+        match rr.rtype {
+            DNS_TYPE_A | DNS_TYPE_AAAA => {
+                let addr: Option<IpAddr> = match rr.rdata.len() {
+                    4 => Some(
+                        Ipv4Addr::new(rr.rdata[0], rr.rdata[1], rr.rdata[2], rr.rdata[3]).into(),
+                    ),
+                    16 => {
+                        let mut octets = [0u8; 16];
+                        octets.copy_from_slice(&rr.rdata);
+                        Some(IpAddr::from(octets))
+                    }
+                    _ => None,
+                };
+                // prefer the name of the address which sent the response
+                if addr == Some(src_addr) || hostname.is_none() {
+                    hostname = Some(strip_local(&rr.name));
+                }
+            }
+            DNS_TYPE_PTR => {
+                if let Ok((ptr, _)) = read_name(buff, rr.rdata_offset) {
+                    if rr.name == DNS_SD_SERVICES {
+                        // service type enumeration
+                        ret.push_service(strip_local(&ptr));
+                    } else if !rr.name.ends_with(".arpa") {
+                        // service instance of the type
+                        ret.push_service(strip_local(&rr.name));
+                    }
+                }
+            }
+            DNS_TYPE_SRV => {
+                if let Ok((target, _)) = read_name(buff, rr.rdata_offset + 6) {
+                    srv_target = Some(strip_local(&target))
+                }
+            }
+            DNS_TYPE_TXT => {
+                for txt in read_txt(&rr.rdata) {
+                    if let Some((key, value)) = txt.split_once('=') {
+                        if ret.model.is_none() && !value.is_empty() && TXT_MODEL_KEYS.contains(&key)
+                        {
+                            ret.model = Some(value.to_string());
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    ret.name = match hostname {
+        Some(h) => Some(h),
+        None => srv_target,
+    };
+    Some(ret)
+}
+
+fn send_mdns_query(
+    socket: &UdpSocket,
+    names: &[String],
+    timeout: Duration,
+) -> Result<Vec<(IpAddr, Vec<u8>)>> {
+    let mut ret = Vec::new();
+    for chunk in names.chunks(MDNS_MAX_QUESTIONS) {
+        let questions: Vec<DnsQuestion> = chunk
+            .iter()
+            .map(|n| DnsQuestion {
+                name: n.to_string(),
+                qtype: DNS_TYPE_PTR,
+                qclass: MDNS_CLASS_QU,
+            })
+            .collect();
+        // the id should be zero in multicast dns
+        let query = DnsMessage::build_query(0, 0, &questions);
+        socket.send_to(&query, SocketAddr::new(MDNS_ADDR.into(), MDNS_PORT))?;
+        ret.extend(recv_all(socket, timeout));
+    }
+    Ok(ret)
+}
+
+/// Ask for all the service types on the link (DNS-SD), and then for the instances of every type.
+pub fn send_mdns_discovery_packet(
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<Vec<DiscoveryRecord>> {
+    let socket = bind_socket(src_addr, timeout)?;
+    let mut responses = send_mdns_query(&socket, &[DNS_SD_SERVICES.to_string()], timeout)?;
+    let mut service_types = Vec::new();
+    for (addr, buff) in &responses {
+        if let Some(record) = parse_mdns_response(*addr, buff) {
+            for s in record.services {
+                let s = format!("{}.local", s);
+                if !service_types.contains(&s) {
+                    service_types.push(s);
+                }
+            }
+        }
+    }
+    debug!("mdns service types: {:?}", service_types);
+    if !service_types.is_empty() {
+        responses.extend(send_mdns_query(&socket, &service_types, timeout)?);
+    }
+    let mut ret = Vec::new();
+    for (addr, buff) in responses {
+        if let Some(record) = parse_mdns_response(addr, &buff) {
+            ret.push(record);
+        }
+    }
+    Ok(ret)
+}
+
+fn ssdp_header(response: &str, key: &str) -> Option<String> {
+    for line in response.lines() {
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim().eq_ignore_ascii_case(key) {
+                return Some(v.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+fn xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let start_tag = format!("<{}>", tag);
+    let end_tag = format!("</{}>", tag);
+    let start = xml.find(&start_tag)? + start_tag.len();
+    let end = xml[start..].find(&end_tag)? + start;
+    let value = xml[start..end].trim();
+    if !value.is_empty() {
+        Some(value.to_string())
+    } else {
+        None
+    }
+}
+
+/// Parse one ssdp response, returns the record and the location of the device description.
+pub fn parse_ssdp_response(
+    src_addr: IpAddr,
+    buff: &[u8],
+) -> Option<(DiscoveryRecord, Option<String>)> {
+    let response = String::from_utf8_lossy(buff);
+    if !response.starts_with("HTTP/1.1 200") && !response.starts_with("NOTIFY") {
+        return None;
+    }
+    let mut ret = DiscoveryRecord::new(src_addr, DiscoveryMethod::Ssdp);
+    match ssdp_header(&response, "ST") {
+        Some(st) => ret.push_service(st),
+        None => {
+            if let Some(nt) = ssdp_header(&response, "NT") {
+                ret.push_service(nt)
+            }
+        }
+    }
+    // such as "Linux/4.9 UPnP/1.0 MiniUPnPd/2.1", better than nothing
+    ret.model = ssdp_header(&response, "SERVER");
+    let location = ssdp_header(&response, "LOCATION");
+    Some((ret, location))
+}
+
+/// Parse the upnp device description, returns the friendly name and the model.
+pub fn parse_upnp_description(xml: &str) -> (Option<String>, Option<String>) {
+    let name = xml_tag(xml, "friendlyName");
+    let model = match (xml_tag(xml, "manufacturer"), xml_tag(xml, "modelName")) {
+        (Some(m), Some(n)) => Some(format!("{} {}", m, n)),
+        (None, Some(n)) => Some(n),
+        (_, None) => None,
+    };
+    (name, model)
+}
+
+/// Split the location into the address, the host and the path,
+/// only plain http location with ip address is supported, the port is 80 if not given.
+fn parse_upnp_location(location: &str) -> Result<(SocketAddr, &str, &str)> {
+    let url = location.trim_start_matches("http://");
+    let (host, path) = match url.find('/') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, "/"),
+    };
+    let addr = match host.parse::<SocketAddr>() {
+        Ok(a) => a,
+        Err(_) => {
+            let ipaddr: IpAddr = host.trim_start_matches('[').trim_end_matches(']').parse()?;
+            SocketAddr::new(ipaddr, UPNP_HTTP_PORT)
+        }
+    };
+    Ok((addr, host, path))
+}
+
+fn fetch_upnp_description(location: &str, timeout: Duration) -> Result<String> {
+    let (addr, host, path) = parse_upnp_location(location)?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes())?;
+    let mut response = Vec::new();
+    stream.take(HTTP_BUFF_SIZE).read_to_end(&mut response)?;
+    Ok(String::from_utf8_lossy(&response).to_string())
+}
+
+/// Send the M-SEARCH for all devices and read the description of every device which responded.
+pub fn send_ssdp_discovery_packet(
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<Vec<DiscoveryRecord>> {
+    let socket = bind_socket(src_addr, timeout)?;
+    let request = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}:{}\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n",
+        SSDP_ADDR, SSDP_PORT
+    );
+    socket.send_to(
+        request.as_bytes(),
+        SocketAddr::new(SSDP_ADDR.into(), SSDP_PORT),
+    )?;
+    let responses = recv_all(&socket, timeout);
+
+    let mut ret = Vec::new();
+    let mut fetched: Vec<String> = Vec::new();
+    for (addr, buff) in responses {
+        if let Some((mut record, location)) = parse_ssdp_response(addr, &buff) {
+            if let Some(location) = location {
+                if !fetched.contains(&location) {
+                    match fetch_upnp_description(&location, timeout) {
+                        Ok(xml) => {
+                            let (name, model) = parse_upnp_description(&xml);
+                            record.name = name;
+                            if model.is_some() {
+                                record.model = model;
+                            }
+                        }
+                        Err(e) => debug!("fetch {} failed: {}", location, e),
+                    }
+                    fetched.push(location);
+                }
+            }
+            ret.push(record);
+        }
+    }
+    Ok(ret)
+}
+
+/// Parse the llmnr response of the reverse query.
+pub fn parse_llmnr_response(src_addr: IpAddr, buff: &[u8]) -> Option<DiscoveryRecord> {
+    let message = match DnsMessage::parse(buff) {
+        Ok(m) => m,
+        Err(_) => return None,
+    };
+    if !message.is_response() {
+        return None;
+    }
+    for rr in &message.answers {
+        if rr.rtype == DNS_TYPE_PTR {
+            if let Ok((name, _)) = read_name(buff, rr.rdata_offset) {
+                let mut ret = DiscoveryRecord::new(src_addr, DiscoveryMethod::Llmnr);
+                ret.name = Some(name);
+                return Some(ret);
+            }
+        }
+    }
+    None
+}
+
+fn llmnr_query(dst_addr: IpAddr) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let question = DnsQuestion {
+        name: reverse_name(&dst_addr),
+        qtype: DNS_TYPE_PTR,
+        qclass: DNS_CLASS_IN,
+    };
+    // one question in each llmnr query
+    DnsMessage::build_query(rng.gen(), 0, &[question])
+}
+
+/// Send the llmnr reverse (PTR) query of every host to the host itself,
+/// the reverse mapping queries are sent by unicast (RFC 4795 2.4), windows hosts answer with its name.
+pub fn send_llmnr_discovery_packet(
+    dst_addrs: &[IpAddr],
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<Vec<DiscoveryRecord>> {
+    let mut responses = Vec::new();
+    let dst_ipv4s: Vec<IpAddr> = dst_addrs.iter().filter(|a| a.is_ipv4()).copied().collect();
+    if !dst_ipv4s.is_empty() {
+        let socket = bind_socket(src_addr, timeout)?;
+        for dst_addr in dst_ipv4s {
+            let query = llmnr_query(dst_addr);
+            socket.send_to(&query, SocketAddr::new(dst_addr, LLMNR_PORT))?;
+        }
+        responses.extend(recv_all(&socket, timeout));
+    }
+    let dst_ipv6s: Vec<Ipv6Addr> = dst_addrs
+        .iter()
+        .filter_map(|a| match a {
+            IpAddr::V6(dst_ipv6) => Some(*dst_ipv6),
+            _ => None,
+        })
+        .collect();
+    if !dst_ipv6s.is_empty() {
+        let socket = bind_socket6(src_addr, timeout)?;
+        for dst_ipv6 in dst_ipv6s {
+            let query = llmnr_query(dst_ipv6.into());
+            // the link local target is reached from the interface of the source address
+            let scope_id = if !dst_ipv6.is_global_x() {
+                match find_source_addr6(src_addr, dst_ipv6)? {
+                    Some(src_ipv6) => match find_interface_by_ip(src_ipv6.into()) {
+                        Some(interface) => interface.index,
+                        None => 0,
+                    },
+                    None => 0,
+                }
+            } else {
+                0
+            };
+            socket.send_to(&query, SocketAddrV6::new(dst_ipv6, LLMNR_PORT, 0, scope_id))?;
+        }
+        responses.extend(recv_all(&socket, timeout));
+    }
+    let mut ret = Vec::new();
+    for (addr, buff) in responses {
+        if let Some(record) = parse_llmnr_response(addr, &buff) {
+            ret.push(record);
+        }
+    }
+    Ok(ret)
+}
+
+fn netbios_service(suffix: u8, group: bool) -> Option<&'static str> {
+    match (suffix, group) {
+        (0x00, false) => Some("workstation"),
+        (0x03, false) => Some("messenger"),
+        (0x20, false) => Some("file server"),
+        (0x1B, false) => Some("domain master browser"),
+        (0x1C, true) => Some("domain controller"),
+        (0x1D, false) => Some("master browser"),
+        _ => None,
+    }
+}
+
+/// Parse the netbios node status response, the names table tells the host name,
+/// the services and the mac address of the host.
+pub fn parse_netbios_node_status(src_addr: IpAddr, buff: &[u8]) -> Option<DiscoveryRecord> {
+    let message = match DnsMessage::parse(buff) {
+        Ok(m) => m,
+        Err(_) => return None,
+    };
+    if !message.is_response() {
+        return None;
+    }
+    for rr in &message.answers {
+        if rr.rtype != NETBIOS_TYPE_NBSTAT || rr.rdata.is_empty() {
+            continue;
+        }
+        let mut ret = DiscoveryRecord::new(src_addr, DiscoveryMethod::Netbios);
+        let num_names = rr.rdata[0] as usize;
+        let names_end = 1 + num_names * NETBIOS_NAME_SIZE;
+        if names_end > rr.rdata.len() {
+            return None;
+        }
+        for i in 0..num_names {
+            let entry = &rr.rdata[1 + i * NETBIOS_NAME_SIZE..1 + (i + 1) * NETBIOS_NAME_SIZE];
+            let name = String::from_utf8_lossy(&entry[..15]).trim_end().to_string();
+            let suffix = entry[15];
+            let flags = u16::from_be_bytes([entry[16], entry[17]]);
+            let group = flags & NETBIOS_GROUP_NAME != 0;
+            if suffix == 0x00 && !group && ret.name.is_none() {
+                ret.name = Some(name);
+            }
+            if let Some(s) = netbios_service(suffix, group) {
+                ret.push_service(s.to_string())
+            }
+        }
+        // the unit id, samba fills it with zeros
+        if names_end + 6 <= rr.rdata.len() {
+            let m = &rr.rdata[names_end..names_end + 6];
+            let mac = MacAddr::new(m[0], m[1], m[2], m[3], m[4], m[5]);
+            if mac != MacAddr::zero() {
+                ret.mac_addr = Some(mac);
+            }
+        }
+        return Some(ret);
+    }
+    None
+}
+
+/// Parse the netbios name query response, the responder is returned without name,
+/// ask its names by the node status request.
+pub fn parse_netbios_name_response(src_addr: IpAddr, buff: &[u8]) -> Option<DiscoveryRecord> {
+    let message = match DnsMessage::parse(buff) {
+        Ok(m) => m,
+        Err(_) => return None,
+    };
+    if !message.is_response() {
+        return None;
+    }
+    for rr in &message.answers {
+        if rr.rtype == NETBIOS_TYPE_NB {
+            return Some(DiscoveryRecord::new(src_addr, DiscoveryMethod::Netbios));
+        }
+    }
+    None
+}
+
+/// Send the netbios name query of the wildcard name "*" to the broadcast address
+/// (like `nmblookup '*'`), returns the responders.
+pub fn send_netbios_discovery_packet(
+    src_addr: Option<IpAddr>,
+    timeout: Duration,
+) -> Result<Vec<DiscoveryRecord>> {
+    let mut rng = rand::thread_rng();
+    let question = DnsQuestion {
+        name: NETBIOS_WILDCARD_NAME.to_string(),
+        qtype: NETBIOS_TYPE_NB,
+        qclass: DNS_CLASS_IN,
+    };
+    let query = DnsMessage::build_query(rng.gen(), NETBIOS_FLAG_BROADCAST, &[question]);
+    let socket = bind_socket(src_addr, timeout)?;
+    socket.set_broadcast(true)?;
+    socket.send_to(
+        &query,
+        SocketAddr::new(NETBIOS_BROADCAST_ADDR.into(), NETBIOS_NS_PORT),
+    )?;
+    let mut ret: Vec<DiscoveryRecord> = Vec::new();
+    for (addr, buff) in recv_all(&socket, timeout) {
+        if let Some(record) = parse_netbios_name_response(addr, &buff) {
+            if !ret.iter().any(|r| r.addr == record.addr) {
+                ret.push(record);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Send the netbios node status request (nbtstat -A) to the host.
+pub fn send_netbios_node_status_packet(
+    dst_ipv4: Ipv4Addr,
+    timeout: Duration,
+) -> Result<Option<DiscoveryRecord>> {
+    let mut rng = rand::thread_rng();
+    let question = DnsQuestion {
+        name: NETBIOS_WILDCARD_NAME.to_string(),
+        qtype: NETBIOS_TYPE_NBSTAT,
+        qclass: DNS_CLASS_IN,
+    };
+    let query = DnsMessage::build_query(rng.gen(), 0, &[question]);
+    let socket = bind_socket(None, timeout)?;
+    socket.connect(SocketAddr::new(dst_ipv4.into(), NETBIOS_NS_PORT))?;
+    socket.send(&query)?;
+    let mut recv_buff = [0u8; UDP_BUFF_SIZE];
+    match socket.recv(&mut recv_buff) {
+        Ok(n) => Ok(parse_netbios_node_status(dst_ipv4.into(), &recv_buff[..n])),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::write_name;
+    #[test]
+    fn test_parse_netbios_node_status() {
+        let mut buff = vec![0x12, 0x34, 0x84, 0x00, 0, 0, 0, 1, 0, 0, 0, 0];
+        buff.extend(write_name(NETBIOS_WILDCARD_NAME));
+        buff.extend(NETBIOS_TYPE_NBSTAT.to_be_bytes());
+        buff.extend(DNS_CLASS_IN.to_be_bytes());
+        buff.extend([0u8; 4]);
+        let mut rdata = vec![3];
+        let names: [(&str, u8, u16); 3] = [
+            ("DESKTOP-1", 0x00, 0x0400),
+            ("WORKGROUP", 0x00, 0x8400),
+            ("DESKTOP-1", 0x20, 0x0400),
+        ];
+        for (name, suffix, flags) in names {
+            rdata.extend(format!("{:<15}", name).as_bytes());
+            rdata.push(suffix);
+            rdata.extend(flags.to_be_bytes());
+        }
+        rdata.extend([0x00, 0x0c, 0x29, 0x01, 0x02, 0x03]);
+        buff.extend((rdata.len() as u16).to_be_bytes());
+        buff.extend(rdata);
+
+        let addr = Ipv4Addr::new(192, 168, 1, 10).into();
+        let record = parse_netbios_node_status(addr, &buff).unwrap();
+        assert_eq!(record.name, Some(String::from("DESKTOP-1")));
+        assert_eq!(record.services, vec!["workstation", "file server"]);
+        assert_eq!(
+            record.mac_addr,
+            Some(MacAddr::new(0x00, 0x0c, 0x29, 0x01, 0x02, 0x03))
+        );
+    }
+    #[test]
+    fn test_parse_netbios_name_response() {
+        let mut buff = vec![0x12, 0x34, 0x85, 0x00, 0, 0, 0, 1, 0, 0, 0, 0];
+        buff.extend(write_name(NETBIOS_WILDCARD_NAME));
+        buff.extend(NETBIOS_TYPE_NB.to_be_bytes());
+        buff.extend(DNS_CLASS_IN.to_be_bytes());
+        buff.extend([0u8; 4]);
+        buff.extend(6u16.to_be_bytes());
+        buff.extend([0x00, 0x00, 192, 168, 1, 10]);
+
+        let addr = Ipv4Addr::new(192, 168, 1, 10).into();
+        let record = parse_netbios_name_response(addr, &buff).unwrap();
+        assert_eq!(record.addr, addr);
+        assert_eq!(record.method, DiscoveryMethod::Netbios);
+        assert_eq!(record.name, None);
+        // the query itself
+        let query = DnsMessage::build_query(
+            0x1234,
+            NETBIOS_FLAG_BROADCAST,
+            &[DnsQuestion {
+                name: NETBIOS_WILDCARD_NAME.to_string(),
+                qtype: NETBIOS_TYPE_NB,
+                qclass: DNS_CLASS_IN,
+            }],
+        );
+        assert_eq!(parse_netbios_name_response(addr, &query), None);
+    }
+    #[test]
+    fn test_parse_upnp_location() {
+        let (addr, host, path) = parse_upnp_location("http://192.168.1.1/desc.xml").unwrap();
+        assert_eq!(addr, "192.168.1.1:80".parse::<SocketAddr>().unwrap());
+        assert_eq!(host, "192.168.1.1");
+        assert_eq!(path, "/desc.xml");
+        let (addr, _, path) = parse_upnp_location("http://192.168.1.1:49152").unwrap();
+        assert_eq!(addr, "192.168.1.1:49152".parse::<SocketAddr>().unwrap());
+        assert_eq!(path, "/");
+        let (addr, _, _) = parse_upnp_location("http://[fe80::1]/rootDesc.xml").unwrap();
+        assert_eq!(addr, "[fe80::1]:80".parse::<SocketAddr>().unwrap());
+        assert!(parse_upnp_location("http://router.local/desc.xml").is_err());
+    }
+    #[test]
+    fn test_parse_ssdp_response() {
+        let response = b"HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://192.168.1.1:1900/rootDesc.xml\r\nSERVER: Linux UPnP/1.0 MiniUPnPd/2.1\r\nST: upnp:rootdevice\r\n\r\n";
+        let addr = Ipv4Addr::new(192, 168, 1, 1).into();
+        let (record, location) = parse_ssdp_response(addr, response).unwrap();
+        assert_eq!(record.services, vec!["upnp:rootdevice"]);
+        assert_eq!(
+            location,
+            Some(String::from("http://192.168.1.1:1900/rootDesc.xml"))
+        );
+        let xml = "<device><friendlyName>Router</friendlyName><manufacturer>ASUS</manufacturer><modelName>RT-AX86U</modelName></device>";
+        let (name, model) = parse_upnp_description(xml);
+        assert_eq!(name, Some(String::from("Router")));
+        assert_eq!(model, Some(String::from("ASUS RT-AX86U")));
+    }
+    #[test]
+    fn test_parse_mdns_response() {
+        let mut buff = vec![0, 0, 0x84, 0x00, 0, 0, 0, 3, 0, 0, 0, 0];
+        fn push_record(buff: &mut Vec<u8>, name: &str, rtype: u16, rdata: &[u8]) {
+            buff.extend(write_name(name));
+            buff.extend(rtype.to_be_bytes());
+            buff.extend(DNS_CLASS_IN.to_be_bytes());
+            buff.extend(120u32.to_be_bytes());
+            buff.extend((rdata.len() as u16).to_be_bytes());
+            buff.extend(rdata);
+        }
+        push_record(
+            &mut buff,
+            "_airplay._tcp.local",
+            DNS_TYPE_PTR,
+            &write_name("TV._airplay._tcp.local"),
+        );
+        push_record(
+            &mut buff,
+            "TV._airplay._tcp.local",
+            DNS_TYPE_TXT,
+            b"\x0dmodel=AppleTV",
+        );
+        push_record(&mut buff, "apple-tv.local", DNS_TYPE_A, &[192, 168, 1, 20]);
+
+        let addr = Ipv4Addr::new(192, 168, 1, 20).into();
+        let record = parse_mdns_response(addr, &buff).unwrap();
+        assert_eq!(record.name, Some(String::from("apple-tv")));
+        assert_eq!(record.services, vec!["_airplay._tcp"]);
+        assert_eq!(record.model, Some(String::from("AppleTV")));
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::Result;
+use pnet::datalink::MacAddr;
+use pnet::datalink::NetworkInterface;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::icmpv6;
+use pnet::packet::icmpv6::echo_request::MutableEchoRequestPacket;
+use pnet::packet::icmpv6::Icmpv6Code;
+use pnet::packet::icmpv6::Icmpv6Type;
+use pnet::packet::icmpv6::Icmpv6Types;
+use pnet::packet::icmpv6::MutableIcmpv6Packet;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::ipv6::MutableIpv6Packet;
+use pnet::packet::Packet;
+use rand::Rng;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+use crate::layers::get_mac_from_ndp_ns;
+use crate::layers::layer2_send;
+use crate::layers::layer2_send_multi;
+use crate::layers::multicast_mac;
+use crate::layers::ndp_ns_packet;
+use crate::layers::ndp_rs_packet;
+use crate::layers::Layer3Match;
+use crate::layers::Layer4MatchIcmpv6;
+use crate::layers::LayersMatch;
+use crate::layers::ICMPV6_ER_HEADER_SIZE;
+use crate::layers::IPV6_HEADER_SIZE;
+
+const HOP_LIMIT: u8 = 255;
+// ff02::1
+const ALL_NODES_ADDR: Ipv6Addr = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0x0001);
+// 33:33 followed by the last 32 bits of the multicast address
+const ALL_NODES_MAC: MacAddr = MacAddr(0x33, 0x33, 0x00, 0x00, 0x00, 0x01);
+const ALL_ROUTERS_MAC: MacAddr = MacAddr(0x33, 0x33, 0x00, 0x00, 0x00, 0x02);
+
+pub fn send_ndp_ns_scan_packet(
+    dst_ipv6: Ipv6Addr,
+    src_ipv6: Ipv6Addr,
+    src_mac: MacAddr,
+    interface: NetworkInterface,
+    timeout: Duration,
+) -> Result<(Option<MacAddr>, Option<Duration>)> {
+    let ipv6_buff = ndp_ns_packet(src_ipv6, src_mac, dst_ipv6);
+
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: Some(dst_ipv6.into()),
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmpv6 = Layer4MatchIcmpv6 {
+        layer3: Some(layer3),
+        icmpv6_type: Some(Icmpv6Types::NeighborAdvert), // 136
+        icmpv6_code: Some(Icmpv6Code(0)),
+    };
+    let layers_match = LayersMatch::Layer4MatchIcmpv6(layer4_icmpv6);
+
+    match layer2_send(
+        multicast_mac(dst_ipv6),
+        interface,
+        &ipv6_buff,
+        EtherTypes::Ipv6,
+        vec![layers_match],
+        timeout,
+    )? {
+        (Some(r), Some(rtt)) => Ok((get_mac_from_ndp_ns(&r), Some(rtt))),
+        (_, _) => Ok((None, None)),
+    }
+}
+
+/// Returns the source ipv6 address and source mac of every response.
+fn get_addr_and_mac(responses: Vec<(Vec<u8>, Duration)>) -> Vec<(Ipv6Addr, MacAddr, Duration)> {
+    let mut ret = Vec::new();
+    for (r, rtt) in responses {
+        if let Some(ethernet_packet) = EthernetPacket::new(&r) {
+            if let Some(ipv6_packet) = Ipv6Packet::new(ethernet_packet.payload()) {
+                ret.push((ipv6_packet.get_source(), ethernet_packet.get_source(), rtt));
+            }
+        }
+    }
+    ret
+}
+
+/// Send one icmpv6 echo request to all-nodes (ff02::1) and collect the echo replies.
+pub fn send_ndp_multicast_echo_packet(
+    src_ipv6: Ipv6Addr,
+    interface: NetworkInterface,
+    timeout: Duration,
+) -> Result<Vec<(Ipv6Addr, MacAddr, Duration)>> {
+    let mut rng = rand::thread_rng();
+    // ipv6
+    let mut ipv6_buff = [0u8; IPV6_HEADER_SIZE + ICMPV6_ER_HEADER_SIZE];
+    let mut ipv6_header = MutableIpv6Packet::new(&mut ipv6_buff).unwrap();
+    ipv6_header.set_version(6);
+    ipv6_header.set_traffic_class(0);
+    ipv6_header.set_flow_label(0);
+    ipv6_header.set_payload_length(ICMPV6_ER_HEADER_SIZE as u16);
+    ipv6_header.set_next_header(IpNextHeaderProtocols::Icmpv6);
+    ipv6_header.set_hop_limit(HOP_LIMIT);
+    ipv6_header.set_source(src_ipv6);
+    ipv6_header.set_destination(ALL_NODES_ADDR);
+
+    // icmpv6
+    let mut icmpv6_header =
+        MutableEchoRequestPacket::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    icmpv6_header.set_icmpv6_type(Icmpv6Type(128));
+    icmpv6_header.set_icmpv6_code(Icmpv6Code(0));
+    icmpv6_header.set_sequence_number(1);
+    icmpv6_header.set_identifier(rng.gen());
+
+    let mut icmpv6_header = MutableIcmpv6Packet::new(&mut ipv6_buff[IPV6_HEADER_SIZE..]).unwrap();
+    let checksum = icmpv6::checksum(&icmpv6_header.to_immutable(), &src_ipv6, &ALL_NODES_ADDR);
+    icmpv6_header.set_checksum(checksum);
+
+    let layer3 = Layer3Match {
+        layer2: None,
+        src_addr: None,
+        dst_addr: Some(src_ipv6.into()),
+    };
+    let layer4_icmpv6 = Layer4MatchIcmpv6 {
+        layer3: Some(layer3),
+        icmpv6_type: Some(Icmpv6Types::EchoReply), // 129
+        icmpv6_code: Some(Icmpv6Code(0)),
+    };
+    let layers_match = LayersMatch::Layer4MatchIcmpv6(layer4_icmpv6);
+
+    let responses = layer2_send_multi(
+        ALL_NODES_MAC,
+        interface,
+        &ipv6_buff,
+        EtherTypes::Ipv6,
+        vec![layers_match],
+        timeout,
+    )?;
+    Ok(get_addr_and_mac(responses))
+}
+
+/// Send one router solicitation to all-routers (ff02::2) and collect the router advertisements.
+pub fn send_ndp_multicast_rs_packet(
+    src_ipv6: Ipv6Addr,
+    src_mac: MacAddr,
+    interface: NetworkInterface,
+    timeout: Duration,
+) -> Result<Vec<(Ipv6Addr, MacAddr, Duration)>> {
+    let ipv6_buff = ndp_rs_packet(src_ipv6, src_mac);
+
+    // the router advertisement may be sent to all-nodes instead of us
+    let layer4_icmpv6 = Layer4MatchIcmpv6 {
+        layer3: None,
+        icmpv6_type: Some(Icmpv6Types::RouterAdvert), // 134
+        icmpv6_code: Some(Icmpv6Code(0)),
+    };
+    let layers_match = LayersMatch::Layer4MatchIcmpv6(layer4_icmpv6);
+
+    let responses = layer2_send_multi(
+        ALL_ROUTERS_MAC,
+        interface,
+        &ipv6_buff,
+        EtherTypes::Ipv6,
+        vec![layers_match],
+        timeout,
+    )?;
+    Ok(get_addr_and_mac(responses))
+}
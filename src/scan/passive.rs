@@ -0,0 +1,576 @@
+use pnet::datalink::MacAddr;
+use pnet::packet::arp::ArpPacket;
+use pnet::packet::ethernet::EtherTypes;
+use pnet::packet::ethernet::EthernetPacket;
+use pnet::packet::icmpv6::Icmpv6Packet;
+use pnet::packet::icmpv6::Icmpv6Types;
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::udp::UdpPacket;
+use pnet::packet::Packet;
+use std::fmt;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+
+use crate::dns::read_name;
+use crate::dns::DnsMessage;
+use crate::dns::DnsResourceRecord;
+use crate::dns::DNS_TYPE_A;
+use crate::dns::DNS_TYPE_AAAA;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const NETBIOS_NS_PORT: u16 = 137;
+const NETBIOS_DGM_PORT: u16 = 138;
+const MDNS_PORT: u16 = 5353;
+const LLMNR_PORT: u16 = 5355;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+// the 802.3 frames use the ethertype field as length
+const IEEE8023_MAX_LENGTH: u16 = 1500;
+const STP_LLC_SAP: u8 = 0x42;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PassiveProtocol {
+    Arp,
+    Ndp,
+    Dhcp,
+    Mdns,
+    Llmnr,
+    Netbios,
+    Stp,
+    Lldp,
+}
+
+impl fmt::Display for PassiveProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            PassiveProtocol::Arp => "arp",
+            PassiveProtocol::Ndp => "ndp",
+            PassiveProtocol::Dhcp => "dhcp",
+            PassiveProtocol::Mdns => "mdns",
+            PassiveProtocol::Llmnr => "llmnr",
+            PassiveProtocol::Netbios => "netbios",
+            PassiveProtocol::Stp => "stp",
+            PassiveProtocol::Lldp => "lldp",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// One host information found in a sniffed frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassiveRecord {
+    pub mac_addr: MacAddr,
+    pub addr: Option<IpAddr>,
+    pub hostname: Option<String>,
+    pub protocol: PassiveProtocol,
+}
+
+impl PassiveRecord {
+    fn new(
+        mac_addr: MacAddr,
+        addr: Option<IpAddr>,
+        hostname: Option<String>,
+        protocol: PassiveProtocol,
+    ) -> PassiveRecord {
+        // ignore the unspecified address such as the arp probe and dhcp discover
+        let addr = addr.filter(|&a| !a.is_unspecified());
+        PassiveRecord {
+            mac_addr,
+            addr,
+            hostname,
+            protocol,
+        }
+    }
+}
+
+/// Decode the netbios first-level encoded name (32 chars), the suffix and padding spaces are removed.
+pub fn decode_netbios_name(encoded: &str) -> Option<String> {
+    let encoded = encoded.as_bytes();
+    if encoded.len() < 32 {
+        return None;
+    }
+    let mut name = Vec::new();
+    for i in 0..15 {
+        let c1 = encoded[i * 2];
+        let c2 = encoded[i * 2 + 1];
+        if !(b'A'..=b'P').contains(&c1) || !(b'A'..=b'P').contains(&c2) {
+            return None;
+        }
+        name.push(((c1 - b'A') << 4) | (c2 - b'A'));
+    }
+    let name = String::from_utf8_lossy(&name).trim_end().to_string();
+    if !name.is_empty() {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+fn dns_record_addr(rr: &DnsResourceRecord) -> Option<IpAddr> {
+    match rr.rtype {
+        DNS_TYPE_A => {
+            if rr.rdata.len() == 4 {
+                let ipv4 = Ipv4Addr::new(rr.rdata[0], rr.rdata[1], rr.rdata[2], rr.rdata[3]);
+                Some(ipv4.into())
+            } else {
+                None
+            }
+        }
+        DNS_TYPE_AAAA => {
+            if rr.rdata.len() == 16 {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&rr.rdata);
+                Some(Ipv6Addr::from(octets).into())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn parse_dns_like(
+    src_mac: MacAddr,
+    src_addr: IpAddr,
+    payload: &[u8],
+    protocol: PassiveProtocol,
+) -> Vec<PassiveRecord> {
+    let mut ret = vec![PassiveRecord::new(src_mac, Some(src_addr), None, protocol)];
+    if let Ok(message) = DnsMessage::parse(payload) {
+        if message.is_response() {
+            // the responder announces its own name and addresses
+            let records = message.answers.iter().chain(message.additionals.iter());
+            for rr in records {
+                if let Some(addr) = dns_record_addr(rr) {
+                    let hostname = Some(rr.name.to_string());
+                    ret.push(PassiveRecord::new(src_mac, Some(addr), hostname, protocol));
+                }
+            }
+        }
+    }
+    ret
+}
+
+fn parse_netbios_ns(src_mac: MacAddr, src_addr: IpAddr, payload: &[u8]) -> Vec<PassiveRecord> {
+    let protocol = PassiveProtocol::Netbios;
+    let mut ret = vec![PassiveRecord::new(src_mac, Some(src_addr), None, protocol)];
+    if let Ok(message) = DnsMessage::parse(payload) {
+        let opcode = (message.flags >> 11) & 0x0F;
+        let names: Vec<String> = if message.is_response() {
+            message
+                .answers
+                .iter()
+                .map(|rr| rr.name.to_string())
+                .collect()
+        } else if opcode == 5 || opcode == 8 || opcode == 9 {
+            // registration and refresh, the name belongs to the sender
+            message
+                .questions
+                .iter()
+                .map(|q| q.name.to_string())
+                .collect()
+        } else {
+            // name query, the name belongs to someone else
+            Vec::new()
+        };
+        for name in names {
+            let hostname = decode_netbios_name(&name);
+            if hostname.is_some() {
+                ret.push(PassiveRecord::new(
+                    src_mac,
+                    Some(src_addr),
+                    hostname,
+                    protocol,
+                ));
+            }
+        }
+    }
+    ret
+}
+
+fn parse_netbios_dgm(src_mac: MacAddr, src_addr: IpAddr, payload: &[u8]) -> Vec<PassiveRecord> {
+    let protocol = PassiveProtocol::Netbios;
+    let mut ret = vec![PassiveRecord::new(src_mac, Some(src_addr), None, protocol)];
+    // direct unique, direct group and broadcast datagram carry the source name after the 14 bytes header
+    if payload.len() > 14 && payload[0] >= 0x10 && payload[0] <= 0x12 {
+        if let Ok((name, _)) = read_name(payload, 14) {
+            let hostname = decode_netbios_name(&name);
+            if hostname.is_some() {
+                ret.push(PassiveRecord::new(
+                    src_mac,
+                    Some(src_addr),
+                    hostname,
+                    protocol,
+                ));
+            }
+        }
+    }
+    ret
+}
+
+fn parse_dhcp(src_mac: MacAddr, src_addr: IpAddr, payload: &[u8]) -> Vec<PassiveRecord> {
+    let protocol = PassiveProtocol::Dhcp;
+    let mut ret = Vec::new();
+    if payload.len() < 240 || payload[236..240] != DHCP_MAGIC_COOKIE {
+        return ret;
+    }
+    let op = payload[0];
+    let ciaddr = Ipv4Addr::new(payload[12], payload[13], payload[14], payload[15]);
+    let yiaddr = Ipv4Addr::new(payload[16], payload[17], payload[18], payload[19]);
+    // htype ethernet and hlen 6
+    let chaddr = if payload[1] == 1 && payload[2] == 6 {
+        MacAddr::new(
+            payload[28],
+            payload[29],
+            payload[30],
+            payload[31],
+            payload[32],
+            payload[33],
+        )
+    } else {
+        src_mac
+    };
+
+    let mut hostname = None;
+    let mut requested_addr = None;
+    let mut i = 240;
+    while i < payload.len() {
+        let code = payload[i];
+        match code {
+            0 => {
+                // pad
+                i += 1;
+                continue;
+            }
+            255 => break, // end
+            _ => (),
+        }
+        if i + 1 >= payload.len() {
+            break;
+        }
+        let len = payload[i + 1] as usize;
+        let start = i + 2;
+        if start + len > payload.len() {
+            break;
+        }
+        let value = &payload[start..start + len];
+        match code {
+            12 => hostname = Some(String::from_utf8_lossy(value).to_string()),
+            50 if len == 4 => {
+                let addr = Ipv4Addr::new(value[0], value[1], value[2], value[3]);
+                requested_addr = Some(addr);
+            }
+            _ => (),
+        }
+        i = start + len;
+    }
+
+    match op {
+        1 => {
+            // client request
+            let addr = if !ciaddr.is_unspecified() {
+                Some(ciaddr.into())
+            } else {
+                requested_addr.map(|r| r.into())
+            };
+            ret.push(PassiveRecord::new(chaddr, addr, hostname, protocol));
+        }
+        2 => {
+            // server reply, the server itself and the address offered to client
+            ret.push(PassiveRecord::new(src_mac, Some(src_addr), None, protocol));
+            ret.push(PassiveRecord::new(
+                chaddr,
+                Some(yiaddr.into()),
+                hostname,
+                protocol,
+            ));
+        }
+        _ => (),
+    }
+    ret
+}
+
+fn parse_udp(src_mac: MacAddr, src_addr: IpAddr, udp_buff: &[u8]) -> Vec<PassiveRecord> {
+    let udp_packet = match UdpPacket::new(udp_buff) {
+        Some(u) => u,
+        None => return Vec::new(),
+    };
+    let src_port = udp_packet.get_source();
+    let dst_port = udp_packet.get_destination();
+    let payload = udp_packet.payload();
+    let is_port = |p: u16| -> bool { src_port == p || dst_port == p };
+
+    if is_port(DHCP_SERVER_PORT) && is_port(DHCP_CLIENT_PORT) {
+        parse_dhcp(src_mac, src_addr, payload)
+    } else if is_port(MDNS_PORT) {
+        parse_dns_like(src_mac, src_addr, payload, PassiveProtocol::Mdns)
+    } else if is_port(LLMNR_PORT) {
+        parse_dns_like(src_mac, src_addr, payload, PassiveProtocol::Llmnr)
+    } else if src_port == NETBIOS_NS_PORT || dst_port == NETBIOS_NS_PORT {
+        parse_netbios_ns(src_mac, src_addr, payload)
+    } else if src_port == NETBIOS_DGM_PORT && dst_port == NETBIOS_DGM_PORT {
+        parse_netbios_dgm(src_mac, src_addr, payload)
+    } else {
+        Vec::new()
+    }
+}
+
+fn parse_lldp(src_mac: MacAddr, payload: &[u8]) -> Vec<PassiveRecord> {
+    let mut hostname = None;
+    let mut addr = None;
+    let mut i = 0;
+    while i + 2 <= payload.len() {
+        let header = u16::from_be_bytes([payload[i], payload[i + 1]]);
+        let tlv_type = header >> 9;
+        let tlv_len = (header & 0x01FF) as usize;
+        let start = i + 2;
+        if tlv_type == 0 || start + tlv_len > payload.len() {
+            // end of lldpdu
+            break;
+        }
+        let value = &payload[start..start + tlv_len];
+        match tlv_type {
+            // system name
+            5 => hostname = Some(String::from_utf8_lossy(value).to_string()),
+            // management address
+            8 => {
+                if value.len() >= 6 && value[1] == 1 {
+                    let ipv4 = Ipv4Addr::new(value[2], value[3], value[4], value[5]);
+                    addr = Some(ipv4.into());
+                } else if value.len() >= 18 && value[1] == 2 {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&value[2..18]);
+                    addr = Some(Ipv6Addr::from(octets).into());
+                }
+            }
+            _ => (),
+        }
+        i = start + tlv_len;
+    }
+    vec![PassiveRecord::new(
+        src_mac,
+        addr,
+        hostname,
+        PassiveProtocol::Lldp,
+    )]
+}
+
+fn parse_ndp(src_mac: MacAddr, ipv6_packet: &Ipv6Packet) -> Vec<PassiveRecord> {
+    let protocol = PassiveProtocol::Ndp;
+    let mut ret = Vec::new();
+    if let Some(icmpv6_packet) = Icmpv6Packet::new(ipv6_packet.payload()) {
+        let icmpv6_type = icmpv6_packet.get_icmpv6_type();
+        // router solicitation (133) to redirect (137)
+        if icmpv6_type.0 >= 133 && icmpv6_type.0 <= 137 {
+            let src_ipv6 = ipv6_packet.get_source();
+            ret.push(PassiveRecord::new(
+                src_mac,
+                Some(src_ipv6.into()),
+                None,
+                protocol,
+            ));
+            // the target address of neighbor advertisement is the sender's own address
+            let icmpv6_buff = ipv6_packet.payload();
+            if icmpv6_type == Icmpv6Types::NeighborAdvert && icmpv6_buff.len() >= 24 {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&icmpv6_buff[8..24]);
+                let target = Ipv6Addr::from(octets);
+                if target != src_ipv6 {
+                    ret.push(PassiveRecord::new(
+                        src_mac,
+                        Some(target.into()),
+                        None,
+                        protocol,
+                    ));
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Parse one sniffed ethernet frame, returns the host informations found in it.
+pub fn parse_frame(ethernet_buff: &[u8]) -> Vec<PassiveRecord> {
+    let ethernet_packet = match EthernetPacket::new(ethernet_buff) {
+        Some(e) => e,
+        None => return Vec::new(),
+    };
+    let src_mac = ethernet_packet.get_source();
+    let ethernet_type = ethernet_packet.get_ethertype();
+    if ethernet_type.0 <= IEEE8023_MAX_LENGTH {
+        // 802.3 frame with llc header, the bpdu use sap 0x42
+        let payload = ethernet_packet.payload();
+        if payload.len() >= 3 && payload[0] == STP_LLC_SAP && payload[1] == STP_LLC_SAP {
+            return vec![PassiveRecord::new(
+                src_mac,
+                None,
+                None,
+                PassiveProtocol::Stp,
+            )];
+        }
+        return Vec::new();
+    }
+    match ethernet_type {
+        EtherTypes::Arp => match ArpPacket::new(ethernet_packet.payload()) {
+            Some(arp_packet) => {
+                let addr = arp_packet.get_sender_proto_addr();
+                vec![PassiveRecord::new(
+                    arp_packet.get_sender_hw_addr(),
+                    Some(addr.into()),
+                    None,
+                    PassiveProtocol::Arp,
+                )]
+            }
+            None => Vec::new(),
+        },
+        EtherTypes::Lldp => parse_lldp(src_mac, ethernet_packet.payload()),
+        EtherTypes::Ipv4 => match Ipv4Packet::new(ethernet_packet.payload()) {
+            Some(ipv4_packet) => match ipv4_packet.get_next_level_protocol() {
+                IpNextHeaderProtocols::Udp => {
+                    let src_ipv4 = ipv4_packet.get_source();
+                    parse_udp(src_mac, src_ipv4.into(), ipv4_packet.payload())
+                }
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        },
+        EtherTypes::Ipv6 => match Ipv6Packet::new(ethernet_packet.payload()) {
+            Some(ipv6_packet) => match ipv6_packet.get_next_header() {
+                IpNextHeaderProtocols::Icmpv6 => parse_ndp(src_mac, &ipv6_packet),
+                IpNextHeaderProtocols::Udp => {
+                    let src_ipv6 = ipv6_packet.get_source();
+                    parse_udp(src_mac, src_ipv6.into(), ipv6_packet.payload())
+                }
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::arp::ArpHardwareTypes;
+    use pnet::packet::arp::ArpOperations;
+    use pnet::packet::arp::MutableArpPacket;
+    use pnet::packet::ethernet::EtherType;
+    use pnet::packet::ethernet::MutableEthernetPacket;
+    use pnet::packet::ipv4::MutableIpv4Packet;
+    use pnet::packet::udp::MutableUdpPacket;
+    const TEST_MAC: MacAddr = MacAddr(0x00, 0x0c, 0x29, 0x11, 0x22, 0x33);
+    fn ethernet_frame(ethernet_type: EtherType, payload: &[u8]) -> Vec<u8> {
+        let mut buff = vec![0u8; 14 + payload.len()];
+        let mut ethernet_packet = MutableEthernetPacket::new(&mut buff).unwrap();
+        ethernet_packet.set_destination(MacAddr::broadcast());
+        ethernet_packet.set_source(TEST_MAC);
+        ethernet_packet.set_ethertype(ethernet_type);
+        ethernet_packet.set_payload(payload);
+        buff
+    }
+    fn udp_frame(src_ipv4: Ipv4Addr, src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut buff = vec![0u8; 20 + 8 + payload.len()];
+        let mut ipv4_packet = MutableIpv4Packet::new(&mut buff).unwrap();
+        ipv4_packet.set_version(4);
+        ipv4_packet.set_header_length(5);
+        ipv4_packet.set_total_length((20 + 8 + payload.len()) as u16);
+        ipv4_packet.set_next_level_protocol(IpNextHeaderProtocols::Udp);
+        ipv4_packet.set_source(src_ipv4);
+        ipv4_packet.set_destination(Ipv4Addr::BROADCAST);
+        let mut udp_packet = MutableUdpPacket::new(&mut buff[20..]).unwrap();
+        udp_packet.set_source(src_port);
+        udp_packet.set_destination(dst_port);
+        udp_packet.set_length((8 + payload.len()) as u16);
+        udp_packet.set_payload(payload);
+        ethernet_frame(EtherTypes::Ipv4, &buff)
+    }
+    #[test]
+    fn test_parse_frame_arp() {
+        let mut arp_buff = [0u8; 28];
+        let mut arp_packet = MutableArpPacket::new(&mut arp_buff).unwrap();
+        arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
+        arp_packet.set_protocol_type(EtherTypes::Ipv4);
+        arp_packet.set_hw_addr_len(6);
+        arp_packet.set_proto_addr_len(4);
+        arp_packet.set_operation(ArpOperations::Request);
+        arp_packet.set_sender_hw_addr(TEST_MAC);
+        arp_packet.set_sender_proto_addr(Ipv4Addr::new(192, 168, 1, 20));
+        arp_packet.set_target_proto_addr(Ipv4Addr::new(192, 168, 1, 1));
+        let frame = ethernet_frame(EtherTypes::Arp, &arp_buff);
+        let records = parse_frame(&frame);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].mac_addr, TEST_MAC);
+        assert_eq!(records[0].addr, Some(Ipv4Addr::new(192, 168, 1, 20).into()));
+        assert_eq!(records[0].protocol, PassiveProtocol::Arp);
+    }
+    #[test]
+    fn test_parse_frame_dhcp() {
+        let mut dhcp = vec![0u8; 240];
+        dhcp[0] = 1; // request
+        dhcp[1] = 1;
+        dhcp[2] = 6;
+        dhcp[28..34].copy_from_slice(&TEST_MAC.octets());
+        dhcp[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
+        dhcp.extend([53, 1, 3]);
+        dhcp.extend([50, 4, 192, 168, 1, 30]);
+        dhcp.extend([12, 7]);
+        dhcp.extend(b"printer");
+        dhcp.push(255);
+        let frame = udp_frame(Ipv4Addr::UNSPECIFIED, 68, 67, &dhcp);
+        let records = parse_frame(&frame);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].addr, Some(Ipv4Addr::new(192, 168, 1, 30).into()));
+        assert_eq!(records[0].hostname, Some(String::from("printer")));
+        assert_eq!(records[0].protocol, PassiveProtocol::Dhcp);
+    }
+    #[test]
+    fn test_parse_frame_mdns() {
+        let mut mdns = vec![0x00, 0x00, 0x84, 0x00, 0, 0, 0, 1, 0, 0, 0, 0];
+        mdns.push(6);
+        mdns.extend(b"laptop");
+        mdns.push(5);
+        mdns.extend(b"local");
+        mdns.push(0);
+        // type a, class in, ttl 120, rdlength 4
+        mdns.extend([0, 1, 0x80, 1, 0, 0, 0, 120, 0, 4, 192, 168, 1, 40]);
+        let frame = udp_frame(Ipv4Addr::new(192, 168, 1, 40), 5353, 5353, &mdns);
+        let records = parse_frame(&frame);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].hostname, Some(String::from("laptop.local")));
+        assert_eq!(records[1].addr, Some(Ipv4Addr::new(192, 168, 1, 40).into()));
+    }
+    #[test]
+    fn test_decode_netbios_name() {
+        // "WORKSTATION" padded with spaces and suffix 0x00
+        let mut encoded = String::new();
+        let mut name = b"WORKSTATION".to_vec();
+        name.resize(15, b' ');
+        name.push(0x00);
+        for c in name {
+            encoded.push((b'A' + (c >> 4)) as char);
+            encoded.push((b'A' + (c & 0x0F)) as char);
+        }
+        let decoded = decode_netbios_name(&encoded);
+        assert_eq!(decoded, Some(String::from("WORKSTATION")));
+    }
+    #[test]
+    fn test_parse_frame_lldp() {
+        let mut lldp = Vec::new();
+        // system name tlv
+        lldp.extend([(5 << 1) as u8, 6]);
+        lldp.extend(b"switch");
+        // management address tlv, ipv4
+        lldp.extend([(8 << 1) as u8, 12, 5, 1, 10, 0, 0, 1, 2, 0, 0, 0, 1, 0]);
+        // end tlv
+        lldp.extend([0, 0]);
+        let frame = ethernet_frame(EtherTypes::Lldp, &lldp);
+        let records = parse_frame(&frame);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].hostname, Some(String::from("switch")));
+        assert_eq!(records[0].addr, Some(Ipv4Addr::new(10, 0, 0, 1).into()));
+    }
+}
@@ -5,6 +5,11 @@ use num_cpus;
 use pnet::datalink::interfaces;
 use pnet::datalink::NetworkInterface;
 use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::Ipv6Addr;
@@ -12,6 +17,7 @@ use std::time::Duration;
 use threadpool::ThreadPool;
 
 use crate::errors::CanNotFoundRouterAddress;
+use crate::errors::SnapshotVersionMismatch;
 use crate::Ipv6CheckMethods;
 use crate::DEFAULT_TIMEOUT;
 use crate::SYSTEM_CACHE;
@@ -230,6 +236,25 @@ pub fn get_default_timeout() -> Duration {
     Duration::new(DEFAULT_TIMEOUT, 0)
 }
 
+/// Write the parsed database as a binary snapshot, tagged with the crate version.
+pub fn save_snapshot<T: Serialize>(db: &T, path: &str) -> Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    let file = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(file, &(version, db))?;
+    Ok(())
+}
+
+/// Read the snapshot written by `save_snapshot`, the snapshot from the other version of pistol is rejected.
+pub fn load_snapshot<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let mut file = BufReader::new(File::open(path)?);
+    let version: String = bincode::deserialize_from(&mut file)?;
+    if version != env!("CARGO_PKG_VERSION") {
+        return Err(SnapshotVersionMismatch::new(version).into());
+    }
+    let db: T = bincode::deserialize_from(&mut file)?;
+    Ok(db)
+}
+
 pub struct SpHex {
     pub hex: Option<String>, // hex => dec
 }
@@ -1,4 +1,5 @@
 use log::debug;
+use once_cell::sync::OnceCell;
 use prettytable::row;
 use prettytable::Cell;
 use prettytable::Row;
@@ -8,18 +9,25 @@ use serde::Serialize;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
 use std::net::IpAddr;
 use std::sync::mpsc::channel;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
 
 pub use self::dbparser::ExcludePorts;
+use crate::dns::addr_with_hostname;
+use crate::dns::ResolveHostnames;
 use crate::utils::get_default_timeout;
 use crate::utils::get_threads_pool;
+use crate::utils::load_snapshot;
+use crate::utils::save_snapshot;
 use crate::vs::dbparser::nsp_exclued_parser;
 use crate::vs::dbparser::nsp_parser;
 use crate::vs::dbparser::Match;
+use crate::vs::dbparser::ServiceProbe;
 use crate::vs::vscan::threads_vs_probe;
 use crate::Target;
 
@@ -44,12 +52,14 @@ impl Services {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VsScanResults {
     pub vss: HashMap<IpAddr, HashMap<u16, Services>>,
+    pub hostnames: HashMap<IpAddr, String>,
 }
 
 impl VsScanResults {
     pub fn new() -> VsScanResults {
         VsScanResults {
             vss: HashMap::new(),
+            hostnames: HashMap::new(),
         }
     }
     pub fn get(&self, k: &IpAddr) -> Option<&HashMap<u16, Services>> {
@@ -57,6 +67,15 @@ impl VsScanResults {
     }
 }
 
+impl ResolveHostnames for VsScanResults {
+    fn addrs(&self) -> Vec<IpAddr> {
+        self.vss.keys().copied().collect()
+    }
+    fn hostnames_mut(&mut self) -> &mut HashMap<IpAddr, String> {
+        &mut self.hostnames
+    }
+}
+
 impl fmt::Display for VsScanResults {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut table = Table::new();
@@ -68,6 +87,7 @@ impl fmt::Display for VsScanResults {
         let vss: BTreeMap<IpAddr, &HashMap<u16, Services>> =
             vss.into_iter().map(|(i, h)| (*i, h)).collect();
         for (ip, ports_service) in vss {
+            let ip_str = addr_with_hostname(&ip, &self.hostnames);
             let ports_service: BTreeMap<u16, &Services> =
                 ports_service.into_iter().map(|(p, s)| (*p, s)).collect();
             for (port, services) in ports_service {
@@ -78,13 +98,84 @@ impl fmt::Display for VsScanResults {
                     }
                 }
                 let services_str = sv.join(",");
-                table.add_row(row![c -> ip, c -> port, c -> services_str]);
+                table.add_row(row![c -> ip_str, c -> port, c -> services_str]);
             }
         }
         write!(f, "{}", table)
     }
 }
 
+static EMBEDDED_SERVICE_PROBES_DB: OnceCell<Arc<ServiceProbesDb>> = OnceCell::new();
+
+/// The parsed nmap-service-probes database, load it once and share it across `vs_scan_with_db` calls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceProbesDb {
+    pub service_probes: Vec<ServiceProbe>,
+    /// The `Exclude` directive of the database.
+    pub exclude_ports: ExcludePorts,
+}
+
+impl ServiceProbesDb {
+    /// The embedded database, parsed at the first call and cached after that.
+    pub fn embedded() -> Result<Arc<ServiceProbesDb>> {
+        let db =
+            EMBEDDED_SERVICE_PROBES_DB.get_or_try_init(|| -> Result<Arc<ServiceProbesDb>> {
+                let db = ServiceProbesDb::from_nmap_service_probes(include_str!(
+                    "./db/nmap-service-probes"
+                ))?;
+                Ok(Arc::new(db))
+            })?;
+        Ok(db.clone())
+    }
+    /// Parse the content of a nmap-service-probes file.
+    pub fn from_nmap_service_probes(nsp_str: &str) -> Result<ServiceProbesDb> {
+        let mut nsp_lines = Vec::new();
+        for l in nsp_str.lines() {
+            nsp_lines.push(l.to_string());
+        }
+        let exclude_ports = nsp_exclued_parser(&nsp_lines)?;
+        let service_probes = nsp_parser(&nsp_lines)?;
+        debug!("nmap service db parse finish");
+        Ok(ServiceProbesDb {
+            service_probes,
+            exclude_ports,
+        })
+    }
+    /// Load a nmap-service-probes file, such as the one from a newer nmap release.
+    pub fn from_file(path: &str) -> Result<ServiceProbesDb> {
+        let nsp_str = fs::read_to_string(path)?;
+        ServiceProbesDb::from_nmap_service_probes(&nsp_str)
+    }
+    /// Save the parsed database as a binary snapshot, which loads much faster than parsing.
+    pub fn save_snapshot(&self, path: &str) -> Result<()> {
+        save_snapshot(self, path)
+    }
+    pub fn load_snapshot(path: &str) -> Result<ServiceProbesDb> {
+        load_snapshot(path)
+    }
+}
+
+/// Which probes of the database are sent, see `vs_scan`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct VsProbeOptions {
+    pub only_null_probe: bool,
+    pub only_tcp_recommended: bool,
+    pub only_udp_recommended: bool,
+    /// The probes with a rarity above the intensity are not sent (0-9, the nmap default is 7).
+    pub intensity: usize,
+}
+
+impl Default for VsProbeOptions {
+    fn default() -> VsProbeOptions {
+        VsProbeOptions {
+            only_null_probe: false,
+            only_tcp_recommended: false,
+            only_udp_recommended: false,
+            intensity: 7,
+        }
+    }
+}
+
 /// Detect target port service.
 pub fn vs_scan(
     target: Target,
@@ -95,17 +186,30 @@ pub fn vs_scan(
     intensity: usize,
     threads_num: usize,
     timeout: Option<Duration>,
+) -> Result<VsScanResults> {
+    let db = ServiceProbesDb::embedded()?;
+    let options = VsProbeOptions {
+        only_null_probe,
+        only_tcp_recommended,
+        only_udp_recommended,
+        intensity,
+    };
+    vs_scan_with_db(target, db, options, exclude_ports, threads_num, timeout)
+}
+
+/// Same as `vs_scan`, but with the given database, see `ServiceProbesDb`.
+pub fn vs_scan_with_db(
+    target: Target,
+    db: Arc<ServiceProbesDb>,
+    options: VsProbeOptions,
+    exclude_ports: Option<ExcludePorts>,
+    threads_num: usize,
+    timeout: Option<Duration>,
 ) -> Result<VsScanResults> {
     let timeout = match timeout {
         Some(t) => t,
         None => get_default_timeout(),
     };
-    let nsp_str = include_str!("./db/nmap-service-probes");
-    let mut nsp_lines = Vec::new();
-    for l in nsp_str.lines() {
-        nsp_lines.push(l.to_string());
-    }
-    debug!("nmap service db load finish");
 
     let pool = get_threads_pool(threads_num);
     let (tx, rx) = channel();
@@ -117,10 +221,8 @@ pub fn vs_scan(
 
     let exclude_ports = match exclude_ports {
         Some(e) => e,
-        None => nsp_exclued_parser(&nsp_lines)?,
+        None => db.exclude_ports.clone(),
     };
-    let service_probes = nsp_parser(&nsp_lines)?;
-    debug!("nmap service db parse finish");
 
     let mut recv_size = 0;
     for (dst_addr, ports) in vs_target {
@@ -128,16 +230,16 @@ pub fn vs_scan(
             // Nmap checks to see if the port is one of the ports to be excluded.
             if !exclude_ports.ports.contains(&dst_port) {
                 let tx = tx.clone();
-                let service_probes = service_probes.clone();
+                let db = db.clone();
                 pool.execute(move || {
                     let ret = threads_vs_probe(
                         dst_addr,
                         dst_port,
-                        only_null_probe,
-                        only_tcp_recommended,
-                        only_udp_recommended,
-                        intensity,
-                        &service_probes,
+                        options.only_null_probe,
+                        options.only_tcp_recommended,
+                        options.only_udp_recommended,
+                        options.intensity,
+                        &db.service_probes,
                         timeout,
                     );
                     match tx.send((dst_addr, dst_port, ret)) {
@@ -183,16 +285,24 @@ pub fn vs_scan_raw(
     intensity: usize,
     timeout: Option<Duration>,
 ) -> Result<Services> {
-    let nsp_str = include_str!("./db/nmap-service-probes");
-    let mut nsp_lines = Vec::new();
-    for l in nsp_str.lines() {
-        nsp_lines.push(l.to_string());
-    }
-    debug!("nmap service db load finish");
-
-    let service_probes = nsp_parser(&nsp_lines)?;
-    debug!("nmap service db parse finish");
+    let db = ServiceProbesDb::embedded()?;
+    let options = VsProbeOptions {
+        only_null_probe,
+        only_tcp_recommended,
+        only_udp_recommended,
+        intensity,
+    };
+    vs_scan_raw_with_db(dst_addr, dst_port, db, options, timeout)
+}
 
+/// Same as `vs_scan_raw`, but with the given database, see `ServiceProbesDb`.
+pub fn vs_scan_raw_with_db(
+    dst_addr: IpAddr,
+    dst_port: u16,
+    db: Arc<ServiceProbesDb>,
+    options: VsProbeOptions,
+    timeout: Option<Duration>,
+) -> Result<Services> {
     let timeout = match timeout {
         Some(t) => t,
         None => get_default_timeout(),
@@ -201,11 +311,11 @@ pub fn vs_scan_raw(
     match threads_vs_probe(
         dst_addr,
         dst_port,
-        only_null_probe,
-        only_tcp_recommended,
-        only_udp_recommended,
-        intensity,
-        &service_probes,
+        options.only_null_probe,
+        options.only_tcp_recommended,
+        options.only_udp_recommended,
+        options.intensity,
+        &db.service_probes,
         timeout,
     ) {
         Ok((r, rtt)) => {
@@ -224,6 +334,33 @@ mod tests {
     use crate::Host;
     // use crate::Logger;
     use crate::TEST_IPV4_LOCAL;
+    use std::env;
+    use std::fs::File;
+    #[test]
+    fn test_service_probes_db_snapshot() -> Result<()> {
+        let nsp_str = "Exclude T:9100-9102\n\
+            Probe TCP NULL q||\n\
+            totalwaitms 6000\n\
+            match ssh m|^SSH-([\\d.]+)-OpenSSH_([\\w._-]+)\\r?\\n| p/OpenSSH/ v/$2/\n";
+        let db = ServiceProbesDb::from_nmap_service_probes(nsp_str)?;
+        assert_eq!(db.service_probes.len(), 1);
+        assert_eq!(db.exclude_ports.tcp_ports, vec![9100, 9101, 9102]);
+
+        let path = env::temp_dir().join("pistol-test-service-probes.snapshot");
+        let path = path.to_str().unwrap();
+        db.save_snapshot(path)?;
+        let loaded = ServiceProbesDb::load_snapshot(path)?;
+        assert_eq!(loaded.service_probes.len(), 1);
+        assert_eq!(loaded.service_probes[0].matchs[0].service, "ssh");
+        assert_eq!(loaded.exclude_ports.tcp_ports, db.exclude_ports.tcp_ports);
+
+        // the snapshot from the other version
+        let file = File::create(path)?;
+        bincode::serialize_into(file, &("0.0.0", &db))?;
+        assert!(ServiceProbesDb::load_snapshot(path).is_err());
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
     #[test]
     fn test_vs_detect() -> Result<()> {
         // Logger::init_debug_logging()?;